@@ -0,0 +1,51 @@
+//! Records build metadata for `dotfilesvault --version --json`
+
+use std::env;
+use std::process::Command;
+
+use chrono::{DateTime, Utc};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-env-changed=DOTFILESVAULT_GIT_SHA");
+
+    // Release builds from a source tarball have no repository to ask
+    let git_sha = env::var("DOTFILESVAULT_GIT_SHA").ok().or_else(|| {
+        if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+            println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+            println!("cargo:rerun-if-changed={}/refs", git_dir);
+        }
+        git(&["rev-parse", "--short=12", "HEAD"])
+    });
+    println!(
+        "cargo:rustc-env=DOTFILESVAULT_GIT_SHA={}",
+        git_sha.as_deref().unwrap_or("unknown")
+    );
+
+    // Reproducible builds pin the date through SOURCE_DATE_EPOCH
+    let build_date = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .and_then(|epoch| DateTime::from_timestamp(epoch, 0))
+        .unwrap_or_else(Utc::now);
+    println!(
+        "cargo:rustc-env=DOTFILESVAULT_BUILD_DATE={}",
+        build_date.format("%Y-%m-%dT%H:%M:%SZ")
+    );
+
+    println!(
+        "cargo:rustc-env=DOTFILESVAULT_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+}
+
+/// Run git and return its trimmed output, if it succeeded
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
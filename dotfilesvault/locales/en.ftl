@@ -0,0 +1,465 @@
+# English messages of the dotfilesvault CLI
+#
+# This catalog is the reference: every message used by the code must exist
+# here. Translations live next to it as <language>.ftl and may leave
+# messages out, which then fall back to English.
+
+## Errors
+
+error-io = IO error: { $error }
+error-no-home-dir = Failed to find home directory
+error-no-vault-dir = Failed to find dotfilesvault directory
+error-dotfile-not-found = Dotfile not found: { $file }
+error-version-not-found = Version not found for dotfile: { $file }
+error-git = Git error: { $error }
+error-hook-failed = Hook { $hook } failed: { $status }
+error-transform-not-found = Transform plugin not found: { $name }
+error-transform-failed = Transform { $name } failed: { $error }
+error-invalid-manifest = Invalid manifest: { $error }
+error-group-not-found = No tracked dotfiles in group: { $group }
+error-invalid-config = Invalid config file { $path }: { $error }
+error-invalid-ignore-pattern = Invalid ignore pattern { $pattern }: { $error }
+error-sync-failed = Sync failed: { $error }
+error-merge-conflicts = Merge conflicts in: { $paths }
+error-vault-not-found = No vault named { $name } in the config file, see: dotfilesvault vault list
+error-vault-exists = A vault named { $name } already exists
+error-remote-unavailable = Can't reach { $url }: { $reason }. Without the remote, carry the vault over with: dotfilesvault bundle FILE
+error-encryption = Encryption error: { $error }
+error-backend = Vault storage error: { $error }
+error-session-not-found = Restore session not found: { $session }
+error-template-failed = Template { $name } failed: { $error }
+error-cancelled = Cancelled
+error-timed-out = Timed out after { $seconds ->
+        [one] 1 second
+       *[other] { $seconds } seconds
+    }
+error-vault-locked = The vault is in use by another dotfilesvault process. If none is running, remove { $path }
+error-invalid-snippet = Invalid snippet { $definition }, expected NAME=BODY with a name of letters, digits, _, -, . or :
+error-snippet-not-found = Snippet not found: { $name }
+error-import-failed = Can't import { $source }: { $error }
+error-adopt-failed = Can't adopt { $file }: { $error }
+error-nothing-to-undo = No restore to undo
+error-changed-since-restore = { $file } changed since the restore, undo --force discards the changes
+error-vault-internal-path = { $file } would be stored where the vault keeps its own files (hooks, plugins, profiles and the like)
+error-invalid-glob = Invalid pattern { $pattern }: { $error }
+error-no-matches = No files match { $pattern }
+error-snapshot-not-found = Snapshot not found: { $name }
+error-snapshot-exists = A snapshot named { $name } already exists
+error-invalid-snapshot-name = Invalid snapshot name { $name }, it must be usable as a git tag name
+error-schedule-unsupported = Scheduled backups need systemd (Linux) or launchd (macOS)
+error-schedule-failed = { $command } failed: { $error }
+error-read-only = Refusing to write in read-only mode
+error-restore-conflict = { $file } has edits that were never backed up
+
+## Failed commands
+
+failed-load-config = Failed to load config: { $error }
+failed-plan-backup = Failed to plan backup: { $error }
+failed-backup-dotfiles = Failed to backup dotfiles: { $error }
+failed-get-status = Failed to get status: { $error }
+failed-list-backed-up-dotfiles = Failed to list backed up dotfiles: { $error }
+failed-get-history-for-dotfile = Failed to get history for dotfile: { $error }
+failed-show-dotfile = Failed to show dotfile: { $error }
+failed-grep = Failed to search the vault: { $error }
+failed-sed = Failed to replace in the dotfiles: { $error }
+failed-diff-dotfiles = Failed to diff dotfiles: { $error }
+failed-serve-metrics = Failed to serve metrics: { $error }
+failed-watch-dotfiles = Failed to watch dotfiles: { $error }
+failed-export-metrics = Failed to export metrics: { $error }
+failed-find-suggestions = Failed to find suggestions: { $error }
+failed-configure-remote = Failed to configure remote: { $error }
+failed-push = Failed to push: { $error }
+failed-export-bundle = Failed to write the bundle: { $error }
+failed-pull = Failed to pull: { $error }
+failed-recover = Failed to recover the vault: { $error }
+failed-import = Failed to import dotfiles: { $error }
+failed-clone = Failed to clone the vault: { $error }
+failed-lint-configuration = Failed to lint the configuration: { $error }
+failed-list-vaults = Failed to list the vaults: { $error }
+failed-create-vault = Failed to create the vault: { $error }
+failed-switch-vault = Failed to switch the vault: { $error }
+failed-load-manifest = Failed to load manifest: { $error }
+failed-set-group = Failed to set group: { $error }
+failed-list-profiles = Failed to list profiles: { $error }
+failed-list-conflicts = Failed to list conflicts: { $error }
+failed-resolve-conflict = Failed to resolve conflict: { $error }
+failed-create-key = Failed to create key: { $error }
+failed-export-key = Failed to export key: { $error }
+failed-simulate-bootstrap = Failed to simulate bootstrap: { $error }
+failed-bootstrap = Failed to generate the bootstrap script: { $error }
+failed-capture-toolchains = Failed to capture toolchains: { $error }
+failed-read-toolchain-snapshots = Failed to read toolchain snapshots: { $error }
+failed-add-snippet = Failed to add snippet: { $error }
+failed-remove-snippet = Failed to remove snippet: { $error }
+failed-list-snippets = Failed to list snippets: { $error }
+failed-write-snippets = Failed to write the snippets script: { $error }
+failed-set-frequency = Failed to set frequency: { $error }
+failed-set-starter = Failed to update the starter content: { $error }
+failed-add = Failed to track paths: { $error }
+failed-adopt = Failed to adopt files: { $error }
+failed-clean = Failed to clean the vault: { $error }
+failed-compact-history = Failed to compact history: { $error }
+failed-find-stray-files = Failed to find stray files: { $error }
+failed-verify-vault = Failed to verify the vault: { $error }
+failed-check-heartbeats = Failed to check backup heartbeats: { $error }
+failed-fix-stray-files = Failed to fix stray files: { $error }
+failed-remove-dotfiles = Failed to remove dotfiles: { $error }
+failed-generate-shell-files = Failed to generate shell files: { $error }
+failed-normalize-vault = Failed to normalize vault paths: { $error }
+failed-prune-history = Failed to prune history: { $error }
+failed-clear-cache = Failed to clear the hash cache: { $error }
+failed-plan-rollback = Failed to plan rollback: { $error }
+failed-apply-rollback = Failed to apply rollback: { $error }
+failed-restore-dotfiles = Failed to restore dotfiles: { $error }
+failed-record-session = Restored, but failed to record the restore for replay: { $error }
+failed-list-sessions = Failed to list the recorded restores: { $error }
+failed-replay = Failed to replay the restore: { $error }
+failed-restore-dotfile = Failed to restore dotfile: { $error }
+failed-create-snapshot = Failed to create snapshot: { $error }
+failed-list-snapshots = Failed to list snapshots: { $error }
+failed-export = Failed to export: { $error }
+failed-safety-snapshot = Failed to take a safety snapshot: { $error }
+failed-install-schedule = Failed to schedule backups: { $error }
+failed-remove-schedule = Failed to remove the backup schedule: { $error }
+failed-schedule-status = Failed to check the backup schedule: { $error }
+failed-list-dotfiles-at = Failed to list dotfiles at { $at }: { $error }
+failed-run-plugin = Failed to run plugin { $name }: { $error }
+failed-format-json = Failed to format output as JSON: { $error }
+invalid-path = Invalid path { $path }: { $error }
+unknown-command = Unknown command '{ $name }' (no dotfilesvault-{ $name } found on PATH)
+
+## Backup and restore
+
+interrupted = Stopping, press Ctrl-C again to quit right away
+
+dry-run-copy = would { $action ->
+        [create] create
+        [quarantine] quarantine
+       *[overwrite] overwrite
+    } { $destination } (from { $source })
+dry-run-commit = would commit: { $message }
+dry-run-nothing = Nothing to copy
+backup-unchanged = Nothing changed since the last backup, no commit made
+bare-backup-dry-run = would commit { $count } files from the home directory
+bare-restore-dry-run = would check out { $count } files into the home directory
+bare-restore-done = Checked out { $count } files into the home directory
+dry-run-unsupported = This command doesn't support --dry-run
+backend-unsupported = This command needs a vault stored in git, but the vault is stored in { $backend }
+bare-unsupported = This command isn't available with the bare layout, run git --git-dir { $vault } --work-tree { $home } instead
+secret-refused = refused { $path }: possible { $pattern } at line { $line }
+secret-hint = Encrypt or ignore these files, or back them up with --allow-secrets
+backup-left-out = { $path }: { $reason }
+backup-left-out-hint = Ignore these files, raise max_file_size, or back them up with --include-large
+case-collision = paths differing only in case: { $paths }
+case-collision-hint = These collide on case-insensitive filesystems (macOS, Windows); rename one, or set on_case_collision
+restore-kept-local-edits = Kept local edits, vault version saved to { $quarantined }
+restore-kept-local-edits-to = Kept local edits to { $file }, vault version saved to { $quarantined }
+restore-merge-hint = Merge it by hand, then run: dotfilesvault conflicts resolve { $file }
+restore-file-failed = Failed to restore { $path }: { $error }
+restore-conflict-kept = Kept { $path }, it has edits that were never backed up
+restore-conflicts-hint = Back them up first, or restore with --force to overwrite them or --on-conflict skip to keep both
+restore-conflict-prompt = { $file } has edits that were never backed up. Show the [d]iff, [k]eep both, [o]verwrite or [a]bort?
+restore-conflict-hint = See the changes with: dotfilesvault diff { $file }, then back it up, or restore with --force to overwrite it or --on-conflict skip to keep both
+restore-summary = Restored { $restored } dotfiles, skipped { $skipped }, failed { $failed }
+restore-deferred = Ran out of time, { $count } dotfiles are left for the next restore
+session-item = { $id } ({ $time } on { $host }): { $command }, { $count ->
+        [one] 1 dotfile restored
+       *[other] { $count } dotfiles restored
+    }
+sessions-empty = No restores recorded yet
+failed-undo = Failed to undo the restore: { $error }
+undo-restored = put back: { $path }
+undo-removed = removed: { $path }
+undo-done = Undid the restore of { $time }, { $count ->
+        [one] 1 file
+       *[other] { $count } files
+    } reverted
+undo-dry-run = Would undo the restore of { $time }
+notice-skipped-sensitive = Left out, line { $line } looks like a secret ({ $pattern })
+notice-skipped-large = Left out, { $size } is over the { $limit } limit
+notice-skipped-binary = Left out, it is a binary file
+notice-case-collision = These paths differ only in case: { $paths }
+notice-conflict-quarantined = Kept local edits, vault version saved to { $quarantined }
+notice-deferred = Not checked, the restore ran out of time
+add-done = { $count ->
+        [one] Tracking 1 path, the next backup copies it
+       *[other] Tracking { $count } paths, the next backup copies them
+    }
+adopt-done = { $count ->
+        [one] Adopted 1 file, it now links into the vault
+       *[other] Adopted { $count } files, they now link into the vault
+    }
+adopt-dry-run = would adopt { $count } files
+starter-added = Stored starter content for { $file }, restores create it only where it doesn't exist
+starter-removed = { $file } is tracked normally again
+
+## Status, list and history
+
+status-modified = modified
+status-new = new
+status-deleted = deleted
+status-up-to-date = Vault is up to date
+status-deleted-hint = { $count ->
+        [one] hint: 1 deleted dotfile is still in the vault, bring it back with dotfilesvault restore or drop it with dotfilesvault clean
+       *[other] hint: { $count } deleted dotfiles are still in the vault, bring them back with dotfilesvault restore or drop them with dotfilesvault clean
+    }
+status-suggestion = hint: you edited ~/{ $path } but it isn't tracked, run: { $command }
+status-stale = { $days ->
+    [one] warning: this machine last backed up 1 day ago, check that scheduled backups still run with: dotfilesvault schedule status
+   *[other] warning: this machine last backed up { $days } days ago, check that scheduled backups still run with: dotfilesvault schedule status
+}
+suggestion = You edited ~/{ $path } ({ $app }) but it isn't tracked: { $command }
+list-empty = No dotfiles have been backed up yet.
+list-header = { $count ->
+        [one] 1 dotfile backed up:
+       *[other] { $count } dotfiles backed up:
+    }
+list-at-header = Dotfiles at { $commit } ({ $time }):
+history-empty = No history found for dotfile: { $file }
+history-header = History for dotfile: { $file }
+history-version = version { $number }, commit { $commit }, { $time }: { $message }
+diff-against-header = { $count ->
+        [one] 1 home file changed since { $commit } ({ $time }):
+       *[other] { $count } home files changed since { $commit } ({ $time }):
+    }
+diff-against-unchanged = No home file changed since { $time }
+diff-against-lines = +{ $added } -{ $removed }
+diff-hint-common = { $file }: last in sync with the vault at { $commit } ({ $time })
+diff-hint-no-common = { $file }: never in sync with a version in the vault history
+diff-hint-vault-changed = vault: { $count ->
+        [one] 1 newer version
+       *[other] { $count } newer versions
+    }, the latest from { $time }
+diff-hint-vault-unchanged = vault: unchanged since
+diff-hint-home-changed = home: changed { $time }
+diff-hint-home-unchanged = home: unchanged since
+grep-match = { $path }:{ $line }: { $text }
+grep-match-version = { $commit } ({ $time }) { $path }:{ $line }: { $text }
+grep-no-matches = No matches
+sed-changed = { $path }: { $lines ->
+        [one] 1 line changed in the vault
+       *[other] { $lines } lines changed in the vault
+    }{ $home ->
+        [true] {" "}and in the home directory
+       *[false] {""}
+    }
+sed-skipped = skipped, encrypted or binary: { $path }
+sed-nothing = Nothing matched, no commit made
+sed-committed = Committed the change as { $commit }
+
+## Scheduled backups
+
+schedule-installed = Backing up every { $interval } with { $platform } ({ $unit })
+schedule-dry-run = would back up every { $interval } with { $platform } ({ $unit })
+schedule-removed = Removed the backup schedule
+schedule-none = No backup schedule installed, add one with: dotfilesvault schedule install --interval 1h
+schedule-active = Backing up every { $interval } with { $platform }
+schedule-inactive = Backups every { $interval } are installed but { $platform } isn't running them, reinstall with: dotfilesvault schedule install
+
+## Sync
+
+pull-outcome = Pull from { $remote }: { $outcome }
+pull-conflicts = Pull would conflict, the vault was left unchanged
+pull-conflict = conflict: { $path }
+failed-sync = Failed to sync: { $error }
+sync-conflict-prompt = { $file } changed at home and on the remote. Show the [d]iff, keep the [h]ome version, take the [v]ault version or [s]kip?
+sync-backed-up = backed up: { $path }
+sync-restored = restored: { $path }
+sync-conflict = changed on both sides: { $path }
+sync-done = Synced with { $remote }: { $backed_up } backed up, { $restored } restored
+sync-conflicts-hint = { $count } files changed on both sides were left alone, run dotfilesvault sync --on-conflict prefer-home or prefer-vault to settle them
+bundle-written = Wrote { $count ->
+    [one] 1 branch
+   *[other] { $count } branches
+} to { $path }
+bundle-hint = Clone it with: git clone { $path } { $vault }, or pull it into an existing vault with: git -C { $vault } pull { $path }
+remote-no-https = this build of dotfilesvault has no HTTPS support, use an SSH or local remote
+remote-no-ssh = this build of dotfilesvault has no SSH support, use an HTTPS or local remote
+remote-no-certificates = no CA certificates were found to verify the server, install them (e.g. the ca-certificates package) or point SSL_CERT_FILE at a certificate bundle
+remote-tls = TLS failed ({ $error }), check the CA certificates or point SSL_CERT_FILE at a certificate bundle
+remote-ssh = SSH failed ({ $error }), check that ssh-agent runs and holds your key (ssh-add -l)
+remote-network = { $error }
+recover-done = Recovered the vault from { $url }, { $tracked ->
+        [one] 1 tracked dotfile
+       *[other] { $tracked } tracked dotfiles
+    }
+clone-done = Cloned the vault from { $url }, { $tracked ->
+        [one] 1 tracked dotfile
+       *[other] { $tracked } tracked dotfiles
+    }
+recover-missing = { $count ->
+        [one] 1 tracked dotfile is missing from the vault:
+       *[other] { $count } tracked dotfiles are missing from the vault:
+    }
+import-done = Imported { $count ->
+        [one] 1 dotfile
+       *[other] { $count } dotfiles
+    } from { $source }
+import-skipped = { $count ->
+        [one] 1 file isn't laid out like the home directory and stays untracked:
+       *[other] { $count } files aren't laid out like the home directory and stay untracked:
+    }
+import-restore-hint = Restore them into the home directory with: dotfilesvault restore --all
+
+## Paranoid mode
+
+paranoid-confirm = Paranoid mode: { $action } is destructive. Continue? [y/N]
+paranoid-aborted = Aborted, nothing was changed
+safety-snapshot-vault = Saved the vault as snapshot { $name }, restore it with: dotfilesvault restore --snapshot { $name }
+safety-snapshot-home = { $count ->
+        [one] Copied 1 home file
+       *[other] Copied { $count } home files
+    } to { $dir }
+
+## Hooks, config, profiles and conflicts
+
+hooks-none = No hooks installed.
+hooks-header = Installed hooks:
+hook-unknown = Unknown hook: { $hook }
+hook-not-installed = Hook { $hook } is not installed
+lint-diagnostic = { $severity }: { $location }: { $message } [{ $code }]
+lint-clean = No problems found
+vault-no-config-file = No config file to keep the vaults in, pass one with --config
+vault-created = Created vault { $name } in { $path }
+vault-use-hint = Use it with --vault { $name }, or make it the default with `dotfilesvault vault switch { $name }`
+vault-switched = Commands now use vault { $name } unless --vault is given
+profiles-none = No profiles, every dotfile uses the common layer
+profile-current = { $profile } (current)
+conflicts-none = No conflicts
+
+## Terminal UI
+
+tui-plain = The terminal UI can't be used with plain output, use status, history and diff instead
+tui-files-title = Tracked dotfiles ({ $count })
+tui-history-title = History
+tui-diff-title = Diff against home
+tui-status-clean = up to date
+tui-help = ↑/↓ select  b back up  r restore  h history  d diff  q quit
+tui-backed-up = Backed up { $path }
+tui-backup-refused = Refused to back up { $path }: { $count ->
+        [one] 1 line looks
+       *[other] { $count } lines look
+    } like a secret
+tui-restored = Restored { $path }
+failed-tui = Failed to run the terminal UI: { $error }
+
+## Keys
+
+key-created = Created key at { $path }
+key-public = Public key: { $recipient }
+key-keep-safe = Keep a copy somewhere safe: without it encrypted files can't be restored.
+
+## Bootstrap simulation and toolchains
+
+bootstrap-written = Wrote the bootstrap script to { $path }
+simulate-check-ok = ok    { $check }
+simulate-check-failed = FAIL  { $check }: { $error }
+simulate-succeeded = Bootstrap simulation succeeded ({ $checks } checks)
+simulate-failed = Bootstrap simulation failed: { $failed } of { $checks } checks failed
+simulate-kept = Simulated home kept at { $path }
+toolchain-captured = Captured { $toolchain }: { $items } items
+
+## Features
+
+feature-enabled = { $name }: enabled
+feature-disabled = { $name }: disabled (not compiled in)
+
+## Snippets
+
+snippet-added = Added { $kind } { $name } to { $script }
+snippet-removed = Removed { $kind } { $name }
+snippet-item = { $name } ({ $kind }): { $body }
+snippets-empty = No snippets yet, add one with: dotfilesvault snippet add alias NAME=COMMAND
+snippets-written = Wrote { $count ->
+        [one] 1 snippet
+       *[other] { $count } snippets
+    } to { $script }
+snippet-source-hint = Load snippets in new shells by adding this line to your shell rc file:
+generate-no-source = No shell environment declared, create { $path } with path, [env] and [aliases]
+generate-source-hint = Load the generated file in new shells by adding its line to your shell rc file:
+
+## History maintenance
+
+compact-nothing = Nothing to compact
+compact-dry-run = would squash { $squashed } commits into a baseline and keep { $kept } newer commits
+compact-done = Squashed { $squashed } commits into a baseline, kept { $kept } newer commits
+compact-force-push = The history was rewritten, update the remote with: git -C { $vault } push --force
+prune-nothing = Nothing to prune, the retention policy keeps every commit
+prune-dry-run = would squash { $squashed } commits outside the retention policy into a baseline and keep { $kept } commits
+prune-done = Squashed { $squashed } commits outside the retention policy into a baseline, kept { $kept } commits, and repacked the vault
+normalize-nothing = All vault paths are already in composed form
+normalize-renamed = renamed: { $path }
+normalize-merged = kept the newer of two copies: { $path }
+normalize-dry-run = would normalize { $files } files and { $entries } manifest entries
+normalize-done = Normalized { $files } files and { $entries } manifest entries
+doctor-no-strays = Every file in the vault is tracked
+verify-ok = The vault is intact
+verify-problems = { $count ->
+        [one] Found 1 problem in the vault:
+       *[other] Found { $count } problems in the vault:
+    }
+verify-uncommitted = uncommitted: { $subject } ({ $detail })
+verify-corrupt-object = corrupt or missing object: { $subject } ({ $detail })
+verify-invalid-manifest = manifest: { $subject } ({ $detail })
+verify-unmapped = can't be restored: { $subject } ({ $detail })
+doctor-strays = { $count ->
+    [one] 1 file in the vault isn't tracked:
+   *[other] { $count } files in the vault aren't tracked:
+}
+doctor-fix-hint = Track them with --fix adopt, remove them with --fix delete, or keep them quietly with --fix ignore
+doctor-dry-run = would { $action } { $count } stray files
+doctor-adopted = Now tracking { $count } more files
+doctor-deleted = Deleted { $count } stray files from the vault
+doctor-ignored = Ignoring { $count } stray files from now on
+doctor-stale = { $count ->
+    [one] 1 machine hasn't backed up in a while:
+   *[other] { $count } machines haven't backed up in a while:
+}
+doctor-stale-host = { $host }: { $days } days ago
+clean-nothing = Every tracked dotfile still exists in the home directory
+clean-confirm = Remove these { $count } deleted dotfiles from the vault? [y/N]
+clean-done = { $count ->
+        [one] Removed 1 deleted dotfile from the vault
+       *[other] Removed { $count } deleted dotfiles from the vault
+    }
+clean-preview = { $count ->
+        [one] 1 tracked dotfile was deleted from the home directory, remove it from the vault with: dotfilesvault clean --yes
+       *[other] { $count } tracked dotfiles were deleted from the home directory, remove them from the vault with: dotfilesvault clean --yes
+    }
+remove-dry-run = would stop tracking { $count } dotfiles
+remove-done = Stopped tracking { $count } dotfiles, the files in your home directory are untouched
+remove-ignored = Added { $count } files in tracked directories to .vaultignore
+remove-purged = Rewrote { $commits } commits to expunge the files from the vault history, force-push any remote to drop them there too
+remove-purge-hint = Past versions stay in the vault history, pass --purge-history to expunge them
+cache-cleared = Cleared the hash cache, home files will be read again
+cache-empty = The hash cache is already empty
+snapshot-created = Created snapshot { $name } at { $commit }, restore it with: dotfilesvault restore --snapshot { $name }
+snapshot-item = { $name } ({ $time }, { $commit }): { $message }
+snapshots-empty = No snapshots yet, create one with: dotfilesvault snapshot create NAME
+rollback-header = Rolling back group { $group } to { $commit } ({ $time })
+rollback-unchanged = unchanged: { $id }
+rollback-missing = not in vault at that time: { $id }
+rollback-vault-header = Rolling the vault back to { $commit } ({ $time }): { $message }
+rollback-vault-committed = Rolled the vault back in commit { $commit }
+rollback-nothing = Nothing to roll back.
+rollback-preview = { $count ->
+        [one] 1 file would change. Run again with --yes to apply.
+       *[other] { $count } files would change. Run again with --yes to apply.
+    }
+
+## Export
+
+export-done = Exported { $count } files{ $history ->
+        [true] {" "}and history
+       *[false] {""}
+    } to { $output }
+export-changed-done = Exported { $count } changed files to { $output }
+export-deleted = deleted in this window (not exported): { $path }
+
+## Progress
+
+progress-backup = Backing up
+progress-restore = Restoring
+progress-push = Pushing
+progress-pull = Pulling
+progress-clone = Cloning
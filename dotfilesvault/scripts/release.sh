@@ -0,0 +1,46 @@
+#!/bin/sh
+# Build static release binaries for several architectures into dist/
+#
+# Usage: scripts/release.sh [TARGET...]
+#
+# Targets default to x86_64 and aarch64 Linux (musl). Cross-compiling uses
+# `cross` (https://github.com/cross-rs/cross) when it's installed, cargo
+# otherwise. Set SOURCE_DATE_EPOCH for a reproducible build date.
+set -eu
+
+cd "$(dirname "$0")/.."
+
+if [ "$#" -eq 0 ]; then
+    set -- x86_64-unknown-linux-musl aarch64-unknown-linux-musl
+fi
+
+if command -v cross >/dev/null 2>&1; then
+    cargo=cross
+else
+    cargo=cargo
+fi
+
+version=$(sed -n 's/^version = "\(.*\)"$/\1/p' Cargo.toml | head -n 1)
+DOTFILESVAULT_GIT_SHA=$(git rev-parse --short=12 HEAD 2>/dev/null || echo unknown)
+export DOTFILESVAULT_GIT_SHA
+# Link the C runtime statically, and OpenSSL for git2's HTTPS support
+export RUSTFLAGS="${RUSTFLAGS:-} -C target-feature=+crt-static"
+export OPENSSL_STATIC=1
+
+mkdir -p dist
+for target in "$@"; do
+    echo "Building $target with $cargo"
+    "$cargo" build --release --locked --target "$target"
+
+    name="dotfilesvault-$version-$target"
+    staging="dist/$name"
+    rm -rf "$staging"
+    mkdir -p "$staging"
+    cp "target/$target/release/dotfilesvault" README.md "$staging/"
+    tar -czf "dist/$name.tar.gz" -C dist "$name"
+    rm -rf "$staging"
+done
+
+cd dist
+sha256sum ./*.tar.gz >SHA256SUMS
+cat SHA256SUMS
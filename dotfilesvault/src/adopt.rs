@@ -0,0 +1,180 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::backup::{Dotfile, is_own_path, own_paths};
+use crate::encryption::should_encrypt;
+use crate::history::commit_changes;
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::symlink::create_symlink;
+use crate::utils::normalize_path;
+use crate::{Config, DotfilesError};
+
+/// Outcome of adopting files into the vault
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AdoptSummary {
+    /// Home-relative paths of the files now linked into the vault
+    pub adopted: Vec<PathBuf>,
+
+    /// The commit recording them
+    pub commit: Option<String>,
+}
+
+/// Move files into the vault and leave symlinks to them in their place
+///
+/// Each file is tracked with `linked` set and moved to its vault path, and a
+/// symlink to the stored copy takes its place, so edits go straight into the
+/// vault; backups skip it while the link is in place and restores recreate
+/// the link. The files are committed. Every file is checked before any is
+/// moved: it must be a regular file in the home directory that the vault
+/// would store as is, not through a template, transforms, encryption or
+/// redaction. Files already adopted are skipped. With `config.dry_run` set
+/// nothing is changed.
+pub fn adopt_dotfiles(config: &Config, files: &[String]) -> Result<AdoptSummary, DotfilesError> {
+    let mut summary = AdoptSummary::default();
+
+    {
+        let _lock = VaultLock::acquire(config)?;
+        let mut manifest = Manifest::load(config)?;
+        let own = own_paths(config);
+
+        let mut entries: Vec<DotfileEntry> = Vec::new();
+        for file in files {
+            let path = normalize_path(file, config);
+            if !path.exists() && !path.is_symlink() {
+                return Err(DotfilesError::DotfileNotFound(file.clone()));
+            }
+            let refuse =
+                |reason: &str| DotfilesError::AdoptFailed(file.clone(), reason.to_string());
+            if !path.starts_with(&config.home_dir) {
+                return Err(refuse("it is outside the home directory"));
+            }
+            if is_own_path(&own, &path) {
+                return Err(refuse("dotfilesvault keeps its own state there"));
+            }
+
+            let mut entry = manifest.entry_for(&Dotfile::new(path.clone(), config), config);
+            if entry.links_to_vault(config) {
+                debug!("Skipping {}: already adopted", entry.id);
+                continue;
+            }
+            if path.is_symlink() || !path.is_file() {
+                return Err(refuse("only regular files can be adopted"));
+            }
+            if entry.is_template()
+                || !entry.transforms.is_empty()
+                || !config.redact.is_empty()
+                || should_encrypt(config, &entry)?
+            {
+                return Err(refuse(
+                    "the vault stores it through a template, transforms, encryption or redaction",
+                ));
+            }
+
+            entry.linked = true;
+            entry.create_if_missing = false;
+            entries.push(entry);
+        }
+
+        for entry in &entries {
+            summary.adopted.push(entry.source.clone());
+            if config.dry_run {
+                continue;
+            }
+
+            let dotfile = entry.dotfile(config);
+            if let Some(parent) = dotfile.vault_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if dotfile.vault_path.is_symlink() {
+                fs::remove_file(&dotfile.vault_path)?;
+            }
+            // Renaming fails across file systems, where the file is copied
+            if fs::rename(&dotfile.original_path, &dotfile.vault_path).is_err() {
+                fs::copy(&dotfile.original_path, &dotfile.vault_path)?;
+                fs::remove_file(&dotfile.original_path)?;
+            }
+            create_symlink(&dotfile.vault_path, &dotfile.original_path)?;
+            manifest.entries.insert(entry.id.clone(), entry.clone());
+
+            info!(
+                "Adopted {:?}, it now links to {:?}",
+                dotfile.original_path, dotfile.vault_path
+            );
+        }
+
+        if config.dry_run || entries.is_empty() {
+            return Ok(summary);
+        }
+        manifest.save(config)?;
+    }
+
+    let names: Vec<String> = summary
+        .adopted
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    summary.commit = Some(commit_changes(
+        config,
+        &format!("Adopt {}", names.join(", ")),
+    )?);
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::restore::restore_all_dotfiles;
+    use crate::status::get_status;
+    use crate::untrack::untrack_dotfiles;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_adopt_dotfiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        let tmux = config.home_dir.join(".tmux.conf");
+        fs::write(&tmux, "set -g mouse on\n").unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+
+        let summary = adopt_dotfiles(&config, &[".tmux.conf".to_string()]).unwrap();
+        assert_eq!(summary.adopted, vec![PathBuf::from(".tmux.conf")]);
+        assert!(summary.commit.is_some());
+        let stored = config.vault_dir.join(".tmux.conf");
+        assert!(stored.is_file() && !stored.is_symlink());
+        assert_eq!(fs::read_link(&tmux).unwrap(), stored);
+
+        // Edits go straight into the vault, and backups leave the link alone
+        fs::write(&tmux, "set -g mouse off\n").unwrap();
+        assert_eq!(fs::read_to_string(&stored).unwrap(), "set -g mouse off\n");
+        backup_all_dotfiles(&config).unwrap();
+        assert!(stored.is_file() && !stored.is_symlink());
+        assert!(get_status(&config).unwrap().is_empty());
+
+        // Adopting again is a no-op, links and directories are refused
+        let again = adopt_dotfiles(&config, &[".tmux.conf".to_string()]).unwrap();
+        assert!(again.adopted.is_empty() && again.commit.is_none());
+        fs::create_dir_all(config.home_dir.join(".config")).unwrap();
+        assert!(matches!(
+            adopt_dotfiles(&config, &[".config".to_string()]),
+            Err(DotfilesError::AdoptFailed(_, _))
+        ));
+
+        // Restores recreate the link
+        fs::remove_file(&tmux).unwrap();
+        restore_all_dotfiles(&config).unwrap();
+        assert_eq!(fs::read_link(&tmux).unwrap(), stored);
+
+        // Removing it from the vault leaves a regular file behind
+        untrack_dotfiles(&config, &[".tmux.conf".to_string()], false).unwrap();
+        assert!(!tmux.is_symlink());
+        assert_eq!(fs::read_to_string(&tmux).unwrap(), "set -g mouse off\n");
+    }
+}
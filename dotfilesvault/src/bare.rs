@@ -0,0 +1,357 @@
+use anyhow::Result;
+#[cfg(feature = "git")]
+use git2::{Delta, DiffOptions, Repository, Tree, build::CheckoutBuilder};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backend::StoreSummary;
+use crate::conflicts::ConflictPolicy;
+#[cfg(feature = "git")]
+use crate::history::signature;
+use crate::lock::VaultLock;
+use crate::status::{FileStatus, StatusEntry};
+use crate::{Config, DotfilesError};
+
+/// How the vault stores the tracked files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VaultLayout {
+    /// The vault is a git worktree holding copies of the dotfiles
+    #[default]
+    Copy,
+
+    /// The vault is a bare repository whose worktree is the home directory,
+    /// so tracked files stay in place
+    Bare,
+}
+
+impl VaultLayout {
+    /// Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            VaultLayout::Copy => "copy",
+            VaultLayout::Bare => "bare",
+        }
+    }
+}
+
+impl FromStr for VaultLayout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "copy" => Ok(VaultLayout::Copy),
+            "bare" => Ok(VaultLayout::Bare),
+            _ => Err(format!("invalid layout {:?}, expected copy or bare", value)),
+        }
+    }
+}
+
+/// Open the vault's bare repository with the home directory as its worktree,
+/// creating it if needed
+///
+/// Like the usual `git --git-dir=... --work-tree=$HOME` setup, untracked
+/// files aren't shown by `git status`, since most of the home directory is
+/// never tracked.
+#[cfg(feature = "git")]
+pub fn open_bare_repo(config: &Config) -> Result<Repository, DotfilesError> {
+    let repo = if config.vault_dir.join("HEAD").is_file() {
+        Repository::open_bare(&config.vault_dir)?
+    } else if config.dry_run {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    } else {
+        let repo = Repository::init_bare(&config.vault_dir)?;
+        repo.config()?.set_str("status.showUntrackedFiles", "no")?;
+        info!("Initialized bare Git repository in {:?}", config.vault_dir);
+        repo
+    };
+    repo.set_workdir(&config.home_dir, false)?;
+
+    Ok(repo)
+}
+
+/// Get the tree of the vault's last commit, if it has one
+#[cfg(feature = "git")]
+fn head_tree(repo: &Repository) -> Option<Tree<'_>> {
+    repo.head().ok()?.peel_to_tree().ok()
+}
+
+/// Get the home-relative files that differ from the last commit
+///
+/// Only files in the last commit are compared, plus the untracked files in
+/// `paths` when `untracked` is set. Empty `paths` compares every file.
+#[cfg(feature = "git")]
+fn changed_files(
+    repo: &Repository,
+    paths: &[PathBuf],
+    untracked: bool,
+) -> Result<Vec<(PathBuf, Delta)>, DotfilesError> {
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(untracked)
+        .recurse_untracked_dirs(untracked)
+        .include_typechange(true);
+    for path in paths {
+        options.pathspec(path);
+    }
+
+    let tree = head_tree(repo);
+    let diff = repo.diff_tree_to_workdir(tree.as_ref(), Some(&mut options))?;
+    let changed = diff
+        .deltas()
+        .filter_map(|delta| {
+            let file = match delta.status() {
+                Delta::Deleted => delta.old_file(),
+                _ => delta.new_file(),
+            };
+            file.path().map(|path| (path.to_path_buf(), delta.status()))
+        })
+        .collect();
+
+    Ok(changed)
+}
+
+/// Get a home-relative path for a file given on the command line
+#[cfg(feature = "git")]
+fn home_relative(config: &Config, file: &str) -> Result<PathBuf, DotfilesError> {
+    let path = Path::new(file);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        config.home_dir.join(path)
+    };
+    if !path.exists() && !path.is_symlink() {
+        return Err(DotfilesError::DotfileNotFound(file.to_string()));
+    }
+
+    path.strip_prefix(&config.home_dir)
+        .map(Path::to_path_buf)
+        .map_err(|_| DotfilesError::DotfileNotFound(file.to_string()))
+}
+
+/// Commit tracked files from the home directory into the bare vault
+///
+/// With no `files` every tracked file is committed, including deletions;
+/// `files` also starts tracking new files and directories. Nothing is
+/// committed when nothing changed. With `config.dry_run` set only the files
+/// that would be committed are listed.
+#[cfg(feature = "git")]
+pub fn bare_backup(
+    config: &Config,
+    files: &[String],
+    message: &str,
+) -> Result<StoreSummary, DotfilesError> {
+    let repo = open_bare_repo(config)?;
+    let _lock = VaultLock::acquire(config)?;
+
+    let paths = files
+        .iter()
+        .map(|file| home_relative(config, file))
+        .collect::<Result<Vec<_>, _>>()?;
+    let changed = changed_files(&repo, &paths, !paths.is_empty())?;
+
+    let mut summary = StoreSummary {
+        files: changed.iter().map(|(path, _)| path.clone()).collect(),
+        ..StoreSummary::default()
+    };
+    if config.dry_run || changed.is_empty() {
+        return Ok(summary);
+    }
+
+    // Start from the last commit, the index is empty in fresh clones
+    let mut index = repo.index()?;
+    match head_tree(&repo) {
+        Some(tree) => index.read_tree(&tree)?,
+        None => index.clear()?,
+    }
+    for (path, delta) in &changed {
+        config.cancellation.check()?;
+        if *delta == Delta::Deleted {
+            index.remove_path(path)?;
+        } else {
+            index.add_path(path)?;
+        }
+        debug!("Staged {:?}", path);
+    }
+    index.write()?;
+
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let signature = signature(config)?;
+    let parents: Vec<_> = parent.iter().collect();
+    let commit = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    summary.commit = Some(commit.to_string());
+
+    info!(
+        "Committed {} files from the home directory",
+        summary.files.len()
+    );
+
+    Ok(summary)
+}
+
+/// Check out the vault's last commit into the home directory
+///
+/// Only tracked files that differ from the last commit are written, so
+/// untracked files in the home directory are never touched. Locally edited
+/// files are handled by `config.on_conflict`: restoring fails with
+/// `RestoreConflict`, leaves them alone, or overwrites them. Files deleted
+/// from the home directory are always restored. With `config.dry_run` set
+/// only the files that would be written are listed.
+#[cfg(feature = "git")]
+pub fn bare_restore(config: &Config) -> Result<StoreSummary, DotfilesError> {
+    let repo = open_bare_repo(config)?;
+    let _lock = VaultLock::acquire(config)?;
+
+    let mut summary = StoreSummary::default();
+    if head_tree(&repo).is_none() {
+        return Ok(summary);
+    }
+
+    for (path, delta) in changed_files(&repo, &[], false)? {
+        let edited = matches!(delta, Delta::Modified | Delta::Typechange);
+        if edited && config.on_conflict != ConflictPolicy::Overwrite {
+            if config.on_conflict == ConflictPolicy::Fail {
+                return Err(DotfilesError::RestoreConflict(
+                    config.home_dir.join(&path).display().to_string(),
+                ));
+            }
+            summary.skipped.push(path);
+        } else {
+            summary.files.push(path);
+        }
+    }
+    if config.dry_run || summary.files.is_empty() {
+        return Ok(summary);
+    }
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    for path in &summary.files {
+        checkout.path(path);
+    }
+    repo.checkout_head(Some(&mut checkout))?;
+
+    info!(
+        "Checked out {} files into the home directory",
+        summary.files.len()
+    );
+
+    Ok(summary)
+}
+
+/// Compare the tracked files in the home directory against the bare vault
+///
+/// Untracked files aren't reported, see `open_bare_repo`.
+#[cfg(feature = "git")]
+pub fn bare_status(config: &Config) -> Result<Vec<StatusEntry>, DotfilesError> {
+    if !config.vault_dir.join("HEAD").is_file() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+    let repo = open_bare_repo(config)?;
+
+    let mut status: Vec<StatusEntry> = changed_files(&repo, &[], false)?
+        .into_iter()
+        .map(|(path, delta)| StatusEntry {
+            path,
+            status: match delta {
+                Delta::Deleted => FileStatus::Deleted,
+                _ => FileStatus::Modified,
+            },
+        })
+        .collect();
+    status.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(status)
+}
+
+#[cfg(all(test, feature = "git"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bare_backup_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.layout = VaultLayout::Bare;
+        fs::create_dir_all(config.home_dir.join(".config/nvim")).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".config/nvim/init.lua"), "lua").unwrap();
+        fs::write(config.home_dir.join(".untracked"), "x").unwrap();
+
+        let summary = bare_backup(
+            &config,
+            &[".bashrc".to_string(), ".config/nvim".to_string()],
+            "Track",
+        )
+        .unwrap();
+        assert_eq!(
+            summary.files,
+            vec![
+                PathBuf::from(".bashrc"),
+                PathBuf::from(".config/nvim/init.lua")
+            ]
+        );
+        assert!(summary.commit.is_some());
+        assert!(config.vault_dir.join("HEAD").is_file());
+        assert!(!config.vault_dir.join(".bashrc").exists());
+
+        // Backing up everything only picks up tracked files, and commits only changes
+        assert!(
+            bare_backup(&config, &[], "Backup")
+                .unwrap()
+                .commit
+                .is_none()
+        );
+        fs::write(config.home_dir.join(".bashrc"), "bash 2").unwrap();
+        assert_eq!(
+            bare_status(&config).unwrap(),
+            vec![StatusEntry {
+                path: PathBuf::from(".bashrc"),
+                status: FileStatus::Modified,
+            }]
+        );
+        let summary = bare_backup(&config, &[], "Backup").unwrap();
+        assert_eq!(summary.files, vec![PathBuf::from(".bashrc")]);
+        assert!(bare_status(&config).unwrap().is_empty());
+
+        // Restores bring back deleted files and respect local edits
+        fs::remove_file(config.home_dir.join(".config/nvim/init.lua")).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "edited").unwrap();
+        assert!(matches!(
+            bare_restore(&config),
+            Err(DotfilesError::RestoreConflict(_))
+        ));
+        config.on_conflict = ConflictPolicy::Skip;
+        let summary = bare_restore(&config).unwrap();
+        assert_eq!(summary.files, vec![PathBuf::from(".config/nvim/init.lua")]);
+        assert_eq!(summary.skipped, vec![PathBuf::from(".bashrc")]);
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".config/nvim/init.lua")).unwrap(),
+            "lua"
+        );
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "edited"
+        );
+        assert!(config.home_dir.join(".untracked").exists());
+
+        config.on_conflict = ConflictPolicy::Overwrite;
+        bare_restore(&config).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "bash 2"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::Result;
+use log::debug;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config_file::VAULT_DIR_VAR;
+use crate::encryption::{is_encrypted_file, should_encrypt};
+use crate::manifest::{Condition, DotfileEntry, Manifest, PermissionsPolicy};
+use crate::metadata::MetadataManifest;
+use crate::sync::remote_url;
+use crate::utils::{shell_quote, write_file};
+use crate::{Config, DotfilesError};
+
+/// Functions the bootstrap script restores files with
+const PRELUDE: &str = r#"set -eu
+
+command -v git >/dev/null 2>&1 || { echo "git is required" >&2; exit 1; }
+
+case "$(uname -s)" in
+    Linux) OS=linux ;;
+    Darwin) OS=macos ;;
+    FreeBSD) OS=freebsd ;;
+    OpenBSD) OS=openbsd ;;
+    NetBSD) OS=netbsd ;;
+    *) OS=unknown ;;
+esac
+HOSTNAME="${HOSTNAME:-$(hostname 2>/dev/null || true)}"
+
+if [ -d "$VAULT/.git" ]; then
+    git -C "$VAULT" pull --ff-only
+else
+    git clone "$REMOTE" "$VAULT"
+fi
+
+RESTORED=0
+KEPT=0
+
+# restore STORED TARGET [MODE]: copy a vault file to the home directory,
+# keeping a home file that differs as TARGET.pre-dotfilesvault
+restore() {
+    src="$VAULT/$1"
+    dest="$HOME/$2"
+    if [ ! -e "$src" ] && [ ! -L "$src" ]; then
+        echo "not in the vault: $1" >&2
+        return 0
+    fi
+    if [ -L "$src" ]; then
+        mkdir -p "$(dirname "$dest")"
+        ln -sfn "$(readlink "$src")" "$dest"
+    else
+        if [ -e "$dest" ] && ! cmp -s "$src" "$dest"; then
+            cp -p "$dest" "$dest.pre-dotfilesvault"
+            KEPT=$((KEPT + 1))
+        fi
+        mkdir -p "$(dirname "$dest")"
+        cp "$src" "$dest"
+        if [ -n "${3:-}" ]; then
+            chmod "$3" "$dest"
+        fi
+    fi
+    RESTORED=$((RESTORED + 1))
+}
+"#;
+
+/// How the bootstrap script handles a tracked file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// Copied, with the mode to set and the shell test guarding the copy
+    Copy {
+        mode: Option<u32>,
+        guard: Option<String>,
+    },
+
+    /// Left to `dotfilesvault restore`, for the reason given
+    NeedsTool(&'static str),
+}
+
+/// Generate a shell script that sets up a new machine from the vault
+///
+/// The script clones the vault remote (or pulls, if the vault is already
+/// there) with plain git and copies every tracked file into the home
+/// directory, so it runs before dotfilesvault is installed. It is generated
+/// from the manifest: entry conditions become shell tests, starter content
+/// is only copied where the file is missing, and recorded modes and
+/// permission policies are applied. Encrypted files, templates and files
+/// with transforms need dotfilesvault and are listed at the end instead.
+pub fn bootstrap_script(config: &Config) -> Result<String, DotfilesError> {
+    let url = remote_url(config)?
+        .or_else(|| config.remote_url.clone())
+        .ok_or_else(|| {
+            DotfilesError::SyncFailed(
+                "no remote configured, set git.remote in the config file".to_string(),
+            )
+        })?;
+    let manifest = Manifest::load(config)?;
+    let metadata = MetadataManifest::load(config)?;
+
+    let vault = match config.vault_dir.strip_prefix(&config.home_dir) {
+        Ok(relative) => format!("\"$HOME\"/{}", shell_quote(&portable(relative))),
+        Err(_) => shell_quote(&portable(&config.vault_dir)),
+    };
+
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str("# Generated by dotfilesvault from the vault's manifest, regenerate it with\n");
+    script.push_str("# dotfilesvault bootstrap. Sets up this machine without dotfilesvault.\n\n");
+    let _ = writeln!(script, "REMOTE={}", shell_quote(&url));
+    let _ = writeln!(script, "VAULT=${{{}:-{}}}", VAULT_DIR_VAR, vault);
+    script.push('\n');
+    script.push_str(PRELUDE);
+    script.push('\n');
+
+    let mut needs_tool = Vec::new();
+    for entry in manifest.entries.values() {
+        // A new machine has no profile yet, so the common copy is restored
+        let stored = &entry.target;
+        let step = entry_step(config, entry, &metadata, stored)?;
+        let (mode, guard) = match step {
+            Step::Copy { mode, guard } => (mode, guard),
+            Step::NeedsTool(reason) => {
+                needs_tool.push(format!("{} ({})", entry.id, reason));
+                continue;
+            }
+        };
+
+        let mut line = format!(
+            "restore {} {}",
+            shell_quote(&portable(stored)),
+            shell_quote(&portable(&entry.source))
+        );
+        if let Some(mode) = mode {
+            let _ = write!(line, " {:o}", mode & 0o7777);
+        }
+        match guard {
+            Some(guard) => {
+                let _ = writeln!(script, "if {}; then {}; fi", guard, line);
+            }
+            None => {
+                let _ = writeln!(script, "{}", line);
+            }
+        }
+    }
+
+    script.push_str("\necho \"Restored $RESTORED files\"\n");
+    script.push_str(
+        "[ \"$KEPT\" -eq 0 ] || echo \"Kept $KEPT replaced files as *.pre-dotfilesvault\"\n",
+    );
+    if !needs_tool.is_empty() {
+        script
+            .push_str("echo \"Install dotfilesvault and run dotfilesvault restore --all for:\"\n");
+        for file in &needs_tool {
+            let _ = writeln!(script, "echo {}", shell_quote(&format!("  {}", file)));
+        }
+    }
+
+    debug!(
+        "Generated a bootstrap script for {} files, {} left to dotfilesvault",
+        manifest.entries.len() - needs_tool.len(),
+        needs_tool.len()
+    );
+
+    Ok(script)
+}
+
+/// Write the bootstrap script to a file, executable on Unix
+pub fn write_bootstrap_script(config: &Config, output: &Path) -> Result<(), DotfilesError> {
+    write_file(output, bootstrap_script(config)?.as_bytes())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(output, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+/// Decide how the script handles an entry
+fn entry_step(
+    config: &Config,
+    entry: &DotfileEntry,
+    metadata: &MetadataManifest,
+    stored: &Path,
+) -> Result<Step, DotfilesError> {
+    if !entry.transforms.is_empty() {
+        return Ok(Step::NeedsTool("transformed"));
+    }
+    if entry.is_template() {
+        return Ok(Step::NeedsTool("template"));
+    }
+    if should_encrypt(config, entry)? || is_encrypted_file(&config.vault_dir.join(stored)) {
+        return Ok(Step::NeedsTool("encrypted"));
+    }
+    if !config.redact.is_empty() {
+        return Ok(Step::NeedsTool("may be redacted"));
+    }
+
+    let mut tests = Vec::new();
+    for condition in &entry.conditions {
+        match condition {
+            Condition::Os(os) => tests.push(format!("[ \"$OS\" = {} ]", shell_quote(os))),
+            Condition::Hostname(hostname) => {
+                tests.push(format!("[ \"$HOSTNAME\" = {} ]", shell_quote(hostname)))
+            }
+            Condition::Env(name) if is_shell_name(name) => {
+                tests.push(format!("[ -n \"${{{}:-}}\" ]", name))
+            }
+            Condition::Env(_) => return Ok(Step::NeedsTool("condition")),
+        }
+    }
+    if entry.create_if_missing {
+        let home = format!("\"$HOME\"/{}", shell_quote(&portable(&entry.source)));
+        tests.push(format!("[ ! -e {0} ] && [ ! -L {0} ]", home));
+    }
+
+    let mode = match entry.permissions {
+        PermissionsPolicy::Preserve => metadata.files.get(&entry.id).and_then(|file| file.mode),
+        PermissionsPolicy::Private => Some(0o600),
+        PermissionsPolicy::Mode(mode) => Some(mode),
+    };
+
+    Ok(Step::Copy {
+        mode,
+        guard: (!tests.is_empty()).then(|| tests.join(" && ")),
+    })
+}
+
+/// Check if a name can be used as a shell variable
+fn is_shell_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Write a path with `/` separators, as the script always runs on Unix
+fn portable(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::history::commit_changes;
+    use crate::manifest::add_paths;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bootstrap_script_restores_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(config.home_dir.join("bin")).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join("bin/setup.sh"), "setup").unwrap();
+        fs::write(config.home_dir.join(".it's"), "quoted").unwrap();
+        fs::write(config.home_dir.join(".macrc"), "mac").unwrap();
+        add_paths(&config, &["bin/setup.sh".to_string()]).unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        let mut manifest = Manifest::load(&config).unwrap();
+        manifest.entries.get_mut(".macrc").unwrap().conditions =
+            vec![Condition::Os("no-such-os".to_string())];
+        manifest.entries.get_mut(".bashrc").unwrap().permissions = PermissionsPolicy::Private;
+        manifest.save(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+
+        assert!(matches!(
+            bootstrap_script(&config),
+            Err(DotfilesError::SyncFailed(_))
+        ));
+        config.remote_url = Some(config.vault_dir.display().to_string());
+
+        let script = bootstrap_script(&config).unwrap();
+        assert!(script.contains("restore '.bashrc' '.bashrc' 600\n"));
+        assert!(script.contains("if [ \"$OS\" = 'no-such-os' ]; then restore '.macrc'"));
+
+        // The script clones the vault and restores into a new home
+        let output = temp_dir.path().join("install.sh");
+        write_bootstrap_script(&config, &output).unwrap();
+        let home = temp_dir.path().join("new-home");
+        fs::create_dir_all(&home).unwrap();
+        fs::write(home.join(".bashrc"), "default").unwrap();
+        let status = Command::new(&output)
+            .env("HOME", &home)
+            .env("DOTFILESVAULT_DIR", home.join("vault"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            fs::read_to_string(home.join("bin/setup.sh")).unwrap(),
+            "setup"
+        );
+        assert_eq!(fs::read_to_string(home.join(".it's")).unwrap(), "quoted");
+        assert_eq!(fs::read_to_string(home.join(".bashrc")).unwrap(), "bash");
+        assert_eq!(
+            fs::read_to_string(home.join(".bashrc.pre-dotfilesvault")).unwrap(),
+            "default"
+        );
+        assert!(!home.join(".macrc").exists());
+        assert!(home.join("vault/.git").is_dir());
+    }
+}
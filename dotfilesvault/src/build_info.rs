@@ -0,0 +1,103 @@
+use serde::Serialize;
+
+use crate::capabilities::detect;
+
+/// An optional subsystem and whether the binary was compiled with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Feature {
+    /// Name of the subsystem, e.g. `tui`
+    pub name: &'static str,
+
+    /// Whether the binary was compiled with it
+    pub enabled: bool,
+}
+
+/// Version and build metadata of the running binary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+
+    /// Abbreviated commit the binary was built from, or `unknown`
+    pub git_sha: &'static str,
+
+    /// When the binary was built, in RFC 3339 format (UTC)
+    pub build_date: &'static str,
+
+    /// Target triple the binary was built for
+    pub target: &'static str,
+
+    /// Optional subsystems
+    pub features: Vec<Feature>,
+}
+
+/// Get the optional subsystems and whether the binary was compiled with them
+///
+/// Encryption and syncing with a remote are always compiled in, but are
+/// listed so scripts can check for them without knowing that. `https` and
+/// `ssh` are the remote transports libgit2 was built with.
+pub fn features() -> Vec<Feature> {
+    let capabilities = detect();
+
+    vec![
+        Feature {
+            name: "encryption",
+            enabled: true,
+        },
+        Feature {
+            name: "remote",
+            enabled: true,
+        },
+        Feature {
+            name: "https",
+            enabled: capabilities.https,
+        },
+        Feature {
+            name: "ssh",
+            enabled: capabilities.ssh,
+        },
+        Feature {
+            name: "s3",
+            enabled: cfg!(feature = "s3"),
+        },
+        Feature {
+            name: "tui",
+            enabled: cfg!(feature = "tui"),
+        },
+        Feature {
+            name: "wasm",
+            enabled: cfg!(feature = "wasm"),
+        },
+    ]
+}
+
+/// Get the version and build metadata of the running binary
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("DOTFILESVAULT_GIT_SHA"),
+        build_date: env!("DOTFILESVAULT_BUILD_DATE"),
+        target: env!("DOTFILESVAULT_TARGET"),
+        features: features(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.target.is_empty());
+        assert!(info.build_date.ends_with('Z'));
+        assert_eq!(
+            info.features
+                .iter()
+                .find(|feature| feature.name == "tui")
+                .map(|feature| feature.enabled),
+            Some(cfg!(feature = "tui"))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::Result;
+#[cfg(feature = "git")]
+use git2::{ObjectType, Oid};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the cache file, see `Config::state_path`
+pub const HASH_CACHE_FILE: &str = "dotfilesvault-hashes.json";
+
+/// Files modified this close to being read aren't cached
+///
+/// Network filesystems may store modification times with coarse
+/// granularity, so a file changed again right after it was read could keep
+/// the same time.
+pub const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Cached hashes are re-checked by reading the file at least this often
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A hash of a home file's content and the file state it was computed for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedHash {
+    /// Size of the file in bytes
+    size: u64,
+
+    /// Modification time in nanoseconds since the Unix epoch
+    mtime: u128,
+
+    /// When the hash was computed, in seconds since the Unix epoch
+    hashed_at: u64,
+
+    /// Git blob hash of the content
+    hash: String,
+}
+
+/// Hashes of home files, so slow network home directories aren't read again
+///
+/// Only used when `home_cache` is enabled. A cached hash is used while the
+/// file's size and modification time are unchanged, unless it is older than
+/// `MAX_AGE`. Files modified within `RACY_WINDOW` of being read aren't
+/// cached. Clones share the cache, which is loaded on first use and written
+/// by `save`.
+#[derive(Debug, Clone, Default)]
+pub struct HashCache {
+    /// Cached hashes by absolute path, `None` until loaded
+    entries: Arc<Mutex<Option<HashMap<PathBuf, CachedHash>>>>,
+
+    /// Set when hashes were added since the cache was loaded
+    dirty: Arc<AtomicBool>,
+}
+
+impl HashCache {
+    /// Get the path of the cache file of a vault
+    pub fn path(config: &Config) -> PathBuf {
+        config.state_path(HASH_CACHE_FILE)
+    }
+
+    /// Get the git blob hash of a home file, from the cache if it's still valid
+    #[cfg(feature = "git")]
+    pub fn home_hash(&self, config: &Config, path: &Path) -> Result<Oid, DotfilesError> {
+        if !config.home_cache {
+            return Ok(Oid::hash_object(ObjectType::Blob, &fs::read(path)?)?);
+        }
+
+        let metadata = fs::metadata(path)?;
+        let mtime = nanos_since_epoch(metadata.modified()?);
+        let now = SystemTime::now();
+
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entries = entries.get_or_insert_with(|| load(config));
+
+        if let Some(cached) = entries.get(path)
+            && cached.size == metadata.len()
+            && cached.mtime == mtime
+            && secs_since_epoch(now).saturating_sub(cached.hashed_at) < MAX_AGE.as_secs()
+            && let Ok(hash) = Oid::from_str(&cached.hash)
+        {
+            return Ok(hash);
+        }
+
+        debug!("Hashing {:?}", path);
+        let hash = Oid::hash_object(ObjectType::Blob, &fs::read(path)?)?;
+
+        let racy = nanos_since_epoch(now).saturating_sub(mtime) < RACY_WINDOW.as_nanos();
+        if racy {
+            entries.remove(path);
+        } else {
+            entries.insert(
+                path.to_path_buf(),
+                CachedHash {
+                    size: metadata.len(),
+                    mtime,
+                    hashed_at: secs_since_epoch(now),
+                    hash: hash.to_string(),
+                },
+            );
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+
+        Ok(hash)
+    }
+
+    /// Write new hashes to the cache file, dropping expired ones
+    pub fn save(&self, config: &Config) -> Result<(), DotfilesError> {
+        if !self.dirty.swap(false, Ordering::Relaxed)
+            || !config.vault_dir.join(".git").is_dir()
+            || config.read_only
+        {
+            return Ok(());
+        }
+
+        let entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(entries) = entries.as_ref() else {
+            return Ok(());
+        };
+
+        let now = secs_since_epoch(SystemTime::now());
+        let fresh: HashMap<&PathBuf, &CachedHash> = entries
+            .iter()
+            .filter(|(_, cached)| now.saturating_sub(cached.hashed_at) < MAX_AGE.as_secs())
+            .collect();
+        let content = serde_json::to_vec(&fresh)
+            .map_err(|err| DotfilesError::Io(std::io::Error::other(err)))?;
+        write_file(Self::path(config), &content)?;
+
+        debug!("Saved {} cached hashes", fresh.len());
+
+        Ok(())
+    }
+}
+
+/// Read the cache file, starting empty if it's missing or unreadable
+fn load(config: &Config) -> HashMap<PathBuf, CachedHash> {
+    fs::read(HashCache::path(config))
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Delete the cache file, returning whether there was one
+pub fn clear_cache(config: &Config) -> Result<bool, DotfilesError> {
+    match fs::remove_file(HashCache::path(config)) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Get the git blob hash of content
+#[cfg(feature = "git")]
+pub fn content_hash(content: &[u8]) -> Result<Oid, DotfilesError> {
+    Ok(Oid::hash_object(ObjectType::Blob, content)?)
+}
+
+fn nanos_since_epoch(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or(0)
+}
+
+fn secs_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(all(test, feature = "git"))]
+mod tests {
+    use super::*;
+    use crate::history::init_git_repo;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hash_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.home_cache = true;
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        let bashrc = config.home_dir.join(".bashrc");
+        fs::write(&bashrc, "v1").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&bashrc)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let v1 = content_hash(b"v1").unwrap();
+        assert_eq!(config.hash_cache.home_hash(&config, &bashrc).unwrap(), v1);
+        config.hash_cache.save(&config).unwrap();
+        assert!(HashCache::path(&config).is_file());
+
+        // A fresh process reads the hash from the cache file
+        let restarted = Config {
+            hash_cache: HashCache::default(),
+            ..config.clone()
+        };
+        fs::write(&bashrc, "v2").unwrap();
+        File::options()
+            .write(true)
+            .open(&bashrc)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_eq!(
+            restarted.hash_cache.home_hash(&restarted, &bashrc).unwrap(),
+            v1
+        );
+
+        // A changed size or modification time invalidates the cached hash
+        fs::write(&bashrc, "v22").unwrap();
+        assert_eq!(
+            restarted.hash_cache.home_hash(&restarted, &bashrc).unwrap(),
+            content_hash(b"v22").unwrap()
+        );
+
+        assert!(clear_cache(&config).unwrap());
+        assert!(!clear_cache(&config).unwrap());
+    }
+}
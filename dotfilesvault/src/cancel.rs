@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::DotfilesError;
+
+/// Cooperative cancellation of long-running operations
+///
+/// Clones share the cancelled flag, so a Ctrl-C handler can cancel the token
+/// that an operation checks. Scans, backups, restores and network transfers
+/// check it as they go and stop with `DotfilesError::Cancelled`, or with
+/// `DotfilesError::TimedOut` once the timeout has passed.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    /// Set when the operation should stop
+    cancelled: Arc<AtomicBool>,
+
+    /// When the operation times out, and the timeout it was given
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl CancellationToken {
+    /// Create a token that is only cancelled explicitly
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a token that also cancels `timeout` from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Some((Instant::now() + timeout, timeout)),
+        }
+    }
+
+    /// Ask operations using this token, or any of its clones, to stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if operations should stop, because of cancellation or the timeout
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Return an error if operations should stop
+    pub fn check(&self) -> Result<(), DotfilesError> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(DotfilesError::Cancelled);
+        }
+
+        match self.deadline {
+            Some((deadline, timeout)) if Instant::now() >= deadline => {
+                Err(DotfilesError::TimedOut(timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+
+        clone.cancel();
+        assert!(matches!(token.check(), Err(DotfilesError::Cancelled)));
+
+        let token = CancellationToken::with_timeout(Duration::ZERO);
+        assert!(matches!(token.check(), Err(DotfilesError::TimedOut(_))));
+        assert!(
+            CancellationToken::with_timeout(Duration::from_secs(60))
+                .check()
+                .is_ok()
+        );
+    }
+}
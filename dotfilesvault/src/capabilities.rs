@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::{DotfilesError, t};
+
+/// How a remote URL is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    /// A repository on this machine, a path or a `file://` URL
+    Local,
+
+    /// An `https://` URL, which needs TLS and CA certificates
+    Https,
+
+    /// An `ssh://` URL or the scp-like `user@host:path`
+    Ssh,
+
+    /// Anything else, e.g. `http://` or `git://`, left to git to judge
+    Other,
+}
+
+impl Transport {
+    /// Find out how a remote URL is reached
+    pub fn of(url: &str) -> Self {
+        if url.starts_with("file://") || Path::new(url).exists() {
+            return Transport::Local;
+        }
+        if url.starts_with("https://") {
+            return Transport::Https;
+        }
+        if url.starts_with("ssh://") || url.starts_with("git+ssh://") {
+            return Transport::Ssh;
+        }
+        if url.contains("://") {
+            return Transport::Other;
+        }
+
+        // scp-like syntax has a colon before any slash, unlike local paths
+        // and Windows drive letters
+        match url.split_once(':') {
+            Some((host, _)) if host.len() > 1 && !host.contains('/') => Transport::Ssh,
+            _ => Transport::Local,
+        }
+    }
+}
+
+/// Why a remote can't be used from this machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteProblem {
+    /// libgit2 was built without HTTPS support
+    NoHttps,
+
+    /// libgit2 was built without SSH support
+    NoSsh,
+
+    /// No CA certificates were found to verify HTTPS servers with
+    NoCertificates,
+
+    /// The TLS handshake failed
+    Tls(String),
+
+    /// Connecting or authenticating over SSH failed
+    Ssh(String),
+
+    /// The server couldn't be reached
+    Network(String),
+}
+
+impl RemoteProblem {
+    /// Describe the problem and how to fix it
+    pub fn describe(&self) -> String {
+        match self {
+            RemoteProblem::NoHttps => t!("remote-no-https"),
+            RemoteProblem::NoSsh => t!("remote-no-ssh"),
+            RemoteProblem::NoCertificates => t!("remote-no-certificates"),
+            RemoteProblem::Tls(error) => t!("remote-tls", error = error.as_str()),
+            RemoteProblem::Ssh(error) => t!("remote-ssh", error = error.as_str()),
+            RemoteProblem::Network(error) => t!("remote-network", error = error.as_str()),
+        }
+    }
+}
+
+/// Remote transports this machine supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Whether libgit2 was built with HTTPS support
+    pub https: bool,
+
+    /// Whether libgit2 was built with SSH support
+    pub ssh: bool,
+
+    /// Whether CA certificates were found for HTTPS
+    pub ca_certificates: bool,
+}
+
+/// Detect the remote transports this machine supports
+pub fn detect() -> Capabilities {
+    let version = git2::Version::get();
+
+    Capabilities {
+        https: version.https(),
+        ssh: version.ssh(),
+        ca_certificates: has_ca_certificates(),
+    }
+}
+
+/// Check if CA certificates can be found where OpenSSL looks for them
+///
+/// Only Unix systems other than macOS use OpenSSL; macOS and Windows
+/// verify against the system's own certificate store.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn has_ca_certificates() -> bool {
+    let probe = openssl_probe::probe();
+    probe.cert_file.is_some() || probe.cert_dir.is_some()
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn has_ca_certificates() -> bool {
+    true
+}
+
+/// Check that a remote can be used before connecting to it
+///
+/// Fails with `RemoteUnavailable` when its transport isn't supported on this
+/// machine, instead of the cryptic error libgit2 gives halfway through.
+pub fn check_remote(url: &str) -> Result<(), DotfilesError> {
+    let capabilities = detect();
+    let problem = match Transport::of(url) {
+        Transport::Https if !capabilities.https => RemoteProblem::NoHttps,
+        Transport::Https if !capabilities.ca_certificates => RemoteProblem::NoCertificates,
+        Transport::Ssh if !capabilities.ssh => RemoteProblem::NoSsh,
+        _ => return Ok(()),
+    };
+
+    Err(DotfilesError::RemoteUnavailable(url.to_string(), problem))
+}
+
+/// Turn a git error talking to a remote into `RemoteUnavailable` if it's
+/// about TLS, SSH or the network, or keep it as it is
+pub fn remote_error(url: &str, err: git2::Error) -> DotfilesError {
+    let message = err.message().to_string();
+    let problem = match err.class() {
+        git2::ErrorClass::Ssl => RemoteProblem::Tls(message),
+        git2::ErrorClass::Ssh => RemoteProblem::Ssh(message),
+        git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Os => {
+            RemoteProblem::Network(message)
+        }
+        _ => return DotfilesError::Git(err),
+    };
+
+    DotfilesError::RemoteUnavailable(url.to_string(), problem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_of() {
+        assert_eq!(
+            Transport::of("https://github.com/me/dotfiles"),
+            Transport::Https
+        );
+        assert_eq!(Transport::of("ssh://git@host/dotfiles.git"), Transport::Ssh);
+        assert_eq!(
+            Transport::of("git@github.com:me/dotfiles.git"),
+            Transport::Ssh
+        );
+        assert_eq!(Transport::of("file:///srv/dotfiles.git"), Transport::Local);
+        assert_eq!(Transport::of("/srv/dotfiles.git"), Transport::Local);
+        assert_eq!(Transport::of(r"C:\dotfiles"), Transport::Local);
+        assert_eq!(Transport::of("git://host/dotfiles"), Transport::Other);
+
+        assert!(check_remote("/srv/dotfiles.git").is_ok());
+        let err = remote_error(
+            "https://host/dotfiles",
+            git2::Error::new(
+                git2::ErrorCode::GenericError,
+                git2::ErrorClass::Ssl,
+                "certificate verify failed",
+            ),
+        );
+        assert!(matches!(
+            err,
+            DotfilesError::RemoteUnavailable(_, RemoteProblem::Tls(_))
+        ));
+    }
+}
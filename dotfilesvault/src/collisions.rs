@@ -0,0 +1,337 @@
+use anyhow::Result;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::restore::list_backed_up_dotfiles;
+use crate::{Config, DotfilesError};
+
+/// What backups and restores do with paths that differ only in case
+///
+/// A vault made on Linux can hold `.Profile` and `.profile`, which are the
+/// same file on the case-insensitive filesystems macOS and Windows use by
+/// default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseCollisionPolicy {
+    /// Back up and restore one of the files, and report the collision
+    #[default]
+    Warn,
+
+    /// Leave colliding files out of backups, and restore none of them on
+    /// case-insensitive filesystems
+    Refuse,
+
+    /// Don't check for collisions
+    Allow,
+}
+
+impl CaseCollisionPolicy {
+    /// Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaseCollisionPolicy::Warn => "warn",
+            CaseCollisionPolicy::Refuse => "refuse",
+            CaseCollisionPolicy::Allow => "allow",
+        }
+    }
+}
+
+impl FromStr for CaseCollisionPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "warn" => Ok(CaseCollisionPolicy::Warn),
+            "refuse" => Ok(CaseCollisionPolicy::Refuse),
+            "allow" => Ok(CaseCollisionPolicy::Allow),
+            _ => Err(format!(
+                "invalid case collision policy {:?}, expected warn, refuse or allow",
+                value
+            )),
+        }
+    }
+}
+
+/// Paths that are the same file, or a file and a directory, when case is ignored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaseCollision {
+    /// The colliding spellings, sorted
+    pub paths: Vec<PathBuf>,
+}
+
+impl CaseCollision {
+    /// Get the spelling a path is, or is inside of
+    fn spelling_of(&self, path: &Path) -> Option<&PathBuf> {
+        self.paths
+            .iter()
+            .find(|spelling| path.starts_with(spelling))
+    }
+}
+
+/// Find the files among relative paths that collide when case is ignored
+///
+/// Besides two files differing only in case, a file collides with a
+/// directory whose name differs only in case. Directories merely differing
+/// in case don't collide, their files end up in one directory.
+pub fn find_case_collisions(paths: &[PathBuf]) -> Vec<CaseCollision> {
+    let files: HashSet<&PathBuf> = paths.iter().collect();
+    let mut spellings: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+
+    for path in paths {
+        for prefix in path
+            .ancestors()
+            .filter(|prefix| !prefix.as_os_str().is_empty())
+        {
+            spellings
+                .entry(prefix.to_string_lossy().to_lowercase())
+                .or_default()
+                .insert(prefix.to_path_buf());
+        }
+    }
+
+    spellings
+        .into_values()
+        .filter(|paths| paths.len() > 1 && paths.iter().any(|path| files.contains(path)))
+        .map(|paths| CaseCollision {
+            paths: paths.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Check if a directory is on a case-insensitive filesystem
+///
+/// The filesystem is probed with a temporary file. If that fails, macOS and
+/// Windows are assumed to be case-insensitive and other platforms not.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let probe = tempfile::Builder::new()
+        .prefix(".dotfilesvault-CaseProbe")
+        .tempfile_in(dir);
+
+    match probe {
+        Ok(probe) => {
+            let name = probe.path().file_name().unwrap_or_default();
+            dir.join(name.to_string_lossy().to_lowercase()).exists()
+        }
+        Err(err) => {
+            debug!("Can't probe {:?} for case sensitivity: {}", dir, err);
+            cfg!(any(target_os = "macos", target_os = "windows"))
+        }
+    }
+}
+
+/// Check the entries a backup is about to store for case collisions
+///
+/// Their vault paths are checked against each other and the files already
+/// in the vault, whatever platform the backup runs on, since the vault may
+/// be checked out elsewhere. With the `refuse` policy, entries whose exact
+/// path isn't in the vault yet are left out of the backup, and the manifest,
+/// when they collide.
+pub fn check_backup_collisions(
+    config: &Config,
+    manifest: &mut Manifest,
+    entries: &mut Vec<DotfileEntry>,
+) -> Result<Vec<CaseCollision>, DotfilesError> {
+    if config.on_case_collision == CaseCollisionPolicy::Allow {
+        return Ok(Vec::new());
+    }
+
+    let stored: HashSet<PathBuf> = if config.vault_dir.exists() {
+        list_backed_up_dotfiles(config)?.into_iter().collect()
+    } else {
+        HashSet::new()
+    };
+    let entry_paths: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| entry.stored_path(config))
+        .collect();
+
+    let mut paths: Vec<PathBuf> = stored.iter().cloned().collect();
+    paths.extend(
+        entry_paths
+            .iter()
+            .filter(|path| !stored.contains(*path))
+            .cloned(),
+    );
+
+    let collisions: Vec<CaseCollision> = find_case_collisions(&paths)
+        .into_iter()
+        .filter(|collision| {
+            entry_paths
+                .iter()
+                .any(|path| collision.spelling_of(path).is_some())
+        })
+        .collect();
+
+    for collision in &collisions {
+        warn!("Paths differing only in case: {:?}", collision.paths);
+    }
+
+    if config.on_case_collision == CaseCollisionPolicy::Refuse {
+        let mut index = 0;
+        entries.retain(|entry| {
+            let path = &entry_paths[index];
+            index += 1;
+            let refused = !stored.contains(path)
+                && collisions
+                    .iter()
+                    .any(|collision| collision.spelling_of(path).is_some());
+            if refused {
+                warn!(
+                    "Not backing up {:?}: it differs only in case from another file",
+                    entry.source
+                );
+                manifest.entries.remove(&entry.id);
+            }
+            !refused
+        });
+    }
+
+    Ok(collisions)
+}
+
+/// Pick the entries a restore writes when their home paths collide in case
+///
+/// Only applies to case-insensitive home directories, where colliding files
+/// would overwrite each other. From each collision the spelling already in
+/// the home directory is restored, or the first one if there is none. With
+/// the `refuse` policy none is restored. Returns the collisions and the
+/// home-relative paths of the entries left out.
+pub fn resolve_restore_collisions(
+    config: &Config,
+    entries: &mut Vec<DotfileEntry>,
+    case_insensitive: bool,
+) -> (Vec<CaseCollision>, Vec<PathBuf>) {
+    if !case_insensitive || config.on_case_collision == CaseCollisionPolicy::Allow {
+        return (Vec::new(), Vec::new());
+    }
+
+    let sources: Vec<PathBuf> = entries.iter().map(|entry| entry.source.clone()).collect();
+    let collisions = find_case_collisions(&sources);
+    if collisions.is_empty() {
+        return (collisions, Vec::new());
+    }
+
+    let kept: Vec<Option<&PathBuf>> = collisions
+        .iter()
+        .map(|collision| {
+            if config.on_case_collision == CaseCollisionPolicy::Refuse {
+                return None;
+            }
+            collision
+                .paths
+                .iter()
+                .find(|spelling| exists_exactly(&config.home_dir, spelling))
+                .or_else(|| collision.paths.first())
+        })
+        .collect();
+
+    let mut skipped = Vec::new();
+    entries.retain(|entry| {
+        let left_out = collisions.iter().zip(&kept).any(|(collision, kept)| {
+            collision
+                .spelling_of(&entry.source)
+                .is_some_and(|spelling| Some(spelling) != *kept)
+        });
+        if left_out {
+            warn!(
+                "Not restoring {:?}: it differs only in case from another tracked file",
+                entry.source
+            );
+            skipped.push(entry.source.clone());
+        }
+        !left_out
+    });
+
+    (collisions, skipped)
+}
+
+/// Check if a relative path exists under a directory with exactly this case
+fn exists_exactly(dir: &Path, relative: &Path) -> bool {
+    let mut current = dir.to_path_buf();
+
+    for component in relative.components() {
+        let found = fs::read_dir(&current).ok().is_some_and(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.file_name() == component.as_os_str())
+        });
+        if !found {
+            return false;
+        }
+        current.push(component);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Dotfile;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_case_collisions() {
+        let paths: Vec<PathBuf> = [
+            ".profile",
+            ".Profile",
+            ".vim",
+            ".VIM/colors",
+            ".config/a",
+            ".Config/b",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let collisions = find_case_collisions(&paths);
+        assert_eq!(
+            collisions,
+            vec![
+                CaseCollision {
+                    paths: vec![PathBuf::from(".Profile"), PathBuf::from(".profile")]
+                },
+                CaseCollision {
+                    paths: vec![PathBuf::from(".VIM"), PathBuf::from(".vim")]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_restore_collisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".Profile"), "existing").unwrap();
+
+        let mut manifest = Manifest::default();
+        for name in [".profile", ".Profile", ".bashrc"] {
+            manifest.track(&Dotfile::new(config.home_dir.join(name), &config), &config);
+        }
+        let entries: Vec<DotfileEntry> = manifest.entries.into_values().collect();
+
+        // Case-sensitive home directories restore everything
+        let mut all = entries.clone();
+        resolve_restore_collisions(&config, &mut all, false);
+        assert_eq!(all.len(), 3);
+
+        // The spelling already in the home directory wins
+        let mut resolved = entries.clone();
+        let (collisions, skipped) = resolve_restore_collisions(&config, &mut resolved, true);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(skipped, vec![PathBuf::from(".profile")]);
+        assert_eq!(resolved.len(), 2);
+
+        config.on_case_collision = CaseCollisionPolicy::Refuse;
+        let mut refused = entries;
+        let (_, skipped) = resolve_restore_collisions(&config, &mut refused, true);
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(refused.len(), 1);
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use git2::build::TreeUpdateBuilder;
+use git2::{Oid, Repository};
+use log::{debug, info};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::history::{commit_at, repack, signature};
+use crate::lock::VaultLock;
+use crate::{Config, DotfilesError};
+
+/// Outcome of compacting the vault history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactSummary {
+    /// Number of commits squashed into the baseline
+    pub squashed: usize,
+
+    /// Number of newer commits replayed on top of the baseline
+    pub kept: usize,
+
+    /// The baseline commit (planned commits have none)
+    pub baseline: Option<String>,
+
+    /// HEAD after compacting (HEAD before it in a dry run)
+    pub head: String,
+}
+
+/// Squash the history up to a point in time into a single baseline commit
+///
+/// The baseline holds the vault as it was at the last commit made at or
+/// before `before`; every later commit is replayed on top of it with its
+/// tree, message, author and date unchanged, so the current vault content
+/// is identical. Old commits stay reachable from the reflog until git
+/// garbage-collects them. With `config.dry_run` set the history is only
+/// counted.
+pub fn compact_history(
+    config: &Config,
+    before: DateTime<Local>,
+) -> Result<CompactSummary, DotfilesError> {
+    let base = commit_at(config, before)?
+        .ok_or_else(|| DotfilesError::VersionNotFound(before.to_rfc3339()))?;
+
+    squash_history(
+        config,
+        Oid::from_str(&base.commit_id)?,
+        &format!("compact: squash history before {}", before.to_rfc3339()),
+    )
+}
+
+/// Squash a commit and all of its ancestors into a single baseline commit
+///
+/// Every later commit is replayed on top of the baseline unchanged, see
+/// `compact_history`. `reflog` is the reason recorded for moving HEAD.
+pub fn squash_history(
+    config: &Config,
+    base: Oid,
+    reflog: &str,
+) -> Result<CompactSummary, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let _lock = VaultLock::acquire(config)?;
+    let head = repo.head()?;
+    let head_id = head.peel_to_commit()?.id();
+    let base = repo.find_commit(base)?;
+
+    // The baseline replaces the base commit and all of its ancestors
+    let mut walk = repo.revwalk()?;
+    walk.push(base.id())?;
+    let old: HashSet<Oid> = walk.collect::<Result<_, _>>()?;
+
+    let mut walk = repo.revwalk()?;
+    walk.push(head_id)?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    let newer: Vec<Oid> = walk
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|oid| !old.contains(oid))
+        .collect();
+
+    let mut summary = CompactSummary {
+        squashed: old.len(),
+        kept: newer.len(),
+        baseline: None,
+        head: head_id.to_string(),
+    };
+
+    if old.len() < 2 {
+        info!("Nothing to squash up to {}", base.id());
+        summary.squashed = 0;
+        return Ok(summary);
+    }
+
+    if config.dry_run {
+        return Ok(summary);
+    }
+
+    let message = format!(
+        "Baseline: {} commits up to {} squashed\n",
+        old.len(),
+        Local
+            .timestamp_opt(base.time().seconds(), 0)
+            .single()
+            .unwrap_or_else(Local::now)
+            .to_rfc3339()
+    );
+    let baseline = repo.commit(
+        None,
+        &base.author(),
+        &base.committer(),
+        &message,
+        &base.tree()?,
+        &[],
+    )?;
+    debug!("Created baseline commit {}", baseline);
+
+    let mut rewritten: HashMap<Oid, Oid> = HashMap::new();
+    for oid in &newer {
+        let commit = repo.find_commit(*oid)?;
+
+        let mut parent_ids: Vec<Oid> = Vec::new();
+        for parent in commit.parent_ids() {
+            let parent = rewritten.get(&parent).copied().unwrap_or(baseline);
+            if !parent_ids.contains(&parent) {
+                parent_ids.push(parent);
+            }
+        }
+        let parents = parent_ids
+            .iter()
+            .map(|id| repo.find_commit(*id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+        let new_id = repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message().unwrap_or(""),
+            &commit.tree()?,
+            &parents,
+        )?;
+        rewritten.insert(*oid, new_id);
+    }
+
+    let new_head = rewritten.get(&head_id).copied().unwrap_or(baseline);
+    if head.is_branch() {
+        let name = head.name().unwrap_or("HEAD").to_string();
+        repo.reference(&name, new_head, true, reflog)?;
+    } else {
+        repo.set_head_detached(new_head)?;
+    }
+
+    info!(
+        "Squashed {} commits into {}, kept {} newer commits",
+        old.len(),
+        baseline,
+        newer.len()
+    );
+
+    summary.baseline = Some(baseline.to_string());
+    summary.head = new_head.to_string();
+
+    Ok(summary)
+}
+
+/// Rewrite the vault history as if some files had never been stored
+///
+/// Every commit is replayed with `paths` (vault-relative) left out of its
+/// tree, keeping its message, author and date; commits that only changed
+/// those files are dropped, and commits from before the files were added
+/// keep their ids. Snapshots are moved to the rewritten commits. The old
+/// commits are then expired and the repository repacked as with
+/// `prune_history`, which needs the git executable. Returns the number of
+/// commits rewritten or dropped.
+pub fn purge_history(config: &Config, paths: &[PathBuf]) -> Result<usize, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let _lock = VaultLock::acquire(config)?;
+    let head = repo.head()?;
+    let head_id = head.peel_to_commit()?.id();
+
+    let mut walk = repo.revwalk()?;
+    walk.push(head_id)?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+
+    // Old commit ids mapped to their replacement; dropped commits map to
+    // their replaced parent, or to nothing if they had none
+    let mut rewritten: HashMap<Oid, Option<Oid>> = HashMap::new();
+    let mut changed = 0;
+    for oid in walk {
+        let oid = oid?;
+        let commit = repo.find_commit(oid)?;
+        let tree = commit.tree()?;
+
+        let mut update = TreeUpdateBuilder::new();
+        for path in paths {
+            if tree.get_path(path).is_ok() {
+                update.remove(path.as_path());
+            }
+        }
+        let new_tree = repo.find_tree(update.create_updated(&repo, &tree)?)?;
+
+        let mut parent_ids: Vec<Oid> = Vec::new();
+        for parent in commit.parent_ids() {
+            if let Some(Some(parent)) = rewritten.get(&parent)
+                && !parent_ids.contains(parent)
+            {
+                parent_ids.push(*parent);
+            }
+        }
+        let unchanged_parents = parent_ids.iter().copied().eq(commit.parent_ids());
+        if new_tree.id() == tree.id() && unchanged_parents {
+            rewritten.insert(oid, Some(oid));
+            continue;
+        }
+        changed += 1;
+
+        let parents = parent_ids
+            .iter()
+            .map(|id| repo.find_commit(*id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let old_parent_tree = commit.parents().next().map(|parent| parent.tree_id());
+        let new_parent_tree = parents.first().map(|parent| parent.tree_id());
+        let was_empty = old_parent_tree == Some(tree.id());
+        let is_empty = match new_parent_tree {
+            Some(parent_tree) => parent_tree == new_tree.id(),
+            None => new_tree.is_empty(),
+        };
+        if commit.parent_count() <= 1 && is_empty && !was_empty {
+            debug!("Dropped commit {}, it only changed purged files", oid);
+            rewritten.insert(oid, parent_ids.first().copied());
+            continue;
+        }
+
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        let new_id = repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message().unwrap_or(""),
+            &new_tree,
+            &parents,
+        )?;
+        rewritten.insert(oid, Some(new_id));
+    }
+
+    if changed == 0 {
+        info!("No commits store {:?}", paths);
+        return Ok(0);
+    }
+
+    let Some(new_head) = rewritten.get(&head_id).copied().flatten() else {
+        return Err(DotfilesError::VersionNotFound(
+            "HEAD (every commit would be dropped)".to_string(),
+        ));
+    };
+    let reflog = "remove: purge files from history";
+    if head.is_branch() {
+        let name = head.name().unwrap_or("HEAD").to_string();
+        repo.reference(&name, new_head, true, reflog)?;
+    } else {
+        repo.set_head_detached(new_head)?;
+    }
+
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let reference = repo.find_reference(&format!("refs/tags/{}", name))?;
+        let target = reference.peel_to_commit()?.id();
+        match rewritten.get(&target).copied() {
+            Some(Some(new_target)) if new_target != target => {
+                let object = repo.find_object(new_target, None)?;
+                match reference.peel_to_tag() {
+                    Ok(tag) => {
+                        let tagger = match tag.tagger() {
+                            Some(tagger) => tagger.to_owned(),
+                            None => signature(config)?,
+                        };
+                        repo.tag(name, &object, &tagger, tag.message().unwrap_or(""), true)?;
+                    }
+                    Err(_) => {
+                        repo.tag_lightweight(name, &object, true)?;
+                    }
+                }
+            }
+            Some(None) => repo
+                .find_reference(&format!("refs/tags/{}", name))?
+                .delete()?,
+            _ => {}
+        }
+    }
+
+    repack(config);
+    info!("Purged {:?} from {} commits", paths, changed);
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::init_git_repo;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Commit a file with a fixed commit time
+    fn commit_at_time(repo: &Repository, config: &Config, content: &str, seconds: i64) -> Oid {
+        fs::write(config.vault_dir.join(".testrc"), content).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(".testrc")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let time = git2::Time::new(seconds, 0);
+        let signature = git2::Signature::new("Test", "test@example.com", &time).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            content,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compact_history() {
+        let vault_dir = TempDir::new().unwrap();
+        let config = Config::new(
+            vault_dir.path().to_path_buf(),
+            vault_dir.path().join("home"),
+        );
+        let repo = init_git_repo(&config).unwrap();
+
+        commit_at_time(&repo, &config, "v1", 1_000);
+        commit_at_time(&repo, &config, "v2", 2_000);
+        commit_at_time(&repo, &config, "v3", 3_000);
+        let head = commit_at_time(&repo, &config, "v4", 4_000);
+        let head_tree = repo.find_commit(head).unwrap().tree_id();
+
+        let cutoff = Local.timestamp_opt(2_500, 0).unwrap();
+        let summary = compact_history(&config, cutoff).unwrap();
+        assert_eq!(summary.squashed, 2);
+        assert_eq!(summary.kept, 2);
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.tree_id(), head_tree);
+        assert_eq!(head.message(), Some("v4"));
+
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        let messages: Vec<String> = walk
+            .map(|oid| {
+                let commit = repo.find_commit(oid.unwrap()).unwrap();
+                commit
+                    .message()
+                    .unwrap()
+                    .lines()
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[2].starts_with("Baseline: 2 commits"));
+    }
+}
@@ -0,0 +1,71 @@
+use clap_complete::Shell;
+
+/// Subcommands whose file arguments complete to tracked dotfiles
+pub const TRACKED_FILE_SUBCOMMANDS: [&str; 5] = ["backup", "restore", "history", "show", "diff"];
+
+/// Shell code completing tracked dotfile names, appended to the generated script
+///
+/// The names come from `list --porcelain` when completing, so they follow the
+/// vault. Returns `None` for shells without dynamic completions.
+pub fn tracked_files_completion(shell: Shell, bin: &str) -> Option<String> {
+    let list = format!("{} list --porcelain 2>/dev/null", bin);
+
+    match shell {
+        Shell::Bash => Some(format!(
+            r#"
+_{bin}_tracked() {{
+    case "${{COMP_WORDS[1]}}" in
+        {subcommands})
+            if [[ ${{COMP_CWORD}} -ge 2 && "${{COMP_WORDS[COMP_CWORD]}}" != -* ]]; then
+                local IFS=$'\n'
+                COMPREPLY=($(compgen -W "$({list})" -- "${{COMP_WORDS[COMP_CWORD]}}"))
+                return 0
+            fi
+            ;;
+    esac
+    _{bin} "$@"
+}}
+complete -F _{bin}_tracked -o nosort -o bashdefault -o default {bin}
+"#,
+            subcommands = TRACKED_FILE_SUBCOMMANDS.join("|"),
+        )),
+        Shell::Zsh => Some(format!(
+            r#"
+_{bin}_tracked() {{
+    if (( CURRENT > 2 )) && [[ ${{words[2]}} == ({subcommands}) && ${{words[CURRENT]}} != -* ]]; then
+        local -a tracked
+        tracked=(${{(f)"$({list})"}})
+        compadd -a tracked
+    else
+        _{bin} "$@"
+    fi
+}}
+compdef _{bin}_tracked {bin}
+"#,
+            subcommands = TRACKED_FILE_SUBCOMMANDS.join("|"),
+        )),
+        Shell::Fish => Some(format!(
+            "complete -c {bin} -n \"__fish_seen_subcommand_from {subcommands}\" -a \"({list})\"\n",
+            subcommands = TRACKED_FILE_SUBCOMMANDS.join(" "),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracked_files_completion() {
+        let bash = tracked_files_completion(Shell::Bash, "dotfilesvault").unwrap();
+        assert!(bash.contains("backup|restore|history|show|diff)"));
+        assert!(bash.contains("dotfilesvault list --porcelain"));
+        assert!(bash.contains("complete -F _dotfilesvault_tracked"));
+
+        let fish = tracked_files_completion(Shell::Fish, "dotfilesvault").unwrap();
+        assert!(fish.starts_with("complete -c dotfilesvault"));
+
+        assert!(tracked_files_completion(Shell::PowerShell, "dotfilesvault").is_none());
+    }
+}
@@ -0,0 +1,632 @@
+use anyhow::Result;
+use log::debug;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bare::VaultLayout;
+use crate::collisions::CaseCollisionPolicy;
+use crate::conflicts::ConflictPolicy;
+use crate::hooks::HookEvent;
+use crate::profile::validate_profile_name;
+use crate::security::SecretPolicy;
+use crate::symlink::SymlinkPolicy;
+use crate::utils::{expand_tilde, parse_duration, parse_size, validate_time_format};
+use crate::vaults::DEFAULT_VAULT;
+use crate::{Config, DotfilesError};
+
+/// Name of the configuration file
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Top-level keys of `config.toml`
+pub const CONFIG_KEYS: [&str; 37] = [
+    "vault_dir",
+    "home_dir",
+    "ignore",
+    "bwlimit",
+    "max_file_size",
+    "skip_binary",
+    "timeout",
+    "stale_after",
+    "scan_threads",
+    "copy_threads",
+    "home_cache",
+    "scan_priority",
+    "scan_follow_links",
+    "scan_max_depth",
+    "config_locations",
+    "suggestions",
+    "encrypt",
+    "key_file",
+    "redact",
+    "capture_toolchains",
+    "time_format",
+    "utc",
+    "language",
+    "plain",
+    "profile",
+    "on_conflict",
+    "on_secret",
+    "on_case_collision",
+    "symlinks",
+    "layout",
+    "backend",
+    "paranoid",
+    "variables",
+    "hooks",
+    "git",
+    "vault",
+    "vaults",
+];
+
+/// Keys of the `[git]` table of `config.toml`
+pub const GIT_KEYS: [&str; 3] = ["author_name", "author_email", "remote"];
+
+/// Settings read from `config.toml`
+///
+/// Every field is optional; missing values keep their defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConfigFile {
+    /// Path to the dotfilesvault directory
+    pub vault_dir: Option<PathBuf>,
+
+    /// Path to the home directory
+    pub home_dir: Option<PathBuf>,
+
+    /// Gitignore-style patterns excluded from backups
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Maximum copy throughput, e.g. `10M`
+    pub bwlimit: Option<String>,
+
+    /// Size above which backups leave files out, e.g. `50M`, `0` for no limit
+    pub max_file_size: Option<String>,
+
+    /// Whether backups leave out binary files
+    pub skip_binary: Option<bool>,
+
+    /// How long a command may run, e.g. `10m`
+    pub timeout: Option<String>,
+
+    /// How old the last backup may get before warnings, e.g. `7d`, `0` to never warn
+    pub stale_after: Option<String>,
+
+    /// Number of threads scanning the home directory
+    pub scan_threads: Option<usize>,
+
+    /// Number of threads copying dotfiles into the vault
+    pub copy_threads: Option<usize>,
+
+    /// Whether hashes of home files are cached locally
+    pub home_cache: Option<bool>,
+
+    /// Home-relative directories scanned first
+    #[serde(default)]
+    pub scan_priority: Vec<PathBuf>,
+
+    /// Whether scans descend into symlinked directories when links are followed
+    pub scan_follow_links: Option<bool>,
+
+    /// How many levels below the home directory scans go
+    pub scan_max_depth: Option<usize>,
+
+    /// Home-relative config files and directories backed up besides dotfiles
+    pub config_locations: Option<Vec<PathBuf>>,
+
+    /// Whether to suggest tracking recently edited well-known config files
+    pub suggestions: Option<bool>,
+
+    /// Gitignore-style patterns of files stored encrypted
+    #[serde(default)]
+    pub encrypt: Vec<String>,
+
+    /// Path of the age identity used for encryption
+    pub key_file: Option<PathBuf>,
+
+    /// Values redacted from backups: placeholder to regular expression
+    #[serde(default)]
+    pub redact: BTreeMap<String, String>,
+
+    /// Whether backups also capture toolchain state
+    pub capture_toolchains: Option<bool>,
+
+    /// `strftime`-style format for displayed timestamps, or `iso8601`
+    pub time_format: Option<String>,
+
+    /// Whether timestamps are displayed in UTC
+    pub utc: Option<bool>,
+
+    /// Language of CLI messages
+    pub language: Option<String>,
+
+    /// Whether CLI output is plain linear text
+    pub plain: Option<bool>,
+
+    /// Machine profile whose variants are used instead of the common files
+    pub profile: Option<String>,
+
+    /// What restores do with home files that have unsaved local edits
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// What backups do with files that look like they contain secrets
+    pub on_secret: Option<SecretPolicy>,
+
+    /// What backups and restores do with paths that differ only in case
+    pub on_case_collision: Option<CaseCollisionPolicy>,
+
+    /// What backups do with dotfiles that are symlinks
+    pub symlinks: Option<SymlinkPolicy>,
+
+    /// Whether the vault holds copies or is a bare repository over the home directory
+    pub layout: Option<VaultLayout>,
+
+    /// Where the vault is stored: `git`, `s3://BUCKET/PREFIX` or `file://DIR`
+    pub backend: Option<String>,
+
+    /// Whether destructive commands ask for confirmation and take safety snapshots
+    pub paranoid: Option<bool>,
+
+    /// Custom template variables
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    /// Shell commands run as hooks, keyed by hook name
+    #[serde(default)]
+    pub hooks: BTreeMap<String, String>,
+
+    /// Git settings for vault commits
+    #[serde(default)]
+    pub git: GitSettings,
+
+    /// Name of the vault used when `--vault` names none, see `vaults`
+    pub vault: Option<String>,
+
+    /// Named vaults, whose settings override the ones above
+    #[serde(default)]
+    pub vaults: BTreeMap<String, ConfigFile>,
+}
+
+/// The `[git]` table of the configuration file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GitSettings {
+    /// Author name for vault commits
+    pub author_name: Option<String>,
+
+    /// Author email for vault commits
+    pub author_email: Option<String>,
+
+    /// URL of the git remote used by `push` and `pull`
+    pub remote: Option<String>,
+}
+
+/// Get the default location of the configuration file
+///
+/// This is `$XDG_CONFIG_HOME/dotfilesvault/config.toml`, falling back to
+/// `~/.config/dotfilesvault/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+
+    Some(config_home.join("dotfilesvault").join(CONFIG_FILE_NAME))
+}
+
+impl ConfigFile {
+    /// Parse configuration file content
+    pub fn parse(content: &str, path: &Path) -> Result<Self, DotfilesError> {
+        toml::from_str(content).map_err(|err| {
+            DotfilesError::InvalidConfig(path.display().to_string(), err.to_string())
+        })
+    }
+
+    /// Apply the file's settings, then those of a named vault, on top of a
+    /// configuration
+    ///
+    /// `requested` is the `--vault` argument: a vault named in the file,
+    /// `default` for the top-level settings alone, or a directory. Without it
+    /// the file's default `vault`, if any, is used.
+    /// A named vault's directory defaults to `~/dotfilesvault-<name>`. Names
+    /// the file doesn't define fail with `VaultNotFound`, unless they are an
+    /// existing directory or look like a path.
+    pub fn apply_vault(
+        mut self,
+        config: &mut Config,
+        path: &Path,
+        requested: Option<&str>,
+    ) -> Result<(), DotfilesError> {
+        let name = match requested {
+            Some(DEFAULT_VAULT) => None,
+            Some(value) if self.vaults.contains_key(value) => Some(value.to_string()),
+            Some(value) if !is_vault_name(value) || Path::new(value).exists() => None,
+            Some(value) => return Err(DotfilesError::VaultNotFound(value.to_string())),
+            None => self.vault.take(),
+        };
+        let mut vaults = std::mem::take(&mut self.vaults);
+        self.apply(config, path)?;
+
+        if let Some(name) = name {
+            let vault = vaults
+                .remove(&name)
+                .ok_or_else(|| DotfilesError::VaultNotFound(name.clone()))?;
+            if let Some(home_dir) = &vault.home_dir {
+                config.home_dir = expand_tilde(home_dir);
+            }
+            config.vault_dir = config.home_dir.join(format!("dotfilesvault-{}", name));
+            vault.apply(config, path)?;
+            config.vault_name = Some(name);
+        }
+
+        Ok(())
+    }
+
+    /// Apply the file's settings on top of a configuration
+    pub fn apply(self, config: &mut Config, path: &Path) -> Result<(), DotfilesError> {
+        if let Some(home_dir) = self.home_dir {
+            config.home_dir = expand_tilde(home_dir);
+        }
+
+        if let Some(vault_dir) = self.vault_dir {
+            let vault_dir = expand_tilde(vault_dir);
+            config.vault_dir = if vault_dir.is_absolute() {
+                vault_dir
+            } else {
+                config.home_dir.join(vault_dir)
+            };
+        }
+
+        if let Some(bwlimit) = self.bwlimit {
+            let limit = parse_size(&bwlimit).ok_or_else(|| {
+                DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("invalid bwlimit: {}", bwlimit),
+                )
+            })?;
+            config.bwlimit = Some(limit);
+        }
+
+        if let Some(max_file_size) = self.max_file_size {
+            let limit = parse_size(&max_file_size).ok_or_else(|| {
+                DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("invalid max_file_size: {}", max_file_size),
+                )
+            })?;
+            config.max_file_size = (limit > 0).then_some(limit);
+        }
+
+        if let Some(skip_binary) = self.skip_binary {
+            config.skip_binary = skip_binary;
+        }
+
+        if let Some(timeout) = self.timeout {
+            let timeout = parse_duration(&timeout).ok_or_else(|| {
+                DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("invalid timeout: {}", timeout),
+                )
+            })?;
+            config.timeout = Some(timeout);
+        }
+
+        if let Some(stale_after) = self.stale_after {
+            let stale_after = parse_duration(&stale_after).ok_or_else(|| {
+                DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("invalid stale_after: {}", stale_after),
+                )
+            })?;
+            config.stale_after = (!stale_after.is_zero()).then_some(stale_after);
+        }
+
+        if let Some(scan_threads) = self.scan_threads {
+            config.scan_threads = scan_threads;
+        }
+
+        if let Some(copy_threads) = self.copy_threads {
+            config.copy_threads = copy_threads;
+        }
+
+        if let Some(home_cache) = self.home_cache {
+            config.home_cache = home_cache;
+        }
+
+        if !self.scan_priority.is_empty() {
+            config.scan_priority = self.scan_priority;
+        }
+
+        if let Some(scan_follow_links) = self.scan_follow_links {
+            config.scan_follow_links = scan_follow_links;
+        }
+
+        if self.scan_max_depth.is_some() {
+            config.scan_max_depth = self.scan_max_depth;
+        }
+
+        if let Some(config_locations) = self.config_locations {
+            config.config_locations = config_locations;
+        }
+
+        if let Some(suggestions) = self.suggestions {
+            config.suggestions = suggestions;
+        }
+
+        if let Some(key_file) = self.key_file {
+            config.key_file = Some(expand_tilde(key_file));
+        }
+
+        if let Some(capture_toolchains) = self.capture_toolchains {
+            config.capture_toolchains = capture_toolchains;
+        }
+
+        if let Some(time_format) = self.time_format {
+            validate_time_format(&time_format)
+                .map_err(|err| DotfilesError::InvalidConfig(path.display().to_string(), err))?;
+            config.time_format = time_format;
+        }
+
+        if let Some(utc) = self.utc {
+            config.utc = utc;
+        }
+
+        if self.language.is_some() {
+            config.language = self.language;
+        }
+
+        if let Some(plain) = self.plain {
+            config.plain = plain;
+        }
+
+        if let Some(profile) = self.profile {
+            validate_profile_name(&profile)
+                .map_err(|err| DotfilesError::InvalidConfig(path.display().to_string(), err))?;
+            config.profile = Some(profile);
+        }
+
+        if let Some(on_conflict) = self.on_conflict {
+            config.on_conflict = on_conflict;
+        }
+
+        if let Some(on_secret) = self.on_secret {
+            config.on_secret = on_secret;
+        }
+
+        if let Some(on_case_collision) = self.on_case_collision {
+            config.on_case_collision = on_case_collision;
+        }
+
+        if let Some(symlinks) = self.symlinks {
+            config.symlinks = symlinks;
+        }
+
+        if let Some(layout) = self.layout {
+            config.layout = layout;
+        }
+
+        if let Some(backend) = self.backend {
+            config.backend = backend
+                .parse()
+                .map_err(|err| DotfilesError::InvalidConfig(path.display().to_string(), err))?;
+        }
+
+        if let Some(paranoid) = self.paranoid {
+            config.paranoid = paranoid;
+        }
+
+        for (placeholder, pattern) in &self.redact {
+            if let Err(err) = regex::Regex::new(pattern) {
+                return Err(DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("invalid redact pattern for {}: {}", placeholder, err),
+                ));
+            }
+        }
+
+        for name in self.hooks.keys() {
+            if HookEvent::from_name(name).is_none() {
+                return Err(DotfilesError::InvalidConfig(
+                    path.display().to_string(),
+                    format!("unknown hook: {}", name),
+                ));
+            }
+        }
+
+        config.ignore.extend(self.ignore);
+        config.encrypt.extend(self.encrypt);
+        config.redact.extend(self.redact);
+        config.variables.extend(self.variables);
+        config.hook_commands.extend(self.hooks);
+        config.author_name = self.git.author_name.or(config.author_name.take());
+        config.author_email = self.git.author_email.or(config.author_email.take());
+        config.remote_url = self.git.remote.or(config.remote_url.take());
+        config.config_file = Some(path.to_path_buf());
+
+        Ok(())
+    }
+}
+
+/// Environment variable overriding the vault directory of the config file
+pub const VAULT_DIR_VAR: &str = "DOTFILESVAULT_DIR";
+
+/// Environment variable overriding the home directory of the config file
+pub const HOME_DIR_VAR: &str = "DOTFILESVAULT_HOME";
+
+/// Check if a `--vault` argument can be a vault name rather than a path
+pub fn is_vault_name(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('.')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+impl Config {
+    /// Load the configuration from the default config file location
+    ///
+    /// Falls back to `Config::default()` when no config file exists.
+    pub fn load() -> Result<Self, DotfilesError> {
+        Self::load_vault(None, None)
+    }
+
+    /// Load the configuration for a vault from a config file, by default
+    /// the one at the default location
+    ///
+    /// `vault` is the `--vault` argument, see `ConfigFile::apply_vault`.
+    /// Without a config file only directories can be given.
+    pub fn load_vault(file: Option<&Path>, vault: Option<&str>) -> Result<Self, DotfilesError> {
+        let path = match file {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_config_path().filter(|path| path.exists()),
+        };
+        let Some(path) = path else {
+            if let Some(vault) = vault
+                && is_vault_name(vault)
+                && !Path::new(vault).exists()
+            {
+                return Err(DotfilesError::VaultNotFound(vault.to_string()));
+            }
+            return Ok(Self::default());
+        };
+
+        debug!("Loading config from {:?}", path);
+        let content = fs::read_to_string(&path)?;
+        let mut config = Self::default();
+        ConfigFile::parse(&content, &path)?.apply_vault(&mut config, &path, vault)?;
+
+        Ok(config)
+    }
+
+    /// Load the configuration like `load_vault`, then apply the paths set in
+    /// the environment
+    ///
+    /// `DOTFILESVAULT_HOME` replaces the home directory, taking a vault inside
+    /// it along like `--home`, and `DOTFILESVAULT_DIR` the vault directory
+    /// unless a `vault` is given. Paths are thus taken from the command line,
+    /// then the environment, then the config file, then the defaults.
+    pub fn from_env(file: Option<&Path>, vault: Option<&str>) -> Result<Self, DotfilesError> {
+        let mut config = Self::load_vault(file, vault)?;
+        config.apply_env(vault.is_some(), |name| std::env::var_os(name))?;
+
+        Ok(config)
+    }
+
+    /// Apply the path overrides of the environment, read with `var`
+    ///
+    /// Empty variables are ignored, `~` is expanded and relative paths are
+    /// resolved against the current directory.
+    fn apply_env<F>(&mut self, keep_vault: bool, var: F) -> Result<(), DotfilesError>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let path = |name: &str| -> Result<Option<PathBuf>, DotfilesError> {
+            match var(name).filter(|value| !value.is_empty()) {
+                Some(value) => Ok(Some(std::path::absolute(expand_tilde(value))?)),
+                None => Ok(None),
+            }
+        };
+
+        if let Some(home_dir) = path(HOME_DIR_VAR)? {
+            debug!(
+                "Using the home directory {:?} from {}",
+                home_dir, HOME_DIR_VAR
+            );
+            self.set_home_dir(home_dir);
+        }
+        if !keep_vault && let Some(vault_dir) = path(VAULT_DIR_VAR)? {
+            debug!("Using the vault {:?} from {}", vault_dir, VAULT_DIR_VAR);
+            self.vault_dir = vault_dir;
+        }
+
+        Ok(())
+    }
+
+    /// Load the configuration from a specific config file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DotfilesError> {
+        Self::load_vault(Some(path.as_ref()), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_config_file() {
+        let path = Path::new("/etc/dotfilesvault/config.toml");
+        let file = ConfigFile::parse(
+            r#"
+                home_dir = "/home/me"
+                vault_dir = "vaults/main"
+                ignore = [".cache/", ".npm"]
+                bwlimit = "2M"
+                timeout = "10m"
+                stale_after = "0"
+                plain = true
+
+                [git]
+                author_name = "Me"
+                author_email = "me@example.com"
+            "#,
+            path,
+        )
+        .unwrap();
+
+        let mut config = Config::new(PathBuf::from("/vault"), PathBuf::from("/home"));
+        file.apply(&mut config, path).unwrap();
+
+        assert_eq!(config.home_dir, PathBuf::from("/home/me"));
+        assert_eq!(config.vault_dir, PathBuf::from("/home/me/vaults/main"));
+        assert_eq!(config.ignore, vec![".cache/", ".npm"]);
+        assert_eq!(config.bwlimit, Some(2 * 1024 * 1024));
+        assert!(config.plain);
+        assert_eq!(config.timeout, Some(std::time::Duration::from_secs(600)));
+        assert_eq!(config.stale_after, None);
+        assert_eq!(config.author_name.as_deref(), Some("Me"));
+        assert_eq!(config.config_file.as_deref(), Some(path));
+    }
+
+    #[test]
+    fn test_invalid_config_file() {
+        let path = Path::new("config.toml");
+
+        assert!(matches!(
+            ConfigFile::parse("vault_dir = [", path),
+            Err(DotfilesError::InvalidConfig(_, _))
+        ));
+
+        let file = ConfigFile::parse("bwlimit = \"fast\"", path).unwrap();
+        let mut config = Config::new(PathBuf::from("/vault"), PathBuf::from("/home"));
+        assert!(file.apply(&mut config, path).is_err());
+
+        let file = ConfigFile::parse("[hooks]\npost-lunch = \"true\"", path).unwrap();
+        assert!(file.apply(&mut config, path).is_err());
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let env = |home: &'static str, vault: &'static str| {
+            move |name: &str| match name {
+                HOME_DIR_VAR => Some(OsString::from(home)),
+                VAULT_DIR_VAR => Some(OsString::from(vault)),
+                _ => None,
+            }
+        };
+
+        // A vault inside the home directory moves along with it
+        let mut config = Config::new(PathBuf::from("/home/dotfilesvault"), PathBuf::from("/home"));
+        config.apply_env(false, env("/ci/home", "")).unwrap();
+        assert_eq!(config.home_dir, PathBuf::from("/ci/home"));
+        assert_eq!(config.vault_dir, PathBuf::from("/ci/home/dotfilesvault"));
+
+        config.apply_env(false, env("", "/ci/vault")).unwrap();
+        assert_eq!(config.home_dir, PathBuf::from("/ci/home"));
+        assert_eq!(config.vault_dir, PathBuf::from("/ci/vault"));
+
+        // A vault given on the command line wins
+        config.apply_env(true, env("", "/other")).unwrap();
+        assert_eq!(config.vault_dir, PathBuf::from("/ci/vault"));
+    }
+}
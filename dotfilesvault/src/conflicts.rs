@@ -0,0 +1,288 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backend::VaultBackend;
+use crate::manifest::DotfileEntry;
+use crate::restore::decode_entry_content;
+use crate::utils::{normalize_path, write_file};
+use crate::{Config, DotfilesError};
+
+/// Home directory holding vault versions that weren't restored because of a conflict
+pub const CONFLICTS_DIR: &str = ".dotfilesvault-conflicts";
+
+/// File in the conflicts directory recording the quarantined versions
+pub const CONFLICTS_INDEX: &str = "conflicts.json";
+
+/// What a restore does when the home file has edits that were never backed up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Leave the home file alone and fail with `DotfilesError::RestoreConflict`
+    #[default]
+    Fail,
+
+    /// Replace the home file with the vault version
+    Overwrite,
+
+    /// Keep the home file and quarantine the vault version for a manual merge
+    Skip,
+}
+
+impl ConflictPolicy {
+    /// Name used in the config file and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Fail => "fail",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Skip => "skip",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fail" => Ok(ConflictPolicy::Fail),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            _ => Err(format!(
+                "invalid conflict policy {:?}, expected fail, overwrite or skip",
+                value
+            )),
+        }
+    }
+}
+
+/// A vault version set aside because restoring it would have lost local edits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Path of the conflicting file relative to the home directory
+    pub path: PathBuf,
+
+    /// Where the vault version was written
+    pub quarantined: PathBuf,
+
+    /// When the restore was skipped
+    pub created: DateTime<Local>,
+}
+
+/// Get the conflicts directory in the home directory
+pub fn conflicts_dir(config: &Config) -> PathBuf {
+    config.home_dir.join(CONFLICTS_DIR)
+}
+
+/// Check if the home copy of an entry has edits that were never backed up
+///
+/// `home` conflicts with `restored` unless it equals it, the stored copy or
+/// any version of the file in the vault history, in which case nothing
+/// would be lost.
+pub fn has_local_edits(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entry: &DotfileEntry,
+    home: &[u8],
+    restored: &[u8],
+) -> Result<bool, DotfilesError> {
+    if home == restored {
+        return Ok(false);
+    }
+
+    for stored in backend.stored_contents(&entry.stored_path(config))? {
+        if decode_entry_content(config, entry, &stored)? == home {
+            debug!("{} matches a backed up version", entry.id);
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Get the path a vault version would be quarantined at
+pub fn quarantine_path(config: &Config, entry: &DotfileEntry, at: DateTime<Local>) -> PathBuf {
+    let mut name = entry.source.clone().into_os_string();
+    name.push(format!(".{}", at.format("%Y%m%dT%H%M%S")));
+
+    conflicts_dir(config).join(name)
+}
+
+/// Write the vault version of a conflicting file to the conflicts directory and record it
+///
+/// `content` is the decoded content that would have been restored.
+pub fn quarantine(
+    config: &Config,
+    entry: &DotfileEntry,
+    content: &[u8],
+) -> Result<Conflict, DotfilesError> {
+    let created = Local::now();
+    let quarantined = quarantine_path(config, entry, created);
+
+    if let Some(parent) = quarantined.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&quarantined, content)?;
+
+    let conflict = Conflict {
+        path: entry.source.clone(),
+        quarantined,
+        created,
+    };
+
+    let mut conflicts = list_conflicts(config)?;
+    conflicts.push(conflict.clone());
+    save_conflicts(config, &conflicts)?;
+
+    info!(
+        "Kept local {:?}, vault version saved to {:?}",
+        entry.source, conflict.quarantined
+    );
+
+    Ok(conflict)
+}
+
+/// List the recorded conflicts, oldest first
+pub fn list_conflicts(config: &Config) -> Result<Vec<Conflict>, DotfilesError> {
+    let path = conflicts_dir(config).join(CONFLICTS_INDEX);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|err| {
+        DotfilesError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        ))
+    })
+}
+
+/// Save the conflict records
+fn save_conflicts(config: &Config, conflicts: &[Conflict]) -> Result<(), DotfilesError> {
+    let dir = conflicts_dir(config);
+    fs::create_dir_all(&dir)?;
+
+    let content = serde_json::to_string_pretty(conflicts)
+        .map_err(|err| DotfilesError::Io(std::io::Error::other(err)))?;
+    write_file(dir.join(CONFLICTS_INDEX), content.as_bytes())?;
+
+    Ok(())
+}
+
+/// Mark the conflicts of a file as resolved, removing the quarantined versions
+///
+/// With `use_vault` the most recent quarantined version replaces the home
+/// file first; otherwise the home file is kept as merged by hand.
+pub fn resolve_conflict(
+    config: &Config,
+    file_path: &str,
+    use_vault: bool,
+) -> Result<Vec<Conflict>, DotfilesError> {
+    let home_path = normalize_path(file_path, config);
+    let relative = home_path
+        .strip_prefix(&config.home_dir)
+        .unwrap_or(&home_path)
+        .to_path_buf();
+
+    let (resolved, remaining): (Vec<Conflict>, Vec<Conflict>) = list_conflicts(config)?
+        .into_iter()
+        .partition(|conflict| conflict.path == relative);
+
+    let Some(latest) = resolved.last() else {
+        return Err(DotfilesError::DotfileNotFound(file_path.to_string()));
+    };
+
+    if use_vault {
+        let content = fs::read(&latest.quarantined)?;
+        write_file(&home_path, &content)?;
+        info!("Replaced {:?} with the vault version", home_path);
+    }
+
+    for conflict in &resolved {
+        remove_quarantined(&conflict.quarantined)?;
+    }
+    save_conflicts(config, &remaining)?;
+
+    Ok(resolved)
+}
+
+/// Remove a quarantined file, tolerating one that was already deleted
+fn remove_quarantined(path: &Path) -> Result<(), DotfilesError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Dotfile;
+    use tempfile::TempDir;
+
+    fn setup_test_env() -> (Config, TempDir, TempDir) {
+        let home_dir = TempDir::new().unwrap();
+        let vault_dir = TempDir::new().unwrap();
+        let config = Config::new(
+            vault_dir.path().to_path_buf(),
+            home_dir.path().to_path_buf(),
+        );
+
+        (config, home_dir, vault_dir)
+    }
+
+    #[test]
+    fn test_quarantine_and_resolve() {
+        let (config, home_dir, _vault_dir) = setup_test_env();
+        let home_path = home_dir.path().join(".testrc");
+        fs::write(&home_path, "local edits").unwrap();
+
+        let entry = DotfileEntry::new(&Dotfile::new(home_path.clone(), &config), &config);
+        let conflict = quarantine(&config, &entry, b"vault version").unwrap();
+
+        assert!(conflict.quarantined.starts_with(conflicts_dir(&config)));
+        assert_eq!(
+            fs::read_to_string(&conflict.quarantined).unwrap(),
+            "vault version"
+        );
+        assert_eq!(list_conflicts(&config).unwrap(), vec![conflict.clone()]);
+
+        let resolved = resolve_conflict(&config, ".testrc", true).unwrap();
+        assert_eq!(resolved, vec![conflict.clone()]);
+        assert_eq!(fs::read_to_string(&home_path).unwrap(), "vault version");
+        assert!(!conflict.quarantined.exists());
+        assert!(list_conflicts(&config).unwrap().is_empty());
+
+        assert!(matches!(
+            resolve_conflict(&config, ".testrc", false),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_local_edits_known_to_history() {
+        use crate::backend::GitBackend;
+        use crate::history::commit_changes;
+
+        let (config, home_dir, vault_dir) = setup_test_env();
+        let home_path = home_dir.path().join(".testrc");
+        let entry = DotfileEntry::new(&Dotfile::new(home_path, &config), &config);
+
+        fs::write(vault_dir.path().join(".testrc"), "v1").unwrap();
+        commit_changes(&config, "First").unwrap();
+        fs::write(vault_dir.path().join(".testrc"), "v2").unwrap();
+        commit_changes(&config, "Second").unwrap();
+
+        let backend = GitBackend::new(&config);
+        assert!(!has_local_edits(&config, &backend, &entry, b"v2", b"v2").unwrap());
+        assert!(!has_local_edits(&config, &backend, &entry, b"v1", b"v2").unwrap());
+        assert!(has_local_edits(&config, &backend, &entry, b"edited", b"v2").unwrap());
+    }
+}
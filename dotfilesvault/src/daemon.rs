@@ -0,0 +1,288 @@
+use anyhow::Result;
+use chrono::Local;
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::backup::{Dotfile, backup_specific_dotfiles, ignore_matcher, is_own_path, own_paths};
+use crate::history::{commit_changes, has_uncommitted_changes, last_changed};
+use crate::manifest::Manifest;
+use crate::metrics::record_backup;
+use crate::symlink::is_file_or_link;
+use crate::{Config, DotfilesError};
+
+/// Default quiet period after the last change before a backup runs
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Collects changed paths until no new change arrived for a quiet period
+///
+/// Rapid edits (editors often write a file several times when saving) are
+/// merged into a single backup.
+#[derive(Debug)]
+pub struct Debouncer {
+    quiet_period: Duration,
+    deadline: Option<Instant>,
+    changed: BTreeSet<PathBuf>,
+}
+
+impl Debouncer {
+    /// Create a debouncer with the given quiet period
+    pub fn new(quiet_period: Duration) -> Self {
+        Self {
+            quiet_period,
+            deadline: None,
+            changed: BTreeSet::new(),
+        }
+    }
+
+    /// Record a change, pushing the deadline back
+    pub fn record(&mut self, path: PathBuf, now: Instant) {
+        self.changed.insert(path);
+        self.deadline = Some(now + self.quiet_period);
+    }
+
+    /// Take the changed paths once the quiet period has passed
+    pub fn ready(&mut self, now: Instant) -> Option<Vec<PathBuf>> {
+        match self.deadline {
+            Some(deadline) if now >= deadline => {
+                self.deadline = None;
+                Some(std::mem::take(&mut self.changed).into_iter().collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Watch tracked dotfiles and back them up when they change
+///
+/// The directories containing tracked files are watched, so files replaced
+/// by atomic saves keep being picked up. Tracked directories are watched
+/// recursively, so files added to them are backed up too. The tracked files
+/// and directories are read once when watching starts; files tracked later
+/// are picked up after a restart. Each backup only copies the changed files
+/// that are due by their entries' backup frequency and creates at most one
+/// commit. Runs until the watcher fails or `config.cancellation` is
+/// cancelled.
+pub fn watch(config: &Config, debounce: Duration) -> Result<(), DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    let tracked: HashSet<PathBuf> = manifest
+        .entries
+        .values()
+        .map(|entry| entry.dotfile(config).original_path)
+        .collect();
+    let directories: Vec<PathBuf> = manifest
+        .directories
+        .iter()
+        .map(|dir| config.home_dir.join(dir))
+        .filter(|dir| dir.is_dir())
+        .collect();
+
+    if tracked.is_empty() && directories.is_empty() {
+        return Err(DotfilesError::DotfileNotFound(
+            "no tracked dotfiles to watch, run a backup first".to_string(),
+        ));
+    }
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+
+    let dirs: BTreeSet<PathBuf> = tracked
+        .iter()
+        .filter_map(|path| path.parent().map(PathBuf::from))
+        .filter(|dir| dir.is_dir())
+        .filter(|dir| !directories.iter().any(|tracked| dir.starts_with(tracked)))
+        .collect();
+    for dir in &dirs {
+        debug!("Watching {:?}", dir);
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+    }
+    for dir in &directories {
+        debug!("Watching {:?} recursively", dir);
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+    }
+
+    info!(
+        "Watching {} dotfiles in {} directories",
+        tracked.len(),
+        dirs.len() + directories.len()
+    );
+
+    // Backups write to the vault, which may be inside a watched directory
+    let own = own_paths(config);
+    let mut debouncer = Debouncer::new(debounce);
+    while !config.cancellation.is_cancelled() {
+        match rx.recv_timeout(debounce / 4) {
+            Ok(Ok(event)) if !matches!(event.kind, EventKind::Access(_)) => {
+                for path in event.paths {
+                    let watched = tracked.contains(&path)
+                        || directories.iter().any(|dir| path.starts_with(dir));
+                    if watched && !is_own_path(&own, &path) {
+                        debouncer.record(path, Instant::now());
+                    }
+                }
+            }
+            Ok(Ok(_)) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Err(err)) => warn!("Watch error: {}", err),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(DotfilesError::Io(std::io::Error::other(
+                    "file watcher stopped",
+                )));
+            }
+        }
+
+        if let Some(changed) = debouncer.ready(Instant::now()) {
+            let result = backup_changes(config, &changed);
+            if let Err(err) = record_backup(config, result.is_ok()) {
+                warn!("Failed to record backup metrics: {}", err);
+            }
+            if let Err(err) = result {
+                error!("Automatic backup failed: {}", err);
+            }
+        }
+    }
+
+    info!("Stopped watching");
+
+    Ok(())
+}
+
+/// Back up a batch of changed files, committing if anything was recorded
+///
+/// Files that are gone again (like an editor's temporary files), not due by
+/// their entry's backup frequency or, inside tracked directories, ignored are
+/// skipped.
+fn backup_changes(config: &Config, changed: &[PathBuf]) -> Result<(), DotfilesError> {
+    debug!("Changes detected: {:?}", changed);
+
+    let manifest = Manifest::load(config)?;
+    let matcher = ignore_matcher(config)?;
+    let last_changed = if config.vault_dir.join(".git").exists() {
+        last_changed(config)?
+    } else {
+        HashMap::new()
+    };
+    let now = Local::now();
+    let paths: Vec<&PathBuf> = changed
+        .iter()
+        .filter(|path| is_file_or_link(path))
+        .filter(|path| {
+            let entry = manifest.entry_for(&Dotfile::new(path.to_path_buf(), config), config);
+            if manifest.find_by_source(&entry.source).is_none() {
+                return !matcher.matched_path_or_any_parents(path, false).is_ignore();
+            }
+
+            let due = entry
+                .frequency
+                .is_due(last_changed.get(&entry.stored_path(config)).copied(), now);
+            if !due {
+                debug!(
+                    "Skipping {}: not due for a {} backup",
+                    entry.id,
+                    entry.frequency.name()
+                );
+            }
+            due
+        })
+        .collect();
+    if paths.is_empty() {
+        debug!("No changed files to back up");
+        return Ok(());
+    }
+
+    let files: Vec<String> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    backup_specific_dotfiles(config, &files)?;
+
+    if !has_uncommitted_changes(config)? {
+        debug!("Nothing to commit");
+        return Ok(());
+    }
+
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            path.strip_prefix(&config.home_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect();
+    commit_changes(config, &format!("Automatic backup: {}", names.join(", ")))?;
+
+    info!("Backed up changes to {}", names.join(", "));
+
+    Ok(())
+}
+
+/// Wrap a watcher error
+fn watch_error(err: notify::Error) -> DotfilesError {
+    DotfilesError::Io(std::io::Error::other(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer_merges_rapid_changes() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_secs(2));
+
+        assert_eq!(debouncer.ready(start), None);
+
+        debouncer.record(PathBuf::from("/home/.zshrc"), start);
+        debouncer.record(
+            PathBuf::from("/home/.vimrc"),
+            start + Duration::from_secs(1),
+        );
+        debouncer.record(
+            PathBuf::from("/home/.zshrc"),
+            start + Duration::from_secs(2),
+        );
+
+        // The last change pushed the deadline back
+        assert_eq!(debouncer.ready(start + Duration::from_secs(3)), None);
+        assert_eq!(
+            debouncer.ready(start + Duration::from_secs(4)),
+            Some(vec![
+                PathBuf::from("/home/.vimrc"),
+                PathBuf::from("/home/.zshrc")
+            ])
+        );
+        assert_eq!(debouncer.ready(start + Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn test_backup_changes_names_only_backed_up_files() {
+        use crate::history::init_git_repo;
+        use tempfile::TempDir;
+
+        let home_dir = TempDir::new().unwrap();
+        let vault_dir = TempDir::new().unwrap();
+        let config = Config::new(
+            vault_dir.path().to_path_buf(),
+            home_dir.path().to_path_buf(),
+        );
+        let repo = init_git_repo(&config).unwrap();
+        std::fs::write(home_dir.path().join(".bashrc"), "bash").unwrap();
+
+        // The second file was deleted before the backup ran
+        let changed = [
+            home_dir.path().join(".bashrc"),
+            home_dir.path().join(".gonerc"),
+        ];
+        backup_changes(&config, &changed).unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("Automatic backup: .bashrc"));
+    }
+}
@@ -0,0 +1,436 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use git2::{DiffOptions, Patch};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backup::Dotfile;
+use crate::cache::content_hash;
+use crate::history::{
+    DotfileVersion, get_dotfile_history, read_file_at, resolve_version, symlink_at,
+};
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::mask::{is_sensitive_file, mask_secrets};
+use crate::restore::decode_entry_content;
+use crate::status::FileStatus;
+use crate::symlink::{is_file_or_link, is_preserved_link};
+use crate::utils::normalize_path;
+use crate::{Config, DotfilesError};
+
+/// Produce a unified diff between two versions of a file
+///
+/// Returns an empty string when the contents are identical.
+pub fn unified_diff(
+    old: &[u8],
+    old_label: &str,
+    new: &[u8],
+    new_label: &str,
+) -> Result<String, DotfilesError> {
+    if old == new {
+        return Ok(String::new());
+    }
+
+    let mut options = DiffOptions::new();
+    options.context_lines(3);
+
+    let mut patch = Patch::from_buffers(
+        old,
+        Some(Path::new(old_label)),
+        new,
+        Some(Path::new(new_label)),
+        Some(&mut options),
+    )?;
+    let buf = patch.to_buf()?;
+
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Diff the stored copy of a tracked file against its live copy in the home directory
+///
+/// A missing copy on either side is diffed as empty. When `mask` is set,
+/// secrets in sensitive files are masked on both sides before diffing.
+pub fn diff_entry(
+    config: &Config,
+    entry: &DotfileEntry,
+    mask: bool,
+) -> Result<String, DotfilesError> {
+    let dotfile = entry.dotfile(config);
+
+    if !is_file_or_link(&dotfile.vault_path) && !is_file_or_link(&dotfile.original_path) {
+        return Err(DotfilesError::DotfileNotFound(entry.id.clone()));
+    }
+
+    // Links are diffed by their targets
+    if dotfile.vault_path.is_symlink() || is_preserved_link(config, &dotfile.original_path) {
+        return unified_diff(
+            &link_content(&dotfile.vault_path),
+            &format!("vault/{}", entry.stored_path(config).display()),
+            &link_content(&dotfile.original_path),
+            &format!("home/{}", entry.source.display()),
+        );
+    }
+
+    let mut stored = match fs::read(&dotfile.vault_path) {
+        Ok(content) => decode_entry_content(config, entry, &content)?,
+        Err(_) => Vec::new(),
+    };
+
+    // An unchanged home copy on a slow network home isn't read again
+    if config.home_cache
+        && dotfile.original_path.is_file()
+        && content_hash(&stored)?
+            == config
+                .hash_cache
+                .home_hash(config, &dotfile.original_path)?
+    {
+        return Ok(String::new());
+    }
+
+    let mut home = fs::read(&dotfile.original_path).unwrap_or_default();
+
+    if mask && is_sensitive_file(&entry.source) {
+        stored = mask_secrets(&String::from_utf8_lossy(&stored)).into_bytes();
+        home = mask_secrets(&String::from_utf8_lossy(&home)).into_bytes();
+    }
+
+    unified_diff(
+        &stored,
+        &format!("vault/{}", entry.stored_path(config).display()),
+        &home,
+        &format!("home/{}", entry.source.display()),
+    )
+}
+
+/// Get what a diff shows for one side of a link: its target, or the file's content
+fn link_content(path: &Path) -> Vec<u8> {
+    match fs::read_link(path) {
+        Ok(target) => format!("-> {}\n", target.display()).into_bytes(),
+        Err(_) => fs::read(path).unwrap_or_default(),
+    }
+}
+
+/// Diff the given files, or every tracked file if none are given
+///
+/// Returns `(entry id, diff)` pairs for the files that differ.
+pub fn diff_files(
+    config: &Config,
+    files: &[String],
+    mask: bool,
+) -> Result<Vec<(String, String)>, DotfilesError> {
+    let mut diffs = Vec::new();
+    for entry in selected_entries(config, files)? {
+        let diff = diff_entry(config, &entry, mask)?;
+        if !diff.is_empty() {
+            diffs.push((entry.id, diff));
+        }
+    }
+
+    config.hash_cache.save(config)?;
+
+    Ok(diffs)
+}
+
+/// Get the entries of the given files, or of every tracked file that applies here
+fn selected_entries(config: &Config, files: &[String]) -> Result<Vec<DotfileEntry>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    if files.is_empty() {
+        return Ok(manifest
+            .entries
+            .into_values()
+            .filter(DotfileEntry::applies)
+            .collect());
+    }
+
+    Ok(files
+        .iter()
+        .map(|file| manifest.entry_for(&Dotfile::new(normalize_path(file, config), config), config))
+        .collect())
+}
+
+/// How a home file changed since a past vault version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HomeChange {
+    /// Path of the file relative to the home directory
+    pub path: PathBuf,
+
+    /// `new` if the file wasn't in the vault then, `deleted` if it's no longer
+    /// in the home directory
+    pub status: FileStatus,
+
+    /// Lines added in the home copy
+    pub added: usize,
+
+    /// Lines removed from the home copy
+    pub removed: usize,
+}
+
+/// Summarize how home files changed since a past vault version
+///
+/// `against` is a commit, tag or date as `resolve_version` takes it. Every
+/// tracked file is compared, or only `files` if given, and the ones that
+/// differ are returned sorted by path along with the version compared
+/// against. Templates are left out, as their home copies are rendered, and
+/// so is starter content, which home copies are meant to depart from.
+pub fn changes_since(
+    config: &Config,
+    against: &str,
+    files: &[String],
+) -> Result<(DotfileVersion, Vec<HomeChange>), DotfilesError> {
+    let version = resolve_version(config, against)?;
+
+    let mut changes = Vec::new();
+    for entry in selected_entries(config, files)? {
+        if entry.is_template() || entry.create_if_missing {
+            continue;
+        }
+
+        let dotfile = entry.dotfile(config);
+        let stored_path = entry.stored_path(config);
+        let link = is_preserved_link(config, &dotfile.original_path);
+        let (past, home) = match symlink_at(config, &version.commit_id, &stored_path)? {
+            Some(target) => (
+                Some(format!("-> {}\n", target.display()).into_bytes()),
+                is_file_or_link(&dotfile.original_path)
+                    .then(|| link_content(&dotfile.original_path)),
+            ),
+            None => {
+                let past = match read_file_at(config, &version.commit_id, &stored_path)? {
+                    Some(content) => Some(decode_entry_content(config, &entry, &content)?),
+                    None => None,
+                };
+                let home = if link {
+                    Some(link_content(&dotfile.original_path))
+                } else {
+                    fs::read(&dotfile.original_path).ok()
+                };
+                (past, home)
+            }
+        };
+
+        let status = match (&past, &home) {
+            (None, Some(_)) => FileStatus::New,
+            (Some(_), None) => FileStatus::Deleted,
+            (Some(past), Some(home)) if past != home => FileStatus::Modified,
+            _ => continue,
+        };
+        let past = past.unwrap_or_default();
+        let home = home.unwrap_or_default();
+        let (_, added, removed) =
+            Patch::from_buffers(&past, None, &home, None, None)?.line_stats()?;
+
+        changes.push(HomeChange {
+            path: entry.source,
+            status,
+            added,
+            removed,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok((version, changes))
+}
+
+/// When the vault and home copies of a drifted file were last in sync
+///
+/// Context for deciding between backing up the home copy and restoring the
+/// vault's.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// The last vault version the home copy was in sync with, if any
+    pub common: Option<DotfileVersion>,
+
+    /// Vault versions made after the common version, newest first
+    pub vault_changes: Vec<DotfileVersion>,
+
+    /// When the home copy was last changed, if it changed after the common version
+    pub home_changed: Option<DateTime<Local>>,
+}
+
+/// Find when the vault and home copies of a tracked file diverged
+///
+/// If the home copy still matches a version in the vault history, that is
+/// the common version. Otherwise the home copy was edited, and the common
+/// version is the last one made before its modification time. Returns `None`
+/// if the vault has no history for the file or the home copy is missing.
+pub fn divergence(config: &Config, file: &str) -> Result<Option<Divergence>, DotfilesError> {
+    let entry = Manifest::load(config)?
+        .entry_for(&Dotfile::new(normalize_path(file, config), config), config);
+    let dotfile = entry.dotfile(config);
+
+    if !config.vault_dir.join(".git").exists()
+        || !dotfile.vault_path.is_file()
+        || dotfile.vault_path.is_symlink()
+    {
+        return Ok(None);
+    }
+    let Ok(home) = fs::read(&dotfile.original_path) else {
+        return Ok(None);
+    };
+
+    let versions = get_dotfile_history(config, file, 0, None)?;
+    let stored_path = entry.stored_path(config);
+
+    let mut matching = None;
+    for (i, version) in versions.iter().enumerate() {
+        if let Some(stored) = read_file_at(config, &version.commit_id, &stored_path)?
+            && decode_entry_content(config, &entry, &stored)? == home
+        {
+            matching = Some(i);
+            break;
+        }
+    }
+
+    let (common, home_changed) = match matching {
+        Some(i) => (Some(i), None),
+        None => {
+            let modified: DateTime<Local> =
+                fs::metadata(&dotfile.original_path)?.modified()?.into();
+            let common = versions
+                .iter()
+                .position(|version| version.timestamp <= modified);
+            (common, Some(modified))
+        }
+    };
+
+    let newer = common.unwrap_or(versions.len());
+    Ok(Some(Divergence {
+        common: common.map(|i| versions[i].clone()),
+        vault_changes: versions[..newer].to_vec(),
+        home_changed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff(b"a\nb\n", "home/.rc", b"a\nc\n", "vault/.rc").unwrap();
+
+        assert!(diff.contains("--- a/home/.rc"));
+        assert!(diff.contains("+++ b/vault/.rc"));
+        assert!(diff.contains("-b\n"));
+        assert!(diff.contains("+c\n"));
+
+        assert!(unified_diff(b"same", "x", b"same", "y").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_changes_since() {
+        use crate::backup::backup_all_dotfiles;
+        use crate::history::{commit_changes, init_git_repo};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "a\nb\n").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "set nu\n").unwrap();
+        fs::write(config.home_dir.join(".oldrc"), "old\n").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        let left = commit_changes(&config, "Before vacation").unwrap();
+
+        fs::write(config.home_dir.join(".bashrc"), "a\nc\nd\n").unwrap();
+        fs::write(config.home_dir.join(".newrc"), "new\n").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Later").unwrap();
+        fs::remove_file(config.home_dir.join(".oldrc")).unwrap();
+
+        let (version, changes) = changes_since(&config, &left, &[]).unwrap();
+        assert_eq!(version.commit_id, left);
+        assert_eq!(
+            changes,
+            vec![
+                HomeChange {
+                    path: PathBuf::from(".bashrc"),
+                    status: FileStatus::Modified,
+                    added: 2,
+                    removed: 1,
+                },
+                HomeChange {
+                    path: PathBuf::from(".newrc"),
+                    status: FileStatus::New,
+                    added: 1,
+                    removed: 0,
+                },
+                HomeChange {
+                    path: PathBuf::from(".oldrc"),
+                    status: FileStatus::Deleted,
+                    added: 0,
+                    removed: 1,
+                },
+            ]
+        );
+
+        let (_, changes) = changes_since(&config, "HEAD", &[".vimrc".to_string()]).unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_files_masks_secrets() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        fs::write(
+            config.vault_dir.join(".npmrc"),
+            "_authToken=old\ncolor=true\n",
+        )
+        .unwrap();
+        fs::write(
+            config.home_dir.join(".npmrc"),
+            "_authToken=new\ncolor=false\n",
+        )
+        .unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "set nu\n").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "set nu\n").unwrap();
+
+        let files = vec![".npmrc".to_string(), ".vimrc".to_string()];
+        let diffs = diff_files(&config, &files, true).unwrap();
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].0, ".npmrc");
+        assert!(diffs[0].1.contains("+color=false"));
+        assert!(!diffs[0].1.contains("new"));
+
+        let diffs = diff_files(&config, &files, false).unwrap();
+        assert!(diffs[0].1.contains("+_authToken=new"));
+
+        // Without a vault history there is nothing to say about divergence
+        assert!(divergence(&config, ".npmrc").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_divergence() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        let bashrc = config.home_dir.join(".bashrc");
+        let backup = |content: &str, message: &str| {
+            fs::write(&bashrc, content).unwrap();
+            crate::backup::backup_specific_dotfiles(&config, &[".bashrc".to_string()])
+                .and_then(|plan| plan.commit(&config, message))
+                .unwrap();
+        };
+
+        backup("v1\n", "First");
+        backup("v2\n", "Second");
+
+        // The home copy matches an older version and only the vault moved on
+        fs::write(&bashrc, "v1\n").unwrap();
+        let divergence = divergence(&config, ".bashrc").unwrap().unwrap();
+        assert_eq!(divergence.common.unwrap().message, "First");
+        assert_eq!(divergence.vault_changes.len(), 1);
+        assert!(divergence.home_changed.is_none());
+
+        // An edited home copy started from the version made before the edit
+        fs::write(&bashrc, "edited\n").unwrap();
+        let divergence = super::divergence(&config, ".bashrc").unwrap().unwrap();
+        assert_eq!(divergence.common.unwrap().message, "Second");
+        assert!(divergence.vault_changes.is_empty());
+        assert!(divergence.home_changed.is_some());
+    }
+}
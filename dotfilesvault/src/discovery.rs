@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Config, is_dotfile};
+
+/// Home-relative config files and directories backed up on Windows by default
+///
+/// Windows programs keep their settings under `AppData` and `Documents`
+/// rather than in dotfiles, and whole `AppData` directories mostly hold
+/// caches, so only well-known settings are picked up.
+pub const WINDOWS_CONFIG_LOCATIONS: [&str; 9] = [
+    "AppData/Roaming/Code/User/settings.json",
+    "AppData/Roaming/Code/User/keybindings.json",
+    "AppData/Roaming/Code/User/snippets",
+    "AppData/Roaming/alacritty",
+    "AppData/Roaming/nushell",
+    "AppData/Local/nvim",
+    "AppData/Local/Packages/Microsoft.WindowsTerminal_8wekyb3d8bbwe/LocalState/settings.json",
+    "Documents/PowerShell/Microsoft.PowerShell_profile.ps1",
+    "Documents/WindowsPowerShell/Microsoft.PowerShell_profile.ps1",
+];
+
+/// Get the config locations backed up when the config file sets none
+pub fn default_config_locations() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        WINDOWS_CONFIG_LOCATIONS.iter().map(PathBuf::from).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Check if a file is a config file backups pick up
+///
+/// That's a dotfile, a file at or under one of `config.config_locations`, or
+/// on Windows a file with the hidden attribute, the counterpart of a leading
+/// dot there.
+pub fn is_config_file(config: &Config, path: &Path) -> bool {
+    is_dotfile(path) || is_in_config_location(config, path) || is_hidden(path)
+}
+
+/// Check if a path is at or under one of the configured config locations
+pub fn is_in_config_location(config: &Config, path: &Path) -> bool {
+    let relative = path.strip_prefix(&config.home_dir).unwrap_or(path);
+    config
+        .config_locations
+        .iter()
+        .any(|location| relative.starts_with(location))
+}
+
+/// Check if a file has the hidden attribute, and isn't a system file
+///
+/// System files like `NTUSER.DAT` are hidden too, but never config. Always
+/// false on other platforms, where hidden files are dotfiles.
+pub fn is_hidden(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+        std::fs::symlink_metadata(path).is_ok_and(|metadata| {
+            let attributes = metadata.file_attributes();
+            attributes & FILE_ATTRIBUTE_HIDDEN != 0 && attributes & FILE_ATTRIBUTE_SYSTEM == 0
+        })
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_config_file() {
+        let mut config = Config::new(PathBuf::from("/vault"), PathBuf::from("/home/me"));
+        config.config_locations = vec![
+            PathBuf::from("AppData/Roaming/alacritty"),
+            PathBuf::from("Documents/PowerShell/Microsoft.PowerShell_profile.ps1"),
+        ];
+
+        assert!(is_config_file(&config, Path::new("/home/me/.bashrc")));
+        assert!(is_config_file(
+            &config,
+            &config
+                .home_dir
+                .join("AppData")
+                .join("Roaming")
+                .join("alacritty")
+                .join("alacritty.toml")
+        ));
+        assert!(is_config_file(
+            &config,
+            Path::new("/home/me/Documents/PowerShell/Microsoft.PowerShell_profile.ps1")
+        ));
+        assert!(!is_config_file(
+            &config,
+            Path::new("/home/me/AppData/Roaming/Code/Cache/data")
+        ));
+        assert!(!is_config_file(&config, Path::new("/home/me/notes.txt")));
+    }
+}
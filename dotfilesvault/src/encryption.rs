@@ -0,0 +1,231 @@
+use age::secrecy::ExposeSecret;
+use age::x25519::Identity;
+use anyhow::Result;
+use ignore::gitignore::GitignoreBuilder;
+use log::{debug, info};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config_file::default_config_path;
+use crate::manifest::DotfileEntry;
+use crate::{Config, DotfilesError};
+
+/// Name of the age identity file, next to the config file
+pub const KEY_FILE_NAME: &str = "key.txt";
+
+/// First line of an ASCII-armored age file
+const ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// First line of a binary age file
+const BINARY_HEADER: &[u8] = b"age-encryption.org/v1";
+
+/// Get the path of the age identity used to encrypt vault files
+///
+/// Defaults to `key.txt` next to the default config file. The key must never
+/// be stored in the vault itself.
+pub fn key_path(config: &Config) -> PathBuf {
+    if let Some(key_file) = &config.key_file {
+        return key_file.clone();
+    }
+
+    default_config_path()
+        .and_then(|path| path.parent().map(|dir| dir.join(KEY_FILE_NAME)))
+        .unwrap_or_else(|| {
+            config
+                .home_dir
+                .join(".config")
+                .join("dotfilesvault")
+                .join(KEY_FILE_NAME)
+        })
+}
+
+/// Generate a new identity and save it, returning its public key
+///
+/// Fails if a key already exists, so an existing key is never overwritten.
+pub fn init_key(config: &Config) -> Result<String, DotfilesError> {
+    let path = key_path(config);
+
+    if path.exists() {
+        return Err(DotfilesError::Encryption(format!(
+            "a key already exists at {}",
+            path.display()
+        )));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let identity = Identity::generate();
+    let recipient = identity.to_public().to_string();
+    let content = format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        chrono::Local::now().to_rfc3339(),
+        recipient,
+        identity.to_string().expose_secret()
+    );
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(content.as_bytes())?;
+
+    info!("Created encryption key at {:?}", path);
+
+    Ok(recipient)
+}
+
+/// Read the identity file, e.g. to copy it to another machine
+pub fn export_key(config: &Config) -> Result<String, DotfilesError> {
+    let path = key_path(config);
+
+    fs::read_to_string(&path).map_err(|_| {
+        DotfilesError::Encryption(format!(
+            "no key at {}, run `dotfilesvault key init` first",
+            path.display()
+        ))
+    })
+}
+
+/// Load the identity used to encrypt and decrypt vault files
+fn load_identity(config: &Config) -> Result<Identity, DotfilesError> {
+    let content = export_key(config)?;
+
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .ok_or_else(|| DotfilesError::Encryption("the key file has no secret key".to_string()))?
+        .parse()
+        .map_err(|err: &str| DotfilesError::Encryption(err.to_string()))
+}
+
+/// Check if an entry matches the configured `encrypt` patterns
+pub fn should_encrypt(config: &Config, entry: &DotfileEntry) -> Result<bool, DotfilesError> {
+    if config.encrypt.is_empty() {
+        return Ok(false);
+    }
+
+    let mut builder = GitignoreBuilder::new(&config.home_dir);
+    for pattern in &config.encrypt {
+        builder
+            .add_line(None, pattern)
+            .map_err(|err| DotfilesError::InvalidIgnorePattern(pattern.clone(), err.to_string()))?;
+    }
+    let matcher = builder
+        .build()
+        .map_err(|err| DotfilesError::InvalidIgnorePattern(String::new(), err.to_string()))?;
+
+    Ok(matcher
+        .matched_path_or_any_parents(config.home_dir.join(&entry.source), false)
+        .is_ignore())
+}
+
+/// Check if content is an age-encrypted file
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(ARMOR_HEADER) || content.starts_with(BINARY_HEADER)
+}
+
+/// Check if a file on disk is age-encrypted, reading only its header
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; ARMOR_HEADER.len()];
+
+    File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .map(|len| is_encrypted(&header[..len]))
+        .unwrap_or(false)
+}
+
+/// Encrypt content to the vault key, ASCII-armored so it diffs as text in git
+pub fn encrypt(config: &Config, plaintext: &[u8]) -> Result<Vec<u8>, DotfilesError> {
+    let recipient = load_identity(config)?.to_public();
+
+    debug!("Encrypting {} bytes", plaintext.len());
+
+    age::encrypt_and_armor(&recipient, plaintext)
+        .map(String::into_bytes)
+        .map_err(|err| DotfilesError::Encryption(err.to_string()))
+}
+
+/// Decrypt content encrypted with the vault key
+pub fn decrypt(config: &Config, ciphertext: &[u8]) -> Result<Vec<u8>, DotfilesError> {
+    let identity = load_identity(config)?;
+
+    age::decrypt(&identity, ciphertext).map_err(|err| DotfilesError::Encryption(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use crate::backend::GitBackend;
+    use crate::backup::{Dotfile, backup_entry};
+    use crate::restore::restore_entry;
+    use tempfile::TempDir;
+
+    fn setup_test_env() -> (Config, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.key_file = Some(temp_dir.path().join("key.txt"));
+        config.encrypt = vec![".netrc".to_string(), ".aws/".to_string()];
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        (config, temp_dir)
+    }
+
+    #[test]
+    fn test_init_and_export_key() {
+        let (config, _temp_dir) = setup_test_env();
+
+        let recipient = init_key(&config).unwrap();
+        assert!(recipient.starts_with("age1"));
+        assert!(export_key(&config).unwrap().contains(&recipient));
+        assert!(matches!(
+            init_key(&config),
+            Err(DotfilesError::Encryption(_))
+        ));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(key_path(&config))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_encrypted_backup_and_restore() {
+        let (config, _temp_dir) = setup_test_env();
+        init_key(&config).unwrap();
+
+        let netrc = config.home_dir.join(".netrc");
+        fs::write(&netrc, "machine example.com password hunter2\n").unwrap();
+        let entry = DotfileEntry::new(&Dotfile::new(netrc.clone(), &config), &config);
+        assert!(should_encrypt(&config, &entry).unwrap());
+
+        backup_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        let stored = fs::read(config.vault_dir.join(".netrc")).unwrap();
+        assert!(is_encrypted(&stored));
+        assert!(!String::from_utf8_lossy(&stored).contains("hunter2"));
+
+        // Unchanged plaintext keeps the stored ciphertext, avoiding churn in git
+        backup_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        assert_eq!(fs::read(config.vault_dir.join(".netrc")).unwrap(), stored);
+
+        fs::remove_file(&netrc).unwrap();
+        restore_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        assert_eq!(
+            fs::read_to_string(&netrc).unwrap(),
+            "machine example.com password hunter2\n"
+        );
+    }
+}
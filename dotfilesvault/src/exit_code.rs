@@ -0,0 +1,43 @@
+/// Stable exit codes returned by the dotfilesvault CLI
+///
+/// These values are part of the public contract for shell scripts and CI and
+/// must not be renumbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The command completed and nothing needs attention
+    Success = 0,
+
+    /// The command completed but home and vault have drifted apart
+    Drift = 1,
+
+    /// The command failed
+    Error = 2,
+
+    /// The command found conflicts that need manual resolution
+    Conflicts = 3,
+}
+
+impl ExitCode {
+    /// Get the numeric process exit code
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Terminate the process with this exit code
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_stable() {
+        assert_eq!(ExitCode::Success.code(), 0);
+        assert_eq!(ExitCode::Drift.code(), 1);
+        assert_eq!(ExitCode::Error.code(), 2);
+        assert_eq!(ExitCode::Conflicts.code(), 3);
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use git2::{Delta, Repository};
+use log::{debug, info};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::history::{commit_at, read_blob};
+use crate::undo::UNDO_DIR;
+use crate::{Config, DotfilesError, is_vault_internal};
+
+/// Summary of an export
+#[derive(Debug, Clone, Default)]
+pub struct ExportSummary {
+    /// Vault-relative paths written to the archive
+    pub exported: Vec<PathBuf>,
+
+    /// Vault-relative paths deleted in the window (not present in the archive)
+    pub deleted: Vec<PathBuf>,
+
+    /// Whether the git history was archived too
+    pub history: bool,
+}
+
+/// Export the whole vault as a tar.gz archive
+///
+/// Files are written with their vault-relative paths, including the manifest,
+/// hooks and profiles, so extracting the archive into an empty directory
+/// gives a working vault. The `.git` directory is only archived with
+/// `include_history`; it is never listed in `exported`.
+pub fn export_vault(
+    config: &Config,
+    output: &Path,
+    include_history: bool,
+) -> Result<ExportSummary, DotfilesError> {
+    if !config.vault_dir.is_dir() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+
+    // Absolute, so a relative output inside the vault is still recognized
+    let output = std::path::absolute(output)?;
+    let encoder = GzEncoder::new(File::create(&output)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    archive.follow_symlinks(false);
+    let mut summary = ExportSummary {
+        history: include_history,
+        ..ExportSummary::default()
+    };
+
+    let entries = WalkDir::new(&config.vault_dir)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            (include_history || entry.file_name() != ".git")
+                && !(entry.depth() == 1 && entry.file_name() == UNDO_DIR)
+        });
+
+    for entry in entries {
+        let entry = entry.map_err(std::io::Error::other)?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        // The archive may be written inside the vault
+        if entry.path() == output {
+            continue;
+        }
+
+        let path = entry
+            .path()
+            .strip_prefix(&config.vault_dir)
+            .unwrap_or(entry.path());
+
+        debug!("Exporting {:?}", path);
+        archive.append_path_with_name(entry.path(), path)?;
+
+        if !path.starts_with(".git") {
+            summary.exported.push(path.to_path_buf());
+        }
+    }
+
+    archive.into_inner()?.finish()?;
+
+    info!(
+        "Exported {} files{} to {:?}",
+        summary.exported.len(),
+        if include_history { " and history" } else { "" },
+        output
+    );
+
+    Ok(summary)
+}
+
+/// Export the files that changed between two points in time as a tar.gz archive
+///
+/// Files are written with their vault-relative paths and the content they
+/// had at `to`. Files deleted in the window are reported but not archived.
+pub fn export_changed_between(
+    config: &Config,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    output: &Path,
+) -> Result<ExportSummary, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let to_version =
+        commit_at(config, to)?.ok_or_else(|| DotfilesError::VersionNotFound(to.to_rfc3339()))?;
+    let to_tree = repo
+        .find_commit(git2::Oid::from_str(&to_version.commit_id)?)?
+        .tree()?;
+
+    // Before the first commit everything counts as changed
+    let from_tree = match commit_at(config, from)? {
+        Some(version) => Some(
+            repo.find_commit(git2::Oid::from_str(&version.commit_id)?)?
+                .tree()?,
+        ),
+        None => None,
+    };
+
+    let diff = repo.diff_tree_to_tree(from_tree.as_ref(), Some(&to_tree), None)?;
+
+    let encoder = GzEncoder::new(File::create(output)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    let mut summary = ExportSummary::default();
+
+    for delta in diff.deltas() {
+        let file = match delta.status() {
+            Delta::Deleted => {
+                if let Some(path) = delta.old_file().path()
+                    && !is_vault_internal(path)
+                {
+                    summary.deleted.push(path.to_path_buf());
+                }
+                continue;
+            }
+            _ => delta.new_file(),
+        };
+
+        let Some(path) = file.path() else {
+            continue;
+        };
+        if is_vault_internal(path) {
+            continue;
+        }
+
+        debug!("Exporting {:?}", path);
+
+        let content = read_blob(config, file.id())?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(u32::from(file.mode()) & 0o777);
+        header.set_mtime(to_version.timestamp.timestamp().max(0) as u64);
+        header.set_cksum();
+        archive.append_data(&mut header, path, content.as_slice())?;
+
+        summary.exported.push(path.to_path_buf());
+    }
+
+    archive.into_inner()?.finish()?;
+
+    info!(
+        "Exported {} changed files to {:?}",
+        summary.exported.len(),
+        output
+    );
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{commit_changes, init_git_repo};
+    use flate2::read::GzDecoder;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(config.vault_dir.join("profiles/work")).unwrap();
+        init_git_repo(&config).unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash\n").unwrap();
+        fs::write(config.vault_dir.join("profiles/work/.bashrc"), "work\n").unwrap();
+        commit_changes(&config, "Initial").unwrap();
+
+        let archive_names = |output: &Path| -> Vec<PathBuf> {
+            let mut archive = tar::Archive::new(GzDecoder::new(File::open(output).unwrap()));
+            archive
+                .entries()
+                .unwrap()
+                .map(|entry| entry.unwrap().path().unwrap().into_owned())
+                .collect()
+        };
+
+        let output = temp_dir.path().join("vault.tar.gz");
+        let summary = export_vault(&config, &output, false).unwrap();
+        assert_eq!(
+            summary.exported,
+            vec![
+                PathBuf::from(".bashrc"),
+                PathBuf::from(".gitignore"),
+                PathBuf::from("profiles/work/.bashrc")
+            ]
+        );
+        assert_eq!(archive_names(&output), summary.exported);
+
+        let summary = export_vault(&config, &output, true).unwrap();
+        assert_eq!(summary.exported.len(), 3);
+        assert!(
+            archive_names(&output)
+                .iter()
+                .any(|name| name.starts_with(".git"))
+        );
+    }
+
+    #[test]
+    fn test_export_changed_between() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        init_git_repo(&config).unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash\n").unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "vim\n").unwrap();
+        commit_changes(&config, "Initial").unwrap();
+
+        let output = temp_dir.path().join("changes.tar.gz");
+        let before = Local::now() - chrono::Duration::days(1);
+        let summary = export_changed_between(&config, before, Local::now(), &output).unwrap();
+
+        let mut exported = summary.exported.clone();
+        exported.sort();
+        assert!(exported.contains(&PathBuf::from(".bashrc")));
+        assert!(exported.contains(&PathBuf::from(".vimrc")));
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(&output).unwrap()));
+        let names: Vec<PathBuf> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert_eq!(names.len(), summary.exported.len());
+
+        // Nothing changed between the same two points in time
+        let now = Local::now();
+        let summary = export_changed_between(&config, now, now, &output).unwrap();
+        assert!(summary.exported.is_empty());
+    }
+}
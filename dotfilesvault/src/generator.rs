@@ -0,0 +1,324 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the file in the vault declaring the shell environment
+pub const SHELL_SOURCE: &str = "shell.toml";
+
+/// Home-relative directory the generated shell files are written to
+pub const GENERATED_DIR: &str = ".dotfilesvault";
+
+/// Header written at the top of generated files
+const GENERATED_HEADER: &str =
+    "# Generated by dotfilesvault from the vault's shell.toml, edit that instead";
+
+/// A shell that files are generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetShell {
+    /// GNU bash
+    Bash,
+
+    /// Z shell
+    Zsh,
+
+    /// fish, whose syntax differs from the POSIX shells
+    Fish,
+}
+
+impl TargetShell {
+    /// Every shell files are generated for
+    pub const ALL: [TargetShell; 3] = [TargetShell::Bash, TargetShell::Zsh, TargetShell::Fish];
+
+    /// Get the shell's name, also the extension of its generated file
+    pub fn name(self) -> &'static str {
+        match self {
+            TargetShell::Bash => "bash",
+            TargetShell::Zsh => "zsh",
+            TargetShell::Fish => "fish",
+        }
+    }
+
+    /// Get the rc file line loading the generated file
+    pub fn source_line(self) -> String {
+        let path = format!("~/{}/shell.{}", GENERATED_DIR, self.name());
+        match self {
+            TargetShell::Bash | TargetShell::Zsh => format!("[ -f {0} ] && . {0}", path),
+            TargetShell::Fish => format!("test -f {0}; and source {0}", path),
+        }
+    }
+}
+
+impl FromStr for TargetShell {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bash" => Ok(TargetShell::Bash),
+            "zsh" => Ok(TargetShell::Zsh),
+            "fish" => Ok(TargetShell::Fish),
+            _ => Err(format!(
+                "invalid shell {:?}, expected bash, zsh or fish",
+                value
+            )),
+        }
+    }
+}
+
+/// Shell environment declared once in the vault's `shell.toml`
+///
+/// ```toml
+/// path = ["~/.cargo/bin", "~/.local/bin"]
+///
+/// [env]
+/// EDITOR = "nvim"
+///
+/// [aliases]
+/// gs = "git status"
+/// ```
+///
+/// Values are double-quoted in the generated files, so variables such as
+/// `$HOME` in them are expanded by the shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellSource {
+    /// Directories put in front of `PATH`, first one first; `~/` is the home directory
+    #[serde(default)]
+    pub path: Vec<String>,
+
+    /// Exported environment variables
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// Aliases, by name
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl ShellSource {
+    /// Parse `shell.toml` content, checking variable and alias names
+    pub fn parse(content: &str, path: &str) -> Result<Self, DotfilesError> {
+        let invalid = |reason: String| DotfilesError::InvalidConfig(path.to_string(), reason);
+
+        let source: ShellSource =
+            toml::from_str(content).map_err(|err| invalid(err.to_string()))?;
+        for name in source.env.keys() {
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(invalid(format!("invalid variable name {:?}", name)));
+            }
+        }
+        for name in source.aliases.keys() {
+            let valid = !name.is_empty()
+                && !name.starts_with('-')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+            if !valid {
+                return Err(invalid(format!("invalid alias name {:?}", name)));
+            }
+        }
+
+        Ok(source)
+    }
+
+    /// Render the environment as code for a shell
+    pub fn render(&self, shell: TargetShell) -> String {
+        let mut script = format!("{}\n", GENERATED_HEADER);
+
+        if !self.path.is_empty() {
+            let dirs: Vec<String> = self.path.iter().map(|dir| home_relative(dir)).collect();
+            match shell {
+                TargetShell::Bash | TargetShell::Zsh => script.push_str(&format!(
+                    "export PATH=\"{}:$PATH\"\n",
+                    dirs.iter()
+                        .map(|dir| quote_inner(dir, shell))
+                        .collect::<Vec<_>>()
+                        .join(":")
+                )),
+                TargetShell::Fish => script.push_str(&format!(
+                    "set -gx PATH {} $PATH\n",
+                    dirs.iter()
+                        .map(|dir| format!("\"{}\"", quote_inner(dir, shell)))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )),
+            }
+        }
+
+        for (name, value) in &self.env {
+            let value = quote_inner(value, shell);
+            script.push_str(&match shell {
+                TargetShell::Bash | TargetShell::Zsh => format!("export {}=\"{}\"\n", name, value),
+                TargetShell::Fish => format!("set -gx {} \"{}\"\n", name, value),
+            });
+        }
+
+        for (name, command) in &self.aliases {
+            script.push_str(&match shell {
+                TargetShell::Bash | TargetShell::Zsh => {
+                    format!("alias {}='{}'\n", name, command.replace('\'', r"'\''"))
+                }
+                TargetShell::Fish => format!(
+                    "alias {} '{}'\n",
+                    name,
+                    command.replace('\\', r"\\").replace('\'', r"\'")
+                ),
+            });
+        }
+
+        script
+    }
+}
+
+/// Write `~/` as `$HOME/`, so it also expands inside double quotes
+fn home_relative(dir: &str) -> String {
+    match dir.strip_prefix("~/") {
+        Some(rest) => format!("$HOME/{}", rest),
+        None if dir == "~" => "$HOME".to_string(),
+        None => dir.to_string(),
+    }
+}
+
+/// Escape a value for use inside double quotes, leaving `$` to be expanded
+fn quote_inner(value: &str, shell: TargetShell) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        let escape = match shell {
+            TargetShell::Bash | TargetShell::Zsh => matches!(c, '"' | '\\' | '`'),
+            TargetShell::Fish => matches!(c, '"' | '\\'),
+        };
+        if escape {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+
+    quoted
+}
+
+/// Get the path of the vault's `shell.toml`
+pub fn source_path(config: &Config) -> PathBuf {
+    config.vault_dir.join(SHELL_SOURCE)
+}
+
+/// Get the path of a shell's generated file in the home directory
+pub fn generated_path(config: &Config, shell: TargetShell) -> PathBuf {
+    config
+        .home_dir
+        .join(GENERATED_DIR)
+        .join(format!("shell.{}", shell.name()))
+}
+
+/// Read the vault's `shell.toml`, if it has one
+pub fn load_shell_source(config: &Config) -> Result<Option<ShellSource>, DotfilesError> {
+    let path = source_path(config);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    ShellSource::parse(&content, &path.display().to_string()).map(Some)
+}
+
+/// Write the files generated from `shell.toml` for every shell into the home directory
+///
+/// Returns the files written. Without a `shell.toml` nothing is written, and
+/// files generated from an earlier one are removed. With `config.dry_run`
+/// set the files are only listed.
+pub fn write_shell_files(config: &Config) -> Result<Vec<PathBuf>, DotfilesError> {
+    let Some(source) = load_shell_source(config)? else {
+        if !config.dry_run {
+            for shell in TargetShell::ALL {
+                let path = generated_path(config, shell);
+                if path.is_file() {
+                    debug!("Removing {:?}, the vault has no {}", path, SHELL_SOURCE);
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        return Ok(Vec::new());
+    };
+
+    let mut written = Vec::new();
+    for shell in TargetShell::ALL {
+        let path = generated_path(config, shell);
+        if !config.dry_run {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_file(&path, source.render(shell).as_bytes())?;
+        }
+        written.push(path);
+    }
+
+    info!("Generated shell files for {} shells", written.len());
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = r#"
+path = ["~/.cargo/bin", "/opt/bin"]
+
+[env]
+EDITOR = "nvim"
+GREETING = "say \"hi\" to $USER"
+
+[aliases]
+gs = "git status"
+quote = "echo 'it works'"
+"#;
+
+    #[test]
+    fn test_render() {
+        let source = ShellSource::parse(SOURCE, SHELL_SOURCE).unwrap();
+
+        let bash = source.render(TargetShell::Bash);
+        assert!(bash.contains("export PATH=\"$HOME/.cargo/bin:/opt/bin:$PATH\"\n"));
+        assert!(bash.contains("export EDITOR=\"nvim\"\n"));
+        assert!(bash.contains(r#"export GREETING="say \"hi\" to $USER""#));
+        assert!(bash.contains("alias gs='git status'\n"));
+        assert!(bash.contains(r"alias quote='echo '\''it works'\'''"));
+        assert_eq!(source.render(TargetShell::Zsh), bash);
+
+        let fish = source.render(TargetShell::Fish);
+        assert!(fish.contains("set -gx PATH \"$HOME/.cargo/bin\" \"/opt/bin\" $PATH\n"));
+        assert!(fish.contains("set -gx EDITOR \"nvim\"\n"));
+        assert!(fish.contains(r"alias quote 'echo \'it works\''"));
+
+        assert!(ShellSource::parse("[env]\n\"1X\" = \"y\"\n", SHELL_SOURCE).is_err());
+        assert!(ShellSource::parse("[aliases]\n\"a b\" = \"y\"\n", SHELL_SOURCE).is_err());
+        assert!(ShellSource::parse("prompt = \"x\"\n", SHELL_SOURCE).is_err());
+    }
+
+    #[test]
+    fn test_write_shell_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        assert!(write_shell_files(&config).unwrap().is_empty());
+
+        fs::write(source_path(&config), SOURCE).unwrap();
+        let written = write_shell_files(&config).unwrap();
+        assert_eq!(written.len(), 3);
+        let fish = fs::read_to_string(generated_path(&config, TargetShell::Fish)).unwrap();
+        assert!(fish.contains("alias gs 'git status'\n"));
+
+        fs::remove_file(source_path(&config)).unwrap();
+        write_shell_files(&config).unwrap();
+        assert!(!generated_path(&config, TargetShell::Bash).exists());
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use log::debug;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::backup::{is_own_path, own_paths};
+use crate::manifest::Manifest;
+use crate::restore::list_backed_up_dotfiles;
+use crate::{Config, DotfilesError};
+
+/// Check if a path argument is a glob pattern rather than a path
+pub fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '[', '{'])
+}
+
+/// Expand a glob pattern into the home files it matches, for backups
+///
+/// The pattern is relative to the home directory (a leading `~/` or the
+/// home directory itself is stripped). `*` and `?` stay within a path
+/// component and `**` matches any number of directories. Only the
+/// directory before the first wildcard is walked, and dotfilesvault's own
+/// files are never matched. Returns the absolute paths of the matching
+/// files, sorted, or fails with `NoMatches`.
+pub fn expand_home_glob(config: &Config, pattern: &str) -> Result<Vec<PathBuf>, DotfilesError> {
+    let relative = home_relative(config, pattern);
+    let matcher = compile(pattern, &relative)?;
+    let own = own_paths(config);
+
+    let mut matches = Vec::new();
+    let root = config.home_dir.join(literal_prefix(&relative));
+    for entry in WalkDir::new(&root).into_iter().filter_entry(|entry| {
+        // The vault and the other dotfilesvault directories aren't walked
+        !is_own_path(&own, entry.path())
+    }) {
+        config.cancellation.check()?;
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if let Ok(path) = entry.path().strip_prefix(&config.home_dir)
+            && matcher.is_match(path)
+        {
+            matches.push(entry.path().to_path_buf());
+        }
+    }
+    matches.sort();
+
+    debug!("{} matches {} home files", pattern, matches.len());
+    if matches.is_empty() {
+        return Err(DotfilesError::NoMatches(pattern.to_string()));
+    }
+
+    Ok(matches)
+}
+
+/// Expand a glob pattern into the tracked dotfiles it matches, for restores
+///
+/// The pattern is matched like in `expand_home_glob` against the
+/// home-relative paths of the dotfiles in the vault: the manifest's
+/// entries, or the vault's files for vaults from before the manifest.
+/// Returns the matching home-relative paths, sorted, or fails with
+/// `NoMatches`.
+pub fn expand_vault_glob(config: &Config, pattern: &str) -> Result<Vec<PathBuf>, DotfilesError> {
+    let relative = home_relative(config, pattern);
+    let matcher = compile(pattern, &relative)?;
+
+    let manifest = Manifest::load(config)?;
+    let tracked: Vec<PathBuf> = if manifest.entries.is_empty() {
+        list_backed_up_dotfiles(config)?
+    } else {
+        manifest
+            .entries
+            .values()
+            .map(|entry| entry.source.clone())
+            .collect()
+    };
+
+    let mut matches: Vec<PathBuf> = tracked
+        .into_iter()
+        .filter(|path| matcher.is_match(path))
+        .collect();
+    matches.sort();
+    matches.dedup();
+
+    debug!("{} matches {} tracked dotfiles", pattern, matches.len());
+    if matches.is_empty() {
+        return Err(DotfilesError::NoMatches(pattern.to_string()));
+    }
+
+    Ok(matches)
+}
+
+/// Strip the home directory from a pattern, as `~/` or an absolute path
+fn home_relative(config: &Config, pattern: &str) -> String {
+    if let Some(rest) = pattern.strip_prefix("~/") {
+        return rest.to_string();
+    }
+    match Path::new(pattern).strip_prefix(&config.home_dir) {
+        Ok(rest) => rest.to_string_lossy().into_owned(),
+        Err(_) => pattern.to_string(),
+    }
+}
+
+/// Compile a home-relative pattern, reporting errors with the pattern given
+fn compile(pattern: &str, relative: &str) -> Result<GlobMatcher, DotfilesError> {
+    GlobBuilder::new(relative)
+        .literal_separator(true)
+        .backslash_escape(cfg!(not(windows)))
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|err| DotfilesError::InvalidGlob(pattern.to_string(), err.kind().to_string()))
+}
+
+/// Get the leading directories of a pattern that have no wildcards
+fn literal_prefix(pattern: &str) -> PathBuf {
+    let mut components: Vec<Component> = Path::new(pattern)
+        .components()
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect();
+    // The last component names the files matched, not a directory to walk
+    if components.len() == Path::new(pattern).components().count() {
+        components.pop();
+    }
+
+    components.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::manifest::add_paths;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_expand_globs() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(
+            temp_dir.path().join("home/dotfilesvault"),
+            temp_dir.path().join("home"),
+        );
+        let nvim = config.home_dir.join(".config/nvim");
+        fs::create_dir_all(nvim.join("lua/plugins")).unwrap();
+        fs::write(nvim.join("init.lua"), "init").unwrap();
+        fs::write(nvim.join("lua/plugins/lsp.lua"), "lsp").unwrap();
+        fs::write(nvim.join("lazy-lock.json"), "{}").unwrap();
+        fs::create_dir_all(config.home_dir.join(".ssh")).unwrap();
+        fs::write(config.home_dir.join(".ssh/config"), "Host *").unwrap();
+        fs::write(config.home_dir.join(".ssh/config.d"), "Host a").unwrap();
+
+        assert_eq!(
+            expand_home_glob(&config, ".config/nvim/**/*.lua").unwrap(),
+            vec![nvim.join("init.lua"), nvim.join("lua/plugins/lsp.lua")]
+        );
+        // A single star stays in its directory
+        assert_eq!(
+            expand_home_glob(&config, "~/.config/nvim/*.lua").unwrap(),
+            vec![nvim.join("init.lua")]
+        );
+        assert!(matches!(
+            expand_home_glob(&config, ".config/nvim/*.vim"),
+            Err(DotfilesError::NoMatches(_))
+        ));
+        assert!(matches!(
+            expand_home_glob(&config, ".config/[nvim"),
+            Err(DotfilesError::InvalidGlob(_, _))
+        ));
+
+        let files: Vec<String> = expand_home_glob(&config, ".ssh/config*")
+            .unwrap()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        add_paths(&config, &files).unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        assert_eq!(
+            expand_vault_glob(&config, ".ssh/config*").unwrap(),
+            vec![PathBuf::from(".ssh/config"), PathBuf::from(".ssh/config.d")]
+        );
+        assert!(matches!(
+            expand_vault_glob(&config, ".config/nvim/*.lua"),
+            Err(DotfilesError::NoMatches(_))
+        ));
+    }
+}
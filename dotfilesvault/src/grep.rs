@@ -0,0 +1,225 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use git2::{Delta, DiffOptions, FileMode, Oid, Repository};
+use log::debug;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::content_hash;
+use crate::encryption::is_encrypted;
+use crate::history::read_blob;
+use crate::mask::{is_sensitive_file, mask_secrets};
+use crate::restore::list_backed_up_dotfiles;
+use crate::{Config, DotfilesError, is_vault_internal};
+
+/// A line of a vault file matching a search pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GrepMatch {
+    /// Vault-relative path of the file
+    pub path: PathBuf,
+
+    /// Commit of the past version the line is in, none for the current file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// When the past version was committed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Local>>,
+
+    /// Line number, starting at 1
+    pub line_number: usize,
+
+    /// The matching line, with secrets masked in sensitive files if requested
+    pub line: String,
+}
+
+/// Search the files in the vault for lines matching a pattern
+///
+/// The current vault files are searched first, sorted by path. With
+/// `history`, every past version of a file is searched too, newest first;
+/// each version is found at the commit that introduced it, and versions
+/// identical to the current file or an already searched one are skipped. Encrypted and binary
+/// files are skipped. When `mask` is set, secrets in sensitive files are
+/// masked in the reported lines.
+pub fn grep_vault(
+    config: &Config,
+    pattern: &Regex,
+    history: bool,
+    mask: bool,
+) -> Result<Vec<GrepMatch>, DotfilesError> {
+    let mut matches = Vec::new();
+    let mut searched = HashSet::new();
+
+    let mut files = list_backed_up_dotfiles(config)?;
+    files.sort();
+    for path in files {
+        config.cancellation.check()?;
+        if config.vault_dir.join(&path).is_symlink() {
+            continue;
+        }
+        let content = fs::read(config.vault_dir.join(&path))?;
+        searched.insert(content_hash(&content)?);
+        search(&path, &content, pattern, mask, None, &mut matches);
+    }
+
+    if history && config.vault_dir.join(".git").exists() {
+        grep_history(config, pattern, mask, &mut searched, &mut matches)?;
+    }
+
+    Ok(matches)
+}
+
+/// Search the versions of files introduced by each commit, newest first
+fn grep_history(
+    config: &Config,
+    pattern: &Regex,
+    mask: bool,
+    searched: &mut HashSet<Oid>,
+    matches: &mut Vec<GrepMatch>,
+) -> Result<(), DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    if repo.head().is_err() {
+        return Ok(());
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+    for oid in revwalk {
+        config.cancellation.check()?;
+        let commit = repo.find_commit(oid?)?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff = repo.diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(&mut DiffOptions::new()),
+        )?;
+
+        let timestamp = Local
+            .timestamp_opt(commit.time().seconds(), 0)
+            .single()
+            .unwrap_or_else(Local::now);
+
+        for delta in diff.deltas() {
+            if !matches!(delta.status(), Delta::Added | Delta::Modified) {
+                continue;
+            }
+            let file = delta.new_file();
+            let Some(path) = file.path() else {
+                continue;
+            };
+            if file.mode() == FileMode::Link {
+                continue;
+            }
+            if is_vault_internal(path) || !searched.insert(file.id()) {
+                continue;
+            }
+
+            let content = read_blob(config, file.id())?;
+            let mut found = Vec::new();
+            search(
+                path,
+                &content,
+                pattern,
+                mask,
+                Some(&commit.id().to_string()),
+                &mut found,
+            );
+            for found in &mut found {
+                found.timestamp = Some(timestamp);
+            }
+            matches.extend(found);
+        }
+    }
+
+    Ok(())
+}
+
+/// Add the lines of a file's content matching a pattern
+fn search(
+    path: &Path,
+    content: &[u8],
+    pattern: &Regex,
+    mask: bool,
+    commit: Option<&str>,
+    matches: &mut Vec<GrepMatch>,
+) {
+    if is_encrypted(content) || content.contains(&0) {
+        debug!("Not searching encrypted or binary {:?}", path);
+        return;
+    }
+
+    let content = String::from_utf8_lossy(content);
+    let mask = mask && is_sensitive_file(path);
+    for (index, line) in content.lines().enumerate() {
+        if pattern.is_match(line) {
+            matches.push(GrepMatch {
+                path: path.to_path_buf(),
+                commit: commit.map(str::to_string),
+                timestamp: None,
+                line_number: index + 1,
+                line: if mask {
+                    mask_secrets(line)
+                } else {
+                    line.to_string()
+                },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{commit_changes, init_git_repo};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_grep_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+
+        fs::write(
+            config.vault_dir.join(".bashrc"),
+            "alias ll='ls -l'\nexport EDITOR=vim\n",
+        )
+        .unwrap();
+        let first = commit_changes(&config, "Add bashrc").unwrap();
+        fs::write(config.vault_dir.join(".bashrc"), "export EDITOR=nvim\n").unwrap();
+        commit_changes(&config, "Drop alias").unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "set number\n").unwrap();
+
+        let alias = Regex::new(r"^alias ll=").unwrap();
+        assert!(grep_vault(&config, &alias, false, true).unwrap().is_empty());
+
+        let found = grep_vault(&config, &alias, true, true).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, PathBuf::from(".bashrc"));
+        assert_eq!(found[0].commit.as_deref(), Some(first.as_str()));
+        assert_eq!(found[0].line_number, 1);
+
+        // Current files come first, then each past version once
+        let editor = Regex::new("EDITOR").unwrap();
+        let found = grep_vault(&config, &editor, true, true).unwrap();
+        let lines: Vec<(Option<&str>, &str)> = found
+            .iter()
+            .map(|found| (found.commit.as_deref(), found.line.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (None, "export EDITOR=nvim"),
+                (Some(first.as_str()), "export EDITOR=vim")
+            ]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::manifest::STATE_DIR;
+use crate::metrics::load_stats;
+use crate::utils::{hostname, write_file};
+use crate::{Config, DotfilesError};
+
+/// Name of the file in the vault's state directory holding the heartbeats
+pub const HEARTBEAT_FILE: &str = "heartbeat.json";
+
+/// How long a backup may be behind before it counts as stale, unless configured
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Longest time a heartbeat goes without being refreshed by a backup
+///
+/// Heartbeats are committed, so refreshing them at every backup would turn
+/// backups without changes into commits.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Last successful backup of every machine backing up into the vault
+///
+/// Unlike the backup counters in `.git`, the heartbeats are committed, so
+/// every clone of the vault can tell when each machine last backed up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeats {
+    /// Unix time of the last successful backup keyed by hostname
+    #[serde(default)]
+    pub hosts: BTreeMap<String, i64>,
+}
+
+impl Heartbeats {
+    /// Get the path of the heartbeat file for a vault
+    pub fn path(config: &Config) -> PathBuf {
+        config.vault_dir.join(STATE_DIR).join(HEARTBEAT_FILE)
+    }
+
+    /// Load the heartbeats from the vault, or none if nothing was recorded yet
+    pub fn load(config: &Config) -> Result<Self, DotfilesError> {
+        let path = Self::path(config);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+
+        // Heartbeats are only used for warnings, a corrupt file just starts over
+        Ok(serde_json::from_str(&content).unwrap_or_else(|err| {
+            warn!("Ignoring unreadable heartbeat file {:?}: {}", path, err);
+            Self::default()
+        }))
+    }
+
+    /// Save the heartbeats into the vault
+    pub fn save(&self, config: &Config) -> Result<(), DotfilesError> {
+        let path = Self::path(config);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        content.push('\n');
+
+        write_file(&path, content.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// A machine whose last successful backup is older than `config.stale_after`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StaleHost {
+    /// Hostname of the machine
+    pub host: String,
+
+    /// Unix time of its last successful backup
+    pub last_backup: i64,
+
+    /// Seconds since then
+    pub age_seconds: i64,
+}
+
+impl StaleHost {
+    /// Get the number of whole days since the last backup
+    pub fn age_days(&self) -> i64 {
+        self.age_seconds / (24 * 60 * 60)
+    }
+}
+
+/// Record a successful backup of this machine in the vault's heartbeat file
+///
+/// The heartbeat is only rewritten when it's older than a day, or half of
+/// `config.stale_after` if that's shorter, so backups without changes still
+/// don't commit. Machines without a hostname aren't recorded.
+pub fn record_heartbeat(config: &Config, now: DateTime<Local>) -> Result<(), DotfilesError> {
+    let Some(host) = hostname() else {
+        return Ok(());
+    };
+
+    let mut heartbeats = Heartbeats::load(config)?;
+    let refresh = match config.stale_after {
+        Some(stale_after) => REFRESH_INTERVAL.min(stale_after / 2),
+        None => REFRESH_INTERVAL,
+    };
+    if let Some(&last) = heartbeats.hosts.get(&host)
+        && now.timestamp().saturating_sub(last) < refresh.as_secs() as i64
+    {
+        return Ok(());
+    }
+
+    debug!("Recording heartbeat of {}", host);
+    heartbeats.hosts.insert(host, now.timestamp());
+    heartbeats.save(config)
+}
+
+/// Get the machines whose last backup is older than `config.stale_after`
+///
+/// For this machine the more recent of its heartbeat and the local backup
+/// counters counts, since the heartbeat is refreshed at most daily. Machines
+/// that never backed up aren't reported. Nothing is stale when
+/// `config.stale_after` is unset.
+pub fn stale_hosts(config: &Config, now: DateTime<Local>) -> Result<Vec<StaleHost>, DotfilesError> {
+    let Some(stale_after) = config.stale_after else {
+        return Ok(Vec::new());
+    };
+
+    let mut last_backups = Heartbeats::load(config)?.hosts;
+    if let Some(host) = hostname()
+        && let Some(last_success) = load_stats(config)?.last_success
+    {
+        let last = last_backups.entry(host).or_insert(last_success);
+        *last = (*last).max(last_success);
+    }
+
+    let stale = last_backups
+        .into_iter()
+        .map(|(host, last_backup)| StaleHost {
+            host,
+            last_backup,
+            age_seconds: now.timestamp().saturating_sub(last_backup),
+        })
+        .filter(|host| host.age_seconds >= stale_after.as_secs() as i64)
+        .collect();
+
+    Ok(stale)
+}
+
+/// Get this machine's entry in `stale_hosts`, if its last backup is stale
+pub fn stale_self(
+    config: &Config,
+    now: DateTime<Local>,
+) -> Result<Option<StaleHost>, DotfilesError> {
+    let Some(host) = hostname() else {
+        return Ok(None);
+    };
+
+    Ok(stale_hosts(config, now)?
+        .into_iter()
+        .find(|stale| stale.host == host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::init_git_repo;
+    use crate::metrics::record_backup;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_heartbeats_and_staleness() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        let host = hostname().unwrap();
+        let now = Local::now();
+        let day = chrono::Duration::days(1);
+
+        // Nothing recorded, nothing stale
+        assert!(stale_hosts(&config, now).unwrap().is_empty());
+
+        record_heartbeat(&config, now - day * 10).unwrap();
+        let mut heartbeats = Heartbeats::load(&config).unwrap();
+        heartbeats
+            .hosts
+            .insert("laptop".to_string(), (now - day * 2).timestamp());
+        heartbeats.save(&config).unwrap();
+
+        let stale = stale_hosts(&config, now).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].host, host);
+        assert_eq!(stale[0].age_days(), 10);
+        assert!(stale_self(&config, now).unwrap().is_some());
+
+        // A recent heartbeat is left alone, an old one is refreshed
+        record_heartbeat(&config, now - day * 10 + chrono::Duration::hours(1)).unwrap();
+        assert_eq!(
+            Heartbeats::load(&config).unwrap().hosts[&host],
+            (now - day * 10).timestamp()
+        );
+        record_heartbeat(&config, now).unwrap();
+        assert!(stale_hosts(&config, now).unwrap().is_empty());
+
+        // The local counters count for this machine, and the threshold is configurable
+        let mut heartbeats = Heartbeats::load(&config).unwrap();
+        heartbeats
+            .hosts
+            .insert(host.clone(), (now - day * 10).timestamp());
+        heartbeats.save(&config).unwrap();
+        record_backup(&config, true).unwrap();
+        assert!(stale_self(&config, now).unwrap().is_none());
+
+        config.stale_after = Some(Duration::from_secs(24 * 60 * 60));
+        let stale = stale_hosts(&config, now).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].host, "laptop");
+
+        config.stale_after = None;
+        assert!(stale_hosts(&config, now).unwrap().is_empty());
+    }
+}
@@ -1,25 +1,31 @@
 use anyhow::Result;
 use chrono::{DateTime, Local, TimeZone};
 use git2::{Repository, Signature};
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
+use crate::backend::{history_with, open_backend, parse_version_index};
 use crate::backup::Dotfile;
-use crate::{Config, DotfilesError};
+use crate::compact::{CompactSummary, squash_history};
+use crate::history_index::{IndexedChange, update_history_index};
+use crate::hooks::{HookEvent, run_operation_hook};
+use crate::lock::VaultLock;
+use crate::manifest::Manifest;
+use crate::profile::PROFILES_DIR;
+use crate::template::rendered_path;
+use crate::utils::parse_datetime;
+use crate::{Config, DotfilesError, is_vault_internal};
 
-/// Represents a version of a dotfile
-#[derive(Debug, Clone)]
-pub struct DotfileVersion {
-    /// The commit ID
-    pub commit_id: String,
+/// Author name used for vault commits when none is configured
+pub const DEFAULT_AUTHOR_NAME: &str = "Dotfilesvault";
 
-    /// The timestamp of the version
-    pub timestamp: DateTime<Local>,
+/// Author email used for vault commits when none is configured
+pub const DEFAULT_AUTHOR_EMAIL: &str = "dotfilesvault@example.com";
 
-    /// The message associated with the version
-    pub message: String,
-}
+pub use crate::backend::DotfileVersion;
 
 /// Initialize a Git repository in the vault directory
 pub fn init_git_repo(config: &Config) -> Result<Repository, DotfilesError> {
@@ -42,12 +48,22 @@ pub fn init_git_repo(config: &Config) -> Result<Repository, DotfilesError> {
     Ok(repo)
 }
 
+/// Build the signature used for vault commits, see `Config::author`
+pub fn signature(config: &Config) -> Result<Signature<'static>, DotfilesError> {
+    let (name, email) = config.author();
+    Ok(Signature::now(&name, &email)?)
+}
+
 /// Commit changes to the Git repository
 pub fn commit_changes(config: &Config, message: &str) -> Result<String, DotfilesError> {
     let repo = init_git_repo(config)?;
+    let _lock = VaultLock::acquire(config)?;
+
+    let files = changed_home_files(config, &repo)?;
+    run_operation_hook(config, HookEvent::PreCommit, files.clone())?;
 
     // Create the signature
-    let signature = Signature::now("Dotfilesvault", "dotfilesvault@example.com")?;
+    let signature = signature(config)?;
 
     // Add all files to the index
     let mut index = repo.index()?;
@@ -81,13 +97,415 @@ pub fn commit_changes(config: &Config, message: &str) -> Result<String, Dotfiles
 
     info!("Committed changes with ID: {}", commit_id);
 
+    // The index is only a cache, history rebuilds it if this fails
+    if let Err(err) = update_history_index(config) {
+        warn!("Failed to update the history index: {}", err);
+    }
+
+    run_operation_hook(config, HookEvent::PostCommit, files)?;
+
     Ok(commit_id.to_string())
 }
 
+/// Home directory paths of the dotfiles with uncommitted changes in the vault,
+/// see `home_file`
+fn changed_home_files(config: &Config, repo: &Repository) -> Result<Vec<PathBuf>, DotfilesError> {
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    let mut files = Vec::new();
+    for status in repo.statuses(Some(&mut options))?.iter() {
+        let Some(path) = status.path() else {
+            continue;
+        };
+
+        if let Some(file) = home_file(config, Path::new(path))
+            && !files.contains(&file)
+        {
+            files.push(file);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Map a vault-relative path to the home directory file it stores
+///
+/// Profile variants map to the file they replace and templates to their
+/// rendered file. Returns `None` for vault internals.
+pub(crate) fn home_file(config: &Config, path: &Path) -> Option<PathBuf> {
+    let relative: PathBuf = match path.strip_prefix(PROFILES_DIR) {
+        Ok(variant) => variant.components().skip(1).collect(),
+        Err(_) if is_vault_internal(path) => return None,
+        Err(_) => path.to_path_buf(),
+    };
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+
+    Some(config.home_dir.join(rendered_path(&relative)))
+}
+
+/// Check if the vault working tree has changes that aren't committed yet
+pub fn has_uncommitted_changes(config: &Config) -> Result<bool, DotfilesError> {
+    let repo = init_git_repo(config)?;
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    Ok(!repo.statuses(Some(&mut options))?.is_empty())
+}
+
+/// Vault-relative paths of the files with uncommitted changes
+pub fn uncommitted_paths(config: &Config) -> Result<HashSet<PathBuf>, DotfilesError> {
+    let repo = init_git_repo(config)?;
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    Ok(repo
+        .statuses(Some(&mut options))?
+        .iter()
+        .filter_map(|status| status.path().map(PathBuf::from))
+        .collect())
+}
+
+/// Throw away uncommitted changes to vault files, except the paths in `keep`
+///
+/// New files are removed and changed or deleted files get their committed
+/// content back. Used to undo the copies of a backup that didn't finish.
+pub fn discard_changes(config: &Config, keep: &HashSet<PathBuf>) -> Result<(), DotfilesError> {
+    let repo = init_git_repo(config)?;
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    let mut changed = false;
+    for status in repo.statuses(Some(&mut options))?.iter() {
+        let Some(path) = status.path().map(PathBuf::from) else {
+            continue;
+        };
+        if keep.contains(&path) {
+            continue;
+        }
+
+        debug!("Discarding changes to {:?}", path);
+        if status.status().is_wt_new() {
+            fs::remove_file(config.vault_dir.join(&path))?;
+        } else {
+            checkout.path(path);
+            changed = true;
+        }
+    }
+
+    if changed && repo.head().is_ok() {
+        repo.checkout_head(Some(&mut checkout))?;
+    }
+
+    Ok(())
+}
+
+/// Check if the vault repository is a partial clone backed by a promisor remote
+pub fn is_partial_clone(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_string("extensions.partialclone"))
+        .is_ok()
+}
+
+/// Read a blob from the vault repository
+///
+/// Partial clones may not have old blobs locally. libgit2 cannot fetch from
+/// promisor remotes, so missing objects are read through the git CLI, which
+/// downloads them on demand.
+pub fn read_blob(config: &Config, oid: git2::Oid) -> Result<Vec<u8>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let err = match repo.find_blob(oid) {
+        Ok(blob) => return Ok(blob.content().to_vec()),
+        Err(err) => err,
+    };
+
+    if !is_partial_clone(&repo) {
+        return Err(DotfilesError::Git(err));
+    }
+
+    debug!("Fetching missing blob {} from promisor remote", oid);
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&config.vault_dir)
+        .args(["cat-file", "blob", &oid.to_string()])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DotfilesError::Git(err));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Find the most recent commit made at or before a point in time
+pub fn commit_at(
+    config: &Config,
+    at: DateTime<Local>,
+) -> Result<Option<DotfileVersion>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+
+    for oid_result in revwalk {
+        let commit = repo.find_commit(oid_result?)?;
+
+        if commit.time().seconds() <= at.timestamp() {
+            return Ok(Some(version_from_commit(&commit)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Read a vault-relative file as it was at a commit
+///
+/// `commit_id` may be any revision git understands, including an
+/// abbreviated SHA. Returns `None` if the file did not exist in that commit.
+pub fn read_file_at(
+    config: &Config,
+    commit_id: &str,
+    relative_path: &Path,
+) -> Result<Option<Vec<u8>>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let commit = repo
+        .revparse_single(commit_id)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| DotfilesError::VersionNotFound(commit_id.to_string()))?;
+
+    let entry = match commit.tree()?.get_path(relative_path) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(None),
+    };
+
+    read_blob(config, entry.id()).map(Some)
+}
+
+/// Get the target of a vault-relative symlink as it was at a commit
+///
+/// Returns `None` if the path was not a symlink in that commit.
+pub fn symlink_at(
+    config: &Config,
+    commit_id: &str,
+    relative_path: &Path,
+) -> Result<Option<PathBuf>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let commit = repo
+        .revparse_single(commit_id)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| DotfilesError::VersionNotFound(commit_id.to_string()))?;
+
+    match commit.tree()?.get_path(relative_path) {
+        Ok(entry) if entry.filemode() == i32::from(git2::FileMode::Link) => {
+            let target = read_blob(config, entry.id())?;
+            Ok(Some(PathBuf::from(
+                String::from_utf8_lossy(&target).into_owned(),
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Resolve a commit or a date into a vault version
+///
+/// Dates are resolved to the last commit made at or before them; anything
+/// else is looked up as a git revision, such as an abbreviated SHA.
+pub fn resolve_version(config: &Config, spec: &str) -> Result<DotfileVersion, DotfilesError> {
+    if let Some(at) = parse_datetime(spec) {
+        return commit_at(config, at)?
+            .ok_or_else(|| DotfilesError::VersionNotFound(spec.to_string()));
+    }
+
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|err| {
+            let reason = match err.code() {
+                git2::ErrorCode::Ambiguous => "short hash matches several commits, use more digits",
+                _ => "not a version index, date, commit, tag or revision",
+            };
+            DotfilesError::VersionNotFound(format!("{} ({})", spec, reason))
+        })?;
+
+    Ok(version_from_commit(&commit))
+}
+
+/// Resolve a version of a dotfile as given on the command line
+///
+/// Besides everything `resolve_version` accepts (dates, full or short
+/// hashes, tags and revisions such as `HEAD~2`), this takes version indices
+/// as shown by `history`: `v1` is the newest version of the file.
+pub fn resolve_file_version(
+    config: &Config,
+    dotfile_path: &str,
+    spec: &str,
+) -> Result<DotfileVersion, DotfilesError> {
+    let Some(index) = parse_version_index(spec) else {
+        return resolve_version(config, spec);
+    };
+
+    let versions = get_dotfile_history(config, dotfile_path, 0, None)?;
+    let count = versions.len();
+
+    versions.into_iter().nth(index - 1).ok_or_else(|| {
+        DotfilesError::VersionNotFound(format!(
+            "{} ({} has {} versions)",
+            spec, dotfile_path, count
+        ))
+    })
+}
+
+/// List the vault-relative dotfiles stored at a commit
+///
+/// Reads the commit's tree rather than the working directory and leaves out
+/// dotfilesvault's own files.
+pub fn list_files_at(config: &Config, commit_id: &str) -> Result<Vec<PathBuf>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let commit = repo
+        .revparse_single(commit_id)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| DotfilesError::VersionNotFound(commit_id.to_string()))?;
+
+    let mut files = Vec::new();
+    commit
+        .tree()?
+        .walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let path = Path::new(dir).join(entry.name().unwrap_or_default());
+                if !is_vault_internal(&path) {
+                    files.push(path);
+                }
+            }
+
+            git2::TreeWalkResult::Ok
+        })?;
+
+    Ok(files)
+}
+
+/// Get when each vault-relative file last changed in the vault history
+///
+/// Returns an empty map for a vault without commits.
+pub fn last_changed(config: &Config) -> Result<HashMap<PathBuf, DateTime<Local>>, DotfilesError> {
+    let index = update_history_index(config)?;
+
+    Ok(index
+        .paths
+        .iter()
+        .filter_map(|(path, changes)| {
+            changes
+                .first()
+                .map(|change| (path.clone(), change.timestamp()))
+        })
+        .collect())
+}
+
+/// List the distinct blobs a vault-relative file had across the vault history
+///
+/// Returns an empty list for a vault without a repository or commits.
+pub fn stored_blobs(
+    config: &Config,
+    relative_path: &Path,
+) -> Result<Vec<git2::Oid>, DotfilesError> {
+    let Ok(repo) = Repository::open(&config.vault_dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut blobs = Vec::new();
+    let mut revwalk = repo.revwalk()?;
+    if revwalk.push_head().is_err() {
+        return Ok(blobs);
+    }
+
+    for oid_result in revwalk {
+        let commit = repo.find_commit(oid_result?)?;
+        if let Ok(entry) = commit.tree()?.get_path(relative_path)
+            && !blobs.contains(&entry.id())
+        {
+            blobs.push(entry.id());
+        }
+    }
+
+    Ok(blobs)
+}
+
+/// Build a version description from a commit
+pub(crate) fn version_from_commit(commit: &git2::Commit) -> DotfileVersion {
+    let timestamp = Local
+        .timestamp_opt(commit.time().seconds(), 0)
+        .single()
+        .unwrap_or_else(Local::now);
+
+    DotfileVersion {
+        commit_id: commit.id().to_string(),
+        timestamp,
+        message: commit.message().unwrap_or("").to_string(),
+    }
+}
+
 /// Get the history of a specific dotfile
+///
+/// Lists the commits that added or changed the file, newest first: a commit
+/// always comes before the commits it was made on, and otherwise the later
+/// commit comes first, so the order holds even if a machine's clock was off.
+/// Version `vN` as taken by `resolve_file_version` is the Nth of the full
+/// list. The newest `skip` versions are left out, and at most `limit` are
+/// returned.
+///
+/// Versions are read from the cached history index, so this stays fast on
+/// large vaults, and only commits and trees are read, so it works on partial
+/// clones that have not downloaded old file contents. Vaults not stored in
+/// git list the versions of their backend, see `history_with`.
 pub fn get_dotfile_history(
     config: &Config,
     dotfile_path: &str,
+    skip: usize,
+    limit: Option<usize>,
+) -> Result<Vec<DotfileVersion>, DotfilesError> {
+    if !config.backend.is_git() {
+        let backend = open_backend(config)?;
+        return history_with(backend.as_ref(), config, dotfile_path, skip, limit);
+    }
+
+    history_of(config, dotfile_path, false, skip, limit)
+}
+
+/// Get the history of a dotfile, continuing across renames in the vault
+///
+/// Ordered and paged like `get_dotfile_history`.
+pub fn follow_dotfile_history(
+    config: &Config,
+    dotfile_path: &str,
+    skip: usize,
+    limit: Option<usize>,
+) -> Result<Vec<DotfileVersion>, DotfilesError> {
+    history_of(config, dotfile_path, true, skip, limit)
+}
+
+/// Look up the history of a dotfile in the history index
+fn history_of(
+    config: &Config,
+    dotfile_path: &str,
+    follow: bool,
+    skip: usize,
+    limit: Option<usize>,
 ) -> Result<Vec<DotfileVersion>, DotfilesError> {
     let path = Path::new(dotfile_path);
     let path = if path.is_absolute() {
@@ -96,7 +514,11 @@ pub fn get_dotfile_history(
         config.home_dir.join(path)
     };
 
+    // Use the copy of the active profile, if the file has one
     let dotfile = Dotfile::new(path, config);
+    let dotfile = Manifest::load(config)?
+        .entry_for(&dotfile, config)
+        .dotfile(config);
 
     // Get the relative path from the vault directory
     let relative_path = match dotfile.vault_path.strip_prefix(&config.vault_dir) {
@@ -109,43 +531,138 @@ pub fn get_dotfile_history(
         return Err(DotfilesError::DotfileNotFound(dotfile_path.to_string()));
     }
 
-    // Open the repository
-    let repo = match Repository::open(&config.vault_dir) {
-        Ok(repo) => repo,
-        Err(_) => return Err(DotfilesError::NoDotfilesVaultDir),
+    let index = update_history_index(config)?;
+    let limit = limit.unwrap_or(usize::MAX);
+    let versions: Vec<DotfileVersion> = if follow {
+        index
+            .follow(&relative_path)
+            .iter()
+            .skip(skip)
+            .take(limit)
+            .map(|(_, change)| version_from_change(change))
+            .collect()
+    } else {
+        index
+            .changes(&relative_path)
+            .iter()
+            .skip(skip)
+            .take(limit)
+            .map(version_from_change)
+            .collect()
     };
 
-    // Get the revwalk
-    let mut revwalk = repo.revwalk()?;
-    revwalk.push_head()?;
+    debug!("Found {} versions for {:?}", versions.len(), dotfile_path);
 
-    let mut versions = Vec::new();
+    Ok(versions)
+}
 
-    for oid_result in revwalk {
-        let oid = oid_result?;
-        let commit = repo.find_commit(oid)?;
-
-        // Check if this commit modified the file
-        let tree = commit.tree()?;
-
-        if tree.get_path(&relative_path).is_ok() {
-            // This commit affected the file
-            let timestamp = Local
-                .timestamp_opt(commit.time().seconds(), 0)
-                .single()
-                .unwrap_or_else(|| Local::now());
-
-            versions.push(DotfileVersion {
-                commit_id: oid.to_string(),
-                timestamp,
-                message: commit.message().unwrap_or("").to_string(),
-            });
+/// Build a version description from an indexed change
+pub(crate) fn version_from_change(change: &IndexedChange) -> DotfileVersion {
+    DotfileVersion {
+        commit_id: change.commit.clone(),
+        timestamp: change.timestamp(),
+        message: change.message.clone(),
+    }
+}
+
+/// How much of the vault history `prune_history` keeps
+///
+/// A commit is kept if any of the rules keeps it. A policy without rules
+/// keeps everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep this many of the latest commits
+    pub keep_last: Option<usize>,
+
+    /// Keep the commits made in this many past days
+    pub keep_days: Option<u64>,
+}
+
+/// Prune the vault history according to a retention policy
+///
+/// The newest commit the policy doesn't keep and all of its ancestors are
+/// squashed into one baseline commit holding the vault as it was then, as
+/// with `compact_history`, so the current vault content is unchanged. The
+/// pruned commits are then expired from the reflog and the repository is
+/// repacked to reclaim their space, which needs the git executable and is
+/// skipped with a warning without it. With `config.dry_run` set the history
+/// is only counted.
+pub fn prune_history(
+    config: &Config,
+    policy: &RetentionPolicy,
+) -> Result<CompactSummary, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let head = repo.head()?.peel_to_commit()?.id();
+    let cutoff = policy
+        .keep_days
+        .map(|days| (Local::now() - chrono::Duration::days(days as i64)).timestamp());
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head)?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+    let mut base = None;
+    if policy.keep_last.is_some() || cutoff.is_some() {
+        for (position, oid) in revwalk.enumerate() {
+            let oid = oid?;
+            let recent = match cutoff {
+                Some(cutoff) => repo.find_commit(oid)?.time().seconds() >= cutoff,
+                None => false,
+            };
+            let latest = policy.keep_last.is_some_and(|keep| position < keep);
+            if !recent && !latest {
+                base = Some(oid);
+                break;
+            }
         }
     }
 
-    debug!("Found {} versions for {:?}", versions.len(), dotfile_path);
+    let Some(base) = base else {
+        info!("Nothing to prune, the retention policy keeps every commit");
+        return Ok(CompactSummary {
+            squashed: 0,
+            kept: 0,
+            baseline: None,
+            head: head.to_string(),
+        });
+    };
 
-    Ok(versions)
+    let summary = squash_history(config, base, "prune: apply retention policy")?;
+    if summary.baseline.is_some() {
+        repack(config);
+    }
+
+    Ok(summary)
+}
+
+/// Drop unreachable commits from the vault repository and repack it
+pub(crate) fn repack(config: &Config) {
+    let steps: [&[&str]; 2] = [
+        &["reflog", "expire", "--expire=now", "--all"],
+        &["gc", "--prune=now", "--quiet"],
+    ];
+
+    for args in steps {
+        match Command::new("git")
+            .arg("-C")
+            .arg(&config.vault_dir)
+            .args(args)
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                warn!("git {} failed with {}", args[0], status);
+                return;
+            }
+            Err(err) => {
+                warn!("Skipping repack, git is not available: {}", err);
+                return;
+            }
+        }
+    }
+
+    debug!("Repacked {:?}", config.vault_dir);
 }
 
 #[cfg(test)]
@@ -208,4 +725,242 @@ mod tests {
 
         assert_eq!(commit.message().unwrap(), "Test commit");
     }
+
+    #[test]
+    fn test_commit_author() {
+        let (mut config, _temp_dir) = setup_test_env();
+        let repo = init_git_repo(&config).unwrap();
+        let mut git_config = repo.config().unwrap();
+        git_config.set_str("user.name", "Vault Owner").unwrap();
+        git_config
+            .set_str("user.email", "owner@example.com")
+            .unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash").unwrap();
+        commit_changes(&config, "First").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.author().name(), Some("Vault Owner"));
+        assert_eq!(head.author().email(), Some("owner@example.com"));
+
+        // The config file wins over git's config
+        config.author_name = Some("Me".to_string());
+        assert_eq!(
+            config.author(),
+            ("Me".to_string(), "owner@example.com".to_string())
+        );
+    }
+
+    #[test]
+    fn test_discard_changes() {
+        let (config, _temp_dir) = setup_test_env();
+        init_git_repo(&config).unwrap();
+        fs::write(config.vault_dir.join(".bashrc"), "committed").unwrap();
+        commit_changes(&config, "First").unwrap();
+
+        // Changes from before an operation are kept
+        fs::write(config.vault_dir.join(".notes"), "mine").unwrap();
+        let keep = uncommitted_paths(&config).unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "partial").unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "partial").unwrap();
+        discard_changes(&config, &keep).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(config.vault_dir.join(".bashrc")).unwrap(),
+            "committed"
+        );
+        assert!(!config.vault_dir.join(".vimrc").exists());
+        assert!(config.vault_dir.join(".notes").exists());
+    }
+
+    #[test]
+    fn test_commit_at_and_read_file_at() {
+        let (config, _temp_dir) = setup_test_env();
+
+        init_git_repo(&config).unwrap();
+        fs::write(config.vault_dir.join(".testrc"), "first\n").unwrap();
+        let first = commit_changes(&config, "First").unwrap();
+
+        let version = commit_at(&config, Local::now()).unwrap().unwrap();
+        assert_eq!(version.commit_id, first);
+
+        let before = Local::now() - chrono::Duration::days(1);
+        assert!(commit_at(&config, before).unwrap().is_none());
+
+        let content = read_file_at(&config, &first, Path::new(".testrc")).unwrap();
+        assert_eq!(content.as_deref(), Some(&b"first\n"[..]));
+        assert!(
+            read_file_at(&config, &first, Path::new(".missingrc"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_list_files_at() {
+        let (config, _temp_dir) = setup_test_env();
+        init_git_repo(&config).unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash").unwrap();
+        fs::create_dir_all(config.vault_dir.join(".config/fish")).unwrap();
+        fs::write(config.vault_dir.join(".config/fish/config.fish"), "fish").unwrap();
+        let first = commit_changes(&config, "First").unwrap();
+
+        fs::remove_file(config.vault_dir.join(".bashrc")).unwrap();
+        fs::write(config.vault_dir.join(".zshrc"), "zsh").unwrap();
+        commit_changes(&config, "Second").unwrap();
+
+        let version = resolve_version(&config, &first[..7]).unwrap();
+        assert_eq!(version.commit_id, first);
+
+        let files = list_files_at(&config, &version.commit_id).unwrap();
+        assert!(files.contains(&PathBuf::from(".bashrc")));
+        assert!(files.contains(&PathBuf::from(".config/fish/config.fish")));
+        assert!(!files.contains(&PathBuf::from(".zshrc")));
+
+        let head = resolve_version(&config, "HEAD").unwrap();
+        let files = list_files_at(&config, &head.commit_id).unwrap();
+        assert!(files.contains(&PathBuf::from(".zshrc")));
+        assert!(!files.contains(&PathBuf::from(".bashrc")));
+
+        assert!(matches!(
+            resolve_version(&config, "2000-01-01"),
+            Err(DotfilesError::VersionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_file_version() {
+        let (config, _temp_dir) = setup_test_env();
+        let repo = init_git_repo(&config).unwrap();
+
+        fs::write(config.vault_dir.join(".testrc"), "first\n").unwrap();
+        let first = commit_changes(&config, "First").unwrap();
+        fs::write(config.vault_dir.join(".testrc"), "second\n").unwrap();
+        let second = commit_changes(&config, "Second").unwrap();
+
+        let object = repo.revparse_single(&first).unwrap();
+        repo.tag_lightweight("stable", &object, false).unwrap();
+
+        let resolve = |spec: &str| {
+            resolve_file_version(&config, ".testrc", spec).map(|version| version.commit_id)
+        };
+
+        assert_eq!(resolve("v1").unwrap(), second);
+        assert_eq!(resolve("v2").unwrap(), first);
+        assert_eq!(resolve(&first[..7]).unwrap(), first);
+        assert_eq!(resolve("HEAD~1").unwrap(), first);
+        assert_eq!(resolve("stable").unwrap(), first);
+
+        assert!(matches!(
+            resolve("v3"),
+            Err(DotfilesError::VersionNotFound(_))
+        ));
+        assert!(matches!(
+            resolve("no-such-tag"),
+            Err(DotfilesError::VersionNotFound(_))
+        ));
+        assert_eq!(parse_version_index("v0"), None);
+    }
+
+    #[test]
+    fn test_get_dotfile_history_order_and_paging() {
+        let (config, _temp_dir) = setup_test_env();
+        let repo = init_git_repo(&config).unwrap();
+        for version in 1..=3 {
+            fs::write(config.vault_dir.join(".testrc"), format!("v{}\n", version)).unwrap();
+            commit_changes(&config, &format!("Version {}", version)).unwrap();
+        }
+
+        // A commit from a machine whose clock was behind still comes first
+        fs::write(config.vault_dir.join(".testrc"), "v4\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(".testrc")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        let skewed =
+            git2::Signature::new("test", "test@example.com", &git2::Time::new(0, 0)).unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &skewed,
+            &skewed,
+            "Version 4",
+            &tree,
+            &[&parent],
+        )
+        .unwrap();
+
+        let messages = |skip, limit| -> Vec<String> {
+            get_dotfile_history(&config, ".testrc", skip, limit)
+                .unwrap()
+                .into_iter()
+                .map(|version| version.message)
+                .collect()
+        };
+
+        assert_eq!(
+            messages(0, None),
+            vec!["Version 4", "Version 3", "Version 2", "Version 1"]
+        );
+        assert_eq!(messages(0, Some(2)), vec!["Version 4", "Version 3"]);
+        assert_eq!(messages(1, Some(2)), vec!["Version 3", "Version 2"]);
+        assert_eq!(messages(3, Some(2)), vec!["Version 1"]);
+        assert!(messages(5, None).is_empty());
+    }
+
+    #[test]
+    fn test_read_blob_and_partial_clone_detection() {
+        let (config, _temp_dir) = setup_test_env();
+
+        let repo = init_git_repo(&config).unwrap();
+        assert!(!is_partial_clone(&repo));
+
+        let oid = repo.blob(b"blob content").unwrap();
+        assert_eq!(read_blob(&config, oid).unwrap(), b"blob content");
+
+        repo.config()
+            .unwrap()
+            .set_str("extensions.partialclone", "origin")
+            .unwrap();
+        assert!(is_partial_clone(&repo));
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let (config, _temp_dir) = setup_test_env();
+        let repo = init_git_repo(&config).unwrap();
+        for version in 1..=4 {
+            fs::write(config.vault_dir.join(".testrc"), format!("v{}\n", version)).unwrap();
+            commit_changes(&config, &format!("Version {}", version)).unwrap();
+        }
+
+        // Every commit was made today
+        let recent = RetentionPolicy {
+            keep_last: Some(1),
+            keep_days: Some(1),
+        };
+        assert_eq!(prune_history(&config, &recent).unwrap().squashed, 0);
+
+        let latest = RetentionPolicy {
+            keep_last: Some(2),
+            keep_days: None,
+        };
+        let summary = prune_history(&config, &latest).unwrap();
+        assert_eq!(summary.squashed, 2);
+        assert_eq!(summary.kept, 2);
+
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        assert_eq!(revwalk.count(), 3);
+        assert_eq!(
+            fs::read_to_string(config.vault_dir.join(".testrc")).unwrap(),
+            "v4\n"
+        );
+        assert_eq!(
+            prune_history(&config, &RetentionPolicy::default())
+                .unwrap()
+                .squashed,
+            0
+        );
+    }
 }
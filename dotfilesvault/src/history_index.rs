@@ -0,0 +1,329 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use git2::{Delta, Oid, Repository};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::history::is_partial_clone;
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the state file caching the per-path history, see `Config::state_path`
+pub const HISTORY_INDEX_FILE: &str = "dotfilesvault-history.json";
+
+/// Version of the history index format; other versions are rebuilt
+const HISTORY_INDEX_VERSION: u32 = 1;
+
+/// A commit that added or changed a path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedChange {
+    /// The commit ID
+    pub commit: String,
+
+    /// Commit time in seconds since the epoch
+    pub time: i64,
+
+    /// The commit message
+    pub message: String,
+
+    /// Previous path of the file, if the commit renamed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<PathBuf>,
+}
+
+impl IndexedChange {
+    /// Get the commit time in local time
+    pub fn timestamp(&self) -> DateTime<Local> {
+        Local
+            .timestamp_opt(self.time, 0)
+            .single()
+            .unwrap_or_else(Local::now)
+    }
+}
+
+/// Index from vault-relative paths to the commits that changed them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryIndex {
+    /// Version of the index format
+    pub version: u32,
+
+    /// The commit the index is up to date with
+    pub head: Option<String>,
+
+    /// Changes per path, newest first
+    pub paths: BTreeMap<PathBuf, Vec<IndexedChange>>,
+}
+
+impl HistoryIndex {
+    /// Get the changes of a path, newest first
+    pub fn changes(&self, path: &Path) -> &[IndexedChange] {
+        self.paths.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Get the changes of a path and, across renames, of its earlier names
+    ///
+    /// Returns each change with the path the file had after it, newest first.
+    pub fn follow(&self, path: &Path) -> Vec<(PathBuf, IndexedChange)> {
+        let mut followed = Vec::new();
+        let mut current = path.to_path_buf();
+        let mut before: Option<i64> = None;
+
+        loop {
+            let mut renamed_from = None;
+
+            for change in self.changes(&current) {
+                if before.is_some_and(|time| change.time > time) {
+                    continue;
+                }
+
+                followed.push((current.clone(), change.clone()));
+
+                if let Some(previous) = &change.renamed_from {
+                    renamed_from = Some((previous.clone(), change.time));
+                    break;
+                }
+            }
+
+            match renamed_from {
+                // Guard against rename cycles
+                Some((previous, time)) if followed.len() < 100_000 => {
+                    current = previous;
+                    before = Some(time);
+                }
+                _ => return followed,
+            }
+        }
+    }
+}
+
+/// Get the path of the history index file
+fn index_path(repo: &Repository) -> PathBuf {
+    repo.path().join(HISTORY_INDEX_FILE)
+}
+
+/// Load the cached history index, or an empty one
+fn load_index(repo: &Repository) -> HistoryIndex {
+    let path = index_path(repo);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return HistoryIndex::default();
+    };
+
+    // The index is only a cache, an unreadable one is rebuilt
+    match serde_json::from_str::<HistoryIndex>(&content) {
+        Ok(index) if index.version == HISTORY_INDEX_VERSION => index,
+        Ok(_) => HistoryIndex::default(),
+        Err(err) => {
+            warn!("Rebuilding unreadable history index {:?}: {}", path, err);
+            HistoryIndex::default()
+        }
+    }
+}
+
+/// Bring the history index up to date with the vault's HEAD and return it
+///
+/// Only commits made since the last update are read, so keeping the index
+/// current after each commit is cheap. If the history was rewritten (e.g.
+/// by `compact`) the index is rebuilt. Commits are diffed on several
+/// threads.
+pub fn update_history_index(config: &Config) -> Result<HistoryIndex, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return Ok(HistoryIndex::default());
+    };
+
+    let mut index = load_index(&repo);
+    if index.head.as_deref() == Some(head.id().to_string().as_str()) {
+        return Ok(index);
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head.id())?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+    let indexed_head = index
+        .head
+        .as_deref()
+        .and_then(|id| Oid::from_str(id).ok())
+        .filter(|id| repo.graph_descendant_of(head.id(), *id).unwrap_or(false));
+    match indexed_head {
+        Some(id) => revwalk.hide(id)?,
+        None => {
+            debug!("Building the history index from scratch");
+            index = HistoryIndex {
+                version: HISTORY_INDEX_VERSION,
+                ..HistoryIndex::default()
+            };
+        }
+    }
+
+    let commits: Vec<Oid> = revwalk.collect::<Result<_, _>>()?;
+    debug!("Indexing {} new commits", commits.len());
+
+    // Newest changes first, then the ones already indexed
+    let mut paths: BTreeMap<PathBuf, Vec<IndexedChange>> = BTreeMap::new();
+    for (path, change) in index_commits(config, &commits, is_partial_clone(&repo))? {
+        paths.entry(path).or_default().push(change);
+    }
+    for (path, changes) in std::mem::take(&mut index.paths) {
+        paths.entry(path).or_default().extend(changes);
+    }
+
+    index.paths = paths;
+    index.head = Some(head.id().to_string());
+
+    // Read-only mode rebuilds the index in memory each time
+    if !config.read_only {
+        let content = serde_json::to_string(&index).map_err(std::io::Error::other)?;
+        write_file(index_path(&repo), content.as_bytes())?;
+    }
+
+    Ok(index)
+}
+
+/// Diff commits against their first parent, in order, on several threads
+fn index_commits(
+    config: &Config,
+    commits: &[Oid],
+    exact_renames: bool,
+) -> Result<Vec<(PathBuf, IndexedChange)>, DotfilesError> {
+    if commits.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(commits.len());
+    let chunk_size = commits.len().div_ceil(threads);
+
+    let results: Vec<Result<Vec<(PathBuf, IndexedChange)>, git2::Error>> = thread::scope(|scope| {
+        let workers: Vec<_> = commits
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    // Repositories can't be shared between threads
+                    let repo = Repository::open(&config.vault_dir)?;
+                    let mut changes = Vec::new();
+                    for oid in chunk {
+                        changes.extend(changes_in_commit(&repo, *oid, exact_renames)?);
+                    }
+
+                    Ok(changes)
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(git2::Error::from_str("indexing thread panicked")))
+            })
+            .collect()
+    });
+
+    let mut changes = Vec::new();
+    for result in results {
+        changes.extend(result?);
+    }
+
+    Ok(changes)
+}
+
+/// List the paths a commit added, changed or renamed
+fn changes_in_commit(
+    repo: &Repository,
+    oid: Oid,
+    exact_renames: bool,
+) -> Result<Vec<(PathBuf, IndexedChange)>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+    // Partial clones may lack the blobs needed to compare file contents
+    let mut find = git2::DiffFindOptions::new();
+    find.renames(true).exact_match_only(exact_renames);
+    diff.find_similar(Some(&mut find))?;
+
+    let message = commit.message().unwrap_or("").to_string();
+    let mut changes = Vec::new();
+    for delta in diff.deltas() {
+        let renamed_from = match delta.status() {
+            Delta::Renamed => delta.old_file().path().map(Path::to_path_buf),
+            Delta::Added | Delta::Modified | Delta::Copied | Delta::Typechange => None,
+            _ => continue,
+        };
+
+        if let Some(path) = delta.new_file().path() {
+            changes.push((
+                path.to_path_buf(),
+                IndexedChange {
+                    commit: oid.to_string(),
+                    time: commit.time().seconds(),
+                    message: message.clone(),
+                    renamed_from,
+                },
+            ));
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::commit_changes;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index_updates_incrementally_and_follows_renames() {
+        let vault_dir = TempDir::new().unwrap();
+        let config = Config::new(
+            vault_dir.path().to_path_buf(),
+            vault_dir.path().join("home"),
+        );
+
+        fs::write(vault_dir.path().join(".vimrc"), "set number\n").unwrap();
+        let first = commit_changes(&config, "Add vimrc").unwrap();
+        fs::write(vault_dir.path().join(".bashrc"), "bash\n").unwrap();
+        commit_changes(&config, "Add bashrc").unwrap();
+
+        let index = update_history_index(&config).unwrap();
+        assert_eq!(index.changes(Path::new(".vimrc")).len(), 1);
+        assert_eq!(index.changes(Path::new(".bashrc")).len(), 1);
+
+        fs::create_dir_all(vault_dir.path().join(".config/nvim")).unwrap();
+        fs::rename(
+            vault_dir.path().join(".vimrc"),
+            vault_dir.path().join(".config/nvim/init.vim"),
+        )
+        .unwrap();
+        let repo = Repository::open(vault_dir.path()).unwrap();
+        let mut git_index = repo.index().unwrap();
+        git_index.remove_path(Path::new(".vimrc")).unwrap();
+        git_index.write().unwrap();
+        let renamed = commit_changes(&config, "Move to nvim").unwrap();
+
+        let index = update_history_index(&config).unwrap();
+        assert_eq!(index.head.as_deref(), Some(renamed.as_str()));
+
+        let followed = index.follow(Path::new(".config/nvim/init.vim"));
+        let commits: Vec<&str> = followed
+            .iter()
+            .map(|(_, change)| change.commit.as_str())
+            .collect();
+        assert_eq!(commits, vec![renamed.as_str(), first.as_str()]);
+        assert_eq!(followed[1].0, PathBuf::from(".vimrc"));
+    }
+}
@@ -0,0 +1,390 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::{Config, DotfilesError};
+
+/// Version of the hook environment/stdin contract
+///
+/// Bumped whenever a variable or JSON field is removed or changes meaning.
+pub const HOOK_CONTRACT_VERSION: u32 = 1;
+
+/// Name of the hooks directory inside the vault
+pub const HOOKS_DIR: &str = "hooks";
+
+/// Points in an operation where a hook can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    PreBackup,
+    PostBackup,
+    PreRestore,
+    PostRestore,
+    PreCommit,
+    PostCommit,
+}
+
+impl HookEvent {
+    /// All hook events, in the order they can occur
+    pub const ALL: [HookEvent; 6] = [
+        HookEvent::PreBackup,
+        HookEvent::PostBackup,
+        HookEvent::PreRestore,
+        HookEvent::PostRestore,
+        HookEvent::PreCommit,
+        HookEvent::PostCommit,
+    ];
+
+    /// The file name of the hook script in the hooks directory
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreBackup => "pre-backup",
+            HookEvent::PostBackup => "post-backup",
+            HookEvent::PreRestore => "pre-restore",
+            HookEvent::PostRestore => "post-restore",
+            HookEvent::PreCommit => "pre-commit",
+            HookEvent::PostCommit => "post-commit",
+        }
+    }
+
+    /// Look up a hook event by its file name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.name() == name)
+    }
+
+    /// The operation this hook belongs to
+    pub fn operation(self) -> &'static str {
+        match self {
+            HookEvent::PreBackup | HookEvent::PostBackup => "backup",
+            HookEvent::PreRestore | HookEvent::PostRestore => "restore",
+            HookEvent::PreCommit | HookEvent::PostCommit => "commit",
+        }
+    }
+
+    /// Whether the hook runs after its operation is done
+    pub fn is_post(self) -> bool {
+        matches!(
+            self,
+            HookEvent::PostBackup | HookEvent::PostRestore | HookEvent::PostCommit
+        )
+    }
+}
+
+/// Input passed to a hook
+///
+/// The same data is provided as `DOTFILESVAULT_*` environment variables and
+/// as a JSON document on stdin.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookContext {
+    /// Version of the hook contract
+    pub version: u32,
+
+    /// The hook being run
+    pub hook: HookEvent,
+
+    /// The operation that triggered the hook
+    pub operation: String,
+
+    /// Files affected by the operation, as paths in the home directory
+    pub files: Vec<PathBuf>,
+
+    /// The active profile, if any
+    pub profile: Option<String>,
+
+    /// Path to the dotfilesvault directory
+    pub vault_dir: PathBuf,
+
+    /// Path to the home directory
+    pub home_dir: PathBuf,
+
+    /// Whether the hook is run by `hooks test` or `simulate-bootstrap` rather than a real operation
+    pub simulated: bool,
+}
+
+impl HookContext {
+    /// Create a new hook context for an operation
+    pub fn new(config: &Config, hook: HookEvent, files: Vec<PathBuf>) -> Self {
+        Self {
+            version: HOOK_CONTRACT_VERSION,
+            hook,
+            operation: hook.operation().to_string(),
+            files,
+            profile: config.profile.clone(),
+            vault_dir: config.vault_dir.clone(),
+            home_dir: config.home_dir.clone(),
+            simulated: false,
+        }
+    }
+
+    /// Environment variables exposed to the hook
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect();
+
+        vec![
+            ("DOTFILESVAULT_HOOK_VERSION", self.version.to_string()),
+            ("DOTFILESVAULT_HOOK", self.hook.name().to_string()),
+            ("DOTFILESVAULT_OPERATION", self.operation.clone()),
+            ("DOTFILESVAULT_FILES", files.join("\n")),
+            (
+                "DOTFILESVAULT_PROFILE",
+                self.profile.clone().unwrap_or_default(),
+            ),
+            (
+                "DOTFILESVAULT_VAULT_DIR",
+                self.vault_dir.to_string_lossy().into_owned(),
+            ),
+            (
+                "DOTFILESVAULT_HOME_DIR",
+                self.home_dir.to_string_lossy().into_owned(),
+            ),
+            (
+                "DOTFILESVAULT_SIMULATED",
+                if self.simulated { "1" } else { "0" }.to_string(),
+            ),
+        ]
+    }
+}
+
+/// Get the path of a hook script in the vault
+pub fn hook_path(config: &Config, hook: HookEvent) -> PathBuf {
+    config.vault_dir.join(HOOKS_DIR).join(hook.name())
+}
+
+/// List the hooks that are installed in the vault or configured as commands
+pub fn list_hooks(config: &Config) -> Vec<HookEvent> {
+    HookEvent::ALL
+        .into_iter()
+        .filter(|hook| {
+            hook_path(config, *hook).is_file() || config.hook_commands.contains_key(hook.name())
+        })
+        .collect()
+}
+
+/// Run a hook if it is installed
+///
+/// The hook script in the vault runs first, then the shell command configured
+/// for the hook in the config file. Returns `false` if neither exists.
+pub fn run_hook(config: &Config, context: &HookContext) -> Result<bool, DotfilesError> {
+    let path = hook_path(config, context.hook);
+    let mut ran = false;
+
+    if path.is_file() {
+        info!("Running {} hook", context.hook.name());
+        run_hook_command(config, context, Command::new(&path))?;
+        ran = true;
+    }
+
+    if let Some(shell_command) = config.hook_commands.get(context.hook.name()) {
+        info!("Running {} hook command", context.hook.name());
+        run_hook_command(config, context, shell(shell_command))?;
+        ran = true;
+    }
+
+    if !ran {
+        debug!("No {} hook installed", context.hook.name());
+    }
+
+    Ok(ran)
+}
+
+/// Run the hook for a real operation
+///
+/// Nothing runs in dry-run mode, since hooks can have arbitrary side effects.
+/// A failing post hook is only logged: the operation already succeeded.
+pub fn run_operation_hook(
+    config: &Config,
+    hook: HookEvent,
+    files: Vec<PathBuf>,
+) -> Result<(), DotfilesError> {
+    if config.dry_run {
+        return Ok(());
+    }
+
+    match run_hook(config, &HookContext::new(config, hook, files)) {
+        Ok(_) => Ok(()),
+        Err(err) if hook.is_post() && !err.is_cancellation() => {
+            warn!("{}", err);
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Build a command running a line through the platform shell
+fn shell(line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", line]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", line]);
+        command
+    }
+}
+
+/// Run a hook process with the context in its environment and on stdin
+fn run_hook_command(
+    config: &Config,
+    context: &HookContext,
+    mut command: Command,
+) -> Result<(), DotfilesError> {
+    let input = serde_json::to_vec_pretty(context).map_err(std::io::Error::other)?;
+
+    command
+        .current_dir(&config.home_dir)
+        .envs(context.env_vars())
+        .stdin(Stdio::piped());
+
+    // Simulated hooks must not touch the real home directory
+    if context.simulated {
+        command.env("HOME", &context.home_dir);
+    }
+
+    let mut child = command.spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores stdin may exit before reading it
+        if let Err(err) = stdin.write_all(&input)
+            && err.kind() != std::io::ErrorKind::BrokenPipe
+        {
+            return Err(err.into());
+        }
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(DotfilesError::HookFailed(
+            context.hook.name().to_string(),
+            status.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn setup_test_env() -> (Config, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+
+        fs::create_dir_all(config.vault_dir.join(HOOKS_DIR)).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        (config, temp_dir)
+    }
+
+    #[cfg(unix)]
+    fn install_hook(config: &Config, hook: HookEvent, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = hook_path(config, hook);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_hook_event_names() {
+        for hook in HookEvent::ALL {
+            assert_eq!(HookEvent::from_name(hook.name()), Some(hook));
+        }
+        assert_eq!(HookEvent::from_name("pre-lunch"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook_receives_contract() {
+        let (config, _temp_dir) = setup_test_env();
+        let output = config.home_dir.join("hook-output");
+
+        install_hook(
+            &config,
+            HookEvent::PostRestore,
+            &format!(
+                "#!/bin/sh\necho \"$DOTFILESVAULT_OPERATION $DOTFILESVAULT_FILES\" > {0}\ncat >> {0}\n",
+                output.display()
+            ),
+        );
+
+        let context = HookContext::new(
+            &config,
+            HookEvent::PostRestore,
+            vec![config.home_dir.join(".bashrc")],
+        );
+
+        assert!(run_hook(&config, &context).unwrap());
+        assert!(
+            !run_hook(
+                &config,
+                &HookContext::new(&config, HookEvent::PreBackup, vec![])
+            )
+            .unwrap()
+        );
+
+        let content = fs::read_to_string(output).unwrap();
+        assert!(content.starts_with("restore "));
+        assert!(content.contains("\"hook\": \"post-restore\""));
+        assert_eq!(list_hooks(&config), vec![HookEvent::PostRestore]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook_command() {
+        let (mut config, _temp_dir) = setup_test_env();
+        let output = config.home_dir.join("hook-output");
+
+        config.hook_commands.insert(
+            "post-restore".to_string(),
+            format!("echo \"$DOTFILESVAULT_FILES\" > {}", output.display()),
+        );
+
+        run_operation_hook(
+            &config,
+            HookEvent::PostRestore,
+            vec![config.home_dir.join(".tmux.conf")],
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&output).unwrap();
+        assert!(content.contains(".tmux.conf"));
+        assert_eq!(list_hooks(&config), vec![HookEvent::PostRestore]);
+
+        // Dry runs never run hooks
+        fs::remove_file(&output).unwrap();
+        config.dry_run = true;
+        run_operation_hook(&config, HookEvent::PostRestore, vec![]).unwrap();
+        assert!(!output.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook_failure() {
+        let (config, _temp_dir) = setup_test_env();
+        install_hook(&config, HookEvent::PreBackup, "#!/bin/sh\nexit 3\n");
+
+        let context = HookContext::new(&config, HookEvent::PreBackup, vec![]);
+
+        assert!(matches!(
+            run_hook(&config, &context),
+            Err(DotfilesError::HookFailed(_, _))
+        ));
+        assert!(run_operation_hook(&config, HookEvent::PreBackup, vec![]).is_err());
+
+        // The backup is done by the time a post hook runs
+        install_hook(&config, HookEvent::PostBackup, "#!/bin/sh\nexit 3\n");
+        run_operation_hook(&config, HookEvent::PostBackup, vec![]).unwrap();
+    }
+}
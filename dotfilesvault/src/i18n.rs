@@ -0,0 +1,225 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use log::warn;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentValue;
+
+/// Language used for messages missing from the selected catalog
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// Message catalogs compiled into the binary, as (language, Fluent source)
+///
+/// To add a translation, add `locales/<language>.ftl` with the message ids
+/// of `locales/en.ftl` and list it here.
+pub const CATALOGS: [(&str, &str); 1] = [("en", include_str!("../locales/en.ftl"))];
+
+/// The language selected for this process
+static LOCALIZER: OnceLock<Localizer> = OnceLock::new();
+
+/// Looks up messages in the catalog for a language, falling back to English
+pub struct Localizer {
+    /// Bundles to try in order: the selected language, then the fallback
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Localizer {
+    /// Create a localizer for a language such as `de` or `pt-BR`
+    ///
+    /// A catalog for the exact language is preferred over one for the same
+    /// base language. Unknown languages use the fallback catalog only.
+    pub fn new(language: &str) -> Self {
+        let requested: Option<LanguageIdentifier> = language.parse().ok();
+        let catalog = |exact: bool| {
+            CATALOGS.iter().find(|(name, _)| {
+                let Ok(available) = name.parse::<LanguageIdentifier>() else {
+                    return false;
+                };
+                requested.as_ref().is_some_and(|requested| {
+                    if exact {
+                        &available == requested
+                    } else {
+                        available.language == requested.language
+                    }
+                })
+            })
+        };
+
+        let mut bundles = Vec::new();
+        if let Some((name, source)) = catalog(true).or_else(|| catalog(false))
+            && *name != FALLBACK_LANGUAGE
+        {
+            bundles.push(bundle(name, source));
+        }
+
+        let (name, source) = CATALOGS
+            .iter()
+            .find(|(name, _)| *name == FALLBACK_LANGUAGE)
+            .expect("the fallback catalog is compiled in");
+        bundles.push(bundle(name, source));
+
+        Self { bundles }
+    }
+
+    /// Format a message, or return its id if no catalog has it
+    pub fn message(&self, id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+
+        for bundle in &self.bundles {
+            let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+                continue;
+            };
+
+            let mut errors = Vec::new();
+            let message = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+            if !errors.is_empty() {
+                warn!("Failed to format message {}: {:?}", id, errors);
+            }
+
+            return message.into_owned();
+        }
+
+        id.to_string()
+    }
+}
+
+/// Parse a compiled-in catalog into a bundle
+fn bundle(name: &str, source: &str) -> FluentBundle<FluentResource> {
+    let language: LanguageIdentifier = name.parse().expect("catalog names are valid languages");
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, errors)| {
+            warn!("Errors in the {} message catalog: {:?}", name, errors);
+            resource
+        });
+
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Unicode isolation marks around arguments show up as garbage in terminals
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        warn!("Duplicate messages in the {} catalog: {:?}", name, errors);
+    }
+
+    bundle
+}
+
+/// Get the user's language from the `LC_ALL`, `LC_MESSAGES` and `LANG` variables
+///
+/// POSIX locale names like `de_DE.UTF-8` are turned into `de-DE`. The `C`
+/// and `POSIX` locales mean no preference.
+pub fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let value = value.split(['.', '@']).next().unwrap_or_default();
+            value.replace('_', "-")
+        })
+        .filter(|value| value != "C" && value != "POSIX")
+}
+
+/// Select the language of messages for the rest of the process
+///
+/// Without an explicit language the system locale is used. Has no effect
+/// once a message was formatted.
+pub fn init(language: Option<&str>) {
+    let language = language
+        .map(str::to_string)
+        .or_else(system_language)
+        .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string());
+
+    let _ = LOCALIZER.set(Localizer::new(&language));
+}
+
+/// Format a message in the selected language
+///
+/// Prefer the `t!` macro, which builds the arguments.
+pub fn tr(id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    LOCALIZER
+        .get_or_init(|| {
+            Localizer::new(&system_language().unwrap_or_else(|| FALLBACK_LANGUAGE.to_string()))
+        })
+        .message(id, args)
+}
+
+/// Format a localized message, e.g. `t!("backup-failed", error = err.to_string())`
+///
+/// Arguments can be anything that converts into a Fluent value: strings and
+/// numbers. Numbers select plural forms.
+#[macro_export]
+macro_rules! t {
+    ($id:expr) => {
+        $crate::i18n::tr($id, &[])
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::tr(
+            $id,
+            &[$((stringify!($name), $crate::i18n::FluentValue::from($value))),+],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_cover_messages() {
+        let english = bundle(FALLBACK_LANGUAGE, CATALOGS[0].1);
+
+        // Every message used in the code exists in the English catalog
+        for source in [
+            include_str!("main.rs"),
+            include_str!("lib.rs"),
+            include_str!("tui.rs"),
+        ] {
+            let mut parts = source.split("t!(\"");
+            let mut before = parts.next().unwrap();
+            for used in parts {
+                // Skip `format!(` and other macros ending in `t`
+                let is_macro = !before.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+                let id = used.split('"').next().unwrap();
+                assert!(
+                    !is_macro || english.has_message(id),
+                    "missing message {}",
+                    id
+                );
+                before = used;
+            }
+        }
+
+        for (name, source) in CATALOGS {
+            assert!(
+                FluentResource::try_new(source.to_string()).is_ok(),
+                "syntax errors in the {} catalog",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_localizer_fallback() {
+        let localizer = Localizer::new("de-AT");
+        assert_eq!(localizer.message("dry-run-nothing", &[]), "Nothing to copy");
+        assert_eq!(
+            localizer.message("list-header", &[("count", FluentValue::from(1))]),
+            "1 dotfile backed up:"
+        );
+        assert_eq!(
+            localizer.message(
+                "export-done",
+                &[
+                    ("count", FluentValue::from(3)),
+                    ("history", FluentValue::from("true")),
+                    ("output", FluentValue::from("vault.tar.gz")),
+                ]
+            ),
+            "Exported 3 files and history to vault.tar.gz"
+        );
+        assert_eq!(localizer.message("no-such-message", &[]), "no-such-message");
+    }
+}
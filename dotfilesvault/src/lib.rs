@@ -1,32 +1,428 @@
+// Without git the copy-layout code paths are compiled out, leaving helpers
+// and imports they share with the storage backends unused
+#![cfg_attr(not(feature = "git"), allow(dead_code, unused_imports))]
+
 use anyhow::Result;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::backend::StorageBackend;
+use crate::bare::VaultLayout;
+use crate::cache::HashCache;
+use crate::cancel::CancellationToken;
+#[cfg(feature = "git")]
+use crate::capabilities::RemoteProblem;
+use crate::collisions::CaseCollisionPolicy;
+use crate::conflicts::ConflictPolicy;
+use crate::discovery::default_config_locations;
+use crate::security::SecretPolicy;
+use crate::symlink::SymlinkPolicy;
+use crate::undo::UndoLog;
+
+#[cfg(feature = "git")]
+pub mod adopt;
+pub mod backend;
 pub mod backup;
+pub mod bare;
+#[cfg(feature = "git")]
+pub mod bootstrap;
+#[cfg(feature = "git")]
+pub mod build_info;
+pub mod cache;
+pub mod cancel;
+#[cfg(feature = "git")]
+pub mod capabilities;
+pub mod collisions;
+#[cfg(feature = "git")]
+pub mod compact;
+pub mod completions;
+pub mod config_file;
+pub mod conflicts;
+#[cfg(feature = "git")]
+pub mod daemon;
+#[cfg(feature = "git")]
+pub mod diff;
+pub mod discovery;
+pub mod encryption;
+pub mod exit_code;
+#[cfg(feature = "git")]
+pub mod export;
+pub mod generator;
+pub mod globs;
+#[cfg(feature = "git")]
+pub mod grep;
+#[cfg(feature = "git")]
+pub mod heartbeat;
+#[cfg(feature = "git")]
 pub mod history;
+#[cfg(feature = "git")]
+pub mod history_index;
+pub mod hooks;
+pub mod i18n;
+pub mod lint;
+pub mod lock;
+pub mod manifest;
+pub mod mask;
+pub mod metadata;
+#[cfg(feature = "git")]
+pub mod metrics;
+pub mod plan;
+pub mod plugin;
+pub mod profile;
+pub mod progress;
+pub mod redact;
 pub mod restore;
+#[cfg(feature = "git")]
+pub mod rollback;
+pub mod s3;
+#[cfg(feature = "git")]
+pub mod safety;
+pub mod schedule;
+pub mod security;
+#[cfg(feature = "git")]
+pub mod sed;
+#[cfg(feature = "git")]
+pub mod session;
+pub mod simulate;
+#[cfg(feature = "git")]
+pub mod snapshot;
+pub mod snippet;
+pub mod status;
+#[cfg(feature = "git")]
+pub mod stray;
+pub mod suggest;
+pub mod symlink;
+#[cfg(feature = "git")]
+pub mod sync;
+pub mod template;
+pub mod toolchains;
+pub mod transform;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod undo;
+pub mod unicode;
+#[cfg(feature = "git")]
+pub mod untrack;
 pub mod utils;
+pub mod vaults;
+#[cfg(feature = "git")]
+pub mod verify;
 
 /// Errors that can occur in the dotfilesvault application
+///
+/// Messages come from the `error-*` entries of the message catalogs.
 #[derive(Error, Debug)]
 pub enum DotfilesError {
-    #[error("IO error: {0}")]
+    /// A file system operation failed
     Io(#[from] std::io::Error),
 
-    #[error("Failed to find home directory")]
+    /// The home directory couldn't be determined
     NoHomeDir,
 
-    #[error("Failed to find dotfilesvault directory")]
+    /// The vault directory doesn't exist or isn't a repository
     NoDotfilesVaultDir,
 
-    #[error("Dotfile not found: {0}")]
+    /// A dotfile isn't tracked or isn't in the vault
     DotfileNotFound(String),
 
-    #[error("Version not found for dotfile: {0}")]
+    /// No stored version of a dotfile matches
     VersionNotFound(String),
 
-    #[error("Git error: {0}")]
+    /// A vault repository operation failed
+    #[cfg(feature = "git")]
     Git(#[from] git2::Error),
+
+    /// A hook exited unsuccessfully: hook name and exit status
+    HookFailed(String, String),
+
+    /// No transform plugin has the name
+    TransformNotFound(String),
+
+    /// A transform plugin failed: plugin name and reason
+    TransformFailed(String, String),
+
+    /// The manifest can't be read
+    InvalidManifest(String),
+
+    /// No tracked dotfile belongs to the group
+    GroupNotFound(String),
+
+    /// The config file is invalid: path and reason
+    InvalidConfig(String, String),
+
+    /// An ignore or encrypt pattern is invalid: pattern and reason
+    InvalidIgnorePattern(String, String),
+
+    /// Pushing or pulling failed
+    SyncFailed(String),
+
+    /// Pulling would conflict in these files
+    MergeConflicts(Vec<String>),
+
+    /// No vault has the name in the config file
+    VaultNotFound(String),
+
+    /// A vault with the name already exists in the config file
+    VaultExists(String),
+
+    /// The remote can't be used from this machine: URL and why
+    #[cfg(feature = "git")]
+    RemoteUnavailable(String, RemoteProblem),
+
+    /// Encrypting or decrypting failed
+    Encryption(String),
+
+    /// Reading or writing the vault's storage backend failed
+    Backend(String),
+
+    /// No recorded restore session has the ID, or its file can't be read
+    SessionNotFound(String),
+
+    /// A template couldn't be rendered: template and reason
+    TemplateFailed(String, String),
+
+    /// The operation was cancelled, e.g. with Ctrl-C
+    Cancelled,
+
+    /// The operation took longer than its timeout
+    TimedOut(Duration),
+
+    /// Another process is changing the vault: path of its lock file
+    VaultLocked(String),
+
+    /// A snippet definition isn't `NAME=BODY` with a valid name
+    InvalidSnippet(String),
+
+    /// No snippet has the name
+    SnippetNotFound(String),
+
+    /// A dotfiles repository couldn't be imported: source and reason
+    ImportFailed(String, String),
+
+    /// A file couldn't be adopted into the vault: path and reason
+    AdoptFailed(String, String),
+
+    /// No restore was recorded that could be undone
+    NothingToUndo,
+
+    /// A home file changed since the restore an undo would revert
+    ChangedSinceRestore(String),
+
+    /// A home path would be stored where the vault keeps its own files
+    VaultInternalPath(String),
+
+    /// A glob pattern given as a path is invalid: pattern and reason
+    InvalidGlob(String, String),
+
+    /// A glob pattern given as a path matches no files
+    NoMatches(String),
+
+    /// No snapshot has the name
+    SnapshotNotFound(String),
+
+    /// A snapshot with the name already exists
+    SnapshotExists(String),
+
+    /// The name can't be used for a snapshot
+    InvalidSnapshotName(String),
+
+    /// Scheduled backups aren't supported on this platform
+    ScheduleUnsupported,
+
+    /// A service manager command failed: command and reason
+    ScheduleFailed(String, String),
+
+    /// The operation would write while `--read-only` is set
+    ReadOnly,
+
+    /// A restore would overwrite a home file with edits that were never backed up
+    RestoreConflict(String),
+}
+
+impl DotfilesError {
+    /// Check if the error means the operation was cancelled or timed out
+    pub fn is_cancellation(&self) -> bool {
+        matches!(self, DotfilesError::Cancelled | DotfilesError::TimedOut(_))
+    }
+
+    /// Get the machine-readable code of the error, as used in JSON output
+    ///
+    /// Codes are part of the public contract like exit codes, so scripts can
+    /// branch on them instead of matching messages, and are never renamed.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DotfilesError::Io(_) => "E_IO",
+            DotfilesError::NoHomeDir => "E_NO_HOME_DIR",
+            DotfilesError::NoDotfilesVaultDir => "E_NO_VAULT",
+            DotfilesError::DotfileNotFound(_) => "E_DOTFILE_NOT_FOUND",
+            DotfilesError::VersionNotFound(_) => "E_VERSION_NOT_FOUND",
+            #[cfg(feature = "git")]
+            DotfilesError::Git(_) => "E_GIT",
+            DotfilesError::HookFailed(_, _) => "E_HOOK_FAILED",
+            DotfilesError::TransformNotFound(_) => "E_TRANSFORM_NOT_FOUND",
+            DotfilesError::TransformFailed(_, _) => "E_TRANSFORM_FAILED",
+            DotfilesError::InvalidManifest(_) => "E_INVALID_MANIFEST",
+            DotfilesError::GroupNotFound(_) => "E_GROUP_NOT_FOUND",
+            DotfilesError::InvalidConfig(_, _) => "E_INVALID_CONFIG",
+            DotfilesError::InvalidIgnorePattern(_, _) => "E_INVALID_IGNORE_PATTERN",
+            DotfilesError::SyncFailed(_) => "E_SYNC_FAILED",
+            DotfilesError::MergeConflicts(_) => "E_CONFLICT_MERGE",
+            #[cfg(feature = "git")]
+            DotfilesError::RemoteUnavailable(_, _) => "E_REMOTE_UNAVAILABLE",
+            DotfilesError::VaultNotFound(_) => "E_VAULT_NOT_FOUND",
+            DotfilesError::VaultExists(_) => "E_VAULT_EXISTS",
+            DotfilesError::Encryption(_) => "E_ENCRYPTION",
+            DotfilesError::Backend(_) => "E_BACKEND",
+            DotfilesError::SessionNotFound(_) => "E_SESSION_NOT_FOUND",
+            DotfilesError::TemplateFailed(_, _) => "E_TEMPLATE_FAILED",
+            DotfilesError::Cancelled => "E_CANCELLED",
+            DotfilesError::TimedOut(_) => "E_TIMED_OUT",
+            DotfilesError::VaultLocked(_) => "E_VAULT_LOCKED",
+            DotfilesError::InvalidSnippet(_) => "E_INVALID_SNIPPET",
+            DotfilesError::SnippetNotFound(_) => "E_SNIPPET_NOT_FOUND",
+            DotfilesError::ImportFailed(_, _) => "E_IMPORT_FAILED",
+            DotfilesError::AdoptFailed(_, _) => "E_ADOPT_FAILED",
+            DotfilesError::NothingToUndo => "E_NOTHING_TO_UNDO",
+            DotfilesError::ChangedSinceRestore(_) => "E_CHANGED_SINCE_RESTORE",
+            DotfilesError::VaultInternalPath(_) => "E_VAULT_INTERNAL_PATH",
+            DotfilesError::InvalidGlob(_, _) => "E_INVALID_GLOB",
+            DotfilesError::NoMatches(_) => "E_NO_MATCHES",
+            DotfilesError::SnapshotNotFound(_) => "E_SNAPSHOT_NOT_FOUND",
+            DotfilesError::SnapshotExists(_) => "E_SNAPSHOT_EXISTS",
+            DotfilesError::InvalidSnapshotName(_) => "E_INVALID_SNAPSHOT_NAME",
+            DotfilesError::ScheduleUnsupported => "E_SCHEDULE_UNSUPPORTED",
+            DotfilesError::ScheduleFailed(_, _) => "E_SCHEDULE_FAILED",
+            DotfilesError::ReadOnly => "E_READ_ONLY",
+            DotfilesError::RestoreConflict(_) => "E_CONFLICT_LOCAL_MODIFIED",
+        }
+    }
+}
+
+impl std::fmt::Display for DotfilesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            DotfilesError::Io(err) => t!("error-io", error = err.to_string()),
+            DotfilesError::NoHomeDir => t!("error-no-home-dir"),
+            DotfilesError::NoDotfilesVaultDir => t!("error-no-vault-dir"),
+            DotfilesError::DotfileNotFound(file) => {
+                t!("error-dotfile-not-found", file = file.as_str())
+            }
+            DotfilesError::VersionNotFound(file) => {
+                t!("error-version-not-found", file = file.as_str())
+            }
+            #[cfg(feature = "git")]
+            DotfilesError::Git(err) => t!("error-git", error = err.to_string()),
+            DotfilesError::HookFailed(hook, status) => t!(
+                "error-hook-failed",
+                hook = hook.as_str(),
+                status = status.as_str()
+            ),
+            DotfilesError::TransformNotFound(name) => {
+                t!("error-transform-not-found", name = name.as_str())
+            }
+            DotfilesError::TransformFailed(name, error) => t!(
+                "error-transform-failed",
+                name = name.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::InvalidManifest(error) => {
+                t!("error-invalid-manifest", error = error.as_str())
+            }
+            DotfilesError::GroupNotFound(group) => {
+                t!("error-group-not-found", group = group.as_str())
+            }
+            DotfilesError::InvalidConfig(path, error) => t!(
+                "error-invalid-config",
+                path = path.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::InvalidIgnorePattern(pattern, error) => t!(
+                "error-invalid-ignore-pattern",
+                pattern = format!("{:?}", pattern),
+                error = error.as_str()
+            ),
+            DotfilesError::SyncFailed(error) => t!("error-sync-failed", error = error.as_str()),
+            DotfilesError::MergeConflicts(paths) => {
+                t!("error-merge-conflicts", paths = paths.join(", "))
+            }
+            DotfilesError::VaultNotFound(name) => t!("error-vault-not-found", name = name.as_str()),
+            DotfilesError::VaultExists(name) => t!("error-vault-exists", name = name.as_str()),
+            #[cfg(feature = "git")]
+            DotfilesError::RemoteUnavailable(url, problem) => t!(
+                "error-remote-unavailable",
+                url = url.as_str(),
+                reason = problem.describe()
+            ),
+            DotfilesError::Encryption(error) => t!("error-encryption", error = error.as_str()),
+            DotfilesError::Backend(error) => t!("error-backend", error = error.as_str()),
+            DotfilesError::SessionNotFound(session) => {
+                t!("error-session-not-found", session = session.as_str())
+            }
+            DotfilesError::TemplateFailed(name, error) => t!(
+                "error-template-failed",
+                name = name.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::Cancelled => t!("error-cancelled"),
+            DotfilesError::TimedOut(timeout) => {
+                t!("error-timed-out", seconds = timeout.as_secs())
+            }
+            DotfilesError::VaultLocked(path) => t!("error-vault-locked", path = path.as_str()),
+            DotfilesError::InvalidSnippet(definition) => t!(
+                "error-invalid-snippet",
+                definition = format!("{:?}", definition)
+            ),
+            DotfilesError::SnippetNotFound(name) => {
+                t!("error-snippet-not-found", name = name.as_str())
+            }
+            DotfilesError::ImportFailed(source, error) => t!(
+                "error-import-failed",
+                source = source.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::AdoptFailed(file, error) => t!(
+                "error-adopt-failed",
+                file = file.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::NothingToUndo => t!("error-nothing-to-undo"),
+            DotfilesError::ChangedSinceRestore(file) => {
+                t!("error-changed-since-restore", file = file.as_str())
+            }
+            DotfilesError::VaultInternalPath(file) => {
+                t!("error-vault-internal-path", file = file.as_str())
+            }
+            DotfilesError::InvalidGlob(pattern, error) => t!(
+                "error-invalid-glob",
+                pattern = pattern.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::NoMatches(pattern) => t!("error-no-matches", pattern = pattern.as_str()),
+            DotfilesError::SnapshotNotFound(name) => {
+                t!("error-snapshot-not-found", name = name.as_str())
+            }
+            DotfilesError::SnapshotExists(name) => {
+                t!("error-snapshot-exists", name = name.as_str())
+            }
+            DotfilesError::InvalidSnapshotName(name) => {
+                t!("error-invalid-snapshot-name", name = format!("{:?}", name))
+            }
+            DotfilesError::ScheduleUnsupported => t!("error-schedule-unsupported"),
+            DotfilesError::ScheduleFailed(command, error) => t!(
+                "error-schedule-failed",
+                command = command.as_str(),
+                error = error.as_str()
+            ),
+            DotfilesError::ReadOnly => t!("error-read-only"),
+            DotfilesError::RestoreConflict(file) => {
+                t!("error-restore-conflict", file = file.as_str())
+            }
+        };
+
+        f.write_str(&message)
+    }
 }
 
 /// Configuration for the dotfilesvault application
@@ -37,6 +433,141 @@ pub struct Config {
 
     /// Path to the home directory
     pub home_dir: PathBuf,
+
+    /// Maximum read/write throughput for file copies in bytes per second
+    pub bwlimit: Option<u64>,
+
+    /// Size in bytes above which backups leave files out, `None` for no limit
+    pub max_file_size: Option<u64>,
+
+    /// Whether backups leave out binary files
+    pub skip_binary: bool,
+
+    /// Number of threads used to scan the home directory (0 means one per CPU)
+    pub scan_threads: usize,
+
+    /// Number of threads copying dotfiles into the vault (0 means one per CPU)
+    pub copy_threads: usize,
+
+    /// Whether hashes of home files are cached locally, for slow network homes
+    pub home_cache: bool,
+
+    /// Home-relative directories scanned before everything else
+    pub scan_priority: Vec<PathBuf>,
+
+    /// Whether scans descend into symlinked directories when `symlinks` is
+    /// `follow`
+    pub scan_follow_links: bool,
+
+    /// How many levels below the home directory scans go, `None` for no limit
+    pub scan_max_depth: Option<usize>,
+
+    /// Home-relative config files and directories backed up besides dotfiles,
+    /// e.g. under `AppData` on Windows
+    pub config_locations: Vec<PathBuf>,
+
+    /// Gitignore-style patterns, relative to the home directory, excluded from backups
+    pub ignore: Vec<String>,
+
+    /// Author name for vault commits, see `author`
+    pub author_name: Option<String>,
+
+    /// Author email for vault commits, see `author`
+    pub author_email: Option<String>,
+
+    /// URL of the git remote the vault is synced with
+    pub remote_url: Option<String>,
+
+    /// Whether to suggest tracking recently edited well-known config files
+    pub suggestions: bool,
+
+    /// Gitignore-style patterns, relative to the home directory, of files stored encrypted
+    pub encrypt: Vec<String>,
+
+    /// Path of the age identity used for encryption (defaults to next to the config file)
+    pub key_file: Option<PathBuf>,
+
+    /// Redaction rules, placeholder to regular expression, see `redact`
+    pub redact: BTreeMap<String, String>,
+
+    /// Whether backups also capture toolchain state (rustup, pyenv, nvm, ...)
+    pub capture_toolchains: bool,
+
+    /// `strftime`-style format for displayed timestamps, or `iso8601`
+    pub time_format: String,
+
+    /// Whether timestamps are displayed in UTC instead of local time
+    pub utc: bool,
+
+    /// Language of CLI messages, e.g. `de` (defaults to the system locale)
+    pub language: Option<String>,
+
+    /// Whether CLI output is plain linear text, without color, alignment or
+    /// markers, for screen readers
+    pub plain: bool,
+
+    /// Whether long operations draw progress bars on stderr
+    pub progress: bool,
+
+    /// Custom template variables, overriding the built-in ones
+    pub variables: BTreeMap<String, String>,
+
+    /// Shell commands run as hooks, keyed by hook name (e.g. `post-restore`)
+    pub hook_commands: BTreeMap<String, String>,
+
+    /// Machine profile whose variants are used instead of the common files
+    pub profile: Option<String>,
+
+    /// What restores do with home files that have edits that were never backed up
+    pub on_conflict: ConflictPolicy,
+
+    /// What backups do with files that look like they contain secrets
+    pub on_secret: SecretPolicy,
+
+    /// What backups and restores do with paths that differ only in case
+    pub on_case_collision: CaseCollisionPolicy,
+
+    /// What backups do with dotfiles that are symlinks
+    pub symlinks: SymlinkPolicy,
+
+    /// Whether the vault holds copies of the dotfiles or is a bare repository
+    /// with the home directory as its worktree
+    pub layout: VaultLayout,
+
+    /// Where the vault's files and versions are stored
+    pub backend: StorageBackend,
+
+    /// Whether destructive commands ask for confirmation and take safety
+    /// snapshots first
+    pub paranoid: bool,
+
+    /// Whether backups and restores only report what they would do
+    pub dry_run: bool,
+
+    /// Whether every write to the home directory and the vault is refused
+    pub read_only: bool,
+
+    /// How long a command may run before it is cancelled
+    pub timeout: Option<Duration>,
+
+    /// How old the last backup may get before `status` and `doctor` warn,
+    /// `None` to never warn
+    pub stale_after: Option<Duration>,
+
+    /// Token that long-running operations check to stop early
+    pub cancellation: CancellationToken,
+
+    /// Hashes of home files shared by the operations of a command
+    pub hash_cache: HashCache,
+
+    /// Home files replaced by the restores of a command, for `undo`
+    pub undo: UndoLog,
+
+    /// Path of the configuration file the settings were loaded from, if any
+    pub config_file: Option<PathBuf>,
+
+    /// Name of the vault selected from the config file's `vaults`, if any
+    pub vault_name: Option<String>,
 }
 
 impl Default for Config {
@@ -44,10 +575,7 @@ impl Default for Config {
         let home_dir = dirs::home_dir().expect("Failed to find home directory");
         let vault_dir = home_dir.join("dotfilesvault");
 
-        Self {
-            vault_dir,
-            home_dir,
-        }
+        Self::new(vault_dir, home_dir)
     }
 }
 
@@ -57,9 +585,129 @@ impl Config {
         Self {
             vault_dir,
             home_dir,
+            bwlimit: None,
+            max_file_size: None,
+            skip_binary: false,
+            scan_threads: 0,
+            copy_threads: 0,
+            home_cache: false,
+            scan_priority: Vec::new(),
+            scan_follow_links: true,
+            scan_max_depth: None,
+            config_locations: default_config_locations(),
+            ignore: Vec::new(),
+            author_name: None,
+            author_email: None,
+            remote_url: None,
+            suggestions: true,
+            encrypt: Vec::new(),
+            key_file: None,
+            redact: BTreeMap::new(),
+            capture_toolchains: false,
+            time_format: utils::DEFAULT_TIME_FORMAT.to_string(),
+            utc: false,
+            language: None,
+            plain: false,
+            progress: false,
+            variables: BTreeMap::new(),
+            hook_commands: BTreeMap::new(),
+            profile: None,
+            on_conflict: ConflictPolicy::Fail,
+            on_secret: SecretPolicy::Refuse,
+            on_case_collision: CaseCollisionPolicy::Warn,
+            symlinks: SymlinkPolicy::Preserve,
+            layout: VaultLayout::Copy,
+            backend: StorageBackend::Git,
+            paranoid: false,
+            dry_run: false,
+            read_only: false,
+            timeout: None,
+            #[cfg(feature = "git")]
+            stale_after: Some(heartbeat::DEFAULT_STALE_AFTER),
+            #[cfg(not(feature = "git"))]
+            stale_after: None,
+            cancellation: CancellationToken::new(),
+            hash_cache: HashCache::default(),
+            undo: UndoLog::default(),
+            config_file: None,
+            vault_name: None,
+        }
+    }
+
+    /// Operate on another home directory
+    ///
+    /// A vault inside the old home directory, such as the default
+    /// `~/dotfilesvault`, moves along to the same place in the new one.
+    pub fn set_home_dir(&mut self, home_dir: PathBuf) {
+        let old_home = std::mem::replace(&mut self.home_dir, home_dir);
+
+        if let Ok(relative) = self.vault_dir.strip_prefix(&old_home) {
+            self.vault_dir = self.home_dir.join(relative);
         }
     }
 
+    /// Get the vault's git directory, the vault itself in the bare layout
+    pub fn git_dir(&self) -> PathBuf {
+        match self.layout {
+            VaultLayout::Copy => self.vault_dir.join(".git"),
+            VaultLayout::Bare => self.vault_dir.clone(),
+        }
+    }
+
+    /// Get the directory holding this machine's state
+    ///
+    /// This is the vault's git directory, so the state is never committed or
+    /// synced. Vaults stored in another backend keep no files of their own in
+    /// `vault_dir`, so it holds the state itself.
+    pub fn state_dir(&self) -> PathBuf {
+        if self.backend.is_git() {
+            self.git_dir()
+        } else {
+            self.vault_dir.clone()
+        }
+    }
+
+    /// Get the path of a per-machine state file or directory
+    ///
+    /// Locks, caches and other state of this machine live in `state_dir`.
+    pub fn state_path(&self, name: &str) -> PathBuf {
+        self.state_dir().join(name)
+    }
+
+    /// Get the author name and email of vault commits
+    ///
+    /// `author_name` and `author_email`, set in the config file, come first.
+    /// Otherwise `user.name` and `user.email` are read from the vault's git
+    /// config, which falls back to the global one, and then the built-in
+    /// `Dotfilesvault <dotfilesvault@example.com>` is used.
+    #[cfg(feature = "git")]
+    pub fn author(&self) -> (String, String) {
+        let git_config = git2::Repository::open(&self.vault_dir)
+            .and_then(|repo| repo.config())
+            .or_else(|_| git2::Config::open_default())
+            .and_then(|mut config| config.snapshot())
+            .ok();
+        let from_git = |key: &str| {
+            git_config
+                .as_ref()
+                .and_then(|config| config.get_string(key).ok())
+                .filter(|value| !value.is_empty())
+        };
+
+        let name = self
+            .author_name
+            .clone()
+            .or_else(|| from_git("user.name"))
+            .unwrap_or_else(|| history::DEFAULT_AUTHOR_NAME.to_string());
+        let email = self
+            .author_email
+            .clone()
+            .or_else(|| from_git("user.email"))
+            .unwrap_or_else(|| history::DEFAULT_AUTHOR_EMAIL.to_string());
+
+        (name, email)
+    }
+
     /// Initialize the dotfilesvault directory
     pub fn init_vault_dir(&self) -> Result<(), DotfilesError> {
         if !self.vault_dir.exists() {
@@ -70,6 +718,31 @@ impl Config {
     }
 }
 
+/// Top-level vault entries that belong to dotfilesvault rather than the home directory
+pub const VAULT_INTERNAL_PATHS: [&str; 10] = [
+    ".git",
+    undo::UNDO_DIR,
+    backup::VAULTIGNORE_FILE,
+    manifest::STATE_DIR,
+    hooks::HOOKS_DIR,
+    transform::PLUGINS_DIR,
+    toolchains::TOOLCHAINS_DIR,
+    profile::PROFILES_DIR,
+    snippet::SNIPPETS_DIR,
+    generator::SHELL_SOURCE,
+];
+
+/// Check if a vault-relative path belongs to dotfilesvault itself
+pub fn is_vault_internal<P: AsRef<Path>>(relative_path: P) -> bool {
+    relative_path
+        .as_ref()
+        .components()
+        .next()
+        .and_then(|component| component.as_os_str().to_str())
+        .map(|first| VAULT_INTERNAL_PATHS.contains(&first))
+        .unwrap_or(false)
+}
+
 /// Check if a file is a dotfile
 pub fn is_dotfile<P: AsRef<Path>>(path: P) -> bool {
     path.as_ref()
@@ -93,4 +766,35 @@ mod tests {
         assert!(!is_dotfile("/home/user/documents"));
         assert!(!is_dotfile(Path::new("/home/user/file.txt")));
     }
+
+    #[test]
+    fn test_set_home_dir() {
+        let mut config = Config::new(
+            PathBuf::from("/home/me/dotfilesvault"),
+            PathBuf::from("/home/me"),
+        );
+        config.set_home_dir(PathBuf::from("/mnt/backup/home/me"));
+        assert_eq!(
+            config.vault_dir,
+            PathBuf::from("/mnt/backup/home/me/dotfilesvault")
+        );
+
+        let mut config = Config::new(PathBuf::from("/srv/vault"), PathBuf::from("/home/me"));
+        config.set_home_dir(PathBuf::from("/home/other"));
+        assert_eq!(config.vault_dir, PathBuf::from("/srv/vault"));
+    }
+
+    #[test]
+    fn test_is_vault_internal() {
+        assert!(is_vault_internal(".git/HEAD"));
+        assert!(is_vault_internal("hooks/post-restore"));
+        assert!(is_vault_internal("plugins/redact.wasm"));
+        assert!(is_vault_internal(".dotfilesvault/manifest.json"));
+        assert!(is_vault_internal(".vaultignore"));
+        assert!(is_vault_internal(".undo/20260101-120000/record.json"));
+        assert!(is_vault_internal("profiles/work/.gitconfig"));
+
+        assert!(!is_vault_internal(".bashrc"));
+        assert!(!is_vault_internal(".config/hooks/file"));
+    }
 }
@@ -0,0 +1,424 @@
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::backup::ignore_matcher;
+use crate::config_file::{CONFIG_KEYS, GIT_KEYS};
+use crate::manifest::{Condition, DotfileEntry, Manifest};
+use crate::{Config, DotfilesError};
+
+/// Operating systems a manifest `os` condition can match
+pub const KNOWN_OSES: [&str; 8] = [
+    "linux", "macos", "windows", "freebsd", "openbsd", "netbsd", "android", "ios",
+];
+
+/// How serious a lint finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Settings that are ignored or have no effect
+    Warning,
+
+    /// Settings that are broken and make operations misbehave
+    Error,
+}
+
+impl Severity {
+    /// Name used in output
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// A problem found in the configuration or the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+
+    /// Stable identifier of the check, e.g. `unknown-key`
+    pub code: &'static str,
+
+    /// Where the problem is: a config key or a manifest entry
+    pub location: String,
+
+    /// Human-readable description
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, code: &'static str, location: String, message: String) -> Self {
+        Self {
+            severity,
+            code,
+            location,
+            message,
+        }
+    }
+}
+
+/// Check the configuration and the vault manifest for mistakes
+///
+/// Problems that would stop other commands, such as an unreadable manifest,
+/// are reported as diagnostics rather than errors.
+pub fn lint(config: &Config) -> Result<Vec<Diagnostic>, DotfilesError> {
+    let mut diagnostics = Vec::new();
+
+    if let Some(path) = &config.config_file {
+        lint_config_keys(&fs::read_to_string(path)?, &mut diagnostics);
+    }
+    lint_patterns(config, &mut diagnostics);
+
+    match Manifest::load(config) {
+        Ok(manifest) => lint_manifest(config, &manifest, &mut diagnostics),
+        Err(DotfilesError::InvalidManifest(err)) => diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            "invalid-manifest",
+            "manifest".to_string(),
+            err,
+        )),
+        Err(err) => return Err(err),
+    }
+
+    Ok(diagnostics)
+}
+
+/// Report keys in the config file that aren't settings
+fn lint_config_keys(content: &str, diagnostics: &mut Vec<Diagnostic>) {
+    // Syntax errors were already reported when the config was loaded
+    let Ok(table) = content.parse::<toml::Table>() else {
+        return;
+    };
+
+    lint_table_keys(&table, "", diagnostics);
+}
+
+/// Report unknown keys in the top-level settings or a named vault's table,
+/// whose keys are reported as `<prefix><key>`
+fn lint_table_keys(table: &toml::Table, prefix: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for (key, value) in table {
+        if !CONFIG_KEYS.contains(&key.as_str()) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "unknown-key",
+                format!("{}{}", prefix, key),
+                "unknown setting, it is ignored".to_string(),
+            ));
+        } else if key == "git"
+            && let Some(git) = value.as_table()
+        {
+            for git_key in git.keys() {
+                if !GIT_KEYS.contains(&git_key.as_str()) {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        "unknown-key",
+                        format!("{}git.{}", prefix, git_key),
+                        "unknown setting, it is ignored".to_string(),
+                    ));
+                }
+            }
+        } else if key == "vaults"
+            && prefix.is_empty()
+            && let Some(vaults) = value.as_table()
+        {
+            for (name, vault) in vaults {
+                if let Some(vault) = vault.as_table() {
+                    lint_table_keys(vault, &format!("vaults.{}.", name), diagnostics);
+                }
+            }
+        }
+    }
+}
+
+/// Report broken, repeated and contradicting ignore and encrypt patterns
+fn lint_patterns(config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    for (key, patterns) in [("ignore", &config.ignore), ("encrypt", &config.encrypt)] {
+        let mut seen = HashSet::new();
+        for pattern in patterns {
+            if !seen.insert(pattern) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    "duplicate-pattern",
+                    key.to_string(),
+                    format!("{:?} is listed more than once", pattern),
+                ));
+            }
+        }
+    }
+
+    // Patterns overlap if a path one of them matches is matched by the other
+    let ignored = ignore_matcher(config).ok();
+    let matches = |matcher: &Gitignore, pattern: &str| {
+        sample_path(pattern).is_some_and(|path| {
+            matcher
+                .matched_path_or_any_parents(config.home_dir.join(path), false)
+                .is_ignore()
+        })
+    };
+    for pattern in &config.encrypt {
+        let Some(encrypted) = pattern_matcher(config, pattern) else {
+            continue;
+        };
+        let overlaps = ignored
+            .as_ref()
+            .is_some_and(|ignored| matches(ignored, pattern))
+            || config
+                .ignore
+                .iter()
+                .any(|ignore| matches(&encrypted, ignore));
+        if overlaps {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "overlapping-pattern",
+                "encrypt".to_string(),
+                format!(
+                    "{:?} overlaps an ignore pattern, files matching both are never backed up",
+                    pattern
+                ),
+            ));
+        }
+    }
+
+    if let Err(err) = ignore_matcher(config) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            "invalid-pattern",
+            "ignore".to_string(),
+            err.to_string(),
+        ));
+    }
+}
+
+/// Build a matcher for a single gitignore-style pattern, `None` if it's invalid
+fn pattern_matcher(config: &Config, pattern: &str) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(&config.home_dir);
+    builder.add_line(None, pattern).ok()?;
+    builder.build().ok()
+}
+
+/// Get a home-relative path a gitignore-style pattern matches
+///
+/// Wildcards are filled in with `x`, and character classes with their first
+/// character. Negations and comments match nothing.
+fn sample_path(pattern: &str) -> Option<PathBuf> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() || pattern.starts_with(['!', '#']) {
+        return None;
+    }
+    // Directory patterns match the files in the directory
+    let (pattern, dir) = match pattern.strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut sample = String::new();
+    let mut chars = pattern.trim_start_matches('/').chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '?' => sample.push('x'),
+            '\\' => sample.extend(chars.next()),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                match class.strip_prefix(['!', '^']) {
+                    // No pattern lists NUL, so it is outside any class
+                    Some(_) => sample.push('\0'),
+                    None => sample.extend(class.chars().next()),
+                }
+            }
+            c => sample.push(c),
+        }
+    }
+    if dir {
+        sample.push_str("/x");
+    }
+
+    Some(PathBuf::from(sample))
+}
+
+/// Report manifest entries that are dead, ignored, clash or can never apply
+fn lint_manifest(config: &Config, manifest: &Manifest, diagnostics: &mut Vec<Diagnostic>) {
+    let matcher = ignore_matcher(config).ok();
+    let mut targets: BTreeMap<&PathBuf, &DotfileEntry> = BTreeMap::new();
+
+    for entry in manifest.entries.values() {
+        let location = format!("manifest:{}", entry.source.display());
+        let dotfile = entry.dotfile(config);
+
+        if !dotfile.original_path.exists()
+            && !dotfile.vault_path.exists()
+            && !config.vault_dir.join(&entry.target).exists()
+        {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "dead-entry",
+                location.clone(),
+                "neither the home file nor a vault copy exists".to_string(),
+            ));
+        }
+
+        if let Some(matcher) = &matcher
+            && matcher
+                .matched_path_or_any_parents(&dotfile.original_path, false)
+                .is_ignore()
+        {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "ignored-entry",
+                location.clone(),
+                "tracked but matched by an ignore pattern".to_string(),
+            ));
+        }
+
+        if let Some(other) = targets.insert(&entry.target, entry) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "duplicate-target",
+                location.clone(),
+                format!(
+                    "stored at {} like {}",
+                    entry.target.display(),
+                    other.source.display()
+                ),
+            ));
+        }
+
+        for (severity, message) in condition_problems(&entry.conditions) {
+            diagnostics.push(Diagnostic::new(
+                severity,
+                "unreachable-condition",
+                location.clone(),
+                message,
+            ));
+        }
+    }
+}
+
+/// Describe why a set of conditions can't all hold on any machine
+fn condition_problems(conditions: &[Condition]) -> Vec<(Severity, String)> {
+    let mut problems = Vec::new();
+    let mut os = None;
+    let mut hostname = None;
+
+    for condition in conditions {
+        match condition {
+            Condition::Os(value) => {
+                if !KNOWN_OSES.contains(&value.as_str()) {
+                    problems.push((
+                        Severity::Warning,
+                        format!("unknown operating system {:?}", value),
+                    ));
+                }
+                if let Some(previous) = os.replace(value)
+                    && previous != value
+                {
+                    problems.push((
+                        Severity::Error,
+                        format!("never applies: requires both os {} and {}", previous, value),
+                    ));
+                }
+            }
+            Condition::Hostname(value) => {
+                if let Some(previous) = hostname.replace(value)
+                    && previous != value
+                {
+                    problems.push((
+                        Severity::Error,
+                        format!(
+                            "never applies: requires both hostname {} and {}",
+                            previous, value
+                        ),
+                    ));
+                }
+            }
+            Condition::Env(_) => {}
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Dotfile;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lint_config_keys() {
+        let mut diagnostics = Vec::new();
+        lint_config_keys(
+            "ignore = []\nvault-dir = \"x\"\n[git]\nauthor_name = \"Me\"\nemail = \"me@example.com\"\n",
+            &mut diagnostics,
+        );
+
+        let locations: Vec<&str> = diagnostics.iter().map(|d| d.location.as_str()).collect();
+        assert_eq!(locations, vec!["git.email", "vault-dir"]);
+    }
+
+    #[test]
+    fn test_lint_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.ignore = vec![".cache".to_string()];
+        config.encrypt = vec![".cache".to_string()];
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        let mut manifest = Manifest::default();
+        let gone = Dotfile::new(config.home_dir.join(".gonerc"), &config);
+        let mut entry = manifest.track(&gone, &config).clone();
+        entry.conditions = vec![
+            Condition::Os("linux".to_string()),
+            Condition::Os("macos".to_string()),
+        ];
+        manifest.entries.insert(entry.id.clone(), entry);
+        manifest.save(&config).unwrap();
+
+        let diagnostics = lint(&config).unwrap();
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec!["overlapping-pattern", "dead-entry", "unreachable-condition"]
+        );
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_lint_overlapping_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.ignore = vec![".ssh/*".to_string(), ".aws/".to_string()];
+        config.encrypt = vec![
+            ".ssh/id_*".to_string(),
+            ".aws".to_string(),
+            "*.k[e]y".to_string(),
+            ".gnupg/*".to_string(),
+        ];
+
+        let mut diagnostics = Vec::new();
+        lint_patterns(&config, &mut diagnostics);
+        let overlapping: Vec<&str> = diagnostics
+            .iter()
+            .filter(|d| d.code == "overlapping-pattern")
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(overlapping.len(), 2);
+        assert!(overlapping[0].starts_with("\".ssh/id_*\""));
+        assert!(overlapping[1].starts_with("\".aws\""));
+
+        // Either side may be the wider one
+        config.ignore = vec!["*.key".to_string()];
+        config.encrypt = vec!["secrets/*".to_string()];
+        let mut diagnostics = Vec::new();
+        lint_patterns(&config, &mut diagnostics);
+        assert!(diagnostics.is_empty());
+        config.ignore = vec!["secrets/api.key".to_string()];
+        lint_patterns(&config, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use log::{debug, warn};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::{Config, DotfilesError};
+
+/// Name of the lock file, see `Config::state_path`
+pub const LOCK_FILE: &str = "dotfilesvault.lock";
+
+/// Lock files held by this process, so nested operations don't lock themselves out
+static HELD: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Exclusive access to a vault for an operation that changes it
+///
+/// The lock is a file holding the process ID, removed when the lock is
+/// dropped, including when the operation fails or is cancelled. Dry runs and
+/// vaults whose state directory doesn't exist yet aren't locked. In
+/// read-only mode nothing may change the vault, so locking it fails.
+#[derive(Debug)]
+pub struct VaultLock {
+    /// The lock file, if this lock created it
+    path: Option<PathBuf>,
+}
+
+impl VaultLock {
+    /// Lock the vault, or fail if another process holds the lock
+    pub fn acquire(config: &Config) -> Result<Self, DotfilesError> {
+        if config.read_only && !config.dry_run {
+            return Err(DotfilesError::ReadOnly);
+        }
+
+        if config.dry_run || !config.state_dir().is_dir() {
+            return Ok(Self { path: None });
+        }
+
+        let path = config.state_path(LOCK_FILE);
+        let mut held = HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if held.contains(&path) {
+            return Ok(Self { path: None });
+        }
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                writeln!(file, "{}", std::process::id())?;
+                debug!("Locked vault with {:?}", path);
+                held.push(path.clone());
+
+                Ok(Self { path: Some(path) })
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                Err(DotfilesError::VaultLocked(path.display().to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Remove the lock files this process holds
+///
+/// For exiting without unwinding, e.g. on a second Ctrl-C, when the locks
+/// won't be dropped.
+pub fn release_held_locks() {
+    let mut held = HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for path in held.drain(..) {
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("Failed to remove the vault lock {:?}: {}", path, err);
+        }
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+
+        // The lock is already gone if `release_held_locks` ran
+        if let Err(err) = fs::remove_file(&path)
+            && err.kind() != ErrorKind::NotFound
+        {
+            warn!("Failed to remove the vault lock {:?}: {}", path, err);
+        }
+
+        let mut held = HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        held.retain(|held_path| *held_path != path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use crate::history::init_git_repo;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_vault_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        let lock_path = config.vault_dir.join(".git").join(LOCK_FILE);
+
+        let lock = VaultLock::acquire(&config).unwrap();
+        assert!(lock_path.exists());
+
+        // Nested operations in the same process share the lock
+        drop(VaultLock::acquire(&config).unwrap());
+        assert!(lock_path.exists());
+
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // Exiting early removes the lock, and dropping it later is harmless
+        let lock = VaultLock::acquire(&config).unwrap();
+        release_held_locks();
+        assert!(!lock_path.exists());
+        drop(lock);
+
+        // A lock file left by another process blocks the vault
+        fs::write(&lock_path, "1\n").unwrap();
+        assert!(matches!(
+            VaultLock::acquire(&config),
+            Err(DotfilesError::VaultLocked(_))
+        ));
+
+        // Read-only mode never changes the vault
+        fs::remove_file(&lock_path).unwrap();
+        config.read_only = true;
+        assert!(matches!(
+            VaultLock::acquire(&config),
+            Err(DotfilesError::ReadOnly)
+        ));
+    }
+}
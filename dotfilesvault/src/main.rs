@@ -1,172 +1,5032 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use log::{LevelFilter, debug, error, info};
-use std::process;
+use chrono::{DateTime, Local};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{Shell, generate};
+use env_logger::{Target, WriteStyle};
+use log::{LevelFilter, debug, error, info, warn};
+use regex::RegexBuilder;
+use serde::Serialize;
+use serde_json::json;
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use dotfilesvault::Config;
-use dotfilesvault::backup::{backup_all_dotfiles, backup_specific_dotfiles};
-use dotfilesvault::history::{commit_changes, get_dotfile_history};
-use dotfilesvault::restore::{list_backed_up_dotfiles, restore_specific_dotfile};
+use dotfilesvault::adopt::adopt_dotfiles;
+use dotfilesvault::backend::{history_with, open_backend};
+use dotfilesvault::backup::{backup_all_dotfiles, backup_due_dotfiles, backup_specific_dotfiles};
+use dotfilesvault::bare::{VaultLayout, bare_backup, bare_restore, bare_status};
+use dotfilesvault::bootstrap::{bootstrap_script, write_bootstrap_script};
+use dotfilesvault::build_info::{build_info, features};
+use dotfilesvault::cache::clear_cache;
+use dotfilesvault::cancel::CancellationToken;
+use dotfilesvault::collisions::CaseCollision;
+use dotfilesvault::compact::compact_history;
+use dotfilesvault::completions::tracked_files_completion;
+use dotfilesvault::config_file::default_config_path;
+use dotfilesvault::conflicts::{ConflictPolicy, list_conflicts, resolve_conflict};
+use dotfilesvault::daemon::watch;
+use dotfilesvault::diff::{changes_since, diff_files, divergence};
+use dotfilesvault::encryption::{export_key, init_key, key_path};
+use dotfilesvault::exit_code::ExitCode;
+use dotfilesvault::export::{export_changed_between, export_vault};
+use dotfilesvault::generator::{TargetShell, load_shell_source, source_path, write_shell_files};
+use dotfilesvault::globs::{expand_home_glob, expand_vault_glob, is_glob};
+use dotfilesvault::grep::grep_vault;
+use dotfilesvault::heartbeat::{stale_hosts, stale_self};
+use dotfilesvault::history::{
+    RetentionPolicy, commit_changes, follow_dotfile_history, get_dotfile_history, list_files_at,
+    prune_history, resolve_version,
+};
+use dotfilesvault::hooks::{HookContext, HookEvent, list_hooks, run_hook};
+use dotfilesvault::lint::{Severity, lint};
+use dotfilesvault::lock::release_held_locks;
+use dotfilesvault::manifest::{
+    BackupFrequency, Manifest, add_paths, set_frequency, set_group, track_starter, untrack_starter,
+};
+use dotfilesvault::mask::{is_sensitive_file, mask_secrets};
+use dotfilesvault::metrics::{collect, record_backup, render, serve, write_textfile};
+use dotfilesvault::plan::{Notice, Plan, PlannedAction, SKIPPED_BINARY, SKIPPED_LARGE};
+use dotfilesvault::plugin::{PluginContext, find_plugin, run_plugin};
+use dotfilesvault::profile::{list_profiles, validate_profile_name};
+use dotfilesvault::progress::LogWriter;
+use dotfilesvault::restore::{
+    RestoreSummary, list_backed_up_dotfiles, read_backed_up_dotfile, restore_all_dotfiles,
+    restore_dotfile_version, restore_drifted, restore_files, restore_files_at,
+    restore_specific_dotfile,
+};
+use dotfilesvault::rollback::{
+    RollbackAction, apply_rollback, apply_vault_rollback, plan_group_rollback, plan_vault_rollback,
+};
+use dotfilesvault::safety::take_safety_snapshot;
+use dotfilesvault::schedule::{
+    format_interval, install_schedule, remove_schedule, schedule_status,
+};
+use dotfilesvault::security::{SecretFinding, SecretPolicy};
+use dotfilesvault::sed::{SedExpression, sed_dotfiles};
+use dotfilesvault::session::{
+    Session, SessionRequest, list_sessions, load_session, record_file_session, record_session,
+    replay_session,
+};
+use dotfilesvault::simulate::simulate_bootstrap;
+use dotfilesvault::snapshot::{create_snapshot, list_snapshots, restore_snapshot};
+use dotfilesvault::snippet::{
+    SOURCE_LINE, Snippet, SnippetKind, add_snippet, is_sourced, list_snippets, remove_snippet,
+    script_path, write_snippets_script,
+};
+use dotfilesvault::status::{FileStatus, get_status};
+use dotfilesvault::stray::{StrayAction, find_stray_files, resolve_stray_files};
+use dotfilesvault::suggest::{SUGGESTION_WINDOW_DAYS, suggest_untracked};
+use dotfilesvault::sync::{
+    PullOutcome, REMOTE_NAME, SyncConflictPolicy, clone_vault, export_bundle, import_dotfiles,
+    pull, push, recover, set_remote, sync_vault,
+};
+use dotfilesvault::toolchains::{capture_toolchains, reinstall_hints};
+#[cfg(feature = "tui")]
+use dotfilesvault::tui::run_tui;
+use dotfilesvault::undo::undo_last_restore;
+use dotfilesvault::unicode::normalize_vault;
+use dotfilesvault::untrack::{find_orphaned_dotfiles, untrack_dotfiles};
+use dotfilesvault::utils::{
+    expand_tilde, format_timestamp, normalize_path, parse_datetime, parse_duration, parse_size,
+    rfc3339, validate_time_format,
+};
+use dotfilesvault::vaults::{DEFAULT_VAULT, create_vault, init_vault, list_vaults, switch_vault};
+use dotfilesvault::verify::{ProblemKind, verify_vault};
+use dotfilesvault::{Config, DotfilesError, i18n, t};
 
 /// Dotfilesvault - A tool for backing up and managing dotfiles with version history
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(author, about, disable_version_flag = true)]
 struct Cli {
+    /// Print version
+    #[clap(short = 'V', long)]
+    version: bool,
+
+    /// With --version, print the version, build metadata and features as JSON
+    #[clap(long, requires = "version")]
+    json: bool,
+
     /// Sets the level of verbosity
     #[clap(short, long, global = true)]
     verbose: bool,
 
+    /// Print stable, line-oriented output for scripts
+    #[clap(long, global = true)]
+    porcelain: bool,
+
+    /// Output format of command results: text (or plain) for people, json for scripts
+    #[clap(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        default_value = "text",
+        conflicts_with = "porcelain"
+    )]
+    format: OutputFormat,
+
+    /// Print plain linear text without color, alignment or markers, e.g. for screen readers
+    #[clap(long, global = true)]
+    plain: bool,
+
+    /// Don't draw progress bars, which long operations otherwise show when stderr is a terminal
+    #[clap(long, global = true)]
+    no_progress: bool,
+
+    /// Show timestamps in UTC instead of local time
+    #[clap(long, global = true)]
+    utc: bool,
+
+    /// Timestamp format: a strftime-style pattern such as "%d.%m.%Y %H:%M", or iso8601
+    #[clap(long, global = true, value_name = "FORMAT", value_parser = parse_time_format)]
+    time_format: Option<String>,
+
+    /// Machine profile: use its variants of dotfiles instead of the common ones,
+    /// and give explicitly backed up files a variant for it
+    #[clap(long, global = true, value_name = "NAME", value_parser = parse_profile)]
+    profile: Option<String>,
+
+    /// Cancel the command if it runs longer than this, e.g. 90, 30s, 10m or 2h
+    #[clap(long, global = true, value_name = "DURATION", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+
+    /// Show what backup or restore would copy and commit, without changing anything
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    /// Refuse every command that would write to the home directory or the vault, e.g. for audits
+    #[clap(long, global = true)]
+    read_only: bool,
+
+    /// Home directory to operate on instead of the current user's; a vault
+    /// inside the home directory moves along unless --vault is given
+    #[clap(long, global = true, value_name = "DIR")]
+    home: Option<PathBuf>,
+
+    /// Vault to operate on instead of the configured one: a vault named in
+    /// the config file (see `vault list`), or a directory
+    #[clap(long, global = true, value_name = "NAME|DIR")]
+    vault: Option<String>,
+
+    /// Path to the config file (defaults to ~/.config/dotfilesvault/config.toml)
+    #[clap(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
     #[clap(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+}
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable text
+    Text,
+
+    /// One JSON document per command
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" | "plain" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("invalid format {:?}, expected text or json", value)),
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Backup dotfiles from home directory
     Backup {
-        /// Specific dotfiles to backup (defaults to all)
+        /// Specific dotfiles or dot-directories to backup (defaults to all)
         #[clap(value_name = "FILES")]
         files: Vec<String>,
+
+        /// Limit read/write throughput, e.g. 512K or 10M per second
+        #[clap(long, value_name = "RATE", value_parser = parse_bwlimit)]
+        bwlimit: Option<u64>,
+
+        /// Number of threads scanning the home directory (0 means one per CPU)
+        #[clap(long, value_name = "N")]
+        scan_threads: Option<usize>,
+
+        /// Number of threads copying dotfiles into the vault (0 means one per CPU)
+        #[clap(long, value_name = "N")]
+        copy_threads: Option<usize>,
+
+        /// Home-relative directory to scan before everything else (repeatable)
+        #[clap(long, value_name = "DIR")]
+        scan_first: Vec<PathBuf>,
+
+        /// Only back up dotfiles that are due according to their frequency
+        #[clap(long, conflicts_with = "files")]
+        due: bool,
+
+        /// Back up files even if they look like they contain secrets
+        #[clap(long)]
+        allow_secrets: bool,
+
+        /// Back up files larger than max_file_size
+        #[clap(long)]
+        include_large: bool,
+
+        /// Message of the vault commit, instead of the generated one
+        #[clap(short, long, value_name = "MESSAGE")]
+        message: Option<String>,
+    },
+
+    /// Track files or directories that aren't dotfiles, e.g. ~/bin/setup.sh;
+    /// later backups copy them
+    Add {
+        /// Home paths to track; directories are tracked as a whole
+        #[clap(value_name = "PATHS", required = true)]
+        files: Vec<String>,
     },
 
+    /// Move files into the vault and leave symlinks to them, so edits go
+    /// straight into the vault
+    Adopt {
+        /// Home files to adopt
+        #[clap(value_name = "FILES", required = true)]
+        files: Vec<String>,
+    },
+
+    /// Show modified, new and deleted dotfiles (exits with 1 if anything differs)
+    Status,
+
     /// List all backed up dotfiles
-    List,
+    List {
+        /// List the dotfiles stored at a commit or date instead, e.g. 3f2a9c1 or 2024-05-01
+        #[clap(long, value_name = "COMMIT|DATE")]
+        at: Option<String>,
+    },
 
     /// Show history of a specific dotfile
     History {
         /// Path to the dotfile
         #[clap(value_name = "FILE")]
         file: String,
+
+        /// Also list versions from before the file was renamed or moved in the vault
+        #[clap(long)]
+        follow: bool,
+
+        /// Show at most this many versions
+        #[clap(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Leave out the newest N versions, e.g. to page through a long history
+        #[clap(long, value_name = "N", default_value_t = 0)]
+        skip: usize,
+    },
+
+    /// Show the backed up copy of a dotfile
+    Show {
+        /// Path to the dotfile
+        #[clap(value_name = "FILE")]
+        file: String,
+
+        /// Reveal values that look like secrets in sensitive files
+        #[clap(long)]
+        no_mask: bool,
+    },
+
+    /// Show differences between home files and their backed up copies
+    Diff {
+        /// Dotfiles to compare (defaults to all tracked dotfiles)
+        #[clap(value_name = "FILES")]
+        files: Vec<String>,
+
+        /// Compare every tracked dotfile, as without FILES
+        #[clap(long, conflicts_with = "files")]
+        all: bool,
+
+        /// Summarize how home files changed since a past vault state instead,
+        /// e.g. 3f2a9c1, a snapshot or 2024-05-01
+        #[clap(long, value_name = "COMMIT|DATE")]
+        against: Option<String>,
+
+        /// Reveal values that look like secrets in sensitive files
+        #[clap(long)]
+        no_mask: bool,
+    },
+
+    /// Search vault files, and with --history their past versions, for a regular expression
+    Grep {
+        /// Regular expression to search for
+        #[clap(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Also search every past version in the vault history
+        #[clap(long)]
+        history: bool,
+
+        /// Match case-insensitively
+        #[clap(short, long)]
+        ignore_case: bool,
+
+        /// Reveal values that look like secrets in sensitive files
+        #[clap(long)]
+        no_mask: bool,
+    },
+
+    /// Find and replace across dotfiles, in the vault and the home directory
+    /// alike, and commit the change, e.g. to migrate an email address
+    Sed {
+        /// Substitution as in sed, e.g. 's/old@example.com/new@example.com/g'
+        #[clap(value_name = "EXPRESSION")]
+        expression: SedExpression,
+
+        /// Comma-separated dotfiles to change (defaults to every tracked dotfile)
+        #[clap(long, value_name = "FILES", value_delimiter = ',')]
+        files: Vec<String>,
+    },
+
+    /// Watch tracked dotfiles and back them up automatically when they change
+    Watch {
+        /// Seconds without changes before a backup runs
+        #[clap(
+            long,
+            value_name = "SECS",
+            default_value_t = 2,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        debounce: u64,
+
+        /// Also serve Prometheus metrics on this address, e.g. 127.0.0.1:9479
+        #[clap(long, value_name = "ADDR")]
+        metrics_listen: Option<String>,
+    },
+
+    /// Run backups periodically with a systemd user timer (Linux) or launchd agent (macOS)
+    Schedule {
+        #[clap(subcommand)]
+        command: ScheduleCommands,
+    },
+
+    /// Print vault health metrics in Prometheus format
+    Metrics {
+        /// Write the metrics to this file for node_exporter's textfile collector
+        #[clap(long, value_name = "FILE", conflicts_with = "listen")]
+        textfile: Option<PathBuf>,
+
+        /// Serve the metrics over HTTP on this address, e.g. 127.0.0.1:9479
+        #[clap(long, value_name = "ADDR")]
+        listen: Option<String>,
+    },
+
+    /// Suggest recently edited well-known config files that aren't tracked
+    Suggest,
+
+    /// Push the vault to its git remote
+    Push {
+        /// Remote URL to use, saved as the vault's remote
+        #[clap(long)]
+        url: Option<String>,
+    },
+
+    /// Write the vault's history to a git bundle file, to carry it to a
+    /// machine without access to the remote
+    Bundle {
+        /// File to write, e.g. vault.bundle
+        file: PathBuf,
+    },
+
+    /// Pull changes from the vault's git remote
+    Pull {
+        /// Remote URL to use, saved as the vault's remote
+        #[clap(long)]
+        url: Option<String>,
+    },
+
+    /// Pull from the git remote, back up files edited at home, restore files
+    /// changed on the remote, then commit and push
+    Sync {
+        /// What to do with files changed both at home and on the remote:
+        /// ask (the default), prefer-home or prefer-vault. Without a
+        /// terminal to ask on, they're left alone
+        #[clap(long, value_name = "POLICY", default_value = "ask")]
+        on_conflict: SyncConflictPolicy,
+    },
+
+    /// Clone a deleted vault again from the git remote set in the config file
+    Recover,
+
+    /// Set up the vault on a new machine by cloning it from a git remote
+    Clone {
+        /// URL of the vault repository, which becomes the sync remote
+        url: String,
+
+        /// Restore every dotfile into the home directory afterwards
+        #[clap(long)]
+        restore: bool,
+    },
+
+    /// Import an existing dotfiles repository (URL or directory) laid out with home-relative paths into an empty vault
+    Import {
+        /// Git URL, local repository or plain directory to import
+        #[clap(value_name = "URL_OR_PATH")]
+        source: String,
+
+        /// Restore every imported dotfile into the home directory afterwards
+        #[clap(long)]
+        restore: bool,
+    },
+
+    /// Manage backup and restore hooks
+    Hooks {
+        #[clap(subcommand)]
+        command: HooksCommands,
+    },
+
+    /// Check the configuration and the vault manifest
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// List, create and switch between named vaults, e.g. for personal and work configs
+    Vault {
+        #[clap(subcommand)]
+        command: VaultCommands,
+    },
+
+    /// Manage groups of tracked dotfiles
+    Group {
+        #[clap(subcommand)]
+        command: GroupCommands,
+    },
+
+    /// Manage the key used to encrypt sensitive dotfiles
+    Key {
+        #[clap(subcommand)]
+        command: KeyCommands,
+    },
+
+    /// Generate a shell script that clones the vault and restores the dotfiles
+    /// on a machine without dotfilesvault
+    Bootstrap {
+        /// Write the script to this file, made executable, instead of printing it
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Rebuild the home directory from the vault in a throwaway location and verify it
+    SimulateBootstrap {
+        /// Keep the throwaway home directory for inspection
+        #[clap(long)]
+        keep: bool,
+    },
+
+    /// Manage shell aliases and functions kept as snippets in the vault
+    Snippet {
+        #[clap(subcommand)]
+        command: SnippetCommands,
+    },
+
+    /// Generate bash, zsh and fish files from the PATH entries, environment
+    /// variables and aliases declared in the vault's shell.toml
+    Generate {
+        /// Print the code for one shell (bash, zsh or fish) instead of writing the files
+        #[clap(long, value_name = "SHELL")]
+        print: Option<TargetShell>,
+    },
+
+    /// Capture toolchain state and print reinstall hints
+    Toolchains {
+        #[clap(subcommand)]
+        command: ToolchainsCommands,
+    },
+
+    /// Set how often tracked dotfiles are backed up by `backup --due`
+    Frequency {
+        /// One of on-change, hourly, daily or weekly
+        #[clap(value_name = "FREQUENCY")]
+        frequency: BackupFrequency,
+
+        /// Dotfiles to update
+        #[clap(value_name = "FILES", required = true)]
+        files: Vec<String>,
+    },
+
+    /// Store starter content for a dotfile, e.g. a default ~/.npmrc, that
+    /// restores create where the file doesn't exist but never overwrite
+    Starter {
+        /// Home path the starter content is restored to
+        #[clap(value_name = "FILE")]
+        file: String,
+
+        /// Read the starter content from this file instead of FILE; a
+        /// `.tmpl` file is rendered with the template variables on restore
+        #[clap(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+
+        /// Track FILE normally again, backing it up and restoring over it
+        #[clap(long, conflicts_with = "from")]
+        remove: bool,
+    },
+
+    /// Squash the vault history before a date into one baseline commit to save space
+    Compact {
+        /// Commits made up to this date or time are squashed, later ones are kept
+        #[clap(long, value_name = "DATE", value_parser = parse_date_arg)]
+        baseline: DateTime<Local>,
+    },
+
+    /// Squash vault history older than a retention policy and repack the vault
+    #[clap(group(clap::ArgGroup::new("retention").required(true).multiple(true)))]
+    Prune {
+        /// Keep this many of the latest commits
+        #[clap(long, value_name = "N", group = "retention")]
+        keep_last: Option<usize>,
+
+        /// Keep the commits made in this many past days
+        #[clap(long, value_name = "D", group = "retention")]
+        keep_days: Option<u64>,
+    },
+
+    /// Rename vault files with decomposed Unicode names, as macOS writes them,
+    /// to the composed form and merge files stored under both
+    Normalize,
+
+    /// Check the vault's integrity: uncommitted files, missing or corrupted
+    /// git objects, the manifests and files that can't be restored; exits
+    /// with 2 on problems, e.g. for cron
+    Verify,
+
+    /// Find files in the vault that no dotfile tracks, e.g. copied in by hand
+    /// or left behind, and adopt, delete or ignore them
+    Doctor {
+        /// Vault-relative stray files to fix (defaults to all of them)
+        #[clap(value_name = "FILE", requires = "fix")]
+        files: Vec<PathBuf>,
+
+        /// What to do with the stray files: adopt (track them), delete, or
+        /// ignore (keep them without reporting them again)
+        #[clap(long, value_name = "ACTION")]
+        fix: Option<StrayAction>,
+    },
+
+    /// Stop tracking dotfiles: delete them from the vault and commit the
+    /// removal, leaving the home files alone
+    #[clap(alias = "untrack")]
+    Remove {
+        /// Dotfiles or tracked directories to remove
+        #[clap(required = true)]
+        files: Vec<String>,
+
+        /// Also rewrite the vault history to expunge the files, e.g. a
+        /// leaked secret
+        #[clap(long)]
+        purge_history: bool,
+    },
+
+    /// List tracked dotfiles deleted from the home directory and remove them
+    /// from the vault, asking first unless --yes is given
+    Clean {
+        /// Remove them without asking
+        #[clap(long, short)]
+        yes: bool,
+    },
+
+    /// Manage the local cache of home file hashes (see `home_cache`)
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Re-apply a recorded restore, e.g. on another machine, or list the
+    /// restores recorded in this vault
+    Replay {
+        /// ID of a recorded restore, or a session file copied from another
+        /// machine (lists the recorded restores when left out)
+        #[clap(value_name = "SESSION")]
+        session: Option<String>,
+    },
+
+    /// Revert the most recent restore, putting back the home files it replaced
+    /// and deleting the ones it created; run again to revert the one before
+    Undo {
+        /// Revert files even if they changed since the restore
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Roll the whole vault back to a commit, or a group of dotfiles back to
+    /// their state at a point in time
+    Rollback {
+        /// Commit, tag, revision or date to roll the whole vault back to; the
+        /// rollback is recorded as a new commit
+        #[clap(value_name = "COMMIT", conflicts_with_all = ["group", "at"])]
+        commit: Option<String>,
+
+        /// Group of dotfiles to roll back
+        #[clap(long, required_unless_present = "commit", requires = "at")]
+        group: Option<String>,
+
+        /// Date or time to roll back to, e.g. 2024-05-01 or "2024-05-01 14:30"
+        #[clap(long, value_name = "DATE", value_parser = parse_date_arg, requires = "group")]
+        at: Option<DateTime<Local>>,
+
+        /// Also restore the dotfiles the vault rollback changed to the home
+        /// directory
+        #[clap(long, requires = "commit")]
+        restore: bool,
+
+        /// Apply the rollback instead of only previewing it
+        #[clap(long)]
+        yes: bool,
+
+        /// Reveal values that look like secrets in the preview
+        #[clap(long)]
+        no_mask: bool,
     },
 
     /// Restore a dotfile from backup
     Restore {
         /// Path to the dotfile to restore
-        #[clap(value_name = "FILE")]
-        file: String,
+        #[clap(value_name = "FILE", required_unless_present_any = ["all", "snapshot", "login"])]
+        file: Option<String>,
+
+        /// Restore every tracked dotfile, e.g. to set up a new machine
+        #[clap(long, conflicts_with_all = ["file", "version"])]
+        all: bool,
+
+        /// Restore every tracked dotfile as it was in a snapshot (see `snapshot create`)
+        #[clap(long, value_name = "NAME", conflicts_with_all = ["file", "all", "version"])]
+        snapshot: Option<String>,
 
-        /// Specific version to restore (defaults to latest)
+        /// Quickly restore only the tracked dotfiles that drifted from the
+        /// local vault, e.g. from a shell profile at every login
+        #[clap(long, conflicts_with_all = ["file", "all", "snapshot", "version"])]
+        login: bool,
+
+        /// Time a --login restore may take; files not reached wait for the next login
+        #[clap(long, value_name = "DURATION", default_value = "300ms", value_parser = parse_interval, requires = "login")]
+        budget: Duration,
+
+        /// Version to restore (defaults to latest): v2 as shown by history, a
+        /// short or full hash, a tag, a revision like HEAD~2, or a date
         #[clap(long)]
         version: Option<String>,
+
+        /// Limit read/write throughput, e.g. 512K or 10M per second
+        #[clap(long, value_name = "RATE", value_parser = parse_bwlimit)]
+        bwlimit: Option<u64>,
+
+        /// What to do if the home file has edits that were never backed up:
+        /// fail (the default), overwrite, or skip and save the vault version
+        /// for a manual merge
+        #[clap(long, value_name = "POLICY")]
+        on_conflict: Option<ConflictPolicy>,
+
+        /// Overwrite home files even if they have edits that were never backed up
+        #[clap(long, conflicts_with = "on_conflict")]
+        force: bool,
+    },
+
+    /// Name states of the vault to restore later, e.g. before reinstalling a machine
+    Snapshot {
+        #[clap(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// List the machine profiles that have their own variants of dotfiles
+    Profiles,
+
+    /// Browse tracked dotfiles, their status and history, and back up, restore
+    /// or diff them interactively
+    #[cfg(feature = "tui")]
+    Tui,
+
+    /// Print a shell completion script, e.g. `source <(dotfilesvault completions bash)`
+    Completions {
+        /// Shell to generate the script for
+        #[clap(value_name = "SHELL")]
+        shell: Shell,
+    },
+
+    /// Report which optional subsystems (encryption, remote, s3, tui, wasm) the binary was compiled with
+    Features,
+
+    /// Manage vault versions set aside by restores that would have lost local edits
+    Conflicts {
+        #[clap(subcommand)]
+        command: ConflictsCommands,
+    },
+
+    /// Export the vault as a tar.gz archive, e.g. to move it to an offline machine
+    Export {
+        /// Path of the archive to write
+        #[clap(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Also archive the git history, so the extracted vault keeps its versions
+        #[clap(long, conflicts_with = "changed_between")]
+        history: bool,
+
+        /// Only export files that changed between two dates
+        #[clap(
+            long,
+            num_args = 2,
+            value_names = ["FROM", "TO"],
+            value_parser = parse_date_arg
+        )]
+        changed_between: Vec<DateTime<Local>>,
     },
+
+    /// Run a `dotfilesvault-<name>` plugin from PATH
+    #[clap(external_subcommand)]
+    External(Vec<OsString>),
 }
 
-fn main() -> Result<()> {
-    // Parse command line arguments
-    let cli = Cli::parse();
+#[derive(Subcommand, Debug)]
+enum GroupCommands {
+    /// List groups and their dotfiles
+    List,
 
-    // Initialize logger
-    env_logger::Builder::new()
-        .filter_level(if cli.verbose {
-            LevelFilter::Debug
-        } else {
-            LevelFilter::Info
-        })
-        .init();
+    /// Assign tracked dotfiles to a group
+    Set {
+        /// Name of the group
+        #[clap(value_name = "GROUP")]
+        group: String,
 
-    info!("Starting Dotfilesvault");
+        /// Dotfiles to add to the group
+        #[clap(value_name = "FILES", required = true)]
+        files: Vec<String>,
+    },
+}
 
-    // Create default configuration
-    let config = Config::default();
+#[derive(Subcommand, Debug)]
+enum ConflictsCommands {
+    /// List quarantined vault versions waiting for a manual merge
+    List,
 
-    // Handle commands
-    match cli.command {
-        Commands::Backup { files } => {
-            debug!("Running backup command");
+    /// Mark a file's conflicts as resolved and delete the quarantined versions
+    Resolve {
+        /// The conflicting dotfile
+        #[clap(value_name = "FILE")]
+        file: String,
 
-            if files.is_empty() {
-                info!("Backing up all dotfiles");
-                if let Err(err) = backup_all_dotfiles(&config) {
-                    error!("Failed to backup dotfiles: {}", err);
-                    process::exit(1);
-                }
+        /// Replace the home file with the quarantined vault version instead of keeping it
+        #[clap(long)]
+        use_vault: bool,
+    },
+}
 
-                // Commit changes to Git repository
-                if let Err(err) = commit_changes(&config, "Backup all dotfiles") {
-                    error!("Failed to commit changes: {}", err);
-                    process::exit(1);
-                }
-            } else {
-                info!("Backing up specific dotfiles: {:?}", files);
-                if let Err(err) = backup_specific_dotfiles(&config, &files) {
-                    error!("Failed to backup specific dotfiles: {}", err);
-                    process::exit(1);
-                }
+#[derive(Subcommand, Debug)]
+enum ScheduleCommands {
+    /// Write and enable the units, replacing an installed schedule
+    Install {
+        /// Time between backups, e.g. 30m or 1h
+        #[clap(long, value_name = "DURATION", default_value = "1h", value_parser = parse_interval)]
+        interval: Duration,
+    },
 
-                // Commit changes to Git repository
-                if let Err(err) =
-                    commit_changes(&config, &format!("Backup specific dotfiles: {:?}", files))
-                {
-                    error!("Failed to commit changes: {}", err);
-                    process::exit(1);
-                }
-            }
+    /// Disable the schedule and delete its units
+    Remove,
 
-            info!("Backup completed successfully");
-        }
+    /// Show whether backups are scheduled and how often
+    Status,
+}
 
-        Commands::List => {
-            debug!("Running list command");
+#[derive(Subcommand, Debug)]
+enum SnapshotCommands {
+    /// Tag the vault's current state as a named snapshot
+    Create {
+        /// Name of the snapshot, e.g. pre-reinstall
+        #[clap(value_name = "NAME")]
+        name: String,
 
-            match list_backed_up_dotfiles(&config) {
-                Ok(files) => {
-                    if files.is_empty() {
-                        println!("No dotfiles have been backed up yet.");
-                    } else {
-                        println!("Backed up dotfiles:");
-                        for file in files {
-                            println!("  {}", file.display());
-                        }
-                    }
-                }
-                Err(err) => {
-                    error!("Failed to list backed up dotfiles: {}", err);
-                    process::exit(1);
-                }
-            }
-        }
+        /// Description of the snapshot, defaults to its name
+        #[clap(short, long)]
+        message: Option<String>,
+    },
 
-        Commands::History { file } => {
-            debug!("Running history command for file: {}", file);
+    /// List snapshots, oldest first
+    List,
+}
 
-            match get_dotfile_history(&config, &file) {
-                Ok(versions) => {
-                    if versions.is_empty() {
-                        println!("No history found for dotfile: {}", file);
-                    } else {
-                        println!("History for dotfile: {}", file);
-                        for (i, version) in versions.iter().enumerate() {
-                            println!(
-                                "  Version {}: {} - {}",
-                                i + 1,
-                                version.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                                version.message
-                            );
-                        }
-                    }
-                }
-                Err(err) => {
-                    error!("Failed to get history for dotfile: {}", err);
-                    process::exit(1);
-                }
-            }
-        }
+#[derive(Subcommand, Debug)]
+enum KeyCommands {
+    /// Generate a new encryption key and print its public key
+    Init,
 
-        Commands::Restore { file, version } => {
-            debug!("Running restore command for file: {}", file);
+    /// Print the encryption key (a secret), to copy it to another machine
+    Export,
+}
 
-            // TODO: Implement version-specific restore
-            if version.is_some() {
-                error!("Version-specific restore is not yet implemented");
-                process::exit(1);
-            }
+#[derive(Subcommand, Debug)]
+enum SnippetCommands {
+    /// Add or replace a snippet, e.g. `snippet add alias gs='git status'`
+    Add {
+        /// Either alias or function
+        #[clap(value_name = "KIND")]
+        kind: SnippetKind,
 
-            if let Err(err) = restore_specific_dotfile(&config, &file) {
-                error!("Failed to restore dotfile: {}", err);
-                process::exit(1);
-            }
+        /// NAME=COMMAND for an alias, NAME=BODY for a function
+        #[clap(value_name = "DEFINITION")]
+        definition: String,
+    },
 
-            info!("Restored dotfile: {}", file);
+    /// List the snippets in the vault
+    List,
+
+    /// Remove a snippet
+    Remove {
+        /// Name of the alias or function
+        #[clap(value_name = "NAME")]
+        name: String,
+    },
+
+    /// Regenerate ~/.dotfilesvault/aliases.sh from the vault's snippets
+    Apply,
+}
+
+#[derive(Subcommand, Debug)]
+enum ToolchainsCommands {
+    /// Snapshot installed toolchains (rustup, pyenv, nvm, Homebrew taps, MacPorts) into the vault
+    Capture,
+
+    /// Print the commands that reinstall the captured toolchains
+    Hints,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommands {
+    /// Forget every cached hash, so home files are read again
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Report unknown settings, overlapping patterns, dead entries and
+    /// conditions that can never hold (exits with 2 if there are errors)
+    Lint,
+}
+
+#[derive(Subcommand, Debug)]
+enum VaultCommands {
+    /// List the vaults in the config file, marking the default and current ones
+    List,
+
+    /// Add a named vault to the config file and create it
+    Create {
+        /// Name of the vault, e.g. work
+        name: String,
+
+        /// Directory of the vault (defaults to ~/dotfilesvault-NAME)
+        #[clap(long, value_name = "DIR")]
+        dir: Option<String>,
+    },
+
+    /// Make a vault the one commands use without --vault; `default` switches
+    /// back to the top-level settings
+    Switch {
+        /// Name of the vault
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HooksCommands {
+    /// List the hooks installed in the vault
+    List,
+
+    /// Run a hook with simulated input, without a real backup or restore
+    Test {
+        /// Name of the hook, e.g. post-restore
+        #[clap(value_name = "HOOK")]
+        hook: String,
+
+        /// Files to report as affected (defaults to all backed up dotfiles)
+        #[clap(long = "file", value_name = "FILE")]
+        files: Vec<String>,
+    },
+}
+
+/// Parse a date or time argument
+fn parse_date_arg(value: &str) -> Result<DateTime<Local>, String> {
+    parse_datetime(value).ok_or_else(|| format!("invalid date: {}", value))
+}
+
+/// Point the vault remote at `--url`, or at the configured `git.remote`
+fn configure_remote(config: &Config, url: Option<String>) -> Result<(), DotfilesError> {
+    match url.or_else(|| config.remote_url.clone()) {
+        Some(url) => set_remote(config, &url),
+        None => Ok(()),
+    }
+}
+
+impl Commands {
+    /// Check if the command writes to the home directory, the vault or the
+    /// system, so `--read-only` refuses it
+    fn writes(&self, dry_run: bool) -> bool {
+        match self {
+            // These only report what they would do in a dry run
+            Commands::Backup { .. }
+            | Commands::Restore { .. }
+            | Commands::Compact { .. }
+            | Commands::Prune { .. }
+            | Commands::Normalize
+            | Commands::Sed { .. }
+            | Commands::Adopt { .. }
+            | Commands::Remove { .. } => !dry_run,
+            Commands::Doctor { fix, .. } => fix.is_some() && !dry_run,
+            Commands::Schedule { command } => {
+                !matches!(command, ScheduleCommands::Status) && !dry_run
+            }
+            Commands::Rollback { yes, .. } => *yes,
+            Commands::Clean { yes } => *yes && !dry_run,
+            Commands::Sync { .. } => !dry_run,
+            Commands::Replay { session } => session.is_some() && !dry_run,
+            Commands::Undo { .. } => !dry_run,
+            Commands::Watch { .. }
+            | Commands::Push { .. }
+            | Commands::Bundle { .. }
+            | Commands::Pull { .. }
+            | Commands::Recover
+            | Commands::Clone { .. }
+            | Commands::Import { .. }
+            | Commands::Frequency { .. }
+            | Commands::Starter { .. }
+            | Commands::Add { .. }
+            | Commands::Cache { .. }
+            | Commands::External(_) => true,
+            Commands::Group { command } => !matches!(command, GroupCommands::List),
+            Commands::Vault { command } => !matches!(command, VaultCommands::List),
+            Commands::Key { command } => matches!(command, KeyCommands::Init),
+            Commands::Snippet { command } => !matches!(command, SnippetCommands::List),
+            Commands::Generate { print } => print.is_none() && !dry_run,
+            Commands::Toolchains { command } => matches!(command, ToolchainsCommands::Capture),
+            Commands::Snapshot { command } => matches!(command, SnapshotCommands::Create { .. }),
+            Commands::Conflicts { command } => {
+                matches!(command, ConflictsCommands::Resolve { .. })
+            }
+            Commands::Hooks { command } => matches!(command, HooksCommands::Test { .. }),
+            _ => false,
+        }
+    }
+
+    /// Check if the command honours `--dry-run`, only reporting what it would do
+    ///
+    /// Commands that would still write in a dry run don't, except `watch`
+    /// whose backups are dry runs, and `hooks test` whose hooks are simulated.
+    fn supports_dry_run(&self) -> bool {
+        match self {
+            Commands::Watch { .. } | Commands::Hooks { .. } => true,
+            _ => !self.writes(true),
+        }
+    }
+
+    /// Check if the command works with a vault stored outside git, see
+    /// `StorageBackend`
+    fn supports_object_storage(&self) -> bool {
+        match self {
+            Commands::Backup { .. } => true,
+            Commands::Restore { snapshot, .. } => snapshot.is_none(),
+            Commands::History { follow, .. } => !follow,
+            Commands::Completions { .. }
+            | Commands::Features
+            | Commands::Config { .. }
+            | Commands::Vault { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Check if the command works in the bare layout, where the vault is a
+    /// bare repository with the home directory as its worktree
+    fn supports_bare(&self) -> bool {
+        match self {
+            Commands::Backup { due, .. } => !due,
+            Commands::Restore {
+                file,
+                snapshot,
+                login,
+                version,
+                ..
+            } => file.is_none() && snapshot.is_none() && !login && version.is_none(),
+            Commands::Status
+            | Commands::Completions { .. }
+            | Commands::Features
+            | Commands::Config { .. }
+            | Commands::Vault { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Describe the destructive action the command takes, with the home files
+    /// it may replace, for paranoid mode
+    fn destructive_action(&self, config: &Config) -> Option<(String, Vec<PathBuf>)> {
+        let tracked = |group: Option<&str>| -> Vec<PathBuf> {
+            Manifest::load(config)
+                .map(|manifest| {
+                    manifest
+                        .entries
+                        .values()
+                        .filter(|entry| group.is_none() || entry.group.as_deref() == group)
+                        .map(|entry| entry.dotfile(config).original_path)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        match self {
+            Commands::Restore {
+                file: Some(file), ..
+            } => Some((
+                format!("restore {}", file),
+                vec![normalize_path(file, config)],
+            )),
+            Commands::Restore { .. } => Some(("restore".to_string(), tracked(None))),
+            Commands::Rollback {
+                commit: Some(commit),
+                restore,
+                yes: true,
+                ..
+            } => Some((
+                format!("rollback to {}", commit),
+                if *restore { tracked(None) } else { Vec::new() },
+            )),
+            Commands::Rollback {
+                group: Some(group),
+                yes: true,
+                ..
+            } => Some((format!("rollback of {}", group), tracked(Some(group)))),
+            Commands::Compact { .. } => Some(("compact".to_string(), Vec::new())),
+            Commands::Prune { .. } => Some(("prune".to_string(), Vec::new())),
+            Commands::Normalize => Some(("normalize".to_string(), Vec::new())),
+            Commands::Doctor {
+                fix: Some(StrayAction::Delete),
+                ..
+            } => Some(("doctor --fix delete".to_string(), Vec::new())),
+            Commands::Remove { files, .. } => {
+                Some((format!("remove {}", files.join(" ")), Vec::new()))
+            }
+            Commands::Clean { yes: true } => Some(("clean".to_string(), Vec::new())),
+            Commands::Sed { expression, files } => Some((
+                format!("sed {}", expression),
+                if files.is_empty() {
+                    tracked(None)
+                } else {
+                    files
+                        .iter()
+                        .map(|file| normalize_path(file, config))
+                        .collect()
+                },
+            )),
+            Commands::Pull { .. } => Some(("pull".to_string(), Vec::new())),
+            Commands::Sync { .. } => Some(("sync".to_string(), Vec::new())),
+            Commands::Replay {
+                session: Some(session),
+            } => Some((
+                format!("replay {}", session),
+                load_session(config, session)
+                    .map(|session| {
+                        session
+                            .restored
+                            .iter()
+                            .map(|path| config.home_dir.join(path))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            )),
+            Commands::Conflicts {
+                command: ConflictsCommands::Resolve { file, use_vault },
+            } => Some((
+                format!("conflicts resolve {}", file),
+                if *use_vault {
+                    vec![normalize_path(file, config)]
+                } else {
+                    Vec::new()
+                },
+            )),
+            Commands::Snippet {
+                command: SnippetCommands::Remove { name },
+            } => Some((format!("snippet remove {}", name), Vec::new())),
+            _ => None,
+        }
+    }
+}
+
+/// Ask for confirmation and take a safety snapshot before a destructive
+/// command in paranoid mode, exiting if the user declines
+fn guard_destructive(config: &Config, command: &Commands) {
+    if !config.paranoid || config.dry_run {
+        return;
+    }
+    let Some((action, files)) = command.destructive_action(config) else {
+        return;
+    };
+
+    eprint!("{} ", t!("paranoid-confirm", action = action.as_str()));
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() || !matches!(answer.trim(), "y" | "yes") {
+        eprintln!("{}", t!("paranoid-aborted"));
+        ExitCode::Error.exit();
+    }
+
+    match take_safety_snapshot(config, &action, &files) {
+        Ok(safety) => {
+            if let Some(name) = &safety.snapshot {
+                eprintln!("{}", t!("safety-snapshot-vault", name = name.as_str()));
+            }
+            if let Some(dir) = &safety.home_copy {
+                eprintln!(
+                    "{}",
+                    t!(
+                        "safety-snapshot-home",
+                        count = safety.files,
+                        dir = dir.display().to_string()
+                    )
+                );
+            }
+        }
+        Err(err) => {
+            fail(
+                t!("failed-safety-snapshot", error = err.to_string()),
+                err.error_code(),
+            );
+        }
+    }
+}
+
+/// Ask how to go on with a restore that would lose local edits to a file
+///
+/// The diff between the home file and the vault can be shown first. Returns
+/// the conflict policy to retry with, or `None` to give up, which is also
+/// the answer when stdin isn't a terminal.
+fn ask_restore_conflict(config: &Config, file: &str) -> Option<ConflictPolicy> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+
+    loop {
+        eprint!("{} ", t!("restore-conflict-prompt", file = file));
+        let _ = std::io::stderr().flush();
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err() {
+            return None;
+        }
+
+        match answer.trim() {
+            "d" | "diff" => match diff_files(config, &[file.to_string()], true) {
+                Ok(diffs) => {
+                    for (_, diff) in &diffs {
+                        eprint!("{}", diff);
+                    }
+                }
+                Err(err) => {
+                    error!("{}", t!("failed-diff-dotfiles", error = err.to_string()));
+                }
+            },
+            "k" | "keep" => return Some(ConflictPolicy::Skip),
+            "o" | "overwrite" => return Some(ConflictPolicy::Overwrite),
+            _ => return None,
+        }
+    }
+}
+
+/// Ask which side wins for a file `sync` found changed at home and on the remote
+///
+/// The diff between the home file and the pulled vault copy can be shown
+/// first. Returns `None` to leave both alone, which is also the answer when
+/// stdin isn't a terminal.
+fn ask_sync_conflict(config: &Config, file: &Path) -> Option<SyncConflictPolicy> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+
+    let file = file.display().to_string();
+    loop {
+        eprint!("{} ", t!("sync-conflict-prompt", file = file.as_str()));
+        let _ = std::io::stderr().flush();
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err() {
+            return None;
+        }
+
+        match answer.trim() {
+            "d" | "diff" => match diff_files(config, std::slice::from_ref(&file), true) {
+                Ok(diffs) => {
+                    for (_, diff) in &diffs {
+                        eprint!("{}", diff);
+                    }
+                }
+                Err(err) => {
+                    error!("{}", t!("failed-diff-dotfiles", error = err.to_string()));
+                }
+            },
+            "h" | "home" => return Some(SyncConflictPolicy::PreferHome),
+            "v" | "vault" => return Some(SyncConflictPolicy::PreferVault),
+            _ => return None,
+        }
+    }
+}
+
+/// Whether failures are reported as JSON, set from `--format json`
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Machine-readable code of an error a command failed with
+trait ErrorCode {
+    fn error_code(&self) -> &'static str;
+}
+
+impl ErrorCode for DotfilesError {
+    fn error_code(&self) -> &'static str {
+        self.code()
+    }
+}
+
+impl ErrorCode for anyhow::Error {
+    fn error_code(&self) -> &'static str {
+        self.downcast_ref::<DotfilesError>()
+            .map_or("E_FAILED", DotfilesError::code)
+    }
+}
+
+impl ErrorCode for std::io::Error {
+    fn error_code(&self) -> &'static str {
+        "E_IO"
+    }
+}
+
+impl ErrorCode for String {
+    fn error_code(&self) -> &'static str {
+        "E_FAILED"
+    }
+}
+
+/// Report a failed command
+///
+/// With `--format json` an object with the error's `code` and `message` is
+/// also printed on stdout, so scripts don't have to parse the log.
+fn report_error(message: &str, code: &str) {
+    error!("{}", message);
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        print_json(&json!({ "error": { "code": code, "message": message } }));
+    }
+}
+
+/// Report a failed command and exit with the error exit code
+fn fail(message: String, code: &str) -> ! {
+    report_error(&message, code);
+    ExitCode::Error.exit()
+}
+
+/// Print a command result as JSON
+fn print_json(value: &impl Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(err) => {
+            error!("{}", t!("failed-format-json", error = err.to_string()));
+            ExitCode::Error.exit();
+        }
+    }
+}
+
+/// Print what a dry run would do
+fn print_plan(plan: &Plan, porcelain: bool) {
+    for copy in &plan.copies {
+        if porcelain {
+            println!(
+                "{}\t{}\t{}",
+                copy.action.name(),
+                copy.source.display(),
+                copy.destination.display()
+            );
+        } else {
+            println!(
+                "{}",
+                t!(
+                    "dry-run-copy",
+                    action = copy.action.name(),
+                    destination = copy.destination.display().to_string(),
+                    source = copy.source.display().to_string()
+                )
+            );
+        }
+    }
+
+    if let Some(message) = &plan.commit {
+        if porcelain {
+            println!("commit\t{}", message);
+        } else {
+            println!("{}", t!("dry-run-commit", message = message.as_str()));
+        }
+    }
+
+    if !porcelain && plan.copies.is_empty() {
+        println!("{}", t!("dry-run-nothing"));
+    }
+}
+
+/// Set up logging to stderr
+///
+/// Plain logs are `level: message` lines without timestamps or color.
+fn init_logger(verbose: bool, plain: bool, progress: bool) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    });
+
+    // Log lines are printed above the progress bars; stderr is a terminal
+    // whenever the bars are drawn
+    if progress {
+        builder
+            .target(Target::Pipe(Box::new(LogWriter)))
+            .write_style(WriteStyle::Always);
+    }
+
+    if plain {
+        builder
+            .write_style(WriteStyle::Never)
+            .format(|buf, record| {
+                writeln!(
+                    buf,
+                    "{}: {}",
+                    record.level().as_str().to_lowercase(),
+                    record.args()
+                )
+            });
+    }
+
+    builder.init();
+}
+
+/// Cancel the token on Ctrl-C so the command can release the vault and undo
+/// partial work; a second Ctrl-C exits right away
+fn handle_interrupts(cancellation: &CancellationToken) {
+    let cancellation = cancellation.clone();
+    let result = ctrlc::set_handler(move || {
+        if cancellation.is_cancelled() {
+            // Exiting skips the drops that would remove the vault lock
+            release_held_locks();
+            ExitCode::Error.exit();
+        }
+        warn!("{}", t!("interrupted"));
+        cancellation.cancel();
+    });
+
+    if let Err(err) = result {
+        warn!("Failed to handle Ctrl-C: {}", err);
+    }
+}
+
+/// Print an entry of a list, indented under its header unless output is plain
+fn print_item(config: &Config, item: impl std::fmt::Display) {
+    if config.plain {
+        println!("{}", item);
+    } else {
+        println!("  {}", item);
+    }
+}
+
+/// Make a directory given on the command line absolute, or exit
+fn absolute_or_exit(path: &Path) -> PathBuf {
+    match std::path::absolute(expand_tilde(path)) {
+        Ok(path) => path,
+        Err(err) => {
+            fail(
+                t!(
+                    "invalid-path",
+                    path = path.display().to_string(),
+                    error = err.to_string()
+                ),
+                err.error_code(),
+            );
+        }
+    }
+}
+
+/// Print the files a backup left out because they look like they contain secrets
+fn print_refused_secrets(findings: &[SecretFinding], porcelain: bool) {
+    for finding in findings {
+        if porcelain {
+            println!(
+                "secret\t{}\t{}\t{}",
+                finding.path.display(),
+                finding.line,
+                finding.pattern
+            );
+        } else {
+            println!(
+                "{}",
+                t!(
+                    "secret-refused",
+                    path = finding.path.display().to_string(),
+                    pattern = finding.pattern,
+                    line = finding.line
+                )
+            );
+        }
+    }
+
+    if !porcelain && !findings.is_empty() {
+        println!("{}", t!("secret-hint"));
+    }
+}
+
+/// Print files a backup left out for their size or because they are binary
+fn print_left_out(notices: &[Notice], porcelain: bool) {
+    let left_out: Vec<&Notice> = notices
+        .iter()
+        .filter(|notice| matches!(notice.code, SKIPPED_LARGE | SKIPPED_BINARY))
+        .collect();
+
+    for notice in &left_out {
+        if porcelain {
+            println!("left-out\t{}\t{}", notice.code, notice.path.display());
+        } else {
+            println!(
+                "{}",
+                t!(
+                    "backup-left-out",
+                    path = notice.path.display().to_string(),
+                    reason = notice.message.as_str()
+                )
+            );
+        }
+    }
+
+    if !porcelain && left_out.iter().any(|notice| notice.code == SKIPPED_LARGE) {
+        println!("{}", t!("backup-left-out-hint"));
+    }
+}
+
+/// Print paths that differ only in case
+fn print_case_collisions(collisions: &[CaseCollision], porcelain: bool) {
+    for collision in collisions {
+        let paths: Vec<String> = collision
+            .paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        if porcelain {
+            println!("collision\t{}", paths.join("\t"));
+        } else {
+            println!("{}", t!("case-collision", paths = paths.join(", ")));
+        }
+    }
+
+    if !porcelain && !collisions.is_empty() {
+        println!("{}", t!("case-collision-hint"));
+    }
+}
+
+/// Print when the vault and home copies of a drifted file were last in sync
+///
+/// Failing to read the history only costs the hint, so it is just logged.
+fn print_divergence(config: &Config, file: &str) {
+    let divergence = match divergence(config, file) {
+        Ok(Some(divergence)) => divergence,
+        Ok(None) => return,
+        Err(err) => {
+            debug!("No divergence hint for {}: {}", file, err);
+            return;
+        }
+    };
+
+    match &divergence.common {
+        Some(version) => println!(
+            "{}",
+            t!(
+                "diff-hint-common",
+                file = file,
+                commit = &version.commit_id[..7],
+                time = format_timestamp(&version.timestamp, config)
+            )
+        ),
+        None => println!("{}", t!("diff-hint-no-common", file = file)),
+    }
+
+    match divergence.vault_changes.first() {
+        Some(latest) => print_item(
+            config,
+            t!(
+                "diff-hint-vault-changed",
+                count = divergence.vault_changes.len(),
+                time = format_timestamp(&latest.timestamp, config)
+            ),
+        ),
+        None => print_item(config, t!("diff-hint-vault-unchanged")),
+    }
+
+    match &divergence.home_changed {
+        Some(modified) => print_item(
+            config,
+            t!(
+                "diff-hint-home-changed",
+                time = format_timestamp(modified, config)
+            ),
+        ),
+        None => print_item(config, t!("diff-hint-home-unchanged")),
+    }
+}
+
+/// Replace the glob patterns among backup arguments with the home files
+/// they match, exiting if a pattern is invalid or matches nothing
+fn expand_backup_globs(config: &Config, files: &[String]) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for file in files {
+        if !is_glob(file) {
+            expanded.push(file.clone());
+            continue;
+        }
+        match expand_home_glob(config, file) {
+            Ok(paths) => expanded.extend(paths.iter().map(|path| path.display().to_string())),
+            Err(err) => {
+                fail(
+                    t!("failed-backup-dotfiles", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Print the outcome of `restore --all` or `replay` in the requested format,
+/// exiting with the matching exit code
+fn report_restore_summary(
+    summary: &RestoreSummary,
+    config: &Config,
+    format: OutputFormat,
+    porcelain: bool,
+) {
+    if format == OutputFormat::Json {
+        print_json(summary);
+    } else if config.dry_run {
+        print_plan(&summary.plan, porcelain);
+    } else {
+        print_restore_summary(summary, porcelain);
+        if !summary.deferred.is_empty() && !porcelain {
+            println!("{}", t!("restore-deferred", count = summary.deferred.len()));
+        }
+    }
+
+    if config.dry_run {
+        return;
+    }
+
+    if !summary.failed.is_empty() {
+        ExitCode::Error.exit();
+    }
+    if !summary.conflicted.is_empty()
+        || summary
+            .plan
+            .copies
+            .iter()
+            .any(|copy| copy.action == PlannedAction::Quarantine)
+    {
+        ExitCode::Conflicts.exit();
+    }
+}
+
+/// Warn if a restore couldn't be recorded as a session; the restore itself
+/// went through
+fn report_session(recorded: Result<Option<Session>, DotfilesError>) {
+    if let Err(err) = recorded {
+        warn!("{}", t!("failed-record-session", error = err.to_string()));
+    }
+}
+
+/// Print the outcome of `restore --all`
+fn print_restore_summary(summary: &RestoreSummary, porcelain: bool) {
+    print_case_collisions(&summary.plan.collisions, porcelain);
+    if porcelain {
+        for path in &summary.restored {
+            println!("restored\t{}", path.display());
+        }
+        for path in &summary.skipped {
+            println!("skipped\t{}", path.display());
+        }
+        for path in &summary.conflicted {
+            println!("conflict\t{}", path.display());
+        }
+        for (path, reason) in &summary.failed {
+            println!("failed\t{}\t{}", path.display(), reason);
+        }
+        for path in &summary.deferred {
+            println!("deferred\t{}", path.display());
+        }
+        return;
+    }
+
+    for copy in &summary.plan.copies {
+        if copy.action == PlannedAction::Quarantine {
+            println!(
+                "{}",
+                t!(
+                    "restore-kept-local-edits",
+                    quarantined = copy.destination.display().to_string()
+                )
+            );
+        }
+    }
+    for path in &summary.conflicted {
+        println!(
+            "{}",
+            t!("restore-conflict-kept", path = path.display().to_string())
+        );
+    }
+    if !summary.conflicted.is_empty() {
+        println!("{}", t!("restore-conflicts-hint"));
+    }
+    for (path, reason) in &summary.failed {
+        println!(
+            "{}",
+            t!(
+                "restore-file-failed",
+                path = path.display().to_string(),
+                error = reason.as_str()
+            )
+        );
+    }
+
+    println!(
+        "{}",
+        t!(
+            "restore-summary",
+            restored = summary.restored.len(),
+            skipped = summary.skipped.len(),
+            failed = summary.failed.len()
+        )
+    );
+}
+
+/// Validate a `--profile` value
+fn parse_profile(value: &str) -> Result<String, String> {
+    validate_profile_name(value)?;
+    Ok(value.to_string())
+}
+
+/// Validate a `--time-format` value
+fn parse_time_format(value: &str) -> Result<String, String> {
+    validate_time_format(value)?;
+    Ok(value.to_string())
+}
+
+/// Parse a `--timeout` value
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    parse_duration(value).ok_or_else(|| format!("invalid duration: {}", value))
+}
+
+/// Parse a `schedule install --interval` value
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    parse_duration(value)
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| format!("invalid interval: {}", value))
+}
+
+/// Parse a `--bwlimit` value into bytes per second
+fn parse_bwlimit(value: &str) -> Result<u64, String> {
+    parse_size(value).ok_or_else(|| format!("invalid rate: {}", value))
+}
+
+fn main() -> Result<()> {
+    // Parse command line arguments
+    let cli = Cli::parse();
+    JSON_ERRORS.store(cli.format == OutputFormat::Json, Ordering::Relaxed);
+
+    if cli.version {
+        let info = build_info();
+        if cli.json || cli.format == OutputFormat::Json {
+            print_json(&info);
+        } else {
+            println!(
+                "dotfilesvault {} ({} {})",
+                info.version, info.git_sha, info.build_date
+            );
+        }
+        return Ok(());
+    }
+    let Some(command) = cli.command else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required",
+            )
+            .exit();
+    };
+
+    // Load configuration from the config file, if any
+    let loaded = Config::from_env(cli.config.as_deref(), cli.vault.as_deref());
+
+    // Initialize logger, plain if requested on the command line or in the config
+    let plain = cli.plain || loaded.as_ref().is_ok_and(|config| config.plain);
+    let progress = !cli.no_progress
+        && !plain
+        && !cli.porcelain
+        && cli.format == OutputFormat::Text
+        && std::io::stderr().is_terminal();
+    init_logger(cli.verbose, plain, progress);
+
+    info!("Starting Dotfilesvault");
+
+    let mut config = match loaded {
+        Ok(config) => config,
+        Err(err) => {
+            fail(
+                t!("failed-load-config", error = err.to_string()),
+                err.error_code(),
+            );
+        }
+    };
+
+    i18n::init(config.language.as_deref());
+
+    config.plain = plain;
+    config.progress = progress;
+    if cli.utc {
+        config.utc = true;
+    }
+    if let Some(time_format) = cli.time_format {
+        config.time_format = time_format;
+    }
+    config.dry_run = cli.dry_run;
+    if cli.profile.is_some() {
+        config.profile = cli.profile;
+    }
+    if let Some(home) = cli.home {
+        config.set_home_dir(absolute_or_exit(&home));
+    }
+    if let Some(vault) = cli.vault
+        && config.vault_name.as_deref() != Some(vault.as_str())
+        && vault != DEFAULT_VAULT
+    {
+        config.vault_dir = absolute_or_exit(Path::new(&vault));
+    }
+    if cli.timeout.is_some() {
+        config.timeout = cli.timeout;
+    }
+    config.read_only = cli.read_only;
+
+    if config.dry_run && !command.supports_dry_run() {
+        fail(t!("dry-run-unsupported"), "E_DRY_RUN_UNSUPPORTED");
+    }
+
+    if config.read_only && command.writes(config.dry_run) {
+        fail(
+            DotfilesError::ReadOnly.to_string(),
+            DotfilesError::ReadOnly.code(),
+        );
+    }
+
+    if config.layout == VaultLayout::Bare && !command.supports_bare() {
+        fail(
+            t!(
+                "bare-unsupported",
+                vault = config.vault_dir.display().to_string(),
+                home = config.home_dir.display().to_string()
+            ),
+            "E_BARE_LAYOUT",
+        );
+    }
+
+    if !config.backend.is_git() && !command.supports_object_storage() {
+        fail(
+            t!("backend-unsupported", backend = config.backend.to_string()),
+            "E_BACKEND_UNSUPPORTED",
+        );
+    }
+
+    // Watching runs until stopped, so only finite commands get the timeout,
+    // and serving metrics has nothing to clean up when interrupted
+    match command {
+        Commands::Watch { .. } => handle_interrupts(&config.cancellation),
+        Commands::Metrics { .. } => {}
+        _ => {
+            if let Some(timeout) = config.timeout {
+                config.cancellation = CancellationToken::with_timeout(timeout);
+            }
+            handle_interrupts(&config.cancellation);
+        }
+    }
+
+    guard_destructive(&config, &command);
+
+    // Handle commands
+    match command {
+        Commands::Backup {
+            files,
+            bwlimit,
+            scan_threads,
+            copy_threads,
+            scan_first,
+            due,
+            allow_secrets,
+            include_large,
+            message,
+        } => {
+            debug!("Running backup command");
+            if allow_secrets {
+                config.on_secret = SecretPolicy::Allow;
+            }
+            if include_large {
+                config.max_file_size = None;
+            }
+            if bwlimit.is_some() {
+                config.bwlimit = bwlimit;
+            }
+            if let Some(scan_threads) = scan_threads {
+                config.scan_threads = scan_threads;
+            }
+            if let Some(copy_threads) = copy_threads {
+                config.copy_threads = copy_threads;
+            }
+            if !scan_first.is_empty() {
+                config.scan_priority = scan_first;
+            }
+            let patterns = files;
+            let files = expand_backup_globs(&config, &patterns);
+
+            if config.layout == VaultLayout::Bare {
+                let message = message.unwrap_or_else(|| "Backup all dotfiles".to_string());
+                info!("Committing tracked files from the home directory");
+                let result = bare_backup(&config, &files, &message);
+                if !config.dry_run
+                    && let Err(err) = record_backup(&config, result.is_ok())
+                {
+                    warn!("Failed to record backup metrics: {}", err);
+                }
+                let summary = match result {
+                    Ok(summary) => summary,
+                    Err(err) => {
+                        fail(
+                            t!("failed-backup-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if cli.format == OutputFormat::Json {
+                    print_json(&summary);
+                } else if cli.porcelain {
+                    for path in &summary.files {
+                        println!("{}", path.display());
+                    }
+                    print_refused_secrets(&summary.secrets, true);
+                    print_left_out(&summary.notices, true);
+                } else {
+                    for path in &summary.files {
+                        print_item(&config, path.display().to_string());
+                    }
+                    print_refused_secrets(&summary.secrets, false);
+                    print_left_out(&summary.notices, false);
+                    if config.dry_run {
+                        println!("{}", t!("bare-backup-dry-run", count = summary.files.len()));
+                    } else if summary.commit.is_none() {
+                        println!("{}", t!("backup-unchanged"));
+                    }
+                }
+                if !summary.secrets.is_empty() {
+                    ExitCode::Error.exit();
+                }
+                return Ok(());
+            }
+
+            // Back up, then record the changes as a new version of the vault
+            let result = if due {
+                info!("Backing up dotfiles that are due");
+                let message = message.unwrap_or_else(|| "Backup due dotfiles".to_string());
+                backup_due_dotfiles(&config, Local::now())
+                    .and_then(|plan| plan.commit(&config, &message))
+            } else if files.is_empty() {
+                info!("Backing up all dotfiles");
+                let message = message.unwrap_or_else(|| "Backup all dotfiles".to_string());
+                backup_all_dotfiles(&config).and_then(|plan| plan.commit(&config, &message))
+            } else {
+                info!("Backing up specific dotfiles: {:?}", files);
+                let message =
+                    message.unwrap_or_else(|| format!("Backup specific dotfiles: {:?}", patterns));
+                backup_specific_dotfiles(&config, &files)
+                    .and_then(|plan| plan.commit(&config, &message))
+            };
+
+            if config.dry_run {
+                match result {
+                    Ok(plan) if cli.format == OutputFormat::Json => print_json(&plan),
+                    Ok(plan) => {
+                        print_plan(&plan, cli.porcelain);
+                        print_refused_secrets(&plan.secrets, cli.porcelain);
+                        print_left_out(&plan.notices, cli.porcelain);
+                        print_case_collisions(&plan.collisions, cli.porcelain);
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-plan-backup", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+                return Ok(());
+            }
+
+            if let Err(err) = record_backup(&config, result.is_ok()) {
+                warn!("Failed to record backup metrics: {}", err);
+            }
+
+            let plan = match result {
+                Ok(plan) => plan,
+                Err(err) => {
+                    fail(
+                        t!("failed-backup-dotfiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&plan);
+            } else {
+                print_refused_secrets(&plan.secrets, cli.porcelain);
+                print_left_out(&plan.notices, cli.porcelain);
+                print_case_collisions(&plan.collisions, cli.porcelain);
+            }
+            if !plan.secrets.is_empty() {
+                ExitCode::Error.exit();
+            }
+            if plan.commit.is_none() && cli.format != OutputFormat::Json && !cli.porcelain {
+                println!("{}", t!("backup-unchanged"));
+            }
+
+            info!("Backup completed successfully");
+        }
+
+        Commands::Status => {
+            debug!("Running status command");
+
+            let status = match config.layout {
+                VaultLayout::Copy => get_status(&config),
+                VaultLayout::Bare => bare_status(&config),
+            };
+            let status = match status {
+                Ok(status) => status,
+                Err(err) => {
+                    fail(
+                        t!("failed-get-status", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&status);
+                if !status.is_empty() {
+                    ExitCode::Drift.exit();
+                }
+                return Ok(());
+            }
+
+            for entry in &status {
+                if cli.porcelain {
+                    println!("{}\t{}", entry.status.code(), entry.path.display());
+                } else {
+                    let label = match entry.status {
+                        FileStatus::Modified => t!("status-modified"),
+                        FileStatus::New => t!("status-new"),
+                        FileStatus::Deleted => t!("status-deleted"),
+                    };
+                    if config.plain {
+                        println!("{}: {}", label, entry.path.display());
+                    } else {
+                        println!("{:>9}: {}", label, entry.path.display());
+                    }
+                }
+            }
+
+            if !cli.porcelain && status.is_empty() {
+                println!("{}", t!("status-up-to-date"));
+            }
+
+            let deleted = status
+                .iter()
+                .filter(|entry| entry.status == FileStatus::Deleted)
+                .count();
+            if !cli.porcelain && deleted > 0 && config.layout == VaultLayout::Copy {
+                println!("{}", t!("status-deleted-hint", count = deleted));
+            }
+
+            // Suggestions are hints for people, not part of the porcelain format
+            if config.suggestions && config.layout == VaultLayout::Copy && !cli.porcelain {
+                let window = chrono::Duration::days(SUGGESTION_WINDOW_DAYS);
+                match suggest_untracked(&config, Local::now(), window) {
+                    Ok(suggestions) => {
+                        for suggestion in &suggestions {
+                            println!(
+                                "{}",
+                                t!(
+                                    "status-suggestion",
+                                    path = suggestion.path.display().to_string(),
+                                    command = suggestion.command()
+                                )
+                            );
+                        }
+                    }
+                    Err(err) => warn!("Failed to find suggestions: {}", err),
+                }
+            }
+
+            if !cli.porcelain {
+                match stale_self(&config, Local::now()) {
+                    Ok(Some(stale)) => println!("{}", t!("status-stale", days = stale.age_days())),
+                    Ok(None) => {}
+                    Err(err) => warn!("Failed to check the backup heartbeat: {}", err),
+                }
+            }
+
+            if !status.is_empty() {
+                ExitCode::Drift.exit();
+            }
+        }
+
+        Commands::List { at: Some(at) } => {
+            debug!("Running list command at {}", at);
+
+            let listed = resolve_version(&config, &at).and_then(|version| {
+                list_files_at(&config, &version.commit_id).map(|files| (version, files))
+            });
+
+            match listed {
+                Ok((version, files)) => {
+                    if cli.format == OutputFormat::Json {
+                        print_json(&json!({ "version": version, "files": files }));
+                    } else if cli.porcelain {
+                        for file in files {
+                            println!("{}", file.display());
+                        }
+                    } else {
+                        println!(
+                            "{}",
+                            t!(
+                                "list-at-header",
+                                commit = version.commit_id.as_str(),
+                                time = format_timestamp(&version.timestamp, &config)
+                            )
+                        );
+                        for file in files {
+                            print_item(&config, file.display());
+                        }
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!(
+                            "failed-list-dotfiles-at",
+                            at = at.as_str(),
+                            error = err.to_string()
+                        ),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::List { at: None } => {
+            debug!("Running list command");
+
+            match list_backed_up_dotfiles(&config) {
+                Ok(files) => {
+                    if cli.format == OutputFormat::Json {
+                        print_json(&files);
+                    } else if cli.porcelain {
+                        for file in files {
+                            println!("{}", file.display());
+                        }
+                    } else if files.is_empty() {
+                        println!("{}", t!("list-empty"));
+                    } else {
+                        println!("{}", t!("list-header", count = files.len()));
+                        for file in files {
+                            print_item(&config, file.display());
+                        }
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-list-backed-up-dotfiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::History {
+            file,
+            follow,
+            limit,
+            skip,
+        } => {
+            debug!("Running history command for file: {}", file);
+
+            let history = if !config.backend.is_git() {
+                open_backend(&config)
+                    .and_then(|backend| history_with(backend.as_ref(), &config, &file, skip, limit))
+            } else if follow {
+                follow_dotfile_history(&config, &file, skip, limit)
+            } else {
+                get_dotfile_history(&config, &file, skip, limit)
+            };
+
+            match history {
+                Ok(versions) => {
+                    if cli.format == OutputFormat::Json {
+                        print_json(&versions);
+                    } else if cli.porcelain {
+                        for version in &versions {
+                            println!(
+                                "{}\t{}\t{}",
+                                version.commit_id,
+                                rfc3339(&version.timestamp, config.utc),
+                                version.message.lines().next().unwrap_or("")
+                            );
+                        }
+                    } else if versions.is_empty() {
+                        println!("{}", t!("history-empty", file = file.as_str()));
+                    } else {
+                        println!("{}", t!("history-header", file = file.as_str()));
+                        // Numbered within the full history, as restore --version takes them
+                        for (i, version) in versions.iter().enumerate() {
+                            let number = skip + i + 1;
+                            let commit = &version.commit_id[..7];
+                            let time = format_timestamp(&version.timestamp, &config);
+                            if config.plain {
+                                println!(
+                                    "{}",
+                                    t!(
+                                        "history-version",
+                                        number = number,
+                                        commit = commit,
+                                        time = time,
+                                        message = version.message.as_str()
+                                    )
+                                );
+                            } else {
+                                println!(
+                                    "  v{}  {}  {} - {}",
+                                    number, commit, time, version.message
+                                );
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-get-history-for-dotfile", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Show { file, no_mask } => {
+            debug!("Running show command for file: {}", file);
+
+            match read_backed_up_dotfile(&config, &file) {
+                Ok(content) => {
+                    if !no_mask && is_sensitive_file(&file) {
+                        print!("{}", mask_secrets(&content));
+                    } else {
+                        print!("{}", content);
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-show-dotfile", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Diff {
+            files,
+            against: Some(against),
+            ..
+        } => {
+            debug!(
+                "Running diff command against {} for files: {:?}",
+                against, files
+            );
+
+            let (version, changes) = match changes_since(&config, &against, &files) {
+                Ok(changes) => changes,
+                Err(err) => {
+                    fail(
+                        t!("failed-diff-dotfiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&json!({ "version": version, "changes": changes }));
+            } else if cli.porcelain {
+                for change in &changes {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        change.status.code(),
+                        change.added,
+                        change.removed,
+                        change.path.display()
+                    );
+                }
+            } else {
+                let time = format_timestamp(&version.timestamp, &config);
+                if changes.is_empty() {
+                    println!("{}", t!("diff-against-unchanged", time = time));
+                } else {
+                    println!(
+                        "{}",
+                        t!(
+                            "diff-against-header",
+                            count = changes.len(),
+                            commit = version.commit_id.as_str(),
+                            time = time
+                        )
+                    );
+                }
+                for change in &changes {
+                    let label = match change.status {
+                        FileStatus::Modified => t!("status-modified"),
+                        FileStatus::New => t!("status-new"),
+                        FileStatus::Deleted => t!("status-deleted"),
+                    };
+                    let counts = t!(
+                        "diff-against-lines",
+                        added = change.added,
+                        removed = change.removed
+                    );
+                    if config.plain {
+                        println!("{}: {}, {}", label, change.path.display(), counts);
+                    } else {
+                        println!("{:>9}: {} ({})", label, change.path.display(), counts);
+                    }
+                }
+            }
+
+            if !changes.is_empty() {
+                ExitCode::Drift.exit();
+            }
+        }
+
+        Commands::Diff { files, no_mask, .. } => {
+            debug!("Running diff command for files: {:?}", files);
+
+            match diff_files(&config, &files, !no_mask) {
+                Ok(diffs) => {
+                    for (id, diff) in &diffs {
+                        if !cli.porcelain {
+                            print_divergence(&config, id);
+                        }
+                        print!("{}", diff);
+                    }
+
+                    if !diffs.is_empty() {
+                        ExitCode::Drift.exit();
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-diff-dotfiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Grep {
+            pattern,
+            history,
+            ignore_case,
+            no_mask,
+        } => {
+            debug!("Running grep command for {:?}", pattern);
+
+            let found = RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|err| (err.to_string(), "E_INVALID_PATTERN"))
+                .and_then(|pattern| {
+                    grep_vault(&config, &pattern, history, !no_mask)
+                        .map_err(|err| (err.to_string(), err.code()))
+                });
+            let found = match found {
+                Ok(found) => found,
+                Err((err, code)) => {
+                    fail(t!("failed-grep", error = err), code);
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&found);
+            } else if cli.porcelain {
+                for found in &found {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        found.commit.as_deref().unwrap_or("-"),
+                        found.path.display(),
+                        found.line_number,
+                        found.line
+                    );
+                }
+            } else if found.is_empty() {
+                println!("{}", t!("grep-no-matches"));
+            } else {
+                for found in &found {
+                    let path = found.path.display().to_string();
+                    let line = match (&found.commit, &found.timestamp) {
+                        (Some(commit), Some(timestamp)) => t!(
+                            "grep-match-version",
+                            commit = &commit[..7],
+                            time = format_timestamp(timestamp, &config),
+                            path = path.as_str(),
+                            line = found.line_number,
+                            text = found.line.as_str()
+                        ),
+                        _ => t!(
+                            "grep-match",
+                            path = path.as_str(),
+                            line = found.line_number,
+                            text = found.line.as_str()
+                        ),
+                    };
+                    println!("{}", line);
+                }
+            }
+        }
+
+        Commands::Sed { expression, files } => {
+            debug!("Running sed command with {}", expression);
+
+            let summary = match sed_dotfiles(&config, &expression, &files) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(t!("failed-sed", error = err.to_string()), err.error_code());
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for change in &summary.changed {
+                    println!(
+                        "changed\t{}\t{}\t{}",
+                        change.path.display(),
+                        change.lines,
+                        if change.home { "home" } else { "vault" }
+                    );
+                }
+                for path in &summary.skipped {
+                    println!("skipped\t{}", path.display());
+                }
+                if let Some(commit) = &summary.commit {
+                    println!("commit\t{}", commit);
+                }
+            } else {
+                for path in &summary.skipped {
+                    print_item(
+                        &config,
+                        t!("sed-skipped", path = path.display().to_string()),
+                    );
+                }
+                for change in &summary.changed {
+                    print_item(
+                        &config,
+                        t!(
+                            "sed-changed",
+                            path = change.path.display().to_string(),
+                            lines = change.lines,
+                            home = change.home.to_string()
+                        ),
+                    );
+                }
+                if summary.changed.is_empty() {
+                    println!("{}", t!("sed-nothing"));
+                } else if let Some(commit) = &summary.commit {
+                    println!("{}", t!("sed-committed", commit = &commit[..7]));
+                }
+            }
+        }
+
+        Commands::Watch {
+            debounce,
+            metrics_listen,
+        } => {
+            debug!("Running watch command");
+
+            if let Some(addr) = metrics_listen {
+                let metrics_config = config.clone();
+                info!("Serving metrics on http://{}/metrics", addr);
+                std::thread::spawn(move || {
+                    if let Err(err) = serve(&metrics_config, &addr) {
+                        error!("{}", t!("failed-serve-metrics", error = err.to_string()));
+                    }
+                });
+            }
+
+            if let Err(err) = watch(&config, Duration::from_secs(debounce)) {
+                fail(
+                    t!("failed-watch-dotfiles", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+        }
+
+        Commands::Schedule { command } => match command {
+            ScheduleCommands::Install { interval } => {
+                debug!("Running schedule install command every {:?}", interval);
+
+                match install_schedule(&config, interval) {
+                    Ok(status) => {
+                        let every = format_interval(interval.as_secs());
+                        let unit = status.units.last().unwrap().display().to_string();
+                        if cli.format == OutputFormat::Json {
+                            print_json(&status);
+                        } else if cli.porcelain {
+                            println!(
+                                "installed\t{}\t{}\t{}",
+                                status.platform.name(),
+                                interval.as_secs(),
+                                unit
+                            );
+                        } else if config.dry_run {
+                            println!(
+                                "{}",
+                                t!(
+                                    "schedule-dry-run",
+                                    interval = every,
+                                    platform = status.platform.name(),
+                                    unit = unit
+                                )
+                            );
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "schedule-installed",
+                                    interval = every,
+                                    platform = status.platform.name(),
+                                    unit = unit
+                                )
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-install-schedule", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            ScheduleCommands::Remove => {
+                debug!("Running schedule remove command");
+
+                match remove_schedule(&config) {
+                    Ok(removed) if cli.porcelain => println!("removed\t{}", removed),
+                    Ok(true) => println!("{}", t!("schedule-removed")),
+                    Ok(false) => println!("{}", t!("schedule-none")),
+                    Err(err) => {
+                        fail(
+                            t!("failed-remove-schedule", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            ScheduleCommands::Status => {
+                debug!("Running schedule status command");
+
+                let status = match schedule_status(&config) {
+                    Ok(status) => status,
+                    Err(err) => {
+                        fail(
+                            t!("failed-schedule-status", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if cli.format == OutputFormat::Json {
+                    print_json(&status);
+                } else if cli.porcelain {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        status.platform.name(),
+                        status.installed,
+                        status.active,
+                        status
+                            .interval
+                            .map(|secs| secs.to_string())
+                            .unwrap_or_default()
+                    );
+                } else if !status.installed {
+                    println!("{}", t!("schedule-none"));
+                } else {
+                    let every = status
+                        .interval
+                        .map(format_interval)
+                        .unwrap_or_else(|| "?".to_string());
+                    if status.active {
+                        println!(
+                            "{}",
+                            t!(
+                                "schedule-active",
+                                interval = every,
+                                platform = status.platform.name()
+                            )
+                        );
+                    } else {
+                        println!(
+                            "{}",
+                            t!(
+                                "schedule-inactive",
+                                interval = every,
+                                platform = status.platform.name()
+                            )
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Metrics { textfile, listen } => {
+            debug!("Running metrics command");
+
+            let result = match (textfile, listen) {
+                (Some(path), _) => write_textfile(&config, &path),
+                (None, Some(addr)) => {
+                    info!("Serving metrics on http://{}/metrics", addr);
+                    serve(&config, &addr)
+                }
+                (None, None) => collect(&config).map(|metrics| {
+                    print!("{}", render(&metrics, Local::now()));
+                }),
+            };
+
+            if let Err(err) = result {
+                fail(
+                    t!("failed-export-metrics", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+        }
+
+        Commands::Suggest => {
+            debug!("Running suggest command");
+
+            let window = chrono::Duration::days(SUGGESTION_WINDOW_DAYS);
+            match suggest_untracked(&config, Local::now(), window) {
+                Ok(suggestions) => {
+                    for suggestion in &suggestions {
+                        if cli.porcelain {
+                            println!("{}\t{}", suggestion.path.display(), suggestion.app);
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "suggestion",
+                                    path = suggestion.path.display().to_string(),
+                                    app = suggestion.app.to_string(),
+                                    command = suggestion.command()
+                                )
+                            );
+                        }
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-find-suggestions", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Push { url } => {
+            debug!("Running push command");
+
+            if let Err(err) = configure_remote(&config, url) {
+                fail(
+                    t!("failed-configure-remote", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+
+            if let Err(err) = push(&config) {
+                fail(t!("failed-push", error = err.to_string()), err.error_code());
+            }
+
+            info!("Pushed vault to {}", REMOTE_NAME);
+        }
+
+        Commands::Bundle { file } => {
+            debug!("Running bundle command for {:?}", file);
+
+            let file = absolute_or_exit(&file);
+            let branches = match export_bundle(&config, &file) {
+                Ok(branches) => branches,
+                Err(err) => {
+                    fail(
+                        t!("failed-export-bundle", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.porcelain {
+                println!("{}", file.display());
+            } else {
+                println!(
+                    "{}",
+                    t!(
+                        "bundle-written",
+                        path = file.display().to_string(),
+                        count = branches.len()
+                    )
+                );
+                println!(
+                    "{}",
+                    t!(
+                        "bundle-hint",
+                        path = file.display().to_string(),
+                        vault = config.vault_dir.display().to_string()
+                    )
+                );
+            }
+        }
+
+        Commands::Pull { url } => {
+            debug!("Running pull command");
+
+            if let Err(err) = configure_remote(&config, url) {
+                fail(
+                    t!("failed-configure-remote", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+
+            match pull(&config) {
+                Ok(outcome) => {
+                    let status = match outcome {
+                        PullOutcome::UpToDate => "up-to-date",
+                        PullOutcome::FastForward => "fast-forward",
+                        PullOutcome::Merged => "merged",
+                    };
+
+                    if cli.porcelain {
+                        println!("{}", status);
+                    } else {
+                        println!(
+                            "{}",
+                            t!("pull-outcome", remote = REMOTE_NAME, outcome = status)
+                        );
+                    }
+                }
+                Err(DotfilesError::MergeConflicts(paths)) => {
+                    report_error(&t!("pull-conflicts"), "E_CONFLICT_MERGE");
+                    for path in &paths {
+                        if cli.porcelain {
+                            println!("conflict\t{}", path);
+                        } else {
+                            print_item(&config, t!("pull-conflict", path = path.as_str()));
+                        }
+                    }
+                    ExitCode::Conflicts.exit();
+                }
+                Err(err) => {
+                    fail(t!("failed-pull", error = err.to_string()), err.error_code());
+                }
+            }
+        }
+
+        Commands::Sync { on_conflict } => {
+            debug!("Running sync command");
+
+            let interactive = cli.format != OutputFormat::Json && !cli.porcelain;
+            let summary = match sync_vault(&config, on_conflict, |file| {
+                if interactive {
+                    ask_sync_conflict(&config, file)
+                } else {
+                    None
+                }
+            }) {
+                Ok(summary) => summary,
+                Err(DotfilesError::MergeConflicts(paths)) => {
+                    report_error(&t!("pull-conflicts"), "E_CONFLICT_MERGE");
+                    for path in &paths {
+                        if cli.porcelain {
+                            println!("conflict\t{}", path);
+                        } else {
+                            print_item(&config, t!("pull-conflict", path = path.as_str()));
+                        }
+                    }
+                    ExitCode::Conflicts.exit();
+                }
+                Err(err) => {
+                    fail(t!("failed-sync", error = err.to_string()), err.error_code());
+                }
+            };
+            let backed_up: Vec<PathBuf> = summary
+                .backup
+                .copies
+                .iter()
+                .filter_map(|copy| copy.source.strip_prefix(&config.home_dir).ok())
+                .map(Path::to_path_buf)
+                .collect();
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for path in &backed_up {
+                    println!("backed-up\t{}", path.display());
+                }
+                for path in &summary.restore.restored {
+                    println!("restored\t{}", path.display());
+                }
+                for path in &summary.conflicts {
+                    println!("conflict\t{}", path.display());
+                }
+            } else {
+                for path in &backed_up {
+                    print_item(
+                        &config,
+                        t!("sync-backed-up", path = path.display().to_string()),
+                    );
+                }
+                for path in &summary.restore.restored {
+                    print_item(
+                        &config,
+                        t!("sync-restored", path = path.display().to_string()),
+                    );
+                }
+                for path in &summary.conflicts {
+                    print_item(
+                        &config,
+                        t!("sync-conflict", path = path.display().to_string()),
+                    );
+                }
+                println!(
+                    "{}",
+                    t!(
+                        "sync-done",
+                        backed_up = backed_up.len(),
+                        restored = summary.restore.restored.len(),
+                        remote = REMOTE_NAME
+                    )
+                );
+                if !summary.conflicts.is_empty() {
+                    println!(
+                        "{}",
+                        t!("sync-conflicts-hint", count = summary.conflicts.len())
+                    );
+                }
+            }
+
+            if !summary.conflicts.is_empty() {
+                ExitCode::Conflicts.exit();
+            }
+        }
+
+        Commands::Recover => {
+            debug!("Running recover command");
+
+            let recovery = match recover(&config) {
+                Ok(recovery) => recovery,
+                Err(err) => {
+                    fail(
+                        t!("failed-recover", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&recovery);
+            } else if cli.porcelain {
+                println!("recovered\t{}", recovery.url);
+                for path in &recovery.missing {
+                    println!("missing\t{}", path);
+                }
+            } else {
+                println!(
+                    "{}",
+                    t!(
+                        "recover-done",
+                        url = recovery.url.as_str(),
+                        tracked = recovery.tracked
+                    )
+                );
+                if !recovery.missing.is_empty() {
+                    println!("{}", t!("recover-missing", count = recovery.missing.len()));
+                    for path in &recovery.missing {
+                        print_item(&config, path);
+                    }
+                }
+            }
+
+            if !recovery.missing.is_empty() {
+                ExitCode::Drift.exit();
+            }
+        }
+
+        Commands::Clone { url, restore } => {
+            debug!("Running clone command for {}", url);
+
+            let cloned = match clone_vault(&config, &url) {
+                Ok(cloned) => cloned,
+                Err(err) => {
+                    fail(
+                        t!("failed-clone", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            let summary = if restore {
+                match restore_all_dotfiles(&config) {
+                    Ok(summary) => Some(summary),
+                    Err(err) => {
+                        fail(
+                            t!("failed-restore-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            } else {
+                None
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&json!({ "clone": cloned, "restore": summary }));
+            } else {
+                if cli.porcelain {
+                    println!("cloned\t{}", cloned.url);
+                    for path in &cloned.missing {
+                        println!("missing\t{}", path);
+                    }
+                } else {
+                    println!(
+                        "{}",
+                        t!(
+                            "clone-done",
+                            url = cloned.url.as_str(),
+                            tracked = cloned.tracked
+                        )
+                    );
+                    if !cloned.missing.is_empty() {
+                        println!("{}", t!("recover-missing", count = cloned.missing.len()));
+                        for path in &cloned.missing {
+                            print_item(&config, path);
+                        }
+                    }
+                }
+
+                match &summary {
+                    Some(summary) if config.dry_run => print_plan(&summary.plan, cli.porcelain),
+                    Some(summary) => print_restore_summary(summary, cli.porcelain),
+                    None if !cli.porcelain && cloned.tracked > 0 => {
+                        println!("{}", t!("import-restore-hint"))
+                    }
+                    None => {}
+                }
+            }
+
+            if let Some(summary) = summary
+                && !config.dry_run
+            {
+                if !summary.failed.is_empty() {
+                    ExitCode::Error.exit();
+                }
+                if summary
+                    .plan
+                    .copies
+                    .iter()
+                    .any(|copy| copy.action == PlannedAction::Quarantine)
+                {
+                    ExitCode::Conflicts.exit();
+                }
+            }
+            if !cloned.missing.is_empty() {
+                ExitCode::Drift.exit();
+            }
+        }
+
+        Commands::Import { source, restore } => {
+            debug!("Running import command for {}", source);
+
+            let imported = match import_dotfiles(&config, &source) {
+                Ok(imported) => imported,
+                Err(err) => {
+                    fail(
+                        t!("failed-import", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            let summary = if restore {
+                match restore_all_dotfiles(&config) {
+                    Ok(summary) => Some(summary),
+                    Err(err) => {
+                        fail(
+                            t!("failed-restore-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            } else {
+                None
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&json!({ "import": imported, "restore": summary }));
+            } else {
+                if cli.porcelain {
+                    for path in &imported.tracked {
+                        println!("tracked\t{}", path.display());
+                    }
+                    for path in &imported.skipped {
+                        println!("skipped\t{}", path.display());
+                    }
+                } else {
+                    println!(
+                        "{}",
+                        t!(
+                            "import-done",
+                            source = imported.source.as_str(),
+                            count = imported.tracked.len()
+                        )
+                    );
+                    if !imported.skipped.is_empty() {
+                        println!("{}", t!("import-skipped", count = imported.skipped.len()));
+                        for path in &imported.skipped {
+                            print_item(&config, path.display());
+                        }
+                    }
+                }
+
+                match &summary {
+                    Some(summary) if config.dry_run => print_plan(&summary.plan, cli.porcelain),
+                    Some(summary) => print_restore_summary(summary, cli.porcelain),
+                    None if !cli.porcelain => println!("{}", t!("import-restore-hint")),
+                    None => {}
+                }
+            }
+
+            if let Some(summary) = summary
+                && !config.dry_run
+            {
+                if !summary.failed.is_empty() {
+                    ExitCode::Error.exit();
+                }
+                if summary
+                    .plan
+                    .copies
+                    .iter()
+                    .any(|copy| copy.action == PlannedAction::Quarantine)
+                {
+                    ExitCode::Conflicts.exit();
+                }
+            }
+        }
+
+        Commands::Hooks { command } => match command {
+            HooksCommands::List => {
+                debug!("Running hooks list command");
+
+                let hooks = list_hooks(&config);
+                if cli.porcelain {
+                    for hook in hooks {
+                        println!("{}", hook.name());
+                    }
+                } else if hooks.is_empty() {
+                    println!("{}", t!("hooks-none"));
+                } else {
+                    println!("{}", t!("hooks-header"));
+                    for hook in hooks {
+                        print_item(&config, hook.name());
+                    }
+                }
+            }
+
+            HooksCommands::Test { hook, files } => {
+                debug!("Running hooks test command for hook: {}", hook);
+
+                let Some(event) = HookEvent::from_name(&hook) else {
+                    fail(t!("hook-unknown", hook = hook.as_str()), "E_UNKNOWN_HOOK");
+                };
+
+                let files = if files.is_empty() {
+                    list_backed_up_dotfiles(&config)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|file| config.home_dir.join(file))
+                        .collect()
+                } else {
+                    files
+                        .iter()
+                        .map(|file| normalize_path(file, &config))
+                        .collect()
+                };
+
+                let mut context = HookContext::new(&config, event, files);
+                context.simulated = true;
+
+                match run_hook(&config, &context) {
+                    Ok(true) => info!("Hook {} succeeded", hook),
+                    Ok(false) => {
+                        fail(
+                            t!("hook-not-installed", hook = hook.as_str()),
+                            "E_HOOK_NOT_INSTALLED",
+                        );
+                    }
+                    Err(err) => fail(err.to_string(), err.code()),
+                }
+            }
+        },
+
+        Commands::Config { command } => match command {
+            ConfigCommands::Lint => {
+                debug!("Running config lint command");
+
+                let diagnostics = match lint(&config) {
+                    Ok(diagnostics) => diagnostics,
+                    Err(err) => {
+                        fail(
+                            t!("failed-lint-configuration", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                for diagnostic in &diagnostics {
+                    if cli.porcelain {
+                        println!(
+                            "{}\t{}\t{}\t{}",
+                            diagnostic.severity.name(),
+                            diagnostic.code,
+                            diagnostic.location,
+                            diagnostic.message
+                        );
+                    } else {
+                        println!(
+                            "{}",
+                            t!(
+                                "lint-diagnostic",
+                                severity = diagnostic.severity.name(),
+                                location = diagnostic.location.as_str(),
+                                message = diagnostic.message.as_str(),
+                                code = diagnostic.code
+                            )
+                        );
+                    }
+                }
+
+                if !cli.porcelain && diagnostics.is_empty() {
+                    println!("{}", t!("lint-clean"));
+                }
+
+                if diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.severity == Severity::Error)
+                {
+                    ExitCode::Error.exit();
+                }
+            }
+        },
+
+        Commands::Vault { command } => {
+            let Some(path) = cli
+                .config
+                .clone()
+                .or_else(|| config.config_file.clone())
+                .or_else(default_config_path)
+            else {
+                fail(t!("vault-no-config-file"), "E_NO_CONFIG_FILE");
+            };
+
+            match command {
+                VaultCommands::List => {
+                    debug!("Running vault list command");
+
+                    let vaults = match list_vaults(&path, &config) {
+                        Ok(vaults) => vaults,
+                        Err(err) => {
+                            fail(
+                                t!("failed-list-vaults", error = err.to_string()),
+                                err.error_code(),
+                            );
+                        }
+                    };
+
+                    if cli.format == OutputFormat::Json {
+                        print_json(&vaults);
+                    } else {
+                        for vault in &vaults {
+                            let marker = match (vault.current, vault.default) {
+                                (true, _) => "*",
+                                (false, true) => "+",
+                                (false, false) => "-",
+                            };
+                            if cli.porcelain {
+                                println!(
+                                    "{}\t{}\t{}",
+                                    marker,
+                                    vault.name,
+                                    vault.vault_dir.display()
+                                );
+                            } else {
+                                println!(
+                                    "{} {:<12} {}",
+                                    if marker == "-" { " " } else { marker },
+                                    vault.name,
+                                    vault.vault_dir.display()
+                                );
+                            }
+                        }
+                    }
+                }
+
+                VaultCommands::Create { name, dir } => {
+                    debug!("Running vault create command for {}", name);
+
+                    let created = create_vault(&path, &name, dir.as_deref())
+                        .and_then(|()| Config::load_vault(Some(&path), Some(&name)))
+                        .and_then(|vault| init_vault(&vault).map(|()| vault));
+                    let vault = match created {
+                        Ok(vault) => vault,
+                        Err(err) => {
+                            fail(
+                                t!("failed-create-vault", error = err.to_string()),
+                                err.error_code(),
+                            );
+                        }
+                    };
+
+                    if cli.porcelain {
+                        println!("{}", vault.vault_dir.display());
+                    } else {
+                        println!(
+                            "{}",
+                            t!(
+                                "vault-created",
+                                name = name.as_str(),
+                                path = vault.vault_dir.display().to_string()
+                            )
+                        );
+                        println!("{}", t!("vault-use-hint", name = name.as_str()));
+                    }
+                }
+
+                VaultCommands::Switch { name } => {
+                    debug!("Running vault switch command for {}", name);
+
+                    if let Err(err) = switch_vault(&path, &name) {
+                        fail(
+                            t!("failed-switch-vault", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+
+                    if !cli.porcelain {
+                        println!("{}", t!("vault-switched", name = name.as_str()));
+                    }
+                }
+            }
+        }
+
+        Commands::Group { command } => match command {
+            GroupCommands::List => {
+                debug!("Running group list command");
+
+                let manifest = match Manifest::load(&config) {
+                    Ok(manifest) => manifest,
+                    Err(err) => {
+                        fail(
+                            t!("failed-load-manifest", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                for entry in manifest.entries.values() {
+                    let Some(group) = &entry.group else {
+                        continue;
+                    };
+
+                    if cli.porcelain {
+                        println!("{}\t{}", group, entry.id);
+                    } else {
+                        println!("{}: {}", group, entry.id);
+                    }
+                }
+            }
+
+            GroupCommands::Set { group, files } => {
+                debug!("Running group set command for group: {}", group);
+
+                if let Err(err) = set_group(&config, &files, &group) {
+                    fail(
+                        t!("failed-set-group", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+
+                info!("Added {} dotfiles to group {}", files.len(), group);
+            }
+        },
+
+        Commands::Snapshot { command } => match command {
+            SnapshotCommands::Create { name, message } => {
+                debug!("Running snapshot create command: {}", name);
+
+                match create_snapshot(&config, &name, message.as_deref()) {
+                    Ok(snapshot) => {
+                        if cli.format == OutputFormat::Json {
+                            print_json(&snapshot);
+                        } else if cli.porcelain {
+                            println!("created\t{}\t{}", snapshot.name, snapshot.commit_id);
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "snapshot-created",
+                                    name = snapshot.name.as_str(),
+                                    commit = &snapshot.commit_id[..7]
+                                )
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-create-snapshot", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            SnapshotCommands::List => {
+                debug!("Running snapshot list command");
+
+                match list_snapshots(&config) {
+                    Ok(snapshots) => {
+                        if cli.format == OutputFormat::Json {
+                            print_json(&snapshots);
+                        } else if cli.porcelain {
+                            for snapshot in &snapshots {
+                                println!(
+                                    "{}\t{}\t{}\t{}",
+                                    snapshot.name,
+                                    snapshot.commit_id,
+                                    snapshot.created.to_rfc3339(),
+                                    snapshot.message.lines().next().unwrap_or("")
+                                );
+                            }
+                        } else if snapshots.is_empty() {
+                            println!("{}", t!("snapshots-empty"));
+                        } else {
+                            for snapshot in &snapshots {
+                                print_item(
+                                    &config,
+                                    t!(
+                                        "snapshot-item",
+                                        name = snapshot.name.as_str(),
+                                        time = format_timestamp(&snapshot.created, &config),
+                                        commit = &snapshot.commit_id[..7],
+                                        message = snapshot.message.lines().next().unwrap_or("")
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-list-snapshots", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Profiles => {
+            debug!("Running profiles command");
+
+            match list_profiles(&config) {
+                Ok(profiles) => {
+                    for profile in &profiles {
+                        if cli.porcelain {
+                            println!("{}", profile);
+                        } else if config.profile.as_ref() == Some(profile) {
+                            if config.plain {
+                                println!("{}", t!("profile-current", profile = profile.as_str()));
+                            } else {
+                                println!("* {}", profile);
+                            }
+                        } else {
+                            print_item(&config, profile);
+                        }
+                    }
+
+                    if !cli.porcelain && profiles.is_empty() {
+                        println!("{}", t!("profiles-none"));
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-list-profiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        #[cfg(feature = "tui")]
+        Commands::Tui => {
+            debug!("Running tui command");
+
+            if config.plain {
+                fail(t!("tui-plain"), "E_TUI_PLAIN");
+            }
+
+            if let Err(err) = run_tui(&config) {
+                fail(t!("failed-tui", error = err.to_string()), err.error_code());
+            }
+        }
+
+        Commands::Completions { shell } => {
+            debug!("Running completions command for {}", shell);
+
+            let mut command = Cli::command();
+            let bin = command.get_name().to_string();
+            generate(shell, &mut command, &bin, &mut std::io::stdout());
+
+            if let Some(tracked) = tracked_files_completion(shell, &bin) {
+                print!("{}", tracked);
+            }
+        }
+
+        Commands::Features => {
+            debug!("Running features command");
+
+            let features = features();
+            if cli.format == OutputFormat::Json {
+                print_json(&features);
+            } else if cli.porcelain {
+                for feature in &features {
+                    let state = if feature.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    };
+                    println!("{}\t{}", feature.name, state);
+                }
+            } else {
+                for feature in &features {
+                    let item = if feature.enabled {
+                        t!("feature-enabled", name = feature.name)
+                    } else {
+                        t!("feature-disabled", name = feature.name)
+                    };
+                    print_item(&config, item);
+                }
+            }
+        }
+
+        Commands::Conflicts { command } => match command {
+            ConflictsCommands::List => {
+                debug!("Running conflicts list command");
+
+                let conflicts = match list_conflicts(&config) {
+                    Ok(conflicts) => conflicts,
+                    Err(err) => {
+                        fail(
+                            t!("failed-list-conflicts", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                for conflict in &conflicts {
+                    if cli.porcelain {
+                        println!(
+                            "{}\t{}\t{}",
+                            conflict.path.display(),
+                            rfc3339(&conflict.created, config.utc),
+                            conflict.quarantined.display()
+                        );
+                    } else {
+                        println!(
+                            "{}  {}  {}",
+                            format_timestamp(&conflict.created, &config),
+                            conflict.path.display(),
+                            conflict.quarantined.display()
+                        );
+                    }
+                }
+
+                if !cli.porcelain && conflicts.is_empty() {
+                    println!("{}", t!("conflicts-none"));
+                }
+
+                if !conflicts.is_empty() {
+                    ExitCode::Conflicts.exit();
+                }
+            }
+
+            ConflictsCommands::Resolve { file, use_vault } => {
+                debug!("Running conflicts resolve command for file: {}", file);
+
+                match resolve_conflict(&config, &file, use_vault) {
+                    Ok(resolved) => {
+                        info!("Resolved {} conflicts for {}", resolved.len(), file)
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-resolve-conflict", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Key { command } => match command {
+            KeyCommands::Init => {
+                debug!("Running key init command");
+
+                match init_key(&config) {
+                    Ok(recipient) => {
+                        if cli.porcelain {
+                            println!("{}", recipient);
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "key-created",
+                                    path = key_path(&config).display().to_string()
+                                )
+                            );
+                            println!("{}", t!("key-public", recipient = recipient.as_str()));
+                            println!("{}", t!("key-keep-safe"));
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-create-key", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            KeyCommands::Export => {
+                debug!("Running key export command");
+
+                match export_key(&config) {
+                    Ok(key) => print!("{}", key),
+                    Err(err) => {
+                        fail(
+                            t!("failed-export-key", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Bootstrap { output: None } => {
+            debug!("Running bootstrap command");
+
+            match bootstrap_script(&config) {
+                Ok(script) => print!("{}", script),
+                Err(err) => {
+                    fail(
+                        t!("failed-bootstrap", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Bootstrap {
+            output: Some(output),
+        } => {
+            debug!("Running bootstrap command to {:?}", output);
+
+            if let Err(err) = write_bootstrap_script(&config, &output) {
+                fail(
+                    t!("failed-bootstrap", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+
+            if cli.porcelain {
+                println!("{}", output.display());
+            } else {
+                println!(
+                    "{}",
+                    t!("bootstrap-written", path = output.display().to_string())
+                );
+            }
+        }
+
+        Commands::SimulateBootstrap { keep } => {
+            debug!("Running simulate-bootstrap command");
+
+            let report = match simulate_bootstrap(&config, keep) {
+                Ok(report) => report,
+                Err(err) => {
+                    fail(
+                        t!("failed-simulate-bootstrap", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            for check in &report.checks {
+                match (&check.error, cli.porcelain) {
+                    (None, true) => println!("ok\t{}", check.name),
+                    (Some(err), true) => println!("fail\t{}\t{}", check.name, err),
+                    (None, false) => print_item(
+                        &config,
+                        t!("simulate-check-ok", check = check.name.as_str()),
+                    ),
+                    (Some(err), false) => print_item(
+                        &config,
+                        t!(
+                            "simulate-check-failed",
+                            check = check.name.as_str(),
+                            error = err.as_str()
+                        ),
+                    ),
+                }
+            }
+
+            if !cli.porcelain {
+                let failed = report.checks.iter().filter(|check| !check.passed()).count();
+                if failed == 0 {
+                    println!("{}", t!("simulate-succeeded", checks = report.checks.len()));
+                } else {
+                    println!(
+                        "{}",
+                        t!(
+                            "simulate-failed",
+                            failed = failed,
+                            checks = report.checks.len()
+                        )
+                    );
+                }
+                if report.kept {
+                    println!(
+                        "{}",
+                        t!(
+                            "simulate-kept",
+                            path = report.home_dir.display().to_string()
+                        )
+                    );
+                }
+            }
+
+            if !report.success() {
+                ExitCode::Error.exit();
+            }
+        }
+
+        Commands::Snippet { command } => match command {
+            SnippetCommands::Add { kind, definition } => {
+                debug!("Running snippet add command: {}", definition);
+
+                let added = Snippet::parse(kind, &definition).and_then(|snippet| {
+                    add_snippet(&config, &snippet)?;
+                    commit_changes(
+                        &config,
+                        &format!("Add {} {}", snippet.kind.name(), snippet.name),
+                    )?;
+                    Ok(snippet)
+                });
+
+                match added {
+                    Ok(snippet) => {
+                        if cli.porcelain {
+                            println!("added\t{}\t{}", snippet.kind.name(), snippet.name);
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "snippet-added",
+                                    kind = snippet.kind.name(),
+                                    name = snippet.name.as_str(),
+                                    script = script_path(&config).display().to_string()
+                                )
+                            );
+                            if !is_sourced(&config) {
+                                println!("{}", t!("snippet-source-hint"));
+                                print_item(&config, SOURCE_LINE);
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-add-snippet", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            SnippetCommands::List => {
+                debug!("Running snippet list command");
+
+                match list_snippets(&config) {
+                    Ok(snippets) => {
+                        if cli.format == OutputFormat::Json {
+                            print_json(&snippets);
+                        } else if cli.porcelain {
+                            for snippet in &snippets {
+                                println!(
+                                    "{}\t{}\t{}",
+                                    snippet.kind.name(),
+                                    snippet.name,
+                                    snippet.body.lines().next().unwrap_or("")
+                                );
+                            }
+                        } else if snippets.is_empty() {
+                            println!("{}", t!("snippets-empty"));
+                        } else {
+                            for snippet in &snippets {
+                                print_item(
+                                    &config,
+                                    t!(
+                                        "snippet-item",
+                                        name = snippet.name.as_str(),
+                                        kind = snippet.kind.name(),
+                                        body = snippet.body.lines().next().unwrap_or("")
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-list-snippets", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            SnippetCommands::Remove { name } => {
+                debug!("Running snippet remove command: {}", name);
+
+                let removed = remove_snippet(&config, &name).and_then(|snippet| {
+                    commit_changes(
+                        &config,
+                        &format!("Remove {} {}", snippet.kind.name(), snippet.name),
+                    )?;
+                    Ok(snippet)
+                });
+
+                match removed {
+                    Ok(snippet) if cli.porcelain => {
+                        println!("removed\t{}\t{}", snippet.kind.name(), snippet.name);
+                    }
+                    Ok(snippet) => println!(
+                        "{}",
+                        t!(
+                            "snippet-removed",
+                            kind = snippet.kind.name(),
+                            name = snippet.name.as_str()
+                        )
+                    ),
+                    Err(err) => {
+                        fail(
+                            t!("failed-remove-snippet", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            SnippetCommands::Apply => {
+                debug!("Running snippet apply command");
+
+                match write_snippets_script(&config).and_then(|()| list_snippets(&config)) {
+                    Ok(snippets) if !cli.porcelain => println!(
+                        "{}",
+                        t!(
+                            "snippets-written",
+                            count = snippets.len(),
+                            script = script_path(&config).display().to_string()
+                        )
+                    ),
+                    Ok(_) => {}
+                    Err(err) => {
+                        fail(
+                            t!("failed-write-snippets", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Generate { print: Some(shell) } => {
+            debug!("Running generate command for {}", shell.name());
+
+            match load_shell_source(&config) {
+                Ok(Some(source)) => print!("{}", source.render(shell)),
+                Ok(None) => fail(
+                    t!(
+                        "generate-no-source",
+                        path = source_path(&config).display().to_string()
+                    ),
+                    "E_NO_SHELL_SOURCE",
+                ),
+                Err(err) => {
+                    fail(
+                        t!("failed-generate-shell-files", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::Generate { print: None } => {
+            debug!("Running generate command");
+
+            let written = match write_shell_files(&config) {
+                Ok(written) => written,
+                Err(err) => {
+                    fail(
+                        t!("failed-generate-shell-files", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.porcelain {
+                for path in &written {
+                    println!("{}", path.display());
+                }
+            } else if written.is_empty() {
+                println!(
+                    "{}",
+                    t!(
+                        "generate-no-source",
+                        path = source_path(&config).display().to_string()
+                    )
+                );
+            } else {
+                for path in &written {
+                    print_item(&config, path.display().to_string());
+                }
+                println!("{}", t!("generate-source-hint"));
+                for shell in TargetShell::ALL {
+                    print_item(
+                        &config,
+                        format!("{}: {}", shell.name(), shell.source_line()),
+                    );
+                }
+            }
+        }
+
+        Commands::Toolchains { command } => match command {
+            ToolchainsCommands::Capture => {
+                debug!("Running toolchains capture command");
+
+                let captured = capture_toolchains(&config).and_then(|snapshots| {
+                    commit_changes(&config, "Capture toolchain state").map(|_| snapshots)
+                });
+
+                match captured {
+                    Ok(snapshots) => {
+                        for snapshot in &snapshots {
+                            if cli.porcelain {
+                                println!("{}\t{}", snapshot.name, snapshot.items.len());
+                            } else {
+                                println!(
+                                    "{}",
+                                    t!(
+                                        "toolchain-captured",
+                                        toolchain = snapshot.name.to_string(),
+                                        items = snapshot.items.len()
+                                    )
+                                );
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-capture-toolchains", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+
+            ToolchainsCommands::Hints => {
+                debug!("Running toolchains hints command");
+
+                match reinstall_hints(&config) {
+                    Ok(hints) => {
+                        for hint in hints {
+                            println!("{}", hint);
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-read-toolchain-snapshots", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Frequency { frequency, files } => {
+            debug!("Running frequency command: {}", frequency.name());
+
+            if let Err(err) = set_frequency(&config, &files, frequency) {
+                fail(
+                    t!("failed-set-frequency", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+
+            info!(
+                "Set frequency of {} dotfiles to {}",
+                files.len(),
+                frequency.name()
+            );
+        }
+
+        Commands::Add { files } => {
+            debug!("Running add command for {:?}", files);
+
+            let added = add_paths(&config, &files).and_then(|added| {
+                if !added.is_empty() {
+                    let paths: Vec<String> = added
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect();
+                    commit_changes(&config, &format!("Track {}", paths.join(", ")))?;
+                }
+                Ok(added)
+            });
+            let added = match added {
+                Ok(added) => added,
+                Err(err) => {
+                    fail(t!("failed-add", error = err.to_string()), err.error_code());
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&added);
+            } else if cli.porcelain {
+                for path in &added {
+                    println!("{}", path.display());
+                }
+            } else {
+                for path in &added {
+                    print_item(&config, path.display().to_string());
+                }
+                println!("{}", t!("add-done", count = added.len()));
+            }
+        }
+
+        Commands::Adopt { files } => {
+            debug!("Running adopt command for {:?}", files);
+
+            let summary = match adopt_dotfiles(&config, &files) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-adopt", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for path in &summary.adopted {
+                    println!("adopted\t{}", path.display());
+                }
+            } else {
+                for path in &summary.adopted {
+                    print_item(&config, path.display().to_string());
+                }
+                let count = summary.adopted.len();
+                if config.dry_run {
+                    println!("{}", t!("adopt-dry-run", count = count));
+                } else {
+                    println!("{}", t!("adopt-done", count = count));
+                }
+            }
+        }
+
+        Commands::Starter { file, from, remove } => {
+            debug!("Running starter command for {}", file);
+
+            let result = if remove {
+                untrack_starter(&config, std::slice::from_ref(&file))
+                    .and_then(|()| commit_changes(&config, &format!("Track {} normally", file)))
+            } else {
+                track_starter(&config, &file, from.as_deref()).and_then(|entry| {
+                    commit_changes(
+                        &config,
+                        &format!("Add starter content for {}", entry.source.display()),
+                    )
+                })
+            };
+            if let Err(err) = result {
+                fail(
+                    t!("failed-set-starter", error = err.to_string()),
+                    err.error_code(),
+                );
+            }
+
+            if !cli.porcelain {
+                if remove {
+                    println!("{}", t!("starter-removed", file = file.as_str()));
+                } else {
+                    println!("{}", t!("starter-added", file = file.as_str()));
+                }
+            }
+        }
+
+        Commands::Compact { baseline } => {
+            debug!("Running compact command before {}", baseline);
+
+            let summary = match compact_history(&config, baseline) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-compact-history", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.porcelain {
+                println!("squashed\t{}", summary.squashed);
+                println!("kept\t{}", summary.kept);
+                println!("head\t{}", summary.head);
+            } else if summary.squashed == 0 {
+                println!("{}", t!("compact-nothing"));
+            } else if config.dry_run {
+                println!(
+                    "{}",
+                    t!(
+                        "compact-dry-run",
+                        squashed = summary.squashed,
+                        kept = summary.kept
+                    )
+                );
+            } else {
+                println!(
+                    "{}",
+                    t!(
+                        "compact-done",
+                        squashed = summary.squashed,
+                        kept = summary.kept
+                    )
+                );
+                if config.remote_url.is_some() {
+                    println!(
+                        "{}",
+                        t!(
+                            "compact-force-push",
+                            vault = config.vault_dir.display().to_string()
+                        )
+                    );
+                }
+            }
+        }
+
+        Commands::Prune {
+            keep_last,
+            keep_days,
+        } => {
+            debug!("Running prune command");
+
+            let policy = RetentionPolicy {
+                keep_last,
+                keep_days,
+            };
+            let summary = match prune_history(&config, &policy) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-prune-history", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.porcelain {
+                println!("squashed\t{}", summary.squashed);
+                println!("kept\t{}", summary.kept);
+                println!("head\t{}", summary.head);
+            } else if summary.squashed == 0 {
+                println!("{}", t!("prune-nothing"));
+            } else if config.dry_run {
+                println!(
+                    "{}",
+                    t!(
+                        "prune-dry-run",
+                        squashed = summary.squashed,
+                        kept = summary.kept
+                    )
+                );
+            } else {
+                println!(
+                    "{}",
+                    t!(
+                        "prune-done",
+                        squashed = summary.squashed,
+                        kept = summary.kept
+                    )
+                );
+                if config.remote_url.is_some() {
+                    println!(
+                        "{}",
+                        t!(
+                            "compact-force-push",
+                            vault = config.vault_dir.display().to_string()
+                        )
+                    );
+                }
+            }
+        }
+
+        Commands::Normalize => {
+            debug!("Running normalize command");
+
+            let summary = match normalize_vault(&config) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-normalize-vault", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for path in &summary.renamed {
+                    println!("renamed\t{}", path.display());
+                }
+                for path in &summary.merged {
+                    println!("merged\t{}", path.display());
+                }
+                println!("entries\t{}", summary.entries);
+            } else if summary.is_empty() {
+                println!("{}", t!("normalize-nothing"));
+            } else {
+                for path in &summary.renamed {
+                    print_item(
+                        &config,
+                        t!("normalize-renamed", path = path.display().to_string()),
+                    );
+                }
+                for path in &summary.merged {
+                    print_item(
+                        &config,
+                        t!("normalize-merged", path = path.display().to_string()),
+                    );
+                }
+                let files = summary.renamed.len() + summary.merged.len();
+                if config.dry_run {
+                    println!(
+                        "{}",
+                        t!(
+                            "normalize-dry-run",
+                            files = files,
+                            entries = summary.entries
+                        )
+                    );
+                } else {
+                    println!(
+                        "{}",
+                        t!("normalize-done", files = files, entries = summary.entries)
+                    );
+                }
+            }
+        }
+
+        Commands::Verify => {
+            debug!("Running verify command");
+
+            let problems = match verify_vault(&config) {
+                Ok(problems) => problems,
+                Err(err) => {
+                    fail(
+                        t!("failed-verify-vault", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&problems);
+            } else if cli.porcelain {
+                for problem in &problems {
+                    println!(
+                        "{}\t{}\t{}",
+                        problem.kind.name(),
+                        problem.subject,
+                        problem.detail
+                    );
+                }
+            } else if problems.is_empty() {
+                println!("{}", t!("verify-ok"));
+            } else {
+                println!("{}", t!("verify-problems", count = problems.len()));
+                for problem in &problems {
+                    let subject = problem.subject.as_str();
+                    let detail = problem.detail.as_str();
+                    let line = match problem.kind {
+                        ProblemKind::Uncommitted => {
+                            t!("verify-uncommitted", subject = subject, detail = detail)
+                        }
+                        ProblemKind::CorruptObject => {
+                            t!("verify-corrupt-object", subject = subject, detail = detail)
+                        }
+                        ProblemKind::InvalidManifest => {
+                            t!(
+                                "verify-invalid-manifest",
+                                subject = subject,
+                                detail = detail
+                            )
+                        }
+                        ProblemKind::Unmapped => {
+                            t!("verify-unmapped", subject = subject, detail = detail)
+                        }
+                    };
+                    print_item(&config, line);
+                }
+            }
+
+            if !problems.is_empty() {
+                ExitCode::Error.exit();
+            }
+        }
+
+        Commands::Doctor { files, fix } => {
+            let Some(action) = fix else {
+                debug!("Running doctor command");
+
+                let strays = match find_stray_files(&config) {
+                    Ok(strays) => strays,
+                    Err(err) => {
+                        fail(
+                            t!("failed-find-stray-files", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                let stale = match stale_hosts(&config, Local::now()) {
+                    Ok(stale) => stale,
+                    Err(err) => {
+                        fail(
+                            t!("failed-check-heartbeats", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if cli.format == OutputFormat::Json {
+                    print_json(&strays);
+                } else if cli.porcelain {
+                    for path in &strays {
+                        println!("stray\t{}", path.display());
+                    }
+                    for host in &stale {
+                        println!("stale\t{}\t{}", host.last_backup, host.host);
+                    }
+                } else {
+                    if strays.is_empty() {
+                        println!("{}", t!("doctor-no-strays"));
+                    } else {
+                        println!("{}", t!("doctor-strays", count = strays.len()));
+                        for path in &strays {
+                            print_item(&config, path.display().to_string());
+                        }
+                        println!("{}", t!("doctor-fix-hint"));
+                    }
+                    if !stale.is_empty() {
+                        println!("{}", t!("doctor-stale", count = stale.len()));
+                        for host in &stale {
+                            print_item(
+                                &config,
+                                t!(
+                                    "doctor-stale-host",
+                                    host = host.host.as_str(),
+                                    days = host.age_days()
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                if !strays.is_empty() || !stale.is_empty() {
+                    ExitCode::Drift.exit();
+                }
+                return Ok(());
+            };
+
+            debug!("Running doctor command to {} {:?}", action.name(), files);
+
+            let summary = match resolve_stray_files(&config, &files, action) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-fix-stray-files", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for path in &summary.files {
+                    println!("{}\t{}", action.name(), path.display());
+                }
+            } else if summary.files.is_empty() {
+                println!("{}", t!("doctor-no-strays"));
+            } else {
+                for path in &summary.files {
+                    print_item(&config, path.display().to_string());
+                }
+                let count = summary.files.len();
+                let message = match (action, config.dry_run) {
+                    (_, true) => t!("doctor-dry-run", action = action.name(), count = count),
+                    (StrayAction::Adopt, false) => t!("doctor-adopted", count = count),
+                    (StrayAction::Delete, false) => t!("doctor-deleted", count = count),
+                    (StrayAction::Ignore, false) => t!("doctor-ignored", count = count),
+                };
+                println!("{}", message);
+            }
+        }
+
+        Commands::Remove {
+            files,
+            purge_history,
+        } => {
+            debug!("Running remove command for {:?}", files);
+
+            let summary = match untrack_dotfiles(&config, &files, purge_history) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    fail(
+                        t!("failed-remove-dotfiles", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&summary);
+            } else if cli.porcelain {
+                for path in &summary.removed {
+                    println!("removed\t{}", path.display());
+                }
+                if purge_history && !config.dry_run {
+                    println!("purged\t{}", summary.purged);
+                }
+            } else {
+                for path in &summary.deleted {
+                    print_item(&config, path.display().to_string());
+                }
+                let count = summary.removed.len();
+                if config.dry_run {
+                    println!("{}", t!("remove-dry-run", count = count));
+                } else {
+                    println!("{}", t!("remove-done", count = count));
+                    if !summary.ignored.is_empty() {
+                        println!("{}", t!("remove-ignored", count = summary.ignored.len()));
+                    }
+                    if purge_history {
+                        println!("{}", t!("remove-purged", commits = summary.purged));
+                    } else {
+                        println!("{}", t!("remove-purge-hint"));
+                    }
+                }
+            }
+        }
+
+        Commands::Clean { yes } => {
+            debug!("Running clean command");
+
+            let orphaned = match find_orphaned_dotfiles(&config) {
+                Ok(orphaned) => orphaned,
+                Err(err) => {
+                    fail(
+                        t!("failed-clean", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            let human = cli.format != OutputFormat::Json && !cli.porcelain;
+            if human {
+                for path in &orphaned {
+                    print_item(&config, path.display().to_string());
+                }
+            }
+
+            // Without --yes, ask where someone can answer
+            let mut confirmed = yes;
+            if !yes
+                && human
+                && !orphaned.is_empty()
+                && !config.dry_run
+                && !config.read_only
+                && std::io::stdin().is_terminal()
+            {
+                eprint!("{} ", t!("clean-confirm", count = orphaned.len()));
+                let _ = std::io::stderr().flush();
+                let mut answer = String::new();
+                confirmed = std::io::stdin().read_line(&mut answer).is_ok()
+                    && matches!(answer.trim(), "y" | "yes");
+            }
+
+            let summary = if confirmed && !orphaned.is_empty() && !config.dry_run {
+                let files: Vec<String> = orphaned
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                match untrack_dotfiles(&config, &files, false) {
+                    Ok(summary) => Some(summary),
+                    Err(err) => {
+                        fail(
+                            t!("failed-clean", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            } else {
+                None
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&json!({ "orphaned": orphaned, "removed": summary }));
+            } else if cli.porcelain {
+                let state = if summary.is_some() {
+                    "removed"
+                } else {
+                    "orphaned"
+                };
+                for path in &orphaned {
+                    println!("{}\t{}", state, path.display());
+                }
+            } else if orphaned.is_empty() {
+                println!("{}", t!("clean-nothing"));
+            } else if let Some(summary) = &summary {
+                println!("{}", t!("clean-done", count = summary.removed.len()));
+            } else {
+                println!("{}", t!("clean-preview", count = orphaned.len()));
+            }
+        }
+
+        Commands::Cache { command } => match command {
+            CacheCommands::Clear => {
+                debug!("Running cache clear command");
+
+                match clear_cache(&config) {
+                    Ok(cleared) if cli.porcelain => println!("cleared\t{}", cleared),
+                    Ok(true) => println!("{}", t!("cache-cleared")),
+                    Ok(false) => println!("{}", t!("cache-empty")),
+                    Err(err) => {
+                        fail(
+                            t!("failed-clear-cache", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        },
+
+        Commands::Replay { session: None } => {
+            debug!("Running replay command to list sessions");
+
+            let sessions = match list_sessions(&config) {
+                Ok(sessions) => sessions,
+                Err(err) => {
+                    fail(
+                        t!("failed-list-sessions", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&sessions);
+            } else if cli.porcelain {
+                for session in &sessions {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        session.id,
+                        session.timestamp.to_rfc3339(),
+                        session.commit.as_deref().unwrap_or("-"),
+                        session.restored.len(),
+                        session.request.command
+                    );
+                }
+            } else if sessions.is_empty() {
+                println!("{}", t!("sessions-empty"));
+            } else {
+                for session in &sessions {
+                    print_item(
+                        &config,
+                        t!(
+                            "session-item",
+                            id = session.id.as_str(),
+                            time = format_timestamp(&session.timestamp, &config),
+                            host = session.hostname.as_deref().unwrap_or("?"),
+                            command = session.request.command.as_str(),
+                            count = session.restored.len()
+                        ),
+                    );
+                }
+            }
+        }
+
+        Commands::Replay {
+            session: Some(session),
+        } => {
+            debug!("Running replay command for session {}", session);
+
+            let replayed = load_session(&config, &session).and_then(|loaded| {
+                replay_session(&config, &loaded).map(|summary| (loaded, summary))
+            });
+            let (loaded, summary) = match replayed {
+                Ok(replayed) => replayed,
+                Err(err) => {
+                    fail(
+                        t!("failed-replay", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if !config.dry_run {
+                let request = SessionRequest {
+                    command: format!("replay {}", loaded.id),
+                    version: loaded.commit.clone(),
+                    ..loaded.request.clone()
+                };
+                report_session(record_session(&config, request, &summary));
+            }
+
+            report_restore_summary(&summary, &config, cli.format, cli.porcelain);
+        }
+
+        Commands::Undo { force } => {
+            debug!("Running undo command");
+
+            let record = match undo_last_restore(&config, force) {
+                Ok(record) => record,
+                Err(err) => {
+                    fail(t!("failed-undo", error = err.to_string()), err.error_code());
+                }
+            };
+
+            if cli.format == OutputFormat::Json {
+                print_json(&record);
+            } else if cli.porcelain {
+                for file in &record.files {
+                    let action = if file.existed { "restored" } else { "removed" };
+                    println!("{}\t{}", action, file.path.display());
+                }
+            } else {
+                for file in &record.files {
+                    let path = file.path.display().to_string();
+                    if file.existed {
+                        print_item(&config, t!("undo-restored", path = path));
+                    } else {
+                        print_item(&config, t!("undo-removed", path = path));
+                    }
+                }
+                let time = format_timestamp(&record.timestamp, &config);
+                if config.dry_run {
+                    println!("{}", t!("undo-dry-run", time = time));
+                } else {
+                    println!(
+                        "{}",
+                        t!("undo-done", time = time, count = record.files.len())
+                    );
+                }
+            }
+        }
+
+        Commands::Rollback {
+            commit: Some(commit),
+            restore,
+            yes,
+            ..
+        } => {
+            debug!("Running rollback command for the vault to {}", commit);
+
+            let plan = match plan_vault_rollback(&config, &commit) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    fail(
+                        t!("failed-plan-rollback", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            println!(
+                "{}",
+                t!(
+                    "rollback-vault-header",
+                    commit = plan.version.commit_id.as_str(),
+                    time = format_timestamp(&plan.version.timestamp, &config),
+                    message = plan.version.message.trim()
+                )
+            );
+            for path in &plan.changed {
+                print_item(&config, path.display().to_string());
+            }
+
+            if plan.changed.is_empty() {
+                println!("{}", t!("rollback-nothing"));
+            } else if !yes || config.dry_run {
+                println!("{}", t!("rollback-preview", count = plan.changed.len()));
+            } else {
+                let committed = match apply_vault_rollback(&config, &plan) {
+                    Ok(committed) => committed.unwrap_or_default(),
+                    Err(err) => {
+                        fail(
+                            t!("failed-apply-rollback", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+                println!(
+                    "{}",
+                    t!("rollback-vault-committed", commit = committed.as_str())
+                );
+
+                if restore {
+                    let summary = match restore_files_at(&config, &plan.files, &committed) {
+                        Ok(summary) => summary,
+                        Err(err) => {
+                            fail(
+                                t!("failed-restore-dotfiles", error = err.to_string()),
+                                err.error_code(),
+                            );
+                        }
+                    };
+                    let request = SessionRequest {
+                        command: format!("rollback {} --restore", commit),
+                        version: Some(committed),
+                        profile: config.profile.clone(),
+                        on_conflict: config.on_conflict,
+                        ..SessionRequest::default()
+                    };
+                    report_session(record_session(&config, request, &summary));
+                    report_restore_summary(&summary, &config, cli.format, cli.porcelain);
+                }
+            }
+        }
+
+        Commands::Rollback {
+            group: Some(group),
+            at: Some(at),
+            yes,
+            no_mask,
+            ..
+        } => {
+            debug!("Running rollback command for group: {}", group);
+
+            let plan = match plan_group_rollback(&config, &group, at) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    fail(
+                        t!("failed-plan-rollback", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            println!(
+                "{}",
+                t!(
+                    "rollback-header",
+                    group = plan.group.as_str(),
+                    commit = plan.version.commit_id.as_str(),
+                    time = format_timestamp(&plan.version.timestamp, &config)
+                )
+            );
+
+            for item in &plan.items {
+                match item.action {
+                    RollbackAction::Restore => {
+                        if !no_mask && is_sensitive_file(&item.entry.source) {
+                            print!("{}", mask_secrets(&item.diff));
+                        } else {
+                            print!("{}", item.diff);
+                        }
+                    }
+                    RollbackAction::Unchanged => {
+                        println!("{}", t!("rollback-unchanged", id = item.entry.id.as_str()))
+                    }
+                    RollbackAction::Missing => {
+                        println!("{}", t!("rollback-missing", id = item.entry.id.as_str()))
+                    }
+                }
+            }
+
+            if plan.change_count() == 0 {
+                println!("{}", t!("rollback-nothing"));
+            } else if !yes {
+                println!("{}", t!("rollback-preview", count = plan.change_count()));
+            } else {
+                match apply_rollback(&config, &plan) {
+                    Ok(count) => info!("Rolled back {} files", count),
+                    Err(err) => {
+                        fail(
+                            t!("failed-apply-rollback", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::Rollback { .. } => unreachable!("clap requires a commit, or --group with --at"),
+
+        Commands::Restore {
+            file,
+            all: _,
+            snapshot,
+            login,
+            budget,
+            version,
+            bwlimit,
+            on_conflict,
+            force,
+        } => {
+            if bwlimit.is_some() {
+                config.bwlimit = bwlimit;
+            }
+            if let Some(on_conflict) = on_conflict {
+                config.on_conflict = on_conflict;
+            }
+            if force {
+                config.on_conflict = ConflictPolicy::Overwrite;
+            }
+
+            if config.layout == VaultLayout::Bare {
+                debug!("Running restore command for the bare vault");
+                let result = bare_restore(&config);
+                let summary = match result {
+                    Ok(summary) => summary,
+                    Err(err) => {
+                        fail(
+                            t!("failed-restore-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if cli.format == OutputFormat::Json {
+                    print_json(&summary);
+                } else if cli.porcelain {
+                    for path in &summary.files {
+                        println!("restored\t{}", path.display());
+                    }
+                    for path in &summary.skipped {
+                        println!("conflict\t{}", path.display());
+                    }
+                } else {
+                    for path in &summary.files {
+                        print_item(&config, path.display().to_string());
+                    }
+                    for path in &summary.skipped {
+                        println!(
+                            "{}",
+                            t!("restore-conflict-kept", path = path.display().to_string())
+                        );
+                    }
+                    if config.dry_run {
+                        println!(
+                            "{}",
+                            t!("bare-restore-dry-run", count = summary.files.len())
+                        );
+                    } else {
+                        println!("{}", t!("bare-restore-done", count = summary.files.len()));
+                    }
+                }
+
+                if !summary.skipped.is_empty() && !config.dry_run {
+                    ExitCode::Conflicts.exit();
+                }
+                return Ok(());
+            }
+
+            let Some(file) = file else {
+                let result = match &snapshot {
+                    _ if login => {
+                        debug!(
+                            "Running restore command for drifted dotfiles within {:?}",
+                            budget
+                        );
+                        restore_drifted(&config, budget)
+                    }
+                    Some(name) => {
+                        debug!("Running restore command for snapshot {}", name);
+                        restore_snapshot(&config, name)
+                    }
+                    None => {
+                        debug!("Running restore command for all dotfiles");
+                        restore_all_dotfiles(&config)
+                    }
+                };
+
+                let summary = match result {
+                    Ok(summary) => summary,
+                    Err(err) => {
+                        fail(
+                            t!("failed-restore-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if !config.dry_run {
+                    let command = match (&snapshot, login) {
+                        (_, true) => "restore --login".to_string(),
+                        (Some(name), _) => format!("restore --snapshot {}", name),
+                        (None, _) => "restore --all".to_string(),
+                    };
+                    let request = SessionRequest {
+                        command,
+                        snapshot: snapshot.clone(),
+                        profile: config.profile.clone(),
+                        on_conflict: config.on_conflict,
+                        ..SessionRequest::default()
+                    };
+                    report_session(record_session(&config, request, &summary));
+                }
+
+                report_restore_summary(&summary, &config, cli.format, cli.porcelain);
+                return Ok(());
+            };
+
+            if is_glob(&file) {
+                debug!("Running restore command for pattern: {}", file);
+
+                let result = expand_vault_glob(&config, &file).and_then(|files| match &version {
+                    Some(version) => open_backend(&config)
+                        .and_then(|backend| backend.resolve_version(version))
+                        .and_then(|version| restore_files_at(&config, &files, &version.commit_id)),
+                    None => restore_files(&config, &files),
+                });
+                let summary = match result {
+                    Ok(summary) => summary,
+                    Err(err) => {
+                        fail(
+                            t!("failed-restore-dotfiles", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                };
+
+                if !config.dry_run {
+                    let command = match &version {
+                        Some(version) => format!("restore {} --version {}", file, version),
+                        None => format!("restore {}", file),
+                    };
+                    let request = SessionRequest {
+                        command,
+                        version: version.clone(),
+                        profile: config.profile.clone(),
+                        on_conflict: config.on_conflict,
+                        ..SessionRequest::default()
+                    };
+                    report_session(record_session(&config, request, &summary));
+                }
+
+                report_restore_summary(&summary, &config, cli.format, cli.porcelain);
+                return Ok(());
+            }
+
+            debug!("Running restore command for file: {}", file);
+
+            let restore = |config: &Config| match &version {
+                Some(version) => restore_dotfile_version(config, &file, version),
+                None => restore_specific_dotfile(config, &file),
+            };
+
+            let mut result = restore(&config);
+            if matches!(result, Err(DotfilesError::RestoreConflict(_)))
+                && cli.format != OutputFormat::Json
+                && !cli.porcelain
+                && let Some(policy) = ask_restore_conflict(&config, &file)
+            {
+                config.on_conflict = policy;
+                result = restore(&config);
+            }
+
+            let plan = match result {
+                Ok(plan) => plan,
+                Err(err @ DotfilesError::RestoreConflict(_)) => {
+                    report_error(&err.to_string(), err.code());
+                    eprintln!("{}", t!("restore-conflict-hint", file = file.as_str()));
+                    ExitCode::Conflicts.exit();
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-restore-dotfile", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            };
+
+            if !config.dry_run {
+                let command = match &version {
+                    Some(version) => format!("restore {} --version {}", file, version),
+                    None => format!("restore {}", file),
+                };
+                let request = SessionRequest {
+                    command,
+                    file: Some(file.clone()),
+                    version: version.clone(),
+                    profile: config.profile.clone(),
+                    on_conflict: config.on_conflict,
+                    ..SessionRequest::default()
+                };
+                report_session(record_file_session(&config, request, &plan));
+            }
+
+            if cli.format == OutputFormat::Json {
+                print_json(&plan);
+                if plan
+                    .copies
+                    .iter()
+                    .any(|copy| copy.action == PlannedAction::Quarantine)
+                {
+                    ExitCode::Conflicts.exit();
+                }
+                return Ok(());
+            }
+
+            if config.dry_run {
+                print_plan(&plan, cli.porcelain);
+                return Ok(());
+            }
+
+            let quarantined: Vec<_> = plan
+                .copies
+                .iter()
+                .filter(|copy| copy.action == PlannedAction::Quarantine)
+                .collect();
+            if quarantined.is_empty() {
+                info!("Restored dotfile: {}", file);
+            } else {
+                for copy in quarantined {
+                    println!(
+                        "{}",
+                        t!(
+                            "restore-kept-local-edits-to",
+                            file = file.as_str(),
+                            quarantined = copy.destination.display().to_string()
+                        )
+                    );
+                }
+                println!("{}", t!("restore-merge-hint", file = file.as_str()));
+                ExitCode::Conflicts.exit();
+            }
+        }
+
+        Commands::Export {
+            output,
+            history,
+            changed_between,
+        } => {
+            debug!("Running export command to {:?}", output);
+
+            if changed_between.is_empty() {
+                match export_vault(&config, &output, history) {
+                    Ok(summary) => {
+                        if cli.porcelain {
+                            for path in &summary.exported {
+                                println!("exported\t{}", path.display());
+                            }
+                        } else {
+                            println!(
+                                "{}",
+                                t!(
+                                    "export-done",
+                                    count = summary.exported.len(),
+                                    history = summary.history.to_string(),
+                                    output = output.display().to_string()
+                                )
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        fail(
+                            t!("failed-export", error = err.to_string()),
+                            err.error_code(),
+                        );
+                    }
+                }
+                return Ok(());
+            }
+
+            let (from, to) = (changed_between[0], changed_between[1]);
+
+            match export_changed_between(&config, from, to, &output) {
+                Ok(summary) => {
+                    if cli.porcelain {
+                        for path in &summary.exported {
+                            println!("exported\t{}", path.display());
+                        }
+                        for path in &summary.deleted {
+                            println!("deleted\t{}", path.display());
+                        }
+                    } else {
+                        println!(
+                            "{}",
+                            t!(
+                                "export-changed-done",
+                                count = summary.exported.len(),
+                                output = output.display().to_string()
+                            )
+                        );
+                        for path in &summary.deleted {
+                            print_item(
+                                &config,
+                                t!("export-deleted", path = path.display().to_string()),
+                            );
+                        }
+                    }
+                }
+                Err(err) => {
+                    fail(
+                        t!("failed-export", error = err.to_string()),
+                        err.error_code(),
+                    );
+                }
+            }
+        }
+
+        Commands::External(args) => {
+            let name = args[0].to_string_lossy().into_owned();
+            debug!("Running plugin command: {}", name);
+
+            let Some(plugin) = find_plugin(&name) else {
+                fail(
+                    t!("unknown-command", name = name.as_str()),
+                    "E_UNKNOWN_COMMAND",
+                );
+            };
+
+            let mut context = PluginContext::new(&config);
+            context.verbose = cli.verbose;
+            context.porcelain = cli.porcelain;
+
+            match run_plugin(&plugin, &args[1..], &context) {
+                Ok(code) => std::process::exit(code),
+                Err(err) => {
+                    fail(
+                        t!(
+                            "failed-run-plugin",
+                            name = name.as_str(),
+                            error = err.to_string()
+                        ),
+                        err.error_code(),
+                    );
+                }
+            }
         }
     }
 
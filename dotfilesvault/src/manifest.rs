@@ -0,0 +1,695 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backup::{Dotfile, directory_files, is_own_path, own_paths};
+use crate::profile::PROFILES_DIR;
+use crate::symlink::is_preserved_link;
+use crate::template::{is_template, template_path};
+use crate::unicode::nfc_path;
+use crate::utils::{normalize_path, write_file};
+use crate::{Config, DotfilesError, is_vault_internal};
+
+/// Name of the directory inside the vault holding dotfilesvault state
+pub const STATE_DIR: &str = ".dotfilesvault";
+
+/// Name of the manifest file inside the state directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Current version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// A condition that must hold on the current machine for an entry to apply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum Condition {
+    /// The operating system matches, e.g. `linux` or `macos`
+    Os(String),
+
+    /// The machine's hostname matches
+    Hostname(String),
+
+    /// The environment variable is set to a non-empty value
+    Env(String),
+}
+
+impl Condition {
+    /// Check if the condition holds on this machine
+    pub fn is_met(&self) -> bool {
+        match self {
+            Condition::Os(os) => std::env::consts::OS == os,
+            Condition::Hostname(hostname) => {
+                crate::utils::hostname().as_deref() == Some(hostname.as_str())
+            }
+            Condition::Env(name) => std::env::var_os(name).is_some_and(|value| !value.is_empty()),
+        }
+    }
+}
+
+/// How file permissions are handled when a file is restored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum PermissionsPolicy {
+    /// Keep the permission bits of the vault copy
+    #[default]
+    Preserve,
+
+    /// Make the restored file readable and writable by the owner only
+    Private,
+
+    /// Set an explicit mode, e.g. `0o644`
+    Mode(u32),
+}
+
+/// How often a tracked file is backed up by scheduled and watch-mode backups
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupFrequency {
+    /// Back up on every change
+    #[default]
+    OnChange,
+
+    /// Back up at most once an hour
+    Hourly,
+
+    /// Back up at most once a day
+    Daily,
+
+    /// Back up at most once a week
+    Weekly,
+}
+
+impl BackupFrequency {
+    /// Get the minimum time between two backups, if any
+    pub fn interval(self) -> Option<Duration> {
+        match self {
+            BackupFrequency::OnChange => None,
+            BackupFrequency::Hourly => Some(Duration::hours(1)),
+            BackupFrequency::Daily => Some(Duration::days(1)),
+            BackupFrequency::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+
+    /// Check if a file last backed up at `last_backup` should be backed up at `now`
+    pub fn is_due(self, last_backup: Option<DateTime<Local>>, now: DateTime<Local>) -> bool {
+        match (self.interval(), last_backup) {
+            (Some(interval), Some(last_backup)) => now - last_backup >= interval,
+            _ => true,
+        }
+    }
+
+    /// Get the name of the frequency as used in the manifest and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            BackupFrequency::OnChange => "on-change",
+            BackupFrequency::Hourly => "hourly",
+            BackupFrequency::Daily => "daily",
+            BackupFrequency::Weekly => "weekly",
+        }
+    }
+}
+
+impl FromStr for BackupFrequency {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "on-change" => Ok(BackupFrequency::OnChange),
+            "hourly" => Ok(BackupFrequency::Hourly),
+            "daily" => Ok(BackupFrequency::Daily),
+            "weekly" => Ok(BackupFrequency::Weekly),
+            _ => Err(format!(
+                "invalid frequency {:?}, expected on-change, hourly, daily or weekly",
+                value
+            )),
+        }
+    }
+}
+
+/// A tracked dotfile as recorded in the vault manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DotfileEntry {
+    /// Stable identifier of the entry
+    pub id: String,
+
+    /// Path of the file relative to the home directory
+    #[serde(serialize_with = "serialize_portable")]
+    pub source: PathBuf,
+
+    /// Path of the stored copy relative to the vault directory
+    #[serde(serialize_with = "serialize_portable")]
+    pub target: PathBuf,
+
+    /// Group the entry belongs to, e.g. `shell` or `editor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Conditions that must all hold for the entry to be backed up or restored
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+
+    /// Names of transform plugins applied on backup (and reversed on restore)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<String>,
+
+    /// How permissions are applied on restore
+    #[serde(default)]
+    pub permissions: PermissionsPolicy,
+
+    /// How often the entry is backed up by `backup --due`
+    #[serde(default)]
+    pub frequency: BackupFrequency,
+
+    /// Profiles with their own variant of the file, stored under `profiles/<name>/`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+
+    /// Whether the stored copy is starter content, restored only where the
+    /// home file doesn't exist yet and never replaced by backups
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub create_if_missing: bool,
+
+    /// Whether the home file is a symlink to the stored copy, as left by
+    /// `adopt`, so edits go straight into the vault
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub linked: bool,
+}
+
+impl DotfileEntry {
+    /// Create an entry for a dotfile with default settings
+    ///
+    /// If the vault holds a `<target>.tmpl` template and no plain copy, the
+    /// template becomes the entry's target.
+    pub fn new(dotfile: &Dotfile, config: &Config) -> Self {
+        let source = nfc_path(
+            dotfile
+                .original_path
+                .strip_prefix(&config.home_dir)
+                .unwrap_or(&dotfile.original_path),
+        );
+        let mut target = dotfile
+            .vault_path
+            .strip_prefix(&config.vault_dir)
+            .unwrap_or(&dotfile.vault_path)
+            .to_path_buf();
+
+        let template = template_path(&target);
+        if !dotfile.vault_path.exists() && config.vault_dir.join(&template).is_file() {
+            target = template;
+        }
+
+        Self {
+            id: entry_id(&source),
+            source,
+            target,
+            group: None,
+            conditions: Vec::new(),
+            transforms: Vec::new(),
+            permissions: PermissionsPolicy::default(),
+            frequency: BackupFrequency::default(),
+            profiles: Vec::new(),
+            create_if_missing: false,
+            linked: false,
+        }
+    }
+
+    /// Get the vault-relative path of the copy used on this machine
+    ///
+    /// This is the active profile's variant if the entry has one, and the
+    /// common copy otherwise.
+    pub fn stored_path(&self, config: &Config) -> PathBuf {
+        match &config.profile {
+            Some(profile) if self.profiles.contains(profile) => {
+                Path::new(PROFILES_DIR).join(profile).join(&self.target)
+            }
+            _ => self.target.clone(),
+        }
+    }
+
+    /// Resolve the entry into absolute home and vault paths
+    pub fn dotfile(&self, config: &Config) -> Dotfile {
+        Dotfile {
+            original_path: config.home_dir.join(&self.source),
+            vault_path: config.vault_dir.join(self.stored_path(config)),
+        }
+    }
+
+    /// Check if the home file of a linked entry is still a symlink to its
+    /// stored copy
+    pub fn links_to_vault(&self, config: &Config) -> bool {
+        let dotfile = self.dotfile(config);
+        self.linked
+            && dotfile.original_path.is_symlink()
+            && matches!(
+                (
+                    fs::canonicalize(&dotfile.original_path),
+                    fs::canonicalize(&dotfile.vault_path),
+                ),
+                (Ok(home), Ok(vault)) if home == vault
+            )
+    }
+
+    /// Check if the entry is stored as a template rendered on restore
+    pub fn is_template(&self) -> bool {
+        is_template(&self.target)
+    }
+
+    /// Check if all of the entry's conditions hold on this machine
+    pub fn applies(&self) -> bool {
+        self.conditions.iter().all(Condition::is_met)
+    }
+}
+
+/// The set of tracked dotfiles, persisted in the vault
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of the manifest format
+    pub version: u32,
+
+    /// Tracked entries keyed by id
+    #[serde(default)]
+    pub entries: BTreeMap<String, DotfileEntry>,
+
+    /// Home-relative directories whose files are all tracked, e.g. `.config/nvim`
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeSet::is_empty",
+        serialize_with = "serialize_portable_set"
+    )]
+    pub directories: BTreeSet<PathBuf>,
+
+    /// Vault-relative files that aren't tracked but are kept on purpose, e.g. a README
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeSet::is_empty",
+        serialize_with = "serialize_portable_set"
+    )]
+    pub ignored: BTreeSet<PathBuf>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: BTreeMap::new(),
+            directories: BTreeSet::new(),
+            ignored: BTreeSet::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Get the path of the manifest file for a vault
+    pub fn path(config: &Config) -> PathBuf {
+        config.vault_dir.join(STATE_DIR).join(MANIFEST_FILE)
+    }
+
+    /// Load the manifest from the vault, or an empty one if none exists yet
+    pub fn load(config: &Config) -> Result<Self, DotfilesError> {
+        let path = Self::path(config);
+
+        if !path.exists() {
+            debug!("No manifest at {:?}, starting empty", path);
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+
+        serde_json::from_str(&content)
+            .map_err(|err| DotfilesError::InvalidManifest(err.to_string()))
+    }
+
+    /// Save the manifest into the vault
+    pub fn save(&self, config: &Config) -> Result<(), DotfilesError> {
+        let path = Self::path(config);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = serde_json::to_string_pretty(self)
+            .map_err(|err| DotfilesError::InvalidManifest(err.to_string()))?;
+        content.push('\n');
+
+        write_file(&path, content.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Record a directory whose files are all tracked, now and in future backups
+    pub fn track_directory(&mut self, dir: &Path, config: &Config) {
+        let relative = dir.strip_prefix(&config.home_dir).unwrap_or(dir);
+        self.directories.insert(relative.to_path_buf());
+    }
+
+    /// Record a dotfile, keeping any settings of an existing entry
+    pub fn track(&mut self, dotfile: &Dotfile, config: &Config) -> &DotfileEntry {
+        let entry = DotfileEntry::new(dotfile, config);
+
+        self.entries.entry(entry.id.clone()).or_insert(entry)
+    }
+
+    /// Find the entry for a home-relative path
+    pub fn find_by_source<P: AsRef<Path>>(&self, source: P) -> Option<&DotfileEntry> {
+        self.entries.get(&entry_id(source.as_ref()))
+    }
+
+    /// Check if a home path is tracked, as an entry or inside a tracked directory
+    pub fn tracks(&self, path: &Path, config: &Config) -> bool {
+        let relative = path.strip_prefix(&config.home_dir).unwrap_or(path);
+
+        self.find_by_source(relative).is_some()
+            || self.directories.iter().any(|dir| relative.starts_with(dir))
+    }
+
+    /// Find the entry for a dotfile, or create an untracked default one
+    pub fn entry_for(&self, dotfile: &Dotfile, config: &Config) -> DotfileEntry {
+        let entry = DotfileEntry::new(dotfile, config);
+
+        self.entries.get(&entry.id).cloned().unwrap_or(entry)
+    }
+}
+
+/// Assign tracked dotfiles to a group and save the manifest
+pub fn set_group(config: &Config, files: &[String], group: &str) -> Result<(), DotfilesError> {
+    update_entries(config, files, |entry| entry.group = Some(group.to_string()))
+}
+
+/// Set the backup frequency of tracked dotfiles and save the manifest
+pub fn set_frequency(
+    config: &Config,
+    files: &[String],
+    frequency: BackupFrequency,
+) -> Result<(), DotfilesError> {
+    update_entries(config, files, |entry| entry.frequency = frequency)
+}
+
+/// Register files or directories in the home directory for tracking and
+/// save the manifest
+///
+/// Unlike dotfiles, they don't need a leading dot, e.g. `~/bin/setup.sh`.
+/// Directories are tracked as a whole, including files added to them later.
+/// The files are copied by the next backup. Paths the vault keeps its own
+/// files at, like `~/hooks`, are refused with `VaultInternalPath`. Returns
+/// the home-relative paths registered.
+pub fn add_paths(config: &Config, files: &[String]) -> Result<Vec<PathBuf>, DotfilesError> {
+    let mut manifest = Manifest::load(config)?;
+    let own = own_paths(config);
+    let mut added = Vec::new();
+
+    for file in files {
+        let path = normalize_path(file, config);
+        if !path.exists() && !path.is_symlink() {
+            return Err(DotfilesError::DotfileNotFound(file.clone()));
+        }
+        let Ok(relative) = path.strip_prefix(&config.home_dir) else {
+            return Err(DotfilesError::DotfileNotFound(format!(
+                "{} (outside the home directory)",
+                file
+            )));
+        };
+        if relative.as_os_str().is_empty() || is_own_path(&own, &path) {
+            warn!(
+                "Not adding {:?}: dotfilesvault keeps its own state there",
+                path
+            );
+            continue;
+        }
+        // Stored there, a file would be taken for a hook, plugin or the like
+        if is_vault_internal(relative) {
+            return Err(DotfilesError::VaultInternalPath(file.clone()));
+        }
+
+        if path.is_dir() && !is_preserved_link(config, &path) {
+            manifest.track_directory(&path, config);
+            for file in directory_files(config, &path)? {
+                manifest.track(&Dotfile::new(file, config), config);
+            }
+        } else {
+            manifest.track(&Dotfile::new(path.clone(), config), config);
+        }
+
+        debug!("Added {:?} for tracking", relative);
+        added.push(relative.to_path_buf());
+    }
+
+    manifest.save(config)?;
+
+    Ok(added)
+}
+
+/// Store starter content for a dotfile, restored only where it doesn't exist
+///
+/// The content is read from `from`, or the home file itself. A `from` ending
+/// in `.tmpl` is stored as a template, rendered on restore. The entry keeps
+/// any other settings it had, and the manifest is saved.
+pub fn track_starter(
+    config: &Config,
+    file: &str,
+    from: Option<&Path>,
+) -> Result<DotfileEntry, DotfilesError> {
+    let path = normalize_path(file, config);
+    let source = from.map_or_else(|| path.clone(), |from| normalize_path(from, config));
+    let content = fs::read(&source)
+        .map_err(|_| DotfilesError::DotfileNotFound(source.display().to_string()))?;
+
+    let mut manifest = Manifest::load(config)?;
+    let mut entry = manifest.entry_for(&Dotfile::new(path, config), config);
+    if from.is_some_and(is_template) && !entry.is_template() {
+        entry.target = template_path(&entry.target);
+    }
+    entry.create_if_missing = true;
+
+    let stored = config.vault_dir.join(entry.stored_path(config));
+    if let Some(parent) = stored.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&stored, &content)?;
+
+    manifest.entries.insert(entry.id.clone(), entry.clone());
+    manifest.save(config)?;
+
+    Ok(entry)
+}
+
+/// Turn starter content back into normally tracked dotfiles and save the manifest
+pub fn untrack_starter(config: &Config, files: &[String]) -> Result<(), DotfilesError> {
+    update_entries(config, files, |entry| entry.create_if_missing = false)
+}
+
+/// Update the entries of tracked dotfiles and save the manifest
+fn update_entries<F>(config: &Config, files: &[String], mut update: F) -> Result<(), DotfilesError>
+where
+    F: FnMut(&mut DotfileEntry),
+{
+    let mut manifest = Manifest::load(config)?;
+
+    for file in files {
+        let path = normalize_path(file, config);
+        let source = path.strip_prefix(&config.home_dir).unwrap_or(&path);
+
+        let entry = manifest
+            .entries
+            .get_mut(&entry_id(source))
+            .ok_or_else(|| DotfilesError::DotfileNotFound(file.clone()))?;
+        update(entry);
+    }
+
+    manifest.save(config)
+}
+
+/// Write a path with `/` separators, so manifests written on Windows work elsewhere
+fn portable_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.into_owned()
+    }
+}
+
+/// Serialize a path with `/` separators
+fn serialize_portable<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&portable_path(path))
+}
+
+/// Serialize paths with `/` separators
+fn serialize_portable_set<S: Serializer>(
+    paths: &BTreeSet<PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| portable_path(path)))
+}
+
+/// Get the entry id for a home-relative path
+///
+/// Ids are in composed Unicode form, so a file gets the same id on macOS.
+pub(crate) fn entry_id(source: &Path) -> String {
+    nfc_path(source)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_env() -> (Config, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+
+        (config, temp_dir)
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let (config, _temp_dir) = setup_test_env();
+
+        let mut manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.entries.is_empty());
+
+        let dotfile = Dotfile::new(config.home_dir.join(".config/fish/config.fish"), &config);
+        manifest.track(&dotfile, &config);
+        manifest.save(&config).unwrap();
+
+        let loaded = Manifest::load(&config).unwrap();
+        let entry = loaded.find_by_source(".config/fish/config.fish").unwrap();
+
+        assert_eq!(loaded, manifest);
+        assert_eq!(entry.id, ".config/fish/config.fish");
+        assert_eq!(entry.target, PathBuf::from(".config/fish/config.fish"));
+        assert_eq!(entry.dotfile(&config).vault_path, dotfile.vault_path);
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_add_paths() {
+        let (config, _temp_dir) = setup_test_env();
+        let script = config.home_dir.join("bin/setup.sh");
+        let code = config
+            .home_dir
+            .join("Library/Application Support/Code/User");
+        fs::create_dir_all(script.parent().unwrap()).unwrap();
+        fs::create_dir_all(&code).unwrap();
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::write(code.join("settings.json"), "{}").unwrap();
+
+        let added = add_paths(
+            &config,
+            &["bin/setup.sh".to_string(), code.display().to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            added,
+            vec![
+                PathBuf::from("bin/setup.sh"),
+                PathBuf::from("Library/Application Support/Code/User"),
+            ]
+        );
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.tracks(&script, &config));
+        assert!(manifest.tracks(&code.join("keybindings.json"), &config));
+        assert!(!manifest.tracks(&config.home_dir.join("bin/other.sh"), &config));
+
+        // Later backups copy them, files added to the directory too
+        fs::write(code.join("keybindings.json"), "[]").unwrap();
+        crate::backup::backup_all_dotfiles(&config).unwrap();
+        assert!(config.vault_dir.join("bin/setup.sh").is_file());
+        assert!(
+            config
+                .vault_dir
+                .join("Library/Application Support/Code/User/keybindings.json")
+                .is_file()
+        );
+
+        assert!(matches!(
+            add_paths(&config, &["missing".to_string()]),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+
+        // A home file stored as a hook would run on the next backup
+        let hook = config.home_dir.join("hooks/pre-backup");
+        fs::create_dir_all(hook.parent().unwrap()).unwrap();
+        fs::write(&hook, "#!/bin/sh").unwrap();
+        for file in ["hooks/pre-backup", "hooks"] {
+            assert!(matches!(
+                add_paths(&config, &[file.to_string()]),
+                Err(DotfilesError::VaultInternalPath(_))
+            ));
+        }
+        assert!(!Manifest::load(&config).unwrap().tracks(&hook, &config));
+    }
+
+    #[test]
+    fn test_track_keeps_existing_settings() {
+        let (config, _temp_dir) = setup_test_env();
+        let dotfile = Dotfile::new(config.home_dir.join(".zshrc"), &config);
+
+        let mut manifest = Manifest::default();
+        let id = manifest.track(&dotfile, &config).id.clone();
+        manifest.entries.get_mut(&id).unwrap().group = Some("shell".to_string());
+
+        assert_eq!(
+            manifest.track(&dotfile, &config).group.as_deref(),
+            Some("shell")
+        );
+    }
+
+    #[test]
+    fn test_set_group() {
+        let (config, _temp_dir) = setup_test_env();
+        let dotfile = Dotfile::new(config.home_dir.join(".zshrc"), &config);
+
+        let mut manifest = Manifest::default();
+        manifest.track(&dotfile, &config);
+        manifest.save(&config).unwrap();
+
+        set_group(&config, &[".zshrc".to_string()], "shell").unwrap();
+        let entry = Manifest::load(&config).unwrap().entries[".zshrc"].clone();
+        assert_eq!(entry.group.as_deref(), Some("shell"));
+
+        assert!(matches!(
+            set_group(&config, &[".untracked".to_string()], "shell"),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_backup_frequency() {
+        let now = Local::now();
+
+        assert!(BackupFrequency::OnChange.is_due(Some(now), now));
+        assert!(BackupFrequency::Daily.is_due(None, now));
+        assert!(!BackupFrequency::Daily.is_due(Some(now - Duration::hours(23)), now));
+        assert!(BackupFrequency::Daily.is_due(Some(now - Duration::hours(24)), now));
+
+        assert_eq!("weekly".parse(), Ok(BackupFrequency::Weekly));
+        assert!("sometimes".parse::<BackupFrequency>().is_err());
+    }
+
+    #[test]
+    fn test_conditions() {
+        let (config, _temp_dir) = setup_test_env();
+        let dotfile = Dotfile::new(config.home_dir.join(".zshrc"), &config);
+        let mut entry = DotfileEntry::new(&dotfile, &config);
+
+        assert!(entry.applies());
+
+        entry.conditions = vec![Condition::Os(std::env::consts::OS.to_string())];
+        assert!(entry.applies());
+
+        entry.conditions.push(Condition::Env(
+            "DOTFILESVAULT_TEST_UNSET_VARIABLE".to_string(),
+        ));
+        assert!(!entry.applies());
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+
+/// Replacement text for masked values
+pub const MASK: &str = "********";
+
+/// File names whose contents commonly carry credentials
+const SENSITIVE_FILE_NAMES: [&str; 8] = [
+    ".npmrc",
+    ".pypirc",
+    ".netrc",
+    ".git-credentials",
+    ".pgpass",
+    ".env",
+    ".s3cfg",
+    ".authinfo",
+];
+
+/// Home-relative path suffixes whose contents commonly carry credentials
+const SENSITIVE_PATH_SUFFIXES: [&str; 3] = [
+    ".aws/credentials",
+    ".docker/config.json",
+    ".config/gh/hosts.yml",
+];
+
+/// Key fragments that mark a `key = value` pair as secret
+const SECRET_KEY_FRAGMENTS: [&str; 7] = [
+    "token", "password", "passwd", "secret", "auth", "apikey", "api_key",
+];
+
+/// Keywords in netrc-style files that are followed by a secret
+const NETRC_SECRET_KEYWORDS: [&str; 2] = ["password", "account"];
+
+/// Check if a dotfile is likely to carry secrets based on its name
+pub fn is_sensitive_file<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+
+    let name_matches = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| SENSITIVE_FILE_NAMES.contains(&name))
+        .unwrap_or(false);
+
+    name_matches
+        || SENSITIVE_PATH_SUFFIXES
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+}
+
+/// Mask token-looking values in file content
+pub fn mask_secrets(content: &str) -> String {
+    let mut masked: Vec<String> = content.lines().map(mask_line).collect();
+
+    if content.ends_with('\n') {
+        masked.push(String::new());
+    }
+
+    masked.join("\n")
+}
+
+/// Mask the secret part of a single line, if any
+fn mask_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return line.to_string();
+    }
+
+    // `key = value`, `key: value` and `"key": "value"` pairs
+    if let Some(index) = line.find('=').or_else(|| line.find(':')) {
+        let key = line[..index].to_ascii_lowercase();
+        let value = line[index + 1..].trim();
+
+        if !value.is_empty()
+            && SECRET_KEY_FRAGMENTS
+                .iter()
+                .any(|fragment| key.contains(fragment))
+        {
+            let trailing_comma = if value.ends_with(',') { "," } else { "" };
+            let quote = if value.starts_with('"') { "\"" } else { "" };
+            let separator = &line[index..=index];
+            let spacing = if line[index + 1..].starts_with(' ') {
+                " "
+            } else {
+                ""
+            };
+
+            return format!(
+                "{}{}{}{}{}{}{}",
+                &line[..index],
+                separator,
+                spacing,
+                quote,
+                MASK,
+                quote,
+                trailing_comma
+            );
+        }
+    }
+
+    // netrc-style `machine host login user password secret`
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words
+        .iter()
+        .any(|word| NETRC_SECRET_KEYWORDS.contains(word))
+    {
+        let mut masked = Vec::with_capacity(words.len());
+        let mut mask_next = false;
+
+        for word in words {
+            if mask_next {
+                masked.push(MASK);
+                mask_next = false;
+            } else {
+                mask_next = NETRC_SECRET_KEYWORDS.contains(&word);
+                masked.push(word);
+            }
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        return format!("{}{}", indent, masked.join(" "));
+    }
+
+    line.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sensitive_file() {
+        assert!(is_sensitive_file(".npmrc"));
+        assert!(is_sensitive_file("/home/user/.netrc"));
+        assert!(is_sensitive_file("/home/user/.aws/credentials"));
+
+        assert!(!is_sensitive_file(".bashrc"));
+        assert!(!is_sensitive_file("/home/user/credentials.txt"));
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let npmrc =
+            "registry=https://registry.npmjs.org/\n//registry.npmjs.org/:_authToken=npm_abc123\n";
+        assert_eq!(
+            mask_secrets(npmrc),
+            "registry=https://registry.npmjs.org/\n//registry.npmjs.org/:_authToken=********\n"
+        );
+
+        let pypirc = "[pypi]\nusername = __token__\npassword = pypi-secret";
+        assert_eq!(
+            mask_secrets(pypirc),
+            "[pypi]\nusername = __token__\npassword = ********"
+        );
+
+        let netrc = "machine example.com login me password hunter2";
+        assert_eq!(
+            mask_secrets(netrc),
+            "machine example.com login me password ********"
+        );
+
+        let json = "  \"auth\": \"c2VjcmV0\",";
+        assert_eq!(mask_secrets(json), "  \"auth\": \"********\",");
+    }
+}
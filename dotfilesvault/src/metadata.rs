@@ -0,0 +1,276 @@
+use anyhow::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::manifest::{DotfileEntry, PermissionsPolicy, STATE_DIR};
+use crate::plan::Plan;
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the file inside the state directory holding file metadata
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Permission bits and modification time of a tracked file's home copy
+///
+/// Git only keeps the execute bit of a file and none of its times, so they
+/// are recorded next to the manifest and re-applied on restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// Unix permission bits, absent for files backed up on other platforms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: i64,
+}
+
+impl FileMetadata {
+    /// Read the metadata of a file
+    pub fn read(path: &Path) -> Result<Self, DotfilesError> {
+        let metadata = fs::metadata(path)?;
+        let mtime = match metadata.modified()?.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+
+        Ok(Self {
+            mode: mode_of(&metadata),
+            mtime,
+        })
+    }
+
+    /// Get the modification time as a system time
+    pub fn modified(&self) -> SystemTime {
+        let offset = Duration::from_secs(self.mtime.unsigned_abs());
+        if self.mtime >= 0 {
+            UNIX_EPOCH + offset
+        } else {
+            UNIX_EPOCH - offset
+        }
+    }
+}
+
+/// Get the permission bits of a file
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Metadata of tracked files as recorded in the vault
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataManifest {
+    /// Metadata of the home copies keyed by entry id
+    #[serde(default)]
+    pub files: BTreeMap<String, FileMetadata>,
+}
+
+impl MetadataManifest {
+    /// Get the path of the metadata file for a vault
+    pub fn path(config: &Config) -> PathBuf {
+        config.vault_dir.join(STATE_DIR).join(METADATA_FILE)
+    }
+
+    /// Load the metadata from the vault, or none if nothing was recorded yet
+    pub fn load(config: &Config) -> Result<Self, DotfilesError> {
+        let path = Self::path(config);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+
+        serde_json::from_str(&content)
+            .map_err(|err| DotfilesError::InvalidManifest(err.to_string()))
+    }
+
+    /// Save the metadata into the vault
+    pub fn save(&self, config: &Config) -> Result<(), DotfilesError> {
+        let path = Self::path(config);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = serde_json::to_string_pretty(self)
+            .map_err(|err| DotfilesError::InvalidManifest(err.to_string()))?;
+        content.push('\n');
+
+        write_file(&path, content.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Record the metadata of the home copies of backed up entries
+///
+/// The modification time is only updated along with the content, so touching
+/// a file doesn't change the vault, but a changed mode is always recorded.
+/// Entries left out of the backup, e.g. because of secrets, keep their record.
+pub fn record_metadata(
+    config: &Config,
+    entries: &[DotfileEntry],
+    plan: &Plan,
+) -> Result<(), DotfilesError> {
+    let mut metadata = MetadataManifest::load(config)?;
+    let mut updated = false;
+
+    for entry in entries {
+        let home = entry.dotfile(config).original_path;
+        if !entry.applies()
+            || entry.is_template()
+            || !home.is_file()
+            || plan.secrets.iter().any(|finding| finding.path == home)
+        {
+            continue;
+        }
+
+        let current = FileMetadata::read(&home)?;
+        let copied = plan.copies.iter().any(|copy| copy.source == home);
+        let keep = metadata
+            .files
+            .get(&entry.id)
+            .is_some_and(|recorded| !copied && recorded.mode == current.mode);
+        if !keep {
+            debug!("Recording metadata of {}: {:?}", entry.id, current);
+            metadata.files.insert(entry.id.clone(), current);
+            updated = true;
+        }
+    }
+
+    if updated {
+        metadata.save(config)?;
+    }
+
+    Ok(())
+}
+
+/// Apply an entry's recorded mode to a restored file
+///
+/// An explicit permissions policy takes precedence over the recorded mode.
+pub fn apply_mode(config: &Config, entry: &DotfileEntry, path: &Path) -> Result<(), DotfilesError> {
+    let mode = match entry.permissions {
+        PermissionsPolicy::Preserve => {
+            match MetadataManifest::load(config)?
+                .files
+                .get(&entry.id)
+                .and_then(|recorded| recorded.mode)
+            {
+                Some(mode) => mode,
+                None => return Ok(()),
+            }
+        }
+        PermissionsPolicy::Private => 0o600,
+        PermissionsPolicy::Mode(mode) => mode,
+    };
+
+    set_mode(path, mode)
+}
+
+/// Set the permission bits of a file
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), DotfilesError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), DotfilesError> {
+    Ok(())
+}
+
+/// Give a restored file the modification time recorded for its entry
+pub fn apply_mtime(
+    config: &Config,
+    entry: &DotfileEntry,
+    path: &Path,
+) -> Result<(), DotfilesError> {
+    if let Some(recorded) = MetadataManifest::load(config)?.files.get(&entry.id) {
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(recorded.modified())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use crate::backend::GitBackend;
+    use crate::backup::backup_specific_dotfiles;
+    use crate::manifest::Manifest;
+    use crate::restore::restore_entry;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_reapplies_mode_and_mtime() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        let script = config.home_dir.join(".local/bin/greet");
+        fs::create_dir_all(script.parent().unwrap()).unwrap();
+        fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(&script)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        backup_specific_dotfiles(&config, &[".local/bin/greet".to_string()]).unwrap();
+        let entry = Manifest::load(&config)
+            .unwrap()
+            .find_by_source(".local/bin/greet")
+            .cloned()
+            .unwrap();
+        assert_eq!(
+            MetadataManifest::load(&config).unwrap().files[&entry.id],
+            FileMetadata {
+                mode: Some(0o750),
+                mtime: 1_600_000_000
+            }
+        );
+
+        // A restore onto a fresh machine brings back the mode and the time
+        fs::remove_file(&script).unwrap();
+        fs::set_permissions(
+            config.vault_dir.join(".local/bin/greet"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        restore_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+
+        let restored = fs::metadata(&script).unwrap();
+        assert_eq!(restored.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(restored.modified().unwrap(), mtime);
+
+        // A new mode is recorded even though the content is unchanged
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        backup_specific_dotfiles(&config, &[".local/bin/greet".to_string()]).unwrap();
+        let recorded = MetadataManifest::load(&config).unwrap().files[&entry.id];
+        assert_eq!(recorded.mode, Some(0o755));
+        assert_eq!(recorded.mtime, 1_600_000_000);
+    }
+}
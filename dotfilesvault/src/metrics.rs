@@ -0,0 +1,292 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use git2::Repository;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::backend::open_backend;
+use crate::backup::has_drifted;
+use crate::manifest::Manifest;
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the state file holding backup counters, see `Config::state_path`
+pub const STATS_FILE: &str = "dotfilesvault-stats.json";
+
+/// Backup counters persisted between runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupStats {
+    /// Number of backups attempted
+    pub backups_total: u64,
+
+    /// Number of backups that failed
+    pub failures_total: u64,
+
+    /// Unix time of the last successful backup
+    pub last_success: Option<i64>,
+
+    /// Unix time of the last failed backup
+    pub last_failure: Option<i64>,
+}
+
+/// Health metrics of a vault
+#[derive(Debug, Clone)]
+pub struct VaultMetrics {
+    /// Time of the last successful backup, or of the last vault commit
+    pub last_backup: Option<DateTime<Local>>,
+
+    /// Number of entries in the manifest
+    pub tracked_files: usize,
+
+    /// Number of tracked files whose home copy differs from the vault
+    pub drifted_files: usize,
+
+    /// Size of the vault's git directory in bytes
+    pub repo_size_bytes: u64,
+
+    /// Persisted backup counters
+    pub stats: BackupStats,
+}
+
+/// Get the path of the backup counters file
+fn stats_path(config: &Config) -> Result<PathBuf, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    Ok(repo.path().join(STATS_FILE))
+}
+
+/// Load the backup counters, or empty ones if none were recorded yet
+pub fn load_stats(config: &Config) -> Result<BackupStats, DotfilesError> {
+    let path = stats_path(config)?;
+
+    if !path.exists() {
+        return Ok(BackupStats::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+
+    // Counters are best effort, a corrupt file just starts over
+    Ok(serde_json::from_str(&content).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable stats file {:?}: {}", path, err);
+        BackupStats::default()
+    }))
+}
+
+/// Record the outcome of a backup
+pub fn record_backup(config: &Config, success: bool) -> Result<(), DotfilesError> {
+    let mut stats = load_stats(config)?;
+    let now = Local::now().timestamp();
+
+    stats.backups_total += 1;
+    if success {
+        stats.last_success = Some(now);
+    } else {
+        stats.failures_total += 1;
+        stats.last_failure = Some(now);
+    }
+
+    let content = serde_json::to_string_pretty(&stats).map_err(std::io::Error::other)?;
+    write_file(&stats_path(config)?, content.as_bytes())?;
+
+    Ok(())
+}
+
+/// Collect the current metrics of a vault
+pub fn collect(config: &Config) -> Result<VaultMetrics, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let stats = load_stats(config)?;
+
+    let last_backup = match stats.last_success {
+        Some(seconds) => Local.timestamp_opt(seconds, 0).single(),
+        None => repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .ok()
+            .and_then(|commit| Local.timestamp_opt(commit.time().seconds(), 0).single()),
+    };
+
+    let manifest = Manifest::load(config)?;
+    let backend = open_backend(config)?;
+    let mut drifted_files = 0;
+    for entry in manifest.entries.values() {
+        if has_drifted(config, backend.as_ref(), entry)? {
+            drifted_files += 1;
+        }
+    }
+
+    Ok(VaultMetrics {
+        last_backup,
+        tracked_files: manifest.entries.len(),
+        drifted_files,
+        repo_size_bytes: dir_size(repo.path()),
+        stats,
+    })
+}
+
+/// Render metrics in the Prometheus text exposition format
+pub fn render(metrics: &VaultMetrics, now: DateTime<Local>) -> String {
+    let mut out = String::new();
+
+    let mut metric = |name: &str, help: &str, kind: &str, value: String| {
+        let _ = writeln!(out, "# HELP dotfilesvault_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE dotfilesvault_{} {}", name, kind);
+        let _ = writeln!(out, "dotfilesvault_{} {}", name, value);
+    };
+
+    if let Some(last_backup) = metrics.last_backup {
+        metric(
+            "last_backup_timestamp_seconds",
+            "Unix time of the last successful backup.",
+            "gauge",
+            last_backup.timestamp().to_string(),
+        );
+        metric(
+            "last_backup_age_seconds",
+            "Seconds since the last successful backup.",
+            "gauge",
+            (now - last_backup).num_seconds().max(0).to_string(),
+        );
+    }
+
+    metric(
+        "tracked_files",
+        "Number of dotfiles tracked in the manifest.",
+        "gauge",
+        metrics.tracked_files.to_string(),
+    );
+    metric(
+        "drifted_files",
+        "Number of tracked dotfiles that differ from the vault.",
+        "gauge",
+        metrics.drifted_files.to_string(),
+    );
+    metric(
+        "repo_size_bytes",
+        "Size of the vault's git directory.",
+        "gauge",
+        metrics.repo_size_bytes.to_string(),
+    );
+    metric(
+        "backups_total",
+        "Number of backups attempted.",
+        "counter",
+        metrics.stats.backups_total.to_string(),
+    );
+    metric(
+        "backup_failures_total",
+        "Number of backups that failed.",
+        "counter",
+        metrics.stats.failures_total.to_string(),
+    );
+
+    out
+}
+
+/// Write the metrics to a file for node_exporter's textfile collector
+///
+/// The file is replaced atomically so the collector never reads a partial file.
+pub fn write_textfile(config: &Config, path: &Path) -> Result<(), DotfilesError> {
+    let metrics = collect(config)?;
+
+    write_file(path, render(&metrics, Local::now()).as_bytes())?;
+
+    Ok(())
+}
+
+/// Serve the metrics over HTTP on `addr`, e.g. `127.0.0.1:9479`
+///
+/// Metrics are collected on every request to `/metrics`. This never returns
+/// unless binding the address fails.
+pub fn serve(config: &Config, addr: &str) -> Result<(), DotfilesError> {
+    let listener = TcpListener::bind(addr)?;
+    debug!("Serving metrics on {}", addr);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to accept metrics connection: {}", err);
+                continue;
+            }
+        };
+
+        let mut request = [0u8; 1024];
+        let len = stream.read(&mut request).unwrap_or(0);
+        let request = String::from_utf8_lossy(&request[..len]);
+
+        let response = if request.starts_with("GET /metrics ") {
+            match collect(config) {
+                Ok(metrics) => http_response("200 OK", &render(&metrics, Local::now())),
+                Err(err) => http_response("500 Internal Server Error", &format!("{}\n", err)),
+            }
+        } else {
+            http_response("404 Not Found", "Not found\n")
+        };
+
+        if let Err(err) = stream.write_all(response.as_bytes()) {
+            warn!("Failed to send metrics: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a minimal HTTP/1.1 response
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Get the total size of the files under a directory
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::init_git_repo;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_backup_and_render() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        init_git_repo(&config).unwrap();
+
+        record_backup(&config, true).unwrap();
+        record_backup(&config, false).unwrap();
+
+        let metrics = collect(&config).unwrap();
+        assert_eq!(metrics.stats.backups_total, 2);
+        assert_eq!(metrics.stats.failures_total, 1);
+        assert!(metrics.last_backup.is_some());
+
+        let now = metrics.last_backup.unwrap() + chrono::Duration::seconds(90);
+        let text = render(&metrics, now);
+        assert!(text.contains("# TYPE dotfilesvault_backups_total counter\n"));
+        assert!(text.contains("dotfilesvault_backups_total 2\n"));
+        assert!(text.contains("dotfilesvault_backup_failures_total 1\n"));
+        assert!(text.contains("dotfilesvault_last_backup_age_seconds 90\n"));
+        assert!(text.contains("dotfilesvault_drifted_files 0\n"));
+    }
+}
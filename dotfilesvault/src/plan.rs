@@ -0,0 +1,254 @@
+use anyhow::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::backend::open_backend;
+use crate::collisions::CaseCollision;
+use crate::security::SecretFinding;
+use crate::{Config, DotfilesError, t};
+
+/// What a planned copy does to its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlannedAction {
+    /// The destination doesn't exist yet
+    Create,
+
+    /// The destination exists with different content
+    Overwrite,
+
+    /// The destination has local edits, so the content is set aside for a manual merge
+    Quarantine,
+}
+
+impl PlannedAction {
+    /// Name used in output
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlannedAction::Create => "create",
+            PlannedAction::Overwrite => "overwrite",
+            PlannedAction::Quarantine => "quarantine",
+        }
+    }
+}
+
+/// A file copied (or, in a dry run, to be copied) between home and vault
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedCopy {
+    /// Where the content comes from
+    pub source: PathBuf,
+
+    /// Where the content is written
+    pub destination: PathBuf,
+
+    /// Whether the destination is created or overwritten
+    pub action: PlannedAction,
+}
+
+/// Code of a file left out of a backup because it looks like it holds secrets
+pub const SKIPPED_SENSITIVE: &str = "W_SKIPPED_SENSITIVE";
+
+/// Code of a file left out of a backup because it is larger than `max_file_size`
+pub const SKIPPED_LARGE: &str = "W_SKIPPED_LARGE";
+
+/// Code of a binary file left out of a backup because of `skip_binary`
+pub const SKIPPED_BINARY: &str = "W_SKIPPED_BINARY";
+
+/// Code of paths that differ only in case, see `CaseCollisionPolicy`
+pub const CASE_COLLISION: &str = "W_CASE_COLLISION";
+
+/// Code of a vault version set aside because the home file has local edits
+pub const CONFLICT_QUARANTINED: &str = "W_CONFLICT_QUARANTINED";
+
+/// Code of a file a time-limited restore didn't get to
+pub const DEFERRED: &str = "W_DEFERRED";
+
+/// Something about a file that needs attention, for scripts reading JSON output
+///
+/// Codes starting with `E_` are failures (the codes of `DotfilesError`),
+/// those starting with `W_` files that were left alone. Like exit codes they
+/// are never renamed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notice {
+    /// Stable machine-readable code, e.g. `E_CONFLICT_LOCAL_MODIFIED`
+    pub code: &'static str,
+
+    /// The file concerned
+    pub path: PathBuf,
+
+    /// Human readable description, which may change or be translated
+    pub message: String,
+}
+
+/// Report of a backup or restore
+///
+/// With `config.dry_run` set nothing is written and the plan lists what a
+/// real run would do; otherwise it lists what was done. Copies whose
+/// destination already holds the same content are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    /// Files copied, in the order they were processed
+    pub copies: Vec<PlannedCopy>,
+
+    /// Lines that look like secrets in files left out of a backup
+    pub secrets: Vec<SecretFinding>,
+
+    /// Paths that differ only in case, see `CaseCollisionPolicy`
+    pub collisions: Vec<CaseCollision>,
+
+    /// Message of the vault commit created, if anything changed
+    pub commit: Option<String>,
+
+    /// Coded outcomes of the files that were left out, failed or need attention
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<Notice>,
+}
+
+impl Plan {
+    /// Record a copy if the destination would change
+    ///
+    /// `changed` tells whether an existing destination differs from the
+    /// content being copied.
+    pub fn record(&mut self, source: PathBuf, destination: PathBuf, changed: bool) {
+        let action = if !destination.exists() {
+            PlannedAction::Create
+        } else if changed {
+            PlannedAction::Overwrite
+        } else {
+            return;
+        };
+
+        self.copies.push(PlannedCopy {
+            source,
+            destination,
+            action,
+        });
+    }
+
+    /// Record a coded outcome for a file
+    pub fn notify(&mut self, code: &'static str, path: PathBuf, message: String) {
+        self.notices.push(Notice {
+            code,
+            path,
+            message,
+        });
+    }
+
+    /// Record a failure for a file, coded by the error
+    pub fn notify_error(&mut self, path: PathBuf, err: &DotfilesError) {
+        self.notify(err.code(), path, err.to_string());
+    }
+
+    /// Record paths that differ only in case, with a notice for each
+    pub fn add_collisions(&mut self, collisions: Vec<CaseCollision>) {
+        for collision in &collisions {
+            let paths: Vec<String> = collision
+                .paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            self.notify(
+                CASE_COLLISION,
+                collision.paths[0].clone(),
+                t!("notice-case-collision", paths = paths.join(", ")),
+            );
+        }
+        self.collisions.extend(collisions);
+    }
+
+    /// Add the copies and findings of another plan, e.g. of a single file
+    pub fn extend(&mut self, other: Plan) {
+        self.copies.extend(other.copies);
+        self.secrets.extend(other.secrets);
+        self.collisions.extend(other.collisions);
+        self.notices.extend(other.notices);
+    }
+
+    /// Home directory paths touched by the copies, as passed to hooks
+    ///
+    /// Backups read from home and restores write to it; quarantined content
+    /// leaves the home file alone.
+    pub fn home_files(&self, config: &Config) -> Vec<PathBuf> {
+        self.copies
+            .iter()
+            .filter(|copy| copy.action != PlannedAction::Quarantine)
+            .map(|copy| {
+                if copy.destination.starts_with(&config.vault_dir) {
+                    copy.source.clone()
+                } else {
+                    copy.destination.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Commit the vault, or only record the commit in a dry run
+    ///
+    /// Takes a snapshot of the vault's backend. Nothing is committed if the
+    /// vault has no changes, e.g. a backup found every file unchanged, so
+    /// unchanged runs don't add empty commits.
+    pub fn commit(mut self, config: &Config, message: &str) -> Result<Self, DotfilesError> {
+        let changed = if config.dry_run {
+            !self.copies.is_empty()
+        } else {
+            open_backend(config)?.snapshot(message)?.is_some()
+        };
+        if !changed {
+            debug!("Nothing to commit, the vault is unchanged");
+            return Ok(self);
+        }
+
+        self.commit = Some(message.to_string());
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_actions() {
+        let dir = TempDir::new().unwrap();
+        let existing = dir.path().join(".existingrc");
+        fs::write(&existing, "content").unwrap();
+
+        let mut plan = Plan::default();
+        plan.record(PathBuf::from("a"), dir.path().join(".newrc"), true);
+        plan.record(PathBuf::from("b"), existing.clone(), true);
+        plan.record(PathBuf::from("c"), existing, false);
+
+        let actions: Vec<PlannedAction> = plan.copies.iter().map(|copy| copy.action).collect();
+        assert_eq!(
+            actions,
+            vec![PlannedAction::Create, PlannedAction::Overwrite]
+        );
+    }
+
+    #[test]
+    fn test_dry_run_commit_leaves_repo_alone() {
+        let vault_dir = TempDir::new().unwrap();
+        let mut config = Config::new(vault_dir.path().to_path_buf(), PathBuf::from("/home"));
+        config.dry_run = true;
+
+        let plan = Plan::default()
+            .commit(&config, "Backup all dotfiles")
+            .unwrap();
+        assert_eq!(plan.commit, None);
+
+        let mut plan = Plan::default();
+        plan.record(
+            PathBuf::from("/home/.bashrc"),
+            vault_dir.path().join(".bashrc"),
+            true,
+        );
+        let plan = plan.commit(&config, "Backup all dotfiles").unwrap();
+
+        assert_eq!(plan.commit.as_deref(), Some("Backup all dotfiles"));
+        assert!(!vault_dir.path().join(".git").exists());
+    }
+}
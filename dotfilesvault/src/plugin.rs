@@ -0,0 +1,130 @@
+use anyhow::Result;
+use log::debug;
+use serde::Serialize;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{Config, DotfilesError};
+
+/// Prefix of plugin executables on PATH
+pub const PLUGIN_PREFIX: &str = "dotfilesvault-";
+
+/// Context passed to a plugin in the `DOTFILESVAULT_CONTEXT` environment variable
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginContext {
+    /// Version of the dotfilesvault binary running the plugin
+    pub version: String,
+
+    /// Path to the dotfilesvault directory
+    pub vault_dir: PathBuf,
+
+    /// Path to the home directory
+    pub home_dir: PathBuf,
+
+    /// Path of the configuration file in use, if any
+    pub config_file: Option<PathBuf>,
+
+    /// Whether verbose output was requested
+    pub verbose: bool,
+
+    /// Whether porcelain output was requested
+    pub porcelain: bool,
+}
+
+impl PluginContext {
+    /// Create a plugin context from the active configuration
+    pub fn new(config: &Config) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            vault_dir: config.vault_dir.clone(),
+            home_dir: config.home_dir.clone(),
+            config_file: config.config_file.clone(),
+            verbose: false,
+            porcelain: false,
+        }
+    }
+}
+
+/// Find the executable for a plugin named `name` in the directories of `path_var`
+pub fn find_plugin_in(name: &str, path_var: &OsString) -> Option<PathBuf> {
+    let file_name = format!("{}{}", PLUGIN_PREFIX, name);
+
+    env::split_paths(path_var)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Find the executable for a plugin named `name` on PATH
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    let path_var = env::var_os("PATH")?;
+    find_plugin_in(name, &path_var)
+}
+
+/// Run a plugin with the given arguments and return its exit code
+pub fn run_plugin(
+    plugin: &Path,
+    args: &[OsString],
+    context: &PluginContext,
+) -> Result<i32, DotfilesError> {
+    debug!("Running plugin {:?} with args {:?}", plugin, args);
+
+    let context_json = serde_json::to_string(context).map_err(std::io::Error::other)?;
+
+    let mut command = Command::new(plugin);
+    command
+        .args(args)
+        .env("DOTFILESVAULT_CONTEXT", context_json)
+        .env("DOTFILESVAULT_VAULT_DIR", &context.vault_dir)
+        .env("DOTFILESVAULT_HOME_DIR", &context.home_dir);
+
+    if let Some(config_file) = &context.config_file {
+        command.env("DOTFILESVAULT_CONFIG", config_file);
+    }
+
+    let status = command.status()?;
+
+    // A plugin killed by a signal has no exit code
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_plugin_in() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin_dir = TempDir::new().unwrap();
+        let plugin = bin_dir.path().join("dotfilesvault-hello");
+        fs::write(&plugin, "#!/bin/sh\nexit 0\n").unwrap();
+
+        let path_var = env::join_paths([bin_dir.path()]).unwrap();
+
+        // Not executable yet
+        assert_eq!(find_plugin_in("hello", &path_var), None);
+
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_plugin_in("hello", &path_var), Some(plugin));
+        assert_eq!(find_plugin_in("missing", &path_var), None);
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+use crate::manifest::Manifest;
+use crate::{Config, DotfilesError};
+
+/// Vault directory holding machine-specific variants, one subdirectory per profile
+///
+/// Files in the vault root form the common layer shared by every machine.
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Check that a profile name can be used as a vault directory name
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !name.starts_with('.');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid profile name {:?}", name))
+    }
+}
+
+/// List the profiles that have variants of at least one tracked file
+pub fn list_profiles(config: &Config) -> Result<Vec<String>, DotfilesError> {
+    let profiles: BTreeSet<String> = Manifest::load(config)?
+        .entries
+        .into_values()
+        .flat_map(|entry| entry.profiles)
+        .collect();
+
+    Ok(profiles.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_profile_name() {
+        assert!(validate_profile_name("work").is_ok());
+        assert!(validate_profile_name("laptop-2").is_ok());
+
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("..").is_err());
+        assert!(validate_profile_name("a/b").is_err());
+        assert!(validate_profile_name(".hidden").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::fs;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+
+use crate::Config;
+use crate::plan::Plan;
+use crate::utils::human_readable_size;
+
+/// Progress bars drawn on stderr, shared with the log so its lines are
+/// printed above the bars instead of through them
+static BARS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Progress bar of a long operation, counting items and bytes
+///
+/// The bar is only drawn when `config.progress` is set, which the CLI does
+/// when stderr is a terminal. Clones update the same bar, which is cleared
+/// once the last of them is dropped.
+#[derive(Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    bytes: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Start a bar over a number of files
+    pub fn files(config: &Config, total: usize, message: String) -> Self {
+        Self::start(config, total, message, "files")
+    }
+
+    /// Start a bar over git objects, whose number is known once the transfer
+    /// started
+    pub fn objects(config: &Config, message: String) -> Self {
+        Self::start(config, 0, message, "objects")
+    }
+
+    fn start(config: &Config, total: usize, message: String, unit: &str) -> Self {
+        let bar = if config.progress {
+            BARS.add(ProgressBar::new(total as u64))
+        } else {
+            ProgressBar::hidden()
+        };
+        let template = format!(
+            "{{msg}} [{{bar:30}}] {{pos}}/{{len}} {}, {{prefix}} ({{elapsed}})",
+            unit
+        );
+        bar.set_style(
+            ProgressStyle::with_template(&template)
+                .expect("the progress template is valid")
+                .progress_chars("=> "),
+        );
+        bar.set_message(message);
+        bar.set_prefix(human_readable_size(0));
+
+        Self {
+            bar,
+            bytes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Count a finished item and the bytes it took
+    pub fn inc(&self, bytes: u64) {
+        let total = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.bar.set_prefix(human_readable_size(total));
+        self.bar.inc(1);
+    }
+
+    /// Set how far a transfer got, as reported by git
+    pub fn set(&self, done: usize, total: usize, bytes: u64) {
+        self.bytes.store(bytes, Ordering::Relaxed);
+        self.bar.set_length(total as u64);
+        self.bar.set_position(done as u64);
+        self.bar.set_prefix(human_readable_size(bytes));
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.bytes) == 1 {
+            self.bar.finish_and_clear();
+            BARS.remove(&self.bar);
+        }
+    }
+}
+
+/// Get the size of the files a plan copies to, as they are now
+pub fn written_bytes(plan: &Plan) -> u64 {
+    plan.copies
+        .iter()
+        .filter_map(|copy| fs::symlink_metadata(&copy.destination).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Target for log lines that hides the progress bars while writing
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        BARS.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_counts_bytes() {
+        let config = Config::default();
+        let progress = Progress::files(&config, 2, "Backing up".to_string());
+        let clone = progress.clone();
+        clone.inc(1024);
+        progress.inc(1024);
+        drop(clone);
+
+        assert!(progress.bar.is_hidden());
+        assert_eq!(progress.bar.position(), 2);
+        assert_eq!(progress.bar.prefix(), "2.00 KB");
+        assert!(!progress.bar.is_finished());
+    }
+}
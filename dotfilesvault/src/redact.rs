@@ -0,0 +1,239 @@
+use anyhow::Result;
+use log::{debug, warn};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::encryption::{decrypt, encrypt, key_path};
+use crate::manifest::DotfileEntry;
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the encrypted file holding redacted values, next to the key file
+pub const REDACTIONS_FILE_NAME: &str = "redactions.age";
+
+/// Values redacted from a file, by placeholder in order of occurrence
+pub type EntryValues = BTreeMap<String, Vec<String>>;
+
+/// Redacted values by entry ID
+type RedactedValues = BTreeMap<String, EntryValues>;
+
+/// Serializes updates of the redactions file between backup threads
+static REDACTIONS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Get the path of the file holding redacted values
+///
+/// It lives next to the encryption key and, like it, never in the vault.
+pub fn redactions_path(config: &Config) -> PathBuf {
+    key_path(config).with_file_name(REDACTIONS_FILE_NAME)
+}
+
+/// Compile the configured redaction rules, placeholder and pattern
+pub fn redaction_rules(config: &Config) -> Result<Vec<(String, Regex)>, DotfilesError> {
+    config
+        .redact
+        .iter()
+        .map(|(placeholder, pattern)| {
+            Regex::new(pattern)
+                .map(|regex| (placeholder.clone(), regex))
+                .map_err(|err| {
+                    DotfilesError::InvalidConfig(format!("redact.{}", placeholder), err.to_string())
+                })
+        })
+        .collect()
+}
+
+/// Replace the values matched by the redaction rules with their placeholders
+///
+/// A rule's pattern replaces its first capture group, or the whole match if
+/// it has none, so `token = (.+)` keeps the key. Returns the redacted content
+/// and the values taken out, by placeholder. Content that isn't UTF-8 is
+/// returned as is.
+pub fn redact(config: &Config, content: &[u8]) -> Result<(Vec<u8>, EntryValues), DotfilesError> {
+    let mut values = BTreeMap::new();
+    let Ok(text) = std::str::from_utf8(content) else {
+        return Ok((content.to_vec(), values));
+    };
+
+    let mut text = text.to_string();
+    for (placeholder, regex) in redaction_rules(config)? {
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        let mut found = Vec::new();
+        for captures in regex.captures_iter(&text) {
+            let Some(value) = captures.get(1).or(captures.get(0)) else {
+                continue;
+            };
+            if value.as_str().is_empty() || value.as_str() == placeholder {
+                continue;
+            }
+            redacted.push_str(&text[last..value.start()]);
+            redacted.push_str(&placeholder);
+            last = value.end();
+            found.push(value.as_str().to_string());
+        }
+        if found.is_empty() {
+            continue;
+        }
+        redacted.push_str(&text[last..]);
+        text = redacted;
+        values.insert(placeholder, found);
+    }
+
+    Ok((text.into_bytes(), values))
+}
+
+/// Put the values redacted from an entry back in place of their placeholders
+///
+/// Placeholders without a recorded value, e.g. on a machine the file wasn't
+/// backed up on, are kept.
+pub fn reinject(
+    config: &Config,
+    entry: &DotfileEntry,
+    content: Vec<u8>,
+) -> Result<Vec<u8>, DotfilesError> {
+    let Ok(text) = std::str::from_utf8(&content) else {
+        return Ok(content);
+    };
+    if !config
+        .redact
+        .keys()
+        .any(|placeholder| text.contains(placeholder.as_str()))
+    {
+        return Ok(content);
+    }
+
+    let Some(values) = load_values(config)?.remove(&entry.id) else {
+        warn!(
+            "No redacted values for {} on this machine, its placeholders are kept",
+            entry.id
+        );
+        return Ok(content);
+    };
+
+    let mut text = text.to_string();
+    // Later rules redacted the output of earlier ones, so undo them first
+    for (placeholder, values) in values.iter().rev() {
+        let mut values = values.iter();
+        let mut restored = String::with_capacity(text.len());
+        let mut parts = text.split(placeholder.as_str()).peekable();
+        while let Some(part) = parts.next() {
+            restored.push_str(part);
+            if parts.peek().is_some() {
+                restored.push_str(values.next().map_or(placeholder.as_str(), String::as_str));
+            }
+        }
+        text = restored;
+    }
+
+    Ok(text.into_bytes())
+}
+
+/// Record the values redacted from an entry, replacing the ones recorded
+/// before
+pub fn store_values(
+    config: &Config,
+    entry: &DotfileEntry,
+    values: EntryValues,
+) -> Result<(), DotfilesError> {
+    let _guard = REDACTIONS_LOCK
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let mut recorded = load_values(config)?;
+
+    let changed = if values.is_empty() {
+        recorded.remove(&entry.id).is_some()
+    } else {
+        recorded.insert(entry.id.clone(), values.clone()) != Some(values)
+    };
+    if !changed {
+        return Ok(());
+    }
+
+    let content = serde_json::to_vec(&recorded).map_err(std::io::Error::from)?;
+    let path = redactions_path(config);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&path, &encrypt(config, &content)?)?;
+
+    debug!("Recorded redacted values of {}", entry.id);
+
+    Ok(())
+}
+
+/// Load every recorded redacted value
+fn load_values(config: &Config) -> Result<RedactedValues, DotfilesError> {
+    let path = redactions_path(config);
+    if !path.exists() {
+        return Ok(RedactedValues::new());
+    }
+
+    let content = decrypt(config, &fs::read(&path)?)?;
+    serde_json::from_slice(&content).map_err(|err| {
+        DotfilesError::Encryption(format!("{} is corrupted: {}", path.display(), err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use crate::backend::GitBackend;
+    use crate::backup::{Dotfile, backup_entry};
+    use crate::encryption::init_key;
+    use crate::restore::restore_entry;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_redacted_backup_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.key_file = Some(temp_dir.path().join("key.txt"));
+        config.redact.insert(
+            "<github-token>".to_string(),
+            r"ghp_[A-Za-z0-9]+".to_string(),
+        );
+        config
+            .redact
+            .insert("<password>".to_string(), r"password = (\S+)".to_string());
+        fs::create_dir_all(&config.home_dir).unwrap();
+        init_key(&config).unwrap();
+
+        let gitconfig = config.home_dir.join(".gitconfig");
+        let original =
+            "[github]\n\ttoken = ghp_abc123\n\tother = ghp_def456\n\tpassword = hunter2\n";
+        fs::write(&gitconfig, original).unwrap();
+        let entry = DotfileEntry::new(&Dotfile::new(gitconfig.clone(), &config), &config);
+
+        backup_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.vault_dir.join(".gitconfig")).unwrap(),
+            "[github]\n\ttoken = <github-token>\n\tother = <github-token>\n\tpassword = <password>\n"
+        );
+        let sidecar = fs::read(redactions_path(&config)).unwrap();
+        assert!(!String::from_utf8_lossy(&sidecar).contains("hunter2"));
+
+        fs::remove_file(&gitconfig).unwrap();
+        restore_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        assert_eq!(fs::read_to_string(&gitconfig).unwrap(), original);
+
+        // Without the recorded values the placeholders are restored as is
+        fs::remove_file(redactions_path(&config)).unwrap();
+        restore_entry(&config, &GitBackend::new(&config), &entry).unwrap();
+        assert!(
+            fs::read_to_string(&gitconfig)
+                .unwrap()
+                .contains("token = <github-token>")
+        );
+
+        config.redact.insert("<bad>".to_string(), "(".to_string());
+        assert!(matches!(
+            redact(&config, b"text"),
+            Err(DotfilesError::InvalidConfig(_, _))
+        ));
+    }
+}
@@ -1,18 +1,48 @@
 use anyhow::Result;
-use log::{debug, info};
+use chrono::Local;
+use log::{debug, info, warn};
+use serde::Serialize;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use crate::backup::Dotfile;
-use crate::{Config, DotfilesError, is_dotfile};
+use crate::backend::{VaultBackend, open_backend, resolve_file_version_with};
+use crate::backup::{Dotfile, has_drifted, is_trackable};
+#[cfg(feature = "git")]
+use crate::cache::content_hash;
+use crate::collisions::{is_case_insensitive, resolve_restore_collisions};
+use crate::conflicts::{ConflictPolicy, has_local_edits, quarantine, quarantine_path};
+use crate::encryption::{decrypt, is_encrypted};
+use crate::generator::write_shell_files;
+use crate::hooks::{HookEvent, run_operation_hook};
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::metadata::{apply_mode, apply_mtime};
+use crate::plan::{CONFLICT_QUARANTINED, DEFERRED, Plan, PlannedAction, PlannedCopy};
+use crate::progress::{Progress, written_bytes};
+use crate::redact::reinject;
+use crate::snippet::write_snippets_script;
+use crate::symlink::{create_symlink, is_file_or_link};
+use crate::template::{is_template, render, rendered_path, template_variables};
+use crate::transform::{TransformDirection, apply_transform};
+use crate::undo::{record_restored, save_for_undo};
+use crate::utils::{normalize_path, write_file};
+use crate::{Config, DotfilesError, is_vault_internal, t};
 
 /// Restore a dotfile from the vault to the home directory
-pub fn restore_dotfile(dotfile: &Dotfile) -> Result<(), DotfilesError> {
-    // Check if the file exists in the vault
-    if !dotfile.vault_path.exists() {
-        return Err(DotfilesError::DotfileNotFound(
-            dotfile.original_path.to_string_lossy().to_string(),
-        ));
+///
+/// The home file is replaced as it is; restores of tracked files check it
+/// for edits that were never backed up first, see `restore_specific_dotfile`.
+pub fn restore_dotfile(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    dotfile: &Dotfile,
+) -> Result<(), DotfilesError> {
+    let path = dotfile.stored_path(config);
+    if let Some(target) = backend.fetch_link(&path, None)? {
+        create_symlink(&target, &dotfile.original_path)?;
+        info!("Restored link: {:?}", dotfile.original_path);
+        return Ok(());
     }
 
     // Create parent directories if they don't exist
@@ -21,30 +51,731 @@ pub fn restore_dotfile(dotfile: &Dotfile) -> Result<(), DotfilesError> {
     }
 
     // Copy the file from the vault to the original location
-    fs::copy(&dotfile.vault_path, &dotfile.original_path)?;
+    if !backend.fetch_into(&path, &dotfile.original_path)? {
+        return Err(DotfilesError::DotfileNotFound(
+            dotfile.original_path.to_string_lossy().to_string(),
+        ));
+    }
 
     info!("Restored: {:?}", dotfile.original_path);
 
     Ok(())
 }
 
-/// Restore a specific dotfile by path
-pub fn restore_specific_dotfile(config: &Config, file_path: &str) -> Result<(), DotfilesError> {
-    let path = Path::new(file_path);
-    let path = if path.is_absolute() {
-        path.to_path_buf()
+/// Restore a manifest entry, applying its conditions, transforms and metadata
+///
+/// Transforms are applied in reverse order, and the file gets the mode and
+/// modification time recorded when it was backed up. Adopted entries are
+/// restored as a symlink to the stored copy. Returns `false` if the entry's
+/// conditions don't hold on this machine.
+pub fn restore_entry(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entry: &DotfileEntry,
+) -> Result<bool, DotfilesError> {
+    if !entry.applies() {
+        debug!("Skipping {}: conditions not met", entry.id);
+        return Ok(false);
+    }
+
+    let dotfile = entry.dotfile(config);
+
+    if entry.linked && dotfile.vault_path.is_file() {
+        if !entry.links_to_vault(config) {
+            create_symlink(&dotfile.vault_path, &dotfile.original_path)?;
+            info!("Linked: {:?}", dotfile.original_path);
+        }
+        return Ok(true);
+    }
+
+    let path = dotfile.stored_path(config);
+    if backend.fetch_link(&path, None)?.is_some() {
+        restore_dotfile(config, backend, &dotfile)?;
+        return Ok(true);
+    }
+
+    let Some(content) = backend.fetch_file(&path, None)? else {
+        return Err(DotfilesError::DotfileNotFound(entry.id.clone()));
+    };
+    if entry.transforms.is_empty()
+        && !entry.is_template()
+        && config.redact.is_empty()
+        && !is_encrypted(&content)
+    {
+        restore_dotfile(config, backend, &dotfile)?;
+        apply_mode(config, entry, &dotfile.original_path)?;
+    } else {
+        restore_entry_content(config, entry, &content)?;
+    }
+    apply_mtime(config, entry, &dotfile.original_path)?;
+
+    Ok(true)
+}
+
+/// Write stored content for an entry to the home directory
+///
+/// The content is the entry's stored form (as found in the vault or a past
+/// commit); transforms are reversed and the recorded mode or the permissions
+/// policy is applied.
+pub fn restore_entry_content(
+    config: &Config,
+    entry: &DotfileEntry,
+    content: &[u8],
+) -> Result<(), DotfilesError> {
+    let dotfile = entry.dotfile(config);
+    let content = decode_entry_content(config, entry, content)?;
+
+    if let Some(parent) = dotfile.original_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&dotfile.original_path, &content)?;
+    apply_mode(config, entry, &dotfile.original_path)?;
+
+    info!("Restored: {:?}", dotfile.original_path);
+
+    Ok(())
+}
+
+/// Turn an entry's stored content back into its home form
+///
+/// Encrypted content is decrypted first, then redacted values are put back,
+/// transforms are reversed and templates are rendered.
+pub fn decode_entry_content(
+    config: &Config,
+    entry: &DotfileEntry,
+    content: &[u8],
+) -> Result<Vec<u8>, DotfilesError> {
+    let mut content = if is_encrypted(content) {
+        decrypt(config, content)?
     } else {
-        config.home_dir.join(path)
+        content.to_vec()
     };
+    if !config.redact.is_empty() {
+        content = reinject(config, entry, content)?;
+    }
+    for transform in entry.transforms.iter().rev() {
+        content = apply_transform(config, transform, TransformDirection::Restore, &content)?;
+    }
+
+    if entry.is_template() {
+        let template = String::from_utf8(content).map_err(|_| {
+            DotfilesError::TemplateFailed(entry.id.clone(), "not valid UTF-8".to_string())
+        })?;
+        content = render(&template, &template_variables(config))
+            .map_err(|err| DotfilesError::TemplateFailed(entry.id.clone(), err))?
+            .into_bytes();
+    }
+
+    Ok(content)
+}
+
+/// Restore stored content for an entry, recording the copy in a plan
+///
+/// `source` names where the content comes from and `restore` writes it.
+/// A home file with edits that were never backed up is kept: with the `fail`
+/// conflict policy the restore fails with `DotfilesError::RestoreConflict`,
+/// with `skip` the vault version is quarantined instead. Only `overwrite`
+/// replaces it. In a dry run nothing is written.
+fn restore_planned<F>(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entry: &DotfileEntry,
+    source: PathBuf,
+    content: &[u8],
+    restore: F,
+) -> Result<Plan, DotfilesError>
+where
+    F: FnOnce() -> Result<(), DotfilesError>,
+{
+    let destination = entry.dotfile(config).original_path;
+    let mut plan = Plan::default();
+
+    let changed = match fs::read(&destination) {
+        Ok(home) => {
+            let restored = decode_entry_content(config, entry, content)?;
+
+            if config.on_conflict != ConflictPolicy::Overwrite
+                && has_local_edits(config, backend, entry, &home, &restored)?
+            {
+                if config.on_conflict == ConflictPolicy::Fail {
+                    return Err(DotfilesError::RestoreConflict(
+                        destination.display().to_string(),
+                    ));
+                }
+
+                let quarantined = if config.dry_run {
+                    quarantine_path(config, entry, Local::now())
+                } else {
+                    quarantine(config, entry, &restored)?.quarantined
+                };
+                plan.notify(
+                    CONFLICT_QUARANTINED,
+                    destination,
+                    t!(
+                        "notice-conflict-quarantined",
+                        quarantined = quarantined.display().to_string()
+                    ),
+                );
+                plan.copies.push(PlannedCopy {
+                    source,
+                    destination: quarantined,
+                    action: PlannedAction::Quarantine,
+                });
+
+                return Ok(plan);
+            }
+
+            restored != home
+        }
+        Err(_) => true,
+    };
+    if changed {
+        save_for_undo(config, &destination)?;
+    }
+    plan.record(source, destination.clone(), changed);
 
-    if !is_dotfile(&path) {
+    if changed && !config.dry_run {
+        restore()?;
+        record_restored(config, &destination)?;
+    }
+
+    Ok(plan)
+}
+
+/// Restore a specific dotfile by path
+pub fn restore_specific_dotfile(config: &Config, file_path: &str) -> Result<Plan, DotfilesError> {
+    restore_file(config, open_backend(config)?.as_ref(), file_path, None)
+}
+
+/// Restore a dotfile from a backend, as stored or as it was at a version
+///
+/// `version` is anything `resolve_file_version_with` accepts. Files that
+/// aren't dotfiles or tracked are skipped.
+pub(crate) fn restore_file(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    file_path: &str,
+    version: Option<&str>,
+) -> Result<Plan, DotfilesError> {
+    let path = normalize_path(file_path, config);
+    let manifest = Manifest::load(config)?;
+    if !is_trackable(config, &path) && !manifest.tracks(&path, config) {
         debug!("Skipping non-dotfile: {:?}", path);
-        return Ok(());
+        return Ok(Plan::default());
     }
 
     let dotfile = Dotfile::new(path, config);
+    let entry = manifest.entry_for(&dotfile, config);
+    let version = match version {
+        Some(version) => {
+            Some(resolve_file_version_with(backend, &entry.stored_path(config), version)?.commit_id)
+        }
+        None => None,
+    };
+
+    hooked_restore(config, vec![dotfile.original_path], || {
+        restore_tracked(config, backend, &entry, version.as_deref())
+    })
+}
+
+/// Run a restore between the pre-restore and post-restore hooks
+///
+/// The post-restore hook gets the files that were actually written.
+fn hooked_restore<F>(
+    config: &Config,
+    files: Vec<PathBuf>,
+    restore: F,
+) -> Result<Plan, DotfilesError>
+where
+    F: FnOnce() -> Result<Plan, DotfilesError>,
+{
+    let _lock = VaultLock::acquire(config)?;
+    run_operation_hook(config, HookEvent::PreRestore, files)?;
+    let plan = restore()?;
+    run_operation_hook(config, HookEvent::PostRestore, plan.home_files(config))?;
+
+    Ok(plan)
+}
+
+/// Restore a tracked entry from the vault, recording the copy in a plan
+///
+/// With a version, the entry's content is read from that version instead
+/// of as stored.
+fn restore_tracked(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entry: &DotfileEntry,
+    version: Option<&str>,
+) -> Result<Plan, DotfilesError> {
+    if !entry.applies() {
+        debug!("Skipping {}: conditions not met", entry.id);
+        return Ok(Plan::default());
+    }
+
+    if entry.create_if_missing && is_file_or_link(&entry.dotfile(config).original_path) {
+        debug!(
+            "Skipping {}: starter content, the home file exists",
+            entry.id
+        );
+        return Ok(Plan::default());
+    }
+
+    let stored_path = entry.stored_path(config);
+    let source = match version {
+        Some(version) => PathBuf::from(format!(
+            "{}:{}",
+            version.get(..7).unwrap_or(version),
+            stored_path.display()
+        )),
+        None => config.vault_dir.join(&stored_path),
+    };
+    if let Some(target) = backend.fetch_link(&stored_path, version)? {
+        return restore_link(config, entry, source, &target);
+    }
+
+    let content = backend
+        .fetch_file(&stored_path, version)?
+        .ok_or_else(|| DotfilesError::DotfileNotFound(entry.source.display().to_string()))?;
+    if version.is_some() {
+        return restore_planned(config, backend, entry, source, &content, || {
+            restore_entry_content(config, entry, &content)
+        });
+    }
+
+    restore_planned(config, backend, entry, source, &content, || {
+        restore_entry(config, backend, entry).map(|_| ())
+    })
+}
+
+/// Recreate a symlink stored in the vault, recording it in a plan
+///
+/// Links carry no content of their own, so there are no local edits to
+/// keep and the conflict policy doesn't apply.
+fn restore_link(
+    config: &Config,
+    entry: &DotfileEntry,
+    source: PathBuf,
+    target: &Path,
+) -> Result<Plan, DotfilesError> {
+    let destination = entry.dotfile(config).original_path;
+    let changed = fs::read_link(&destination).ok().as_deref() != Some(target);
+
+    let mut plan = Plan::default();
+    plan.record(source, destination.clone(), changed);
+    if changed && !config.dry_run {
+        save_for_undo(config, &destination)?;
+        create_symlink(target, &destination)?;
+        record_restored(config, &destination)?;
+        info!("Restored link: {:?}", destination);
+    }
+
+    Ok(plan)
+}
+
+/// Outcome of restoring every tracked dotfile
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    /// Copies made (or, in a dry run, planned), including quarantined versions
+    pub plan: Plan,
+
+    /// Home-relative paths of the files that were restored
+    pub restored: Vec<PathBuf>,
+
+    /// Files left alone: up to date, not for this machine, or kept because of a conflict
+    pub skipped: Vec<PathBuf>,
+
+    /// Files left alone because they have edits that were never backed up
+    pub conflicted: Vec<PathBuf>,
+
+    /// Files that couldn't be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+
+    /// Files not looked at because the restore ran out of time
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deferred: Vec<PathBuf>,
+}
+
+/// Restore every tracked dotfile to the home directory
+///
+/// Files are taken from the manifest, or from the vault contents for vaults
+/// from before the manifest. A failing file doesn't stop the others.
+pub fn restore_all_dotfiles(config: &Config) -> Result<RestoreSummary, DotfilesError> {
+    restore_all_at(config, None)
+}
+
+/// Restore every tracked dotfile as stored in the vault or at a commit
+///
+/// At a commit, tracked files that weren't in the vault then are skipped.
+pub fn restore_all_at(
+    config: &Config,
+    commit_id: Option<&str>,
+) -> Result<RestoreSummary, DotfilesError> {
+    restore_all_from(config, open_backend(config)?.as_ref(), commit_id)
+}
+
+/// Restore every tracked dotfile from a backend, as stored or at a version
+///
+/// `version` is anything `VaultBackend::resolve_version` accepts.
+pub(crate) fn restore_all_from(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    version: Option<&str>,
+) -> Result<RestoreSummary, DotfilesError> {
+    let commit_id = match version {
+        Some(version) => Some(backend.resolve_version(version)?.commit_id),
+        None => None,
+    };
+    let commit_id = commit_id.as_deref();
+
+    let _lock = VaultLock::acquire(config)?;
+    let manifest = Manifest::load(config)?;
+    let vault_files = backend.list_files(commit_id)?;
+    let mut entries: Vec<DotfileEntry> = manifest.entries.values().cloned().collect();
+    let mut absent = Vec::new();
+    if commit_id.is_some() {
+        entries.retain(|entry| {
+            let stored = vault_files.contains(&entry.stored_path(config));
+            if !stored {
+                absent.push(entry.source.clone());
+            }
+            stored
+        });
+    }
+
+    // Vaults from before the manifest only have their files to go by, and
+    // templates may be added to the vault by hand
+    for relative in vault_files {
+        if manifest.entries.is_empty() || is_template(&relative) {
+            let home_path = config.home_dir.join(rendered_path(&relative));
+            let entry = manifest.entry_for(&Dotfile::new(home_path, config), config);
+            if !entries.iter().any(|known| known.id == entry.id) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.source.cmp(&b.source));
+
+    // Files differing only in case would overwrite each other
+    let case_insensitive = is_case_insensitive(&config.home_dir);
+    let (collisions, left_out) = resolve_restore_collisions(config, &mut entries, case_insensitive);
+
+    let mut summary = RestoreSummary::default();
+    summary.plan.add_collisions(collisions);
+    summary.skipped = left_out;
+    summary.skipped.extend(absent);
+    restore_entries(config, backend, &entries, commit_id, summary)
+}
+
+/// Restore dotfiles as they were at a commit, e.g. to replay a recorded restore
+///
+/// `files` are home-relative paths of tracked dotfiles. Works like
+/// `restore_all_at` for just these files.
+pub fn restore_files_at(
+    config: &Config,
+    files: &[PathBuf],
+    commit_id: &str,
+) -> Result<RestoreSummary, DotfilesError> {
+    restore_files_from(
+        config,
+        open_backend(config)?.as_ref(),
+        files,
+        Some(commit_id),
+    )
+}
+
+/// Restore some dotfiles as stored in the vault, e.g. the ones a pattern matched
+///
+/// `files` are home-relative paths of tracked dotfiles. Works like
+/// `restore_all_dotfiles` for just these files.
+pub fn restore_files(config: &Config, files: &[PathBuf]) -> Result<RestoreSummary, DotfilesError> {
+    restore_files_from(config, open_backend(config)?.as_ref(), files, None)
+}
+
+/// Restore dotfiles from a backend, as stored or at a commit
+fn restore_files_from(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    files: &[PathBuf],
+    commit_id: Option<&str>,
+) -> Result<RestoreSummary, DotfilesError> {
+    if !config.vault_dir.exists() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+
+    let _lock = VaultLock::acquire(config)?;
+    let manifest = Manifest::load(config)?;
+    let entries: Vec<DotfileEntry> = files
+        .iter()
+        .map(|file| manifest.entry_for(&Dotfile::new(config.home_dir.join(file), config), config))
+        .collect();
+
+    restore_entries(
+        config,
+        backend,
+        &entries,
+        commit_id,
+        RestoreSummary::default(),
+    )
+}
+
+/// Restore entries one by one between the restore hooks, adding the
+/// outcomes to a summary
+fn restore_entries(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entries: &[DotfileEntry],
+    commit_id: Option<&str>,
+    mut summary: RestoreSummary,
+) -> Result<RestoreSummary, DotfilesError> {
+    let files = entries
+        .iter()
+        .map(|entry| entry.dotfile(config).original_path)
+        .collect();
+    run_operation_hook(config, HookEvent::PreRestore, files)?;
+
+    // Each file is replaced atomically, so a cancelled restore stops between
+    // files and leaves the ones already restored in place
+    let progress = Progress::files(config, entries.len(), t!("progress-restore"));
+    for entry in entries {
+        if let Err(err) = config.cancellation.check() {
+            warn!(
+                "Restore stopped after restoring {} dotfiles",
+                summary.restored.len()
+            );
+            return Err(err);
+        }
+
+        match restore_tracked(config, backend, entry, commit_id) {
+            Ok(plan) => {
+                let restored = plan
+                    .copies
+                    .iter()
+                    .any(|copy| copy.action != PlannedAction::Quarantine);
+                if restored {
+                    summary.restored.push(entry.source.clone());
+                } else {
+                    summary.skipped.push(entry.source.clone());
+                }
+                progress.inc(written_bytes(&plan));
+                summary.plan.extend(plan);
+            }
+            Err(err @ DotfilesError::RestoreConflict(_)) => {
+                warn!(
+                    "Kept {:?}, it has edits that were never backed up",
+                    entry.source
+                );
+                summary.plan.notify_error(entry.source.clone(), &err);
+                summary.conflicted.push(entry.source.clone());
+                progress.inc(0);
+            }
+            Err(err) => {
+                warn!("Failed to restore {:?}: {}", entry.source, err);
+                summary.plan.notify_error(entry.source.clone(), &err);
+                summary.failed.push((entry.source.clone(), err.to_string()));
+                progress.inc(0);
+            }
+        }
+    }
+    drop(progress);
+
+    if !config.dry_run {
+        write_snippets_script(config)?;
+        write_shell_files(config)?;
+    }
+
+    run_operation_hook(
+        config,
+        HookEvent::PostRestore,
+        summary.plan.home_files(config),
+    )?;
+
+    info!(
+        "Restored {} dotfiles, skipped {}, failed {}",
+        summary.restored.len(),
+        summary.skipped.len(),
+        summary.failed.len()
+    );
+
+    Ok(summary)
+}
+
+/// Restore the tracked dotfiles that drifted from the vault, within a time budget
+///
+/// Meant to run at every login, so machines converge to the vault: only the
+/// local vault is read, never a remote. Files are first compared using the
+/// hash cache, so files unchanged since the last run aren't read, and only
+/// the drifted ones are restored, together between one pair of hooks. Files
+/// not reached when the budget runs out are deferred to the next run.
+#[cfg(feature = "git")]
+pub fn restore_drifted(config: &Config, budget: Duration) -> Result<RestoreSummary, DotfilesError> {
+    let started = Instant::now();
+    if !config.vault_dir.exists() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+    let backend = open_backend(config)?;
+    let backend = backend.as_ref();
+
+    let mut config = config.clone();
+    config.home_cache = true;
+    let config = &config;
+
+    let _lock = VaultLock::acquire(config)?;
+    let manifest = Manifest::load(config)?;
+    let mut summary = RestoreSummary::default();
+
+    let mut drifted = Vec::new();
+    for entry in manifest.entries.values().filter(|entry| entry.applies()) {
+        if started.elapsed() >= budget {
+            summary
+                .plan
+                .notify(DEFERRED, entry.source.clone(), t!("notice-deferred"));
+            summary.deferred.push(entry.source.clone());
+            continue;
+        }
+        match drifted_quickly(config, backend, entry) {
+            Ok(true) => drifted.push(entry),
+            Ok(false) => {}
+            Err(err) => {
+                warn!("Failed to check {:?}: {}", entry.source, err);
+                summary.plan.notify_error(entry.source.clone(), &err);
+                summary.failed.push((entry.source.clone(), err.to_string()));
+            }
+        }
+    }
+
+    if !drifted.is_empty() {
+        let files = drifted
+            .iter()
+            .map(|entry| entry.dotfile(config).original_path)
+            .collect();
+        run_operation_hook(config, HookEvent::PreRestore, files)?;
+
+        for entry in drifted {
+            if started.elapsed() >= budget {
+                summary
+                    .plan
+                    .notify(DEFERRED, entry.source.clone(), t!("notice-deferred"));
+                summary.deferred.push(entry.source.clone());
+                continue;
+            }
+            match restore_tracked(config, backend, entry, None) {
+                Ok(plan) => {
+                    let restored = plan
+                        .copies
+                        .iter()
+                        .any(|copy| copy.action != PlannedAction::Quarantine);
+                    if restored {
+                        summary.restored.push(entry.source.clone());
+                    } else {
+                        summary.skipped.push(entry.source.clone());
+                    }
+                    summary.plan.extend(plan);
+                }
+                Err(err @ DotfilesError::RestoreConflict(_)) => {
+                    warn!(
+                        "Kept {:?}, it has edits that were never backed up",
+                        entry.source
+                    );
+                    summary.plan.notify_error(entry.source.clone(), &err);
+                    summary.conflicted.push(entry.source.clone());
+                }
+                Err(err) => {
+                    warn!("Failed to restore {:?}: {}", entry.source, err);
+                    summary.plan.notify_error(entry.source.clone(), &err);
+                    summary.failed.push((entry.source.clone(), err.to_string()));
+                }
+            }
+        }
+
+        run_operation_hook(
+            config,
+            HookEvent::PostRestore,
+            summary.plan.home_files(config),
+        )?;
+    }
+
+    config.hash_cache.save(config)?;
+
+    info!(
+        "Restored {} drifted dotfiles in {:?}, deferred {}",
+        summary.restored.len(),
+        started.elapsed(),
+        summary.deferred.len()
+    );
+
+    Ok(summary)
+}
+
+/// Check if a tracked file needs restoring, using cached hashes where possible
+///
+/// Plain files are compared by hash, with the home copy's hash taken from
+/// the cache. Links, templates, and transformed or encrypted files need
+/// their stored content decoded. Files that aren't stored never need it.
+#[cfg(feature = "git")]
+fn drifted_quickly(
+    config: &Config,
+    backend: &dyn VaultBackend,
+    entry: &DotfileEntry,
+) -> Result<bool, DotfilesError> {
+    let dotfile = entry.dotfile(config);
+    let path = dotfile.stored_path(config);
+    let Some(stored) = backend.fetch_file(&path, None)? else {
+        return Ok(
+            backend.fetch_link(&path, None)?.is_some() && has_drifted(config, backend, entry)?
+        );
+    };
+
+    let plain = entry.transforms.is_empty()
+        && !entry.is_template()
+        && !dotfile.original_path.is_symlink()
+        && !is_encrypted(&stored);
+    if !plain || !dotfile.original_path.is_file() {
+        return has_drifted(config, backend, entry);
+    }
+
+    Ok(content_hash(&stored)?
+        != config
+            .hash_cache
+            .home_hash(config, &dotfile.original_path)?)
+}
+
+/// Restore a dotfile as it was stored at a specific version
+///
+/// `version` is anything `resolve_file_version_with` accepts, such as `v2`,
+/// an abbreviated SHA, a tag or `HEAD~2` in a git vault. The entry's
+/// transforms and permissions policy are applied as for a regular restore.
+pub fn restore_dotfile_version(
+    config: &Config,
+    file_path: &str,
+    version: &str,
+) -> Result<Plan, DotfilesError> {
+    restore_file(
+        config,
+        open_backend(config)?.as_ref(),
+        file_path,
+        Some(version),
+    )
+}
+
+/// Read the backed up copy of a dotfile from the vault, decrypting it if needed
+///
+/// For templates this is the unrendered template.
+pub fn read_backed_up_dotfile(config: &Config, file_path: &str) -> Result<String, DotfilesError> {
+    let path = normalize_path(file_path, config);
+    let dotfile = Manifest::load(config)?
+        .entry_for(&Dotfile::new(path, config), config)
+        .dotfile(config);
+
+    if !dotfile.vault_path.is_file() {
+        return Err(DotfilesError::DotfileNotFound(file_path.to_string()));
+    }
+
+    let mut content = fs::read(&dotfile.vault_path)?;
+    if is_encrypted(&content) {
+        content = decrypt(config, &content)?;
+    }
 
-    restore_dotfile(&dotfile)
+    Ok(String::from_utf8_lossy(&content).into_owned())
 }
 
 /// List all backed up dotfiles
@@ -55,18 +786,19 @@ pub fn list_backed_up_dotfiles(config: &Config) -> Result<Vec<PathBuf>, Dotfiles
 
     let mut backed_up_files = Vec::new();
 
-    // Walk through the vault directory
+    // Walk through the vault directory; stored symlinks are files of their own
     for entry in walkdir::WalkDir::new(&config.vault_dir)
-        .follow_links(true)
         .into_iter()
         .filter_map(|e| e.ok())
     {
         let path = entry.path();
 
         // Only include files
-        if path.is_file() {
+        if entry.file_type().is_file() || entry.path_is_symlink() {
             // Get the relative path from the vault directory
-            if let Ok(relative_path) = path.strip_prefix(&config.vault_dir) {
+            if let Ok(relative_path) = path.strip_prefix(&config.vault_dir)
+                && !is_vault_internal(relative_path)
+            {
                 backed_up_files.push(relative_path.to_path_buf());
             }
         }
@@ -78,6 +810,9 @@ pub fn list_backed_up_dotfiles(config: &Config) -> Result<Vec<PathBuf>, Dotfiles
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "git")]
+    use crate::backend::GitBackend;
+    use crate::manifest::{PermissionsPolicy, track_starter};
     use std::fs::File;
     use std::io::Write;
     use tempfile::TempDir;
@@ -104,7 +839,53 @@ mod tests {
         (config, home_dir, vault_dir)
     }
 
+    #[cfg(unix)]
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_runs_hooks() {
+        let (mut config, home_dir, _vault_dir) = setup_test_env();
+        let output = home_dir.path().join("hook-output");
+        config.hook_commands.insert(
+            "post-restore".to_string(),
+            format!("echo \"$DOTFILESVAULT_FILES\" >> {}", output.display()),
+        );
+
+        restore_specific_dotfile(&config, ".testrc").unwrap();
+        restore_specific_dotfile(&config, ".testrc").unwrap();
+
+        // The second restore had nothing to write
+        let content = fs::read_to_string(&output).unwrap();
+        assert_eq!(
+            content,
+            format!("{}\n\n", home_dir.path().join(".testrc").display())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_leaves_unchanged_files_alone() {
+        use std::time::{Duration, SystemTime};
+
+        let (config, home_dir, _vault_dir) = setup_test_env();
+        let home_file = home_dir.path().join(".testrc");
+        fs::write(&home_file, "test content\n").unwrap();
+        let earlier = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&home_file)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+
+        let plan = restore_specific_dotfile(&config, ".testrc").unwrap();
+
+        assert!(plan.copies.is_empty());
+        let modified = fs::metadata(&home_file).unwrap().modified().unwrap();
+        assert_eq!(modified, earlier);
+    }
+
     #[test]
+    #[cfg(feature = "git")]
     fn test_restore_dotfile() {
         let (config, home_dir, _vault_dir) = setup_test_env();
 
@@ -120,7 +901,7 @@ mod tests {
         writeln!(file, "test content").unwrap();
 
         // Restore the dotfile
-        restore_dotfile(&dotfile).unwrap();
+        restore_dotfile(&config, &GitBackend::new(&config), &dotfile).unwrap();
 
         // Check if the file was restored
         assert!(original_path.exists());
@@ -130,6 +911,292 @@ mod tests {
         assert!(content.contains("test content"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_entry_applies_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (config, home_dir, _vault_dir) = setup_test_env();
+
+        let dotfile = Dotfile::new(home_dir.path().join(".testrc"), &config);
+        let mut entry = DotfileEntry::new(&dotfile, &config);
+        entry.permissions = PermissionsPolicy::Private;
+
+        assert!(restore_entry(&config, &GitBackend::new(&config), &entry).unwrap());
+
+        let mode = fs::metadata(&dotfile.original_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_dotfile_version() {
+        use crate::history::{commit_changes, init_git_repo};
+
+        let (config, home_dir, vault_dir) = setup_test_env();
+        init_git_repo(&config).unwrap();
+        let first = commit_changes(&config, "First").unwrap();
+
+        fs::write(vault_dir.path().join(".testrc"), "newer content\n").unwrap();
+        commit_changes(&config, "Second").unwrap();
+
+        restore_dotfile_version(&config, ".testrc", &first[..8]).unwrap();
+        let content = fs::read_to_string(home_dir.path().join(".testrc")).unwrap();
+        assert_eq!(content, "test content\n");
+
+        assert!(matches!(
+            restore_dotfile_version(&config, ".missingrc", &first),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+        assert!(matches!(
+            restore_dotfile_version(&config, ".testrc", "not-a-commit"),
+            Err(DotfilesError::VersionNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_dry_run_writes_nothing() {
+        let (mut config, home_dir, vault_dir) = setup_test_env();
+        config.dry_run = true;
+
+        let plan = restore_specific_dotfile(&config, ".testrc").unwrap();
+
+        assert_eq!(plan.copies.len(), 1);
+        assert_eq!(plan.copies[0].source, vault_dir.path().join(".testrc"));
+        assert_eq!(plan.copies[0].action, PlannedAction::Create);
+        assert!(!home_dir.path().join(".testrc").exists());
+
+        // An identical home copy needs no restore
+        fs::write(home_dir.path().join(".testrc"), "test content\n").unwrap();
+        let plan = restore_specific_dotfile(&config, ".testrc").unwrap();
+        assert!(plan.copies.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_refuses_to_overwrite_local_edits() {
+        use crate::history::{commit_changes, init_git_repo};
+
+        let (mut config, home_dir, vault_dir) = setup_test_env();
+        init_git_repo(&config).unwrap();
+        commit_changes(&config, "First").unwrap();
+        fs::write(vault_dir.path().join(".testrc"), "newer content\n").unwrap();
+        commit_changes(&config, "Second").unwrap();
+
+        // A home file matching an older backup is safe to replace
+        let home_path = home_dir.path().join(".testrc");
+        fs::write(&home_path, "test content\n").unwrap();
+        restore_specific_dotfile(&config, ".testrc").unwrap();
+        assert_eq!(fs::read_to_string(&home_path).unwrap(), "newer content\n");
+
+        fs::write(&home_path, "local edits\n").unwrap();
+        assert!(matches!(
+            restore_specific_dotfile(&config, ".testrc"),
+            Err(DotfilesError::RestoreConflict(_))
+        ));
+        assert_eq!(fs::read_to_string(&home_path).unwrap(), "local edits\n");
+
+        config.on_conflict = ConflictPolicy::Overwrite;
+        restore_specific_dotfile(&config, ".testrc").unwrap();
+        assert_eq!(fs::read_to_string(&home_path).unwrap(), "newer content\n");
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_skip_policy_quarantines_local_edits() {
+        use crate::conflicts::list_conflicts;
+
+        let (mut config, home_dir, _vault_dir) = setup_test_env();
+        config.on_conflict = ConflictPolicy::Skip;
+        let home_path = home_dir.path().join(".testrc");
+        fs::write(&home_path, "local edits\n").unwrap();
+
+        let plan = restore_specific_dotfile(&config, ".testrc").unwrap();
+
+        assert_eq!(plan.copies[0].action, PlannedAction::Quarantine);
+        assert_eq!(fs::read_to_string(&home_path).unwrap(), "local edits\n");
+        let conflicts = list_conflicts(&config).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&conflicts[0].quarantined).unwrap(),
+            "test content\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_all_dotfiles() {
+        let (config, home_dir, vault_dir) = setup_test_env();
+        fs::create_dir_all(vault_dir.path().join(".config/app")).unwrap();
+        fs::write(vault_dir.path().join(".config/app/config"), "app\n").unwrap();
+        fs::write(home_dir.path().join(".testrc"), "test content\n").unwrap();
+
+        let summary = restore_all_dotfiles(&config).unwrap();
+
+        assert_eq!(summary.restored, vec![PathBuf::from(".config/app/config")]);
+        assert_eq!(summary.skipped, vec![PathBuf::from(".testrc")]);
+        assert!(summary.failed.is_empty());
+        assert_eq!(
+            fs::read_to_string(home_dir.path().join(".config/app/config")).unwrap(),
+            "app\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_drifted() {
+        let (config, home_dir, _vault_dir) = setup_test_env();
+        fs::write(home_dir.path().join(".testrc"), "test content\n").unwrap();
+        fs::write(home_dir.path().join(".bashrc"), "bash\n").unwrap();
+        fs::write(home_dir.path().join(".vimrc"), "vim\n").unwrap();
+        crate::backup::backup_all_dotfiles(&config).unwrap();
+
+        fs::write(home_dir.path().join(".bashrc"), "edited\n").unwrap();
+        fs::remove_file(home_dir.path().join(".vimrc")).unwrap();
+
+        // Nothing is checked without any time
+        let summary = restore_drifted(&config, Duration::ZERO).unwrap();
+        assert_eq!(summary.deferred.len(), 3);
+        assert!(!home_dir.path().join(".vimrc").exists());
+
+        // Edits that were never backed up are kept
+        let summary = restore_drifted(&config, Duration::from_secs(60)).unwrap();
+        assert_eq!(summary.restored, vec![PathBuf::from(".vimrc")]);
+        assert_eq!(summary.conflicted, vec![PathBuf::from(".bashrc")]);
+        let codes: Vec<&str> = summary
+            .plan
+            .notices
+            .iter()
+            .map(|notice| notice.code)
+            .collect();
+        assert_eq!(codes, vec!["E_CONFLICT_LOCAL_MODIFIED"]);
+        assert!(summary.deferred.is_empty());
+        assert_eq!(
+            fs::read_to_string(home_dir.path().join(".bashrc")).unwrap(),
+            "edited\n"
+        );
+        assert_eq!(
+            fs::read_to_string(home_dir.path().join(".vimrc")).unwrap(),
+            "vim\n"
+        );
+
+        fs::write(home_dir.path().join(".bashrc"), "bash\n").unwrap();
+        let summary = restore_drifted(&config, Duration::from_secs(60)).unwrap();
+        assert!(summary.restored.is_empty());
+        assert!(summary.conflicted.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_config_locations() {
+        let (mut config, home_dir, _vault_dir) = setup_test_env();
+        config.config_locations = vec![PathBuf::from("AppData/Roaming/alacritty")];
+        let settings = home_dir
+            .path()
+            .join("AppData")
+            .join("Roaming")
+            .join("alacritty")
+            .join("alacritty.toml");
+        fs::create_dir_all(settings.parent().unwrap()).unwrap();
+        fs::write(&settings, "[font]\n").unwrap();
+        fs::write(home_dir.path().join("notes.txt"), "not config\n").unwrap();
+
+        crate::backup::backup_all_dotfiles(&config).unwrap();
+        let manifest = fs::read_to_string(Manifest::path(&config)).unwrap();
+        assert!(manifest.contains("\"source\": \"AppData/Roaming/alacritty/alacritty.toml\""));
+        assert!(!manifest.contains("notes.txt"));
+
+        fs::remove_dir_all(home_dir.path().join("AppData")).unwrap();
+        let summary = restore_all_dotfiles(&config).unwrap();
+        assert!(summary.failed.is_empty());
+        assert_eq!(fs::read_to_string(&settings).unwrap(), "[font]\n");
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_renders_templates() {
+        let (mut config, home_dir, vault_dir) = setup_test_env();
+        config.author_email = Some("me@example.com".to_string());
+        config
+            .variables
+            .insert("editor".to_string(), "vim".to_string());
+        fs::write(
+            vault_dir.path().join(".gitconfig.tmpl"),
+            "[user]\n  email = {{ email }}\n[core]\n  editor = {{ editor }}\n",
+        )
+        .unwrap();
+
+        let summary = restore_all_dotfiles(&config).unwrap();
+        assert!(summary.restored.contains(&PathBuf::from(".gitconfig")));
+
+        let gitconfig = home_dir.path().join(".gitconfig");
+        assert_eq!(
+            fs::read_to_string(&gitconfig).unwrap(),
+            "[user]\n  email = me@example.com\n[core]\n  editor = vim\n"
+        );
+        assert!(!home_dir.path().join(".gitconfig.tmpl").exists());
+
+        // Rendered output matching the template counts as up to date
+        let plan = restore_specific_dotfile(&config, ".gitconfig").unwrap();
+        assert!(plan.copies.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_restore_creates_starters_only_if_missing() {
+        let (mut config, home_dir, vault_dir) = setup_test_env();
+        config.variables.insert(
+            "registry".to_string(),
+            "https://npm.example.com".to_string(),
+        );
+        let starter = vault_dir.path().join("npmrc.tmpl");
+        fs::write(&starter, "registry={{ registry }}\n").unwrap();
+        let entry = track_starter(&config, ".npmrc", Some(&starter)).unwrap();
+        assert!(entry.create_if_missing && entry.is_template());
+
+        let summary = restore_all_dotfiles(&config).unwrap();
+        assert!(summary.restored.contains(&PathBuf::from(".npmrc")));
+        let npmrc = home_dir.path().join(".npmrc");
+        assert_eq!(
+            fs::read_to_string(&npmrc).unwrap(),
+            "registry=https://npm.example.com\n"
+        );
+
+        // Established local files are never overwritten or backed up
+        fs::write(&npmrc, "registry=https://local\n").unwrap();
+        let summary = restore_all_dotfiles(&config).unwrap();
+        assert!(summary.skipped.contains(&PathBuf::from(".npmrc")));
+        assert!(summary.conflicted.is_empty());
+        assert!(!has_drifted(&config, &GitBackend::new(&config), &entry).unwrap());
+        assert!(!crate::backup::backup_entry(&config, &GitBackend::new(&config), &entry).unwrap());
+        assert_eq!(
+            fs::read_to_string(&npmrc).unwrap(),
+            "registry=https://local\n"
+        );
+        assert_eq!(
+            fs::read_to_string(vault_dir.path().join(".npmrc.tmpl")).unwrap(),
+            "registry={{ registry }}\n"
+        );
+    }
+
+    #[test]
+    fn test_read_backed_up_dotfile() {
+        let (config, _home_dir, _vault_dir) = setup_test_env();
+
+        let content = read_backed_up_dotfile(&config, ".testrc").unwrap();
+        assert_eq!(content, "test content\n");
+
+        assert!(matches!(
+            read_backed_up_dotfile(&config, ".missingrc"),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_list_backed_up_dotfiles() {
         let (config, _home_dir, vault_dir) = setup_test_env();
@@ -0,0 +1,363 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use git2::Repository;
+use git2::build::CheckoutBuilder;
+use log::{debug, info};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::diff::unified_diff;
+use crate::history::{
+    DotfileVersion, commit_at, commit_changes, home_file, read_file_at, resolve_version,
+};
+use crate::hooks::{HookEvent, run_operation_hook};
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::restore::{decode_entry_content, restore_entry_content};
+use crate::{Config, DotfilesError};
+
+/// What rolling back a single file will do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollbackAction {
+    /// The home file will be replaced with the historical version
+    Restore,
+
+    /// The home file already matches the historical version
+    Unchanged,
+
+    /// The file was not in the vault at that time and is left untouched
+    Missing,
+}
+
+/// A single file in a rollback plan
+#[derive(Debug, Clone)]
+pub struct RollbackItem {
+    /// The manifest entry being rolled back
+    pub entry: DotfileEntry,
+
+    /// What will happen to the file
+    pub action: RollbackAction,
+
+    /// The stored content at the target version, if the file existed
+    pub content: Option<Vec<u8>>,
+
+    /// Unified diff from the current home file to the target version
+    pub diff: String,
+}
+
+/// A planned rollback of a group of files to a point in time
+#[derive(Debug, Clone)]
+pub struct RollbackPlan {
+    /// The group being rolled back
+    pub group: String,
+
+    /// The vault version the files are rolled back to
+    pub version: DotfileVersion,
+
+    /// One item per entry in the group
+    pub items: Vec<RollbackItem>,
+}
+
+impl RollbackPlan {
+    /// Number of files that will be changed
+    pub fn change_count(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.action == RollbackAction::Restore)
+            .count()
+    }
+}
+
+/// Plan rolling back every file in a group to its state at a point in time
+pub fn plan_group_rollback(
+    config: &Config,
+    group: &str,
+    at: DateTime<Local>,
+) -> Result<RollbackPlan, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    let entries: Vec<&DotfileEntry> = manifest
+        .entries
+        .values()
+        .filter(|entry| entry.group.as_deref() == Some(group))
+        .collect();
+
+    if entries.is_empty() {
+        return Err(DotfilesError::GroupNotFound(group.to_string()));
+    }
+
+    let version =
+        commit_at(config, at)?.ok_or_else(|| DotfilesError::VersionNotFound(at.to_rfc3339()))?;
+
+    debug!(
+        "Rolling back group {} to commit {}",
+        group, version.commit_id
+    );
+
+    let mut items = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let content = read_file_at(config, &version.commit_id, &entry.stored_path(config))?;
+
+        let Some(stored) = content else {
+            items.push(RollbackItem {
+                entry: entry.clone(),
+                action: RollbackAction::Missing,
+                content: None,
+                diff: String::new(),
+            });
+            continue;
+        };
+
+        let historical = decode_entry_content(config, entry, &stored)?;
+        let current = fs::read(entry.dotfile(config).original_path).unwrap_or_default();
+        let diff = unified_diff(&current, &entry.id, &historical, &entry.id)?;
+
+        let action = if current == historical {
+            RollbackAction::Unchanged
+        } else {
+            RollbackAction::Restore
+        };
+
+        items.push(RollbackItem {
+            entry: entry.clone(),
+            action,
+            content: Some(stored),
+            diff,
+        });
+    }
+
+    Ok(RollbackPlan {
+        group: group.to_string(),
+        version,
+        items,
+    })
+}
+
+/// Apply a rollback plan, returning the number of files restored
+pub fn apply_rollback(config: &Config, plan: &RollbackPlan) -> Result<usize, DotfilesError> {
+    let items: Vec<(&RollbackItem, &Vec<u8>)> = plan
+        .items
+        .iter()
+        .filter(|item| item.action == RollbackAction::Restore)
+        .filter_map(|item| item.content.as_ref().map(|content| (item, content)))
+        .collect();
+    let files: Vec<_> = items
+        .iter()
+        .map(|(item, _)| item.entry.dotfile(config).original_path)
+        .collect();
+
+    let _lock = VaultLock::acquire(config)?;
+    run_operation_hook(config, HookEvent::PreRestore, files.clone())?;
+
+    for (item, content) in &items {
+        config.cancellation.check()?;
+        restore_entry_content(config, &item.entry, content)?;
+    }
+    let restored = items.len();
+
+    run_operation_hook(config, HookEvent::PostRestore, files)?;
+
+    info!(
+        "Rolled back {} files in group {} to {}",
+        restored, plan.group, plan.version.commit_id
+    );
+
+    Ok(restored)
+}
+
+/// A planned rollback of the whole vault to an earlier commit
+#[derive(Debug, Clone)]
+pub struct VaultRollback {
+    /// The commit the vault is rolled back to
+    pub version: DotfileVersion,
+
+    /// Vault-relative files the rollback changes, adds or deletes
+    pub changed: Vec<PathBuf>,
+
+    /// Home-relative paths of the dotfiles the rollback gives another
+    /// version, the ones restored with `--restore`
+    pub files: Vec<PathBuf>,
+}
+
+/// Plan rolling the whole vault back to a commit
+///
+/// `spec` is anything `resolve_version` accepts. Uncommitted vault changes
+/// count as changes the rollback undoes.
+pub fn plan_vault_rollback(config: &Config, spec: &str) -> Result<VaultRollback, DotfilesError> {
+    let version = resolve_version(config, spec)?;
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let tree = repo.find_commit(version.commit_id.parse()?)?.tree()?;
+
+    let mut options = git2::DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?;
+
+    let mut changed = Vec::new();
+    let mut files = Vec::new();
+    for delta in diff.deltas() {
+        let Some(path) = delta.old_file().path().or(delta.new_file().path()) else {
+            continue;
+        };
+        changed.push(path.to_path_buf());
+
+        // Dotfiles the commit didn't have are left alone in the home directory
+        if tree.get_path(path).is_err() {
+            continue;
+        }
+        if let Some(file) = home_file(config, path)
+            && let Ok(relative) = file.strip_prefix(&config.home_dir)
+            && !files.iter().any(|known| known == relative)
+        {
+            files.push(relative.to_path_buf());
+        }
+    }
+
+    debug!(
+        "Rolling the vault back to {} changes {} files",
+        version.commit_id,
+        changed.len()
+    );
+
+    Ok(VaultRollback {
+        version,
+        changed,
+        files,
+    })
+}
+
+/// Roll the whole vault back, recording it as a new commit
+///
+/// History is kept: the vault gets the files of the target commit in a new
+/// commit on top of the current one. Returns the ID of that commit, or
+/// `None` if the vault already matches the target.
+pub fn apply_vault_rollback(
+    config: &Config,
+    plan: &VaultRollback,
+) -> Result<Option<String>, DotfilesError> {
+    if plan.changed.is_empty() {
+        return Ok(None);
+    }
+
+    {
+        let _lock = VaultLock::acquire(config)?;
+        let repo =
+            Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+        let commit = repo.find_commit(plan.version.commit_id.parse()?)?;
+        repo.checkout_tree(
+            commit.as_object(),
+            Some(CheckoutBuilder::new().force().remove_untracked(true)),
+        )?;
+    }
+
+    let short = &plan.version.commit_id[..7.min(plan.version.commit_id.len())];
+    let commit_id = commit_changes(config, &format!("Roll back to {}", short))?;
+
+    info!(
+        "Rolled the vault back to {} in commit {}",
+        plan.version.commit_id, commit_id
+    );
+
+    Ok(Some(commit_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::history::commit_changes;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_group_rollback() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        let bashrc = config.home_dir.join(".bashrc");
+        let vimrc = config.home_dir.join(".vimrc");
+        fs::write(&bashrc, "old bash\n").unwrap();
+        fs::write(&vimrc, "old vim\n").unwrap();
+
+        backup_all_dotfiles(&config).unwrap();
+        let mut manifest = Manifest::load(&config).unwrap();
+        manifest.entries.get_mut(".bashrc").unwrap().group = Some("shell".to_string());
+        manifest.save(&config).unwrap();
+        commit_changes(&config, "Initial backup").unwrap();
+
+        fs::write(&bashrc, "new bash\n").unwrap();
+        fs::write(&vimrc, "new vim\n").unwrap();
+
+        assert!(matches!(
+            plan_group_rollback(&config, "editor", Local::now()),
+            Err(DotfilesError::GroupNotFound(_))
+        ));
+
+        let plan = plan_group_rollback(&config, "shell", Local::now()).unwrap();
+        assert_eq!(plan.items.len(), 1);
+        assert_eq!(plan.change_count(), 1);
+        assert!(plan.items[0].diff.contains("+old bash"));
+
+        assert_eq!(apply_rollback(&config, &plan).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), "old bash\n");
+
+        // Files outside the group are untouched
+        assert_eq!(fs::read_to_string(&vimrc).unwrap(), "new vim\n");
+    }
+
+    #[test]
+    fn test_vault_rollback() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        let bashrc = config.home_dir.join(".bashrc");
+        let vimrc = config.home_dir.join(".vimrc");
+        fs::write(&bashrc, "good bash\n").unwrap();
+        fs::write(&vimrc, "vim\n").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        let good = commit_changes(&config, "Good backup").unwrap();
+
+        fs::write(&bashrc, "broken bash\n").unwrap();
+        fs::write(config.home_dir.join(".inputrc"), "input\n").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        let bad = commit_changes(&config, "Bad backup").unwrap();
+
+        let plan = plan_vault_rollback(&config, &good[..7]).unwrap();
+        assert_eq!(plan.version.commit_id, good);
+        assert!(plan.changed.contains(&PathBuf::from(".bashrc")));
+        assert!(plan.changed.contains(&PathBuf::from(".inputrc")));
+        assert_eq!(plan.files, vec![PathBuf::from(".bashrc")]);
+
+        let commit = apply_vault_rollback(&config, &plan).unwrap().unwrap();
+        assert_eq!(
+            fs::read_to_string(config.vault_dir.join(".bashrc")).unwrap(),
+            "good bash\n"
+        );
+        assert!(!config.vault_dir.join(".inputrc").exists());
+
+        // The bad backup stays in history below the rollback commit
+        let repo = Repository::open(&config.vault_dir).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id().to_string(), commit);
+        assert_eq!(head.parent_id(0).unwrap().to_string(), bad);
+        assert_eq!(
+            head.tree_id(),
+            repo.find_commit(good.parse().unwrap()).unwrap().tree_id()
+        );
+
+        // Home files are only touched by a restore
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), "broken bash\n");
+        let restored = crate::restore::restore_files_at(&config, &plan.files, &commit).unwrap();
+        assert_eq!(restored.restored, vec![PathBuf::from(".bashrc")]);
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), "good bash\n");
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".inputrc")).unwrap(),
+            "input\n"
+        );
+
+        let plan = plan_vault_rollback(&config, &good).unwrap();
+        assert!(plan.changed.is_empty());
+        assert_eq!(apply_vault_rollback(&config, &plan).unwrap(), None);
+    }
+}
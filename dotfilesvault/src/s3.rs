@@ -0,0 +1,270 @@
+use anyhow::Result;
+
+use crate::DotfilesError;
+use crate::backend::ObjectStore;
+
+/// Objects kept in an S3 bucket, or in a service compatible with S3
+///
+/// Requests are signed with AWS Signature Version 4 and use path-style
+/// URLs, which S3-compatible services such as MinIO also accept.
+#[cfg(feature = "s3")]
+pub struct S3Store {
+    /// Base URL of the service, e.g. `https://s3.eu-west-1.amazonaws.com`
+    endpoint: String,
+
+    /// Region requests are signed for
+    region: String,
+
+    /// Name of the bucket
+    bucket: String,
+
+    /// Key prefix of the vault's objects, empty or ending with `/`
+    prefix: String,
+
+    /// Access key ID
+    access_key: String,
+
+    /// Secret access key
+    secret_key: String,
+
+    /// Session token of temporary credentials
+    session_token: Option<String>,
+
+    /// HTTP client reused across requests
+    agent: ureq::Agent,
+}
+
+/// Objects kept in an S3 bucket, unavailable without the `s3` feature
+#[cfg(not(feature = "s3"))]
+pub struct S3Store;
+
+impl S3Store {
+    /// Use a bucket with the credentials and region of the environment
+    ///
+    /// Reads the standard `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// `AWS_SESSION_TOKEN`, `AWS_REGION` (or `AWS_DEFAULT_REGION`, defaulting
+    /// to `us-east-1`), and `AWS_ENDPOINT_URL_S3` or `AWS_ENDPOINT_URL` for
+    /// S3-compatible services.
+    #[cfg(feature = "s3")]
+    pub fn from_env(bucket: &str, prefix: &str) -> Result<Self, DotfilesError> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let (Some(access_key), Some(secret_key)) =
+            (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+        else {
+            return Err(DotfilesError::Backend(
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set for S3".to_string(),
+            ));
+        };
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let endpoint = var("AWS_ENDPOINT_URL_S3")
+            .or_else(|| var("AWS_ENDPOINT_URL"))
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+
+        Ok(Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region,
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", prefix)
+            },
+            access_key,
+            secret_key,
+            session_token: var("AWS_SESSION_TOKEN"),
+            agent: ureq::Agent::new(),
+        })
+    }
+
+    #[cfg(not(feature = "s3"))]
+    pub fn from_env(_bucket: &str, _prefix: &str) -> Result<Self, DotfilesError> {
+        Err(DotfilesError::Backend(
+            "dotfilesvault was built without S3 support (enable the `s3` feature)".to_string(),
+        ))
+    }
+
+    /// Send a signed request for an object
+    ///
+    /// Returns the response body, or `None` if the object doesn't exist.
+    #[cfg(feature = "s3")]
+    fn request(
+        &self,
+        method: &str,
+        key: &str,
+        body: &[u8],
+    ) -> Result<Option<Vec<u8>>, DotfilesError> {
+        use sha2::{Digest, Sha256};
+        use std::io::Read;
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let path = format!(
+            "/{}/{}",
+            self.bucket,
+            uri_encode(&format!("{}{}", self.prefix, key))
+        );
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, rest)| rest);
+        let payload_hash = format!("{:x}", Sha256::digest(body));
+
+        // Signed headers must be sorted by name
+        let mut headers = vec![
+            ("host", host.to_string()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method, path, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+        let signing_key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex(&hmac(&signing_key, &string_to_sign));
+
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{}", self.endpoint, path))
+            .set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, signed_headers, signature
+                ),
+            );
+        for (name, value) in &headers[1..] {
+            request = request.set(name, value);
+        }
+
+        let response = match request.send_bytes(body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(ureq::Error::Status(status, response)) => {
+                return Err(DotfilesError::Backend(format!(
+                    "S3 {} {} failed with HTTP {}: {}",
+                    method,
+                    key,
+                    status,
+                    response.into_string().unwrap_or_default().trim()
+                )));
+            }
+            Err(err) => {
+                return Err(DotfilesError::Backend(format!(
+                    "S3 {} {} failed: {}",
+                    method, key, err
+                )));
+            }
+        };
+
+        let mut content = Vec::new();
+        response.into_reader().read_to_end(&mut content)?;
+
+        Ok(Some(content))
+    }
+}
+
+#[cfg(feature = "s3")]
+impl ObjectStore for S3Store {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, DotfilesError> {
+        self.request("GET", key, &[])
+    }
+
+    fn put(&self, key: &str, content: &[u8]) -> Result<(), DotfilesError> {
+        self.request("PUT", key, content).map(|_| ())
+    }
+}
+
+#[cfg(not(feature = "s3"))]
+impl ObjectStore for S3Store {
+    fn get(&self, _key: &str) -> Result<Option<Vec<u8>>, DotfilesError> {
+        Self::from_env("", "").map(|_| None)
+    }
+
+    fn put(&self, _key: &str, _content: &[u8]) -> Result<(), DotfilesError> {
+        Self::from_env("", "").map(|_| ())
+    }
+}
+
+/// Percent-encode an object key for a URL path, keeping its `/`s
+#[cfg(feature = "s3")]
+fn uri_encode(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Compute an HMAC-SHA256
+#[cfg(feature = "s3")]
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Format bytes as lowercase hex
+#[cfg(feature = "s3")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Derive the Signature Version 4 key for a day, region and service
+#[cfg(feature = "s3")]
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+#[cfg(all(test, feature = "s3"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing() {
+        assert_eq!(uri_encode("vault/objects/a b+c"), "vault/objects/a%20b%2Bc");
+
+        // Example from the AWS Signature Version 4 documentation
+        assert_eq!(
+            hex(&signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam"
+            )),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use chrono::Local;
+use git2::Repository;
+use log::{debug, info};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::snapshot::{create_snapshot, find_snapshot};
+use crate::symlink::copy_symlink;
+use crate::utils::copy_file;
+use crate::{Config, DotfilesError};
+
+/// Home directory holding copies of home files taken before destructive commands
+pub const SAFETY_DIR: &str = ".dotfilesvault/safety";
+
+/// Prefix of the names of snapshots taken before destructive commands
+pub const SAFETY_SNAPSHOT_PREFIX: &str = "safety/";
+
+/// What was saved before a destructive command in paranoid mode
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SafetySnapshot {
+    /// Name of the vault snapshot, if the vault had any commits
+    pub snapshot: Option<String>,
+
+    /// Directory holding the copies of home files, if any were copied
+    pub home_copy: Option<PathBuf>,
+
+    /// Number of home files copied
+    pub files: usize,
+}
+
+/// Save the vault and the given home files before a destructive command
+///
+/// The vault's state is kept as a snapshot named `safety/<time>`, restorable
+/// with `restore --snapshot`. Home files that exist are copied to
+/// `~/.dotfilesvault/safety/<time>/`, keeping their home-relative paths;
+/// symlinks are copied as links.
+pub fn take_safety_snapshot(
+    config: &Config,
+    action: &str,
+    home_files: &[PathBuf],
+) -> Result<SafetySnapshot, DotfilesError> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut safety = SafetySnapshot::default();
+
+    let has_commits = Repository::open(&config.vault_dir)
+        .is_ok_and(|repo| repo.head().and_then(|head| head.peel_to_commit()).is_ok());
+    if has_commits {
+        let mut name = format!("{}{}", SAFETY_SNAPSHOT_PREFIX, stamp);
+        let mut suffix = 1;
+        while find_snapshot(config, &name).is_ok() {
+            suffix += 1;
+            name = format!("{}{}-{}", SAFETY_SNAPSHOT_PREFIX, stamp, suffix);
+        }
+        create_snapshot(config, &name, Some(&format!("Before {}", action)))?;
+        safety.snapshot = Some(name);
+    }
+
+    let dir = config.home_dir.join(SAFETY_DIR).join(&stamp);
+    for path in home_files {
+        if !path.is_file() && !path.is_symlink() {
+            continue;
+        }
+        // Files outside the home directory keep their path below the root
+        let relative: PathBuf = path
+            .strip_prefix(&config.home_dir)
+            .unwrap_or(path)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+        let copy = dir.join(relative);
+        save_copy(path, &copy)?;
+        safety.files += 1;
+    }
+    if safety.files > 0 {
+        safety.home_copy = Some(dir);
+    }
+
+    info!(
+        "Took a safety snapshot before {}: {:?}, {} home files",
+        action, safety.snapshot, safety.files
+    );
+    Ok(safety)
+}
+
+/// Copy a home file into the safety directory
+fn save_copy(path: &Path, copy: &Path) -> Result<(), DotfilesError> {
+    if let Some(parent) = copy.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if path.is_symlink() {
+        copy_symlink(path, copy)?;
+    } else {
+        copy_file(path, copy, None)?;
+    }
+
+    debug!("Saved {:?} to {:?}", path, copy);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{commit_changes, init_git_repo};
+    use crate::snapshot::list_snapshots;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_take_safety_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(config.home_dir.join(".config/fish")).unwrap();
+        fs::write(config.home_dir.join(".config/fish/config.fish"), "fish").unwrap();
+
+        // Without commits there is no vault state to keep
+        let files = vec![
+            config.home_dir.join(".config/fish/config.fish"),
+            config.home_dir.join(".missing"),
+        ];
+        let safety = take_safety_snapshot(&config, "restore", &files).unwrap();
+        assert!(safety.snapshot.is_none());
+        assert_eq!(safety.files, 1);
+        let copy = safety.home_copy.unwrap().join(".config/fish/config.fish");
+        assert_eq!(fs::read_to_string(copy).unwrap(), "fish");
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash").unwrap();
+        commit_changes(&config, "Add bashrc").unwrap();
+        let first = take_safety_snapshot(&config, "compact", &[]).unwrap();
+        let second = take_safety_snapshot(&config, "compact", &[]).unwrap();
+        assert_ne!(first.snapshot, second.snapshot);
+        assert!(second.home_copy.is_none());
+
+        let snapshots = list_snapshots(&config).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].message, "Before compact");
+    }
+}
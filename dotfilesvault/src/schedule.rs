@@ -0,0 +1,433 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the systemd service and timer units
+pub const SYSTEMD_UNIT: &str = "dotfilesvault-backup";
+
+/// Label of the launchd agent
+pub const LAUNCHD_LABEL: &str = "io.github.jujumilk3.dotfilesvault.backup";
+
+/// Service manager that runs scheduled backups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SchedulePlatform {
+    /// A systemd user timer, on Linux
+    Systemd,
+
+    /// A launchd user agent, on macOS
+    Launchd,
+}
+
+impl SchedulePlatform {
+    /// Get the service manager of the platform the binary was built for
+    pub fn current() -> Result<Self, DotfilesError> {
+        if cfg!(target_os = "macos") {
+            Ok(SchedulePlatform::Launchd)
+        } else if cfg!(target_os = "linux") {
+            Ok(SchedulePlatform::Systemd)
+        } else {
+            Err(DotfilesError::ScheduleUnsupported)
+        }
+    }
+
+    /// Name shown to users
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchedulePlatform::Systemd => "systemd",
+            SchedulePlatform::Launchd => "launchd",
+        }
+    }
+
+    /// Get the unit files a schedule consists of, the one holding the interval last
+    pub fn unit_paths(&self, config: &Config) -> Vec<PathBuf> {
+        match self {
+            SchedulePlatform::Systemd => {
+                let dir = config.home_dir.join(".config/systemd/user");
+                vec![
+                    dir.join(format!("{}.service", SYSTEMD_UNIT)),
+                    dir.join(format!("{}.timer", SYSTEMD_UNIT)),
+                ]
+            }
+            SchedulePlatform::Launchd => vec![
+                config
+                    .home_dir
+                    .join("Library/LaunchAgents")
+                    .join(format!("{}.plist", LAUNCHD_LABEL)),
+            ],
+        }
+    }
+}
+
+/// State of the backup schedule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduleStatus {
+    /// Service manager running the backups
+    pub platform: SchedulePlatform,
+
+    /// Whether the unit files exist
+    pub installed: bool,
+
+    /// Whether the service manager has the schedule loaded and running
+    pub active: bool,
+
+    /// Seconds between backups, as written in the unit files
+    pub interval: Option<u64>,
+
+    /// The unit files
+    pub units: Vec<PathBuf>,
+}
+
+/// Write and enable units that run `dotfilesvault backup` periodically
+///
+/// The units call the running binary with the current vault and config
+/// file, so they keep working when run outside of this shell. Installing
+/// again replaces the interval. With `config.dry_run` set nothing is written.
+pub fn install_schedule(
+    config: &Config,
+    interval: Duration,
+) -> Result<ScheduleStatus, DotfilesError> {
+    let platform = SchedulePlatform::current()?;
+    let command = backup_command(config)?;
+    let units = platform.unit_paths(config);
+
+    let contents = match platform {
+        SchedulePlatform::Systemd => vec![
+            render_systemd_service(&command),
+            render_systemd_timer(interval),
+        ],
+        SchedulePlatform::Launchd => vec![render_launchd_plist(
+            &command,
+            interval,
+            &config
+                .home_dir
+                .join("Library/Logs/dotfilesvault-backup.log"),
+        )],
+    };
+
+    if !config.dry_run {
+        for (path, content) in units.iter().zip(&contents) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_file(path, content.as_bytes())?;
+            debug!("Wrote {:?}", path);
+        }
+
+        match platform {
+            SchedulePlatform::Systemd => {
+                run_manager("systemctl", &["--user", "daemon-reload"])?;
+                run_manager(
+                    "systemctl",
+                    &[
+                        "--user",
+                        "enable",
+                        "--now",
+                        &format!("{}.timer", SYSTEMD_UNIT),
+                    ],
+                )?;
+            }
+            SchedulePlatform::Launchd => {
+                let plist = units[0].to_string_lossy();
+                // A loaded agent keeps its old interval until it is unloaded
+                let _ = run_manager("launchctl", &["unload", &plist]);
+                run_manager("launchctl", &["load", "-w", &plist])?;
+            }
+        }
+        info!(
+            "Scheduled backups every {} with {}",
+            format_interval(interval.as_secs()),
+            platform.name()
+        );
+    }
+
+    Ok(ScheduleStatus {
+        platform,
+        installed: true,
+        active: !config.dry_run,
+        interval: Some(interval.as_secs()),
+        units,
+    })
+}
+
+/// Disable the backup schedule and delete its units
+///
+/// Returns whether a schedule was installed.
+pub fn remove_schedule(config: &Config) -> Result<bool, DotfilesError> {
+    let platform = SchedulePlatform::current()?;
+    let units = platform.unit_paths(config);
+
+    if !units.iter().any(|path| path.exists()) {
+        return Ok(false);
+    }
+    if config.dry_run {
+        return Ok(true);
+    }
+
+    // A schedule already disabled by hand still has its files removed
+    let disabled = match platform {
+        SchedulePlatform::Systemd => run_manager(
+            "systemctl",
+            &[
+                "--user",
+                "disable",
+                "--now",
+                &format!("{}.timer", SYSTEMD_UNIT),
+            ],
+        ),
+        SchedulePlatform::Launchd => {
+            run_manager("launchctl", &["unload", "-w", &units[0].to_string_lossy()])
+        }
+    };
+    if let Err(err) = disabled {
+        warn!("{}", err);
+    }
+
+    for path in &units {
+        match fs::remove_file(path) {
+            Ok(()) => debug!("Removed {:?}", path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if platform == SchedulePlatform::Systemd
+        && let Err(err) = run_manager("systemctl", &["--user", "daemon-reload"])
+    {
+        warn!("{}", err);
+    }
+
+    info!("Removed the backup schedule");
+    Ok(true)
+}
+
+/// Report whether backups are scheduled and how often
+pub fn schedule_status(config: &Config) -> Result<ScheduleStatus, DotfilesError> {
+    let platform = SchedulePlatform::current()?;
+    let units = platform.unit_paths(config);
+    let installed = units.iter().all(|path| path.exists());
+
+    let interval = units
+        .last()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| read_interval(&content));
+
+    let active = installed
+        && match platform {
+            SchedulePlatform::Systemd => run_manager(
+                "systemctl",
+                &[
+                    "--user",
+                    "is-active",
+                    "--quiet",
+                    &format!("{}.timer", SYSTEMD_UNIT),
+                ],
+            )
+            .is_ok(),
+            SchedulePlatform::Launchd => run_manager("launchctl", &["list", LAUNCHD_LABEL]).is_ok(),
+        };
+
+    Ok(ScheduleStatus {
+        platform,
+        installed,
+        active,
+        interval,
+        units,
+    })
+}
+
+/// Format seconds as the largest whole unit, e.g. `2h`, `90m` or `45s`
+pub fn format_interval(seconds: u64) -> String {
+    if seconds > 0 && seconds.is_multiple_of(3600) {
+        format!("{}h", seconds / 3600)
+    } else if seconds > 0 && seconds.is_multiple_of(60) {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Get the command line scheduled backups run
+fn backup_command(config: &Config) -> Result<Vec<String>, DotfilesError> {
+    // Named vaults are passed by name so later edits of their settings apply
+    let vault = match &config.vault_name {
+        Some(name) => name.clone(),
+        None => config.vault_dir.to_string_lossy().into_owned(),
+    };
+    let mut command = vec![
+        std::env::current_exe()?.to_string_lossy().into_owned(),
+        "--vault".to_string(),
+        vault,
+    ];
+    if let Some(config_file) = &config.config_file {
+        command.push("--config".to_string());
+        command.push(config_file.to_string_lossy().into_owned());
+    }
+    command.push("backup".to_string());
+
+    Ok(command)
+}
+
+/// Render the systemd service running a backup
+fn render_systemd_service(command: &[String]) -> String {
+    let exec = command
+        .iter()
+        .map(|arg| {
+            format!(
+                "\"{}\"",
+                arg.replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('%', "%%")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "[Unit]\n\
+         Description=Back up dotfiles with dotfilesvault\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        exec
+    )
+}
+
+/// Render the systemd timer starting the service periodically
+fn render_systemd_timer(interval: Duration) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Back up dotfiles with dotfilesvault every {interval}\n\
+         \n\
+         [Timer]\n\
+         OnBootSec={seconds}s\n\
+         OnUnitActiveSec={seconds}s\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        interval = format_interval(interval.as_secs()),
+        seconds = interval.as_secs()
+    )
+}
+
+/// Render the launchd agent running a backup periodically
+fn render_launchd_plist(command: &[String], interval: Duration, log: &Path) -> String {
+    let arguments: String = command
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{label}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         {arguments}\
+         \x20   </array>\n\
+         \x20   <key>StartInterval</key>\n\
+         \x20   <integer>{seconds}</integer>\n\
+         \x20   <key>StandardErrorPath</key>\n\
+         \x20   <string>{log}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        label = LAUNCHD_LABEL,
+        arguments = arguments,
+        seconds = interval.as_secs(),
+        log = xml_escape(&log.to_string_lossy())
+    )
+}
+
+/// Read the interval in seconds from a systemd timer or launchd plist
+fn read_interval(content: &str) -> Option<u64> {
+    let mut lines = content.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        if let Some(value) = line.strip_prefix("OnUnitActiveSec=") {
+            return value.trim_end_matches('s').parse().ok();
+        }
+        if line == "<key>StartInterval</key>" {
+            return lines
+                .next()?
+                .strip_prefix("<integer>")?
+                .strip_suffix("</integer>")?
+                .parse()
+                .ok();
+        }
+    }
+
+    None
+}
+
+/// Escape text for an XML element
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Run a service manager command, failing unless it exits successfully
+fn run_manager(program: &str, args: &[&str]) -> Result<(), DotfilesError> {
+    let command = format!("{} {}", program, args.join(" "));
+    debug!("Running {}", command);
+
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| DotfilesError::ScheduleFailed(command.clone(), err.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let reason = if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            stderr
+        };
+        return Err(DotfilesError::ScheduleFailed(command, reason));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_units() {
+        let command = vec![
+            "/opt/dotfiles vault/dotfilesvault".to_string(),
+            "--vault".to_string(),
+            "/home/me/100%".to_string(),
+            "backup".to_string(),
+        ];
+        let interval = Duration::from_secs(90 * 60);
+
+        let service = render_systemd_service(&command);
+        assert!(service.contains(
+            "ExecStart=\"/opt/dotfiles vault/dotfilesvault\" \"--vault\" \"/home/me/100%%\" \"backup\"\n"
+        ));
+
+        let timer = render_systemd_timer(interval);
+        assert!(timer.contains("Description=Back up dotfiles with dotfilesvault every 90m\n"));
+        assert_eq!(read_interval(&timer), Some(5400));
+
+        let plist = render_launchd_plist(&command, interval, Path::new("/tmp/a&b.log"));
+        assert!(plist.contains("        <string>/home/me/100%</string>\n"));
+        assert!(plist.contains("<string>/tmp/a&amp;b.log</string>"));
+        assert_eq!(read_interval(&plist), Some(5400));
+
+        assert_eq!(format_interval(7200), "2h");
+        assert_eq!(format_interval(45), "45s");
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::encryption::is_encrypted;
+use crate::history::commit_changes;
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest, entry_id};
+use crate::utils::{normalize_path, write_file};
+use crate::{Config, DotfilesError};
+
+/// A `s/pattern/replacement/flags` substitution, as in sed
+///
+/// Any character can delimit the parts, e.g. `s|/old/path|/new/path|`, and
+/// is escaped with a backslash inside them. The pattern is a regular
+/// expression matched within each line. In the replacement `&` is the whole
+/// match and `\1` to `\9` are capture groups. The `g` flag replaces every
+/// match on a line instead of the first, `i` matches case-insensitively.
+#[derive(Debug, Clone)]
+pub struct SedExpression {
+    /// The expression as given
+    source: String,
+
+    /// Pattern to replace
+    pattern: Regex,
+
+    /// Replacement in the syntax of the `regex` crate
+    replacement: String,
+
+    /// Whether every match on a line is replaced
+    global: bool,
+}
+
+impl SedExpression {
+    /// Apply the substitution to every line of a text
+    ///
+    /// Returns the new text and the number of lines changed.
+    pub fn apply(&self, text: &str) -> (String, usize) {
+        let limit = if self.global { 0 } else { 1 };
+        let mut changed = 0;
+        let mut result = String::with_capacity(text.len());
+
+        for line in text.split_inclusive('\n') {
+            let (content, newline) = match line.strip_suffix('\n') {
+                Some(content) => (content, "\n"),
+                None => (line, ""),
+            };
+            let replaced = self
+                .pattern
+                .replacen(content, limit, self.replacement.as_str());
+            if replaced != content {
+                changed += 1;
+            }
+            result.push_str(&replaced);
+            result.push_str(newline);
+        }
+
+        (result, changed)
+    }
+}
+
+impl fmt::Display for SedExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for SedExpression {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut chars = value.chars();
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) if delimiter != '\\' && delimiter != '\n' => delimiter,
+            _ => {
+                return Err(format!(
+                    "invalid expression {:?}, expected s/pattern/replacement/",
+                    value
+                ));
+            }
+        };
+
+        // Split into pattern, replacement and flags at unescaped delimiters
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for c in chars {
+            let part = parts.last_mut().expect("there is always a part");
+            if escaped {
+                if c != delimiter {
+                    part.push('\\');
+                }
+                part.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                parts.push(String::new());
+            } else {
+                part.push(c);
+            }
+        }
+        let [pattern, replacement, flags] = parts.as_slice() else {
+            return Err(format!(
+                "invalid expression {:?}, expected s{}pattern{}replacement{}",
+                value, delimiter, delimiter, delimiter
+            ));
+        };
+
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' | 'I' => ignore_case = true,
+                _ => return Err(format!("unknown flag {:?} in {:?}", flag, value)),
+            }
+        }
+
+        let pattern = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            source: value.to_string(),
+            pattern,
+            replacement: regex_replacement(replacement),
+            global,
+        })
+    }
+}
+
+/// Turn a sed replacement into the syntax of the `regex` crate
+fn regex_replacement(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => result.push_str(&format!("${{{}}}", digit)),
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('$') => result.push_str("$$"),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// A dotfile changed by a substitution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SedChange {
+    /// Home-relative path of the dotfile
+    pub path: PathBuf,
+
+    /// Number of lines changed in the vault copy
+    pub lines: usize,
+
+    /// Whether the file in the home directory was changed too
+    pub home: bool,
+}
+
+/// Outcome of a substitution across dotfiles
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SedSummary {
+    /// Dotfiles that changed
+    pub changed: Vec<SedChange>,
+
+    /// Home-relative paths of dotfiles left alone as they are encrypted or binary
+    pub skipped: Vec<PathBuf>,
+
+    /// The commit recording the change
+    pub commit: Option<String>,
+}
+
+/// A file about to be rewritten, with its content to restore on failure
+struct Rewrite {
+    path: PathBuf,
+    original: Vec<u8>,
+    content: String,
+}
+
+/// Apply a substitution to tracked dotfiles, in the vault and the home directory
+///
+/// `files` are home paths of tracked dotfiles; without any, every tracked
+/// dotfile is changed. The vault copy and the home file are changed alike,
+/// with templates changed as they are stored. Every new content is worked
+/// out first, and if writing any file fails the files already written get
+/// their old content back, so the vault and the home directory never end
+/// up half migrated. The change is committed with the expression and the
+/// files in the message. Encrypted and binary files are skipped, as are
+/// home files that are symlinks. With `config.dry_run` set only the
+/// changes are listed.
+pub fn sed_dotfiles(
+    config: &Config,
+    expression: &SedExpression,
+    files: &[String],
+) -> Result<SedSummary, DotfilesError> {
+    if !config.vault_dir.exists() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+
+    let _lock = VaultLock::acquire(config)?;
+    let manifest = Manifest::load(config)?;
+    let entries = selected_entries(config, &manifest, files)?;
+
+    let mut summary = SedSummary::default();
+    let mut rewrites = Vec::new();
+    for entry in entries {
+        config.cancellation.check()?;
+        let dotfile = entry.dotfile(config);
+        let Ok(original) = fs::read(&dotfile.vault_path) else {
+            debug!("{:?} isn't in the vault, skipping", entry.source);
+            continue;
+        };
+        let Some(text) = editable_text(&original) else {
+            warn!("Skipped {:?}, it is encrypted or binary", entry.source);
+            summary.skipped.push(entry.source.clone());
+            continue;
+        };
+
+        let (content, lines) = expression.apply(text);
+        let mut home = false;
+        if let Some((home_original, home_content)) =
+            rewrite_home(&dotfile.original_path, expression)
+        {
+            rewrites.push(Rewrite {
+                path: dotfile.original_path.clone(),
+                original: home_original,
+                content: home_content,
+            });
+            home = true;
+        }
+        if lines == 0 && !home {
+            continue;
+        }
+        if lines > 0 {
+            rewrites.push(Rewrite {
+                path: dotfile.vault_path.clone(),
+                original,
+                content,
+            });
+        }
+        summary.changed.push(SedChange {
+            path: entry.source.clone(),
+            lines,
+            home,
+        });
+    }
+
+    if config.dry_run || summary.changed.is_empty() {
+        return Ok(summary);
+    }
+
+    let names: Vec<String> = summary
+        .changed
+        .iter()
+        .map(|change| change.path.display().to_string())
+        .collect();
+    let message = format!("Apply {} to {}", expression, names.join(", "));
+
+    let written = write_all(&rewrites)
+        .and_then(|()| commit_changes(config, &message))
+        .inspect_err(|_| revert(&rewrites));
+    summary.commit = Some(written?);
+
+    info!(
+        "Applied {} to {} dotfiles",
+        expression,
+        summary.changed.len()
+    );
+
+    Ok(summary)
+}
+
+/// Find the manifest entries of the given files, or all that apply here
+fn selected_entries(
+    config: &Config,
+    manifest: &Manifest,
+    files: &[String],
+) -> Result<Vec<DotfileEntry>, DotfilesError> {
+    if files.is_empty() {
+        let mut entries: Vec<DotfileEntry> = manifest
+            .entries
+            .values()
+            .filter(|entry| entry.applies())
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.source.cmp(&b.source));
+        return Ok(entries);
+    }
+
+    files
+        .iter()
+        .map(|file| {
+            let path = normalize_path(file, config);
+            let source = path.strip_prefix(&config.home_dir).unwrap_or(&path);
+            manifest
+                .entries
+                .get(&entry_id(source))
+                .cloned()
+                .ok_or_else(|| DotfilesError::DotfileNotFound(file.clone()))
+        })
+        .collect()
+}
+
+/// Get the text of a file that can be edited, none for encrypted or binary files
+fn editable_text(content: &[u8]) -> Option<&str> {
+    if is_encrypted(content) {
+        return None;
+    }
+    std::str::from_utf8(content)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
+
+/// Work out the new content of a home file, if it exists and changes
+fn rewrite_home(path: &Path, expression: &SedExpression) -> Option<(Vec<u8>, String)> {
+    if path.is_symlink() {
+        return None;
+    }
+    let original = fs::read(path).ok()?;
+    let (content, lines) = expression.apply(editable_text(&original)?);
+
+    (lines > 0).then_some((original, content))
+}
+
+/// Write the new content of every file
+fn write_all(rewrites: &[Rewrite]) -> Result<(), DotfilesError> {
+    for rewrite in rewrites {
+        write_file(&rewrite.path, rewrite.content.as_bytes())?;
+        debug!("Rewrote {:?}", rewrite.path);
+    }
+
+    Ok(())
+}
+
+/// Give the files their old content back after a failed rewrite
+fn revert(rewrites: &[Rewrite]) {
+    for rewrite in rewrites {
+        if let Err(err) = write_file(&rewrite.path, &rewrite.original) {
+            warn!("Failed to restore {:?}: {}", rewrite.path, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_expression() {
+        let expression: SedExpression = "s/old@example.com/new@example.com/".parse().unwrap();
+        assert_eq!(
+            expression.apply("email = old@example.com\nname = me\n"),
+            ("email = new@example.com\nname = me\n".to_string(), 1)
+        );
+
+        let expression: SedExpression = r"s|/home/(\w+)|/Users/\1 (&)|g".parse().unwrap();
+        assert_eq!(
+            expression.apply("/home/me:/home/you").0,
+            "/Users/me (/home/me):/Users/you (/home/you)"
+        );
+
+        let expression: SedExpression = r"s/a\/b/$HOME/i".parse().unwrap();
+        assert_eq!(expression.apply("A/B a/b").0, "$HOME a/b");
+
+        assert!("s/a/b".parse::<SedExpression>().is_err());
+        assert!("s/a/b/x".parse::<SedExpression>().is_err());
+        assert!("y/a/b/".parse::<SedExpression>().is_err());
+        assert!("s/(/b/".parse::<SedExpression>().is_err());
+    }
+
+    #[test]
+    fn test_sed_dotfiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(
+            config.home_dir.join(".gitconfig"),
+            "[user]\n\temail = old@example.com\n",
+        )
+        .unwrap();
+        fs::write(
+            config.home_dir.join(".hgrc"),
+            "username = old@example.com\n",
+        )
+        .unwrap();
+        fs::write(config.home_dir.join(".zshrc"), "# old@example.com\n").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+
+        // The home copy of .hgrc was already migrated by hand
+        fs::write(
+            config.home_dir.join(".hgrc"),
+            "username = new@example.com\n",
+        )
+        .unwrap();
+
+        let expression: SedExpression = "s/old@example.com/new@example.com/".parse().unwrap();
+        let files = vec![".gitconfig".to_string(), ".hgrc".to_string()];
+        let summary = sed_dotfiles(&config, &expression, &files).unwrap();
+        assert_eq!(
+            summary.changed,
+            vec![
+                SedChange {
+                    path: PathBuf::from(".gitconfig"),
+                    lines: 1,
+                    home: true,
+                },
+                SedChange {
+                    path: PathBuf::from(".hgrc"),
+                    lines: 1,
+                    home: false,
+                },
+            ]
+        );
+        assert!(summary.commit.is_some());
+        for dir in [&config.home_dir, &config.vault_dir] {
+            assert_eq!(
+                fs::read_to_string(dir.join(".gitconfig")).unwrap(),
+                "[user]\n\temail = new@example.com\n"
+            );
+            assert_eq!(
+                fs::read_to_string(dir.join(".hgrc")).unwrap(),
+                "username = new@example.com\n"
+            );
+            assert_eq!(
+                fs::read_to_string(dir.join(".zshrc")).unwrap(),
+                "# old@example.com\n"
+            );
+        }
+
+        assert!(
+            sed_dotfiles(&config, &expression, &files)
+                .unwrap()
+                .changed
+                .is_empty()
+        );
+        assert!(matches!(
+            sed_dotfiles(&config, &expression, &[".missing".to_string()]),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::conflicts::ConflictPolicy;
+use crate::history::{resolve_file_version, resolve_version};
+use crate::plan::{Plan, PlannedAction, PlannedCopy};
+use crate::restore::{RestoreSummary, restore_files_at};
+use crate::snapshot::find_snapshot;
+use crate::utils::{hostname, normalize_path, write_file};
+use crate::{Config, DotfilesError};
+
+/// Directory of the recorded restore sessions, see `Config::state_path`
+pub const SESSIONS_DIR: &str = "dotfilesvault-sessions";
+
+/// What a restore was asked to do
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionRequest {
+    /// The command that restored, e.g. `restore --all`
+    pub command: String,
+
+    /// The single dotfile restored, none for restores of every dotfile
+    pub file: Option<String>,
+
+    /// Version restored: of the single dotfile, or the vault commit a replay
+    /// restored from
+    pub version: Option<String>,
+
+    /// Snapshot restored
+    pub snapshot: Option<String>,
+
+    /// Machine profile in use
+    pub profile: Option<String>,
+
+    /// What the restore did with home files that had local edits
+    pub on_conflict: ConflictPolicy,
+}
+
+/// A recorded restore: what was asked, what was planned and what happened
+///
+/// Sessions are JSON files that can be copied to another machine and
+/// replayed there with `replay_session`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// ID of the session, from the time it was recorded
+    pub id: String,
+
+    /// When the restore ran
+    pub timestamp: DateTime<Local>,
+
+    /// Machine the restore ran on
+    pub hostname: Option<String>,
+
+    /// What the restore was asked to do
+    pub request: SessionRequest,
+
+    /// Vault commit the files were restored from
+    pub commit: Option<String>,
+
+    /// Copies made, including quarantined versions
+    pub copies: Vec<PlannedCopy>,
+
+    /// Home-relative paths of the dotfiles restored
+    pub restored: Vec<PathBuf>,
+
+    /// Dotfiles left alone
+    pub skipped: Vec<PathBuf>,
+
+    /// Dotfiles left alone because they had edits that were never backed up
+    pub conflicted: Vec<PathBuf>,
+
+    /// Dotfiles that couldn't be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Get the directory of the recorded sessions
+fn sessions_dir(config: &Config) -> PathBuf {
+    config.state_path(SESSIONS_DIR)
+}
+
+/// Find the vault commit a restore read its files from
+///
+/// Restores of the vault directory are recorded with its last commit.
+fn restored_commit(config: &Config, request: &SessionRequest) -> Option<String> {
+    let version = match (&request.snapshot, &request.version, &request.file) {
+        (Some(name), _, _) => return find_snapshot(config, name).ok().map(|s| s.commit_id),
+        (None, Some(version), Some(file)) => resolve_file_version(config, file, version),
+        (None, Some(version), None) => resolve_version(config, version),
+        _ => resolve_version(config, "HEAD"),
+    };
+
+    version.ok().map(|version| version.commit_id)
+}
+
+/// Record a restore of every dotfile as a session
+///
+/// Returns the session, or `None` for vaults without a git repository.
+pub fn record_session(
+    config: &Config,
+    request: SessionRequest,
+    summary: &RestoreSummary,
+) -> Result<Option<Session>, DotfilesError> {
+    let git_dir = config.vault_dir.join(".git");
+    if !git_dir.is_dir() {
+        debug!("Not recording the restore, the vault has no repository");
+        return Ok(None);
+    }
+
+    let timestamp = Local::now();
+    let dir = sessions_dir(config);
+    fs::create_dir_all(&dir)?;
+    let base = timestamp.format("%Y%m%d-%H%M%S").to_string();
+    let mut id = base.clone();
+    let mut count = 1;
+    while dir.join(format!("{}.json", id)).exists() {
+        count += 1;
+        id = format!("{}-{}", base, count);
+    }
+
+    let session = Session {
+        id,
+        timestamp,
+        hostname: hostname(),
+        commit: restored_commit(config, &request),
+        request,
+        copies: summary.plan.copies.clone(),
+        restored: summary.restored.clone(),
+        skipped: summary.skipped.clone(),
+        conflicted: summary.conflicted.clone(),
+        failed: summary.failed.clone(),
+    };
+    let content = serde_json::to_vec_pretty(&session).map_err(std::io::Error::from)?;
+    write_file(dir.join(format!("{}.json", session.id)), &content)?;
+
+    debug!("Recorded restore session {}", session.id);
+
+    Ok(Some(session))
+}
+
+/// Record a restore of a single dotfile as a session
+pub fn record_file_session(
+    config: &Config,
+    request: SessionRequest,
+    plan: &Plan,
+) -> Result<Option<Session>, DotfilesError> {
+    let mut summary = RestoreSummary {
+        plan: plan.clone(),
+        ..RestoreSummary::default()
+    };
+    if let Some(file) = &request.file {
+        let path = normalize_path(file, config);
+        let path = path
+            .strip_prefix(&config.home_dir)
+            .map_or(path.clone(), Path::to_path_buf);
+        let quarantined = plan
+            .copies
+            .iter()
+            .any(|copy| copy.action == PlannedAction::Quarantine);
+        if quarantined {
+            summary.conflicted.push(path);
+        } else if plan.copies.is_empty() {
+            summary.skipped.push(path);
+        } else {
+            summary.restored.push(path);
+        }
+    }
+
+    record_session(config, request, &summary)
+}
+
+/// Load a session by ID, or from a session file copied from another machine
+pub fn load_session(config: &Config, session: &str) -> Result<Session, DotfilesError> {
+    let path = Path::new(session);
+    let path = if path.is_file() {
+        path.to_path_buf()
+    } else {
+        sessions_dir(config).join(format!("{}.json", session))
+    };
+
+    let content =
+        fs::read(&path).map_err(|_| DotfilesError::SessionNotFound(session.to_string()))?;
+    serde_json::from_slice(&content)
+        .map_err(|err| DotfilesError::SessionNotFound(format!("{} ({})", session, err)))
+}
+
+/// List the recorded sessions, oldest first
+///
+/// Files that aren't sessions are skipped.
+pub fn list_sessions(config: &Config) -> Result<Vec<Session>, DotfilesError> {
+    let dir = sessions_dir(config);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut sessions: Vec<Session> = fs::read_dir(&dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let content = fs::read(&path).ok()?;
+            serde_json::from_slice(&content).ok()
+        })
+        .collect();
+    sessions.sort_by_key(|session| session.timestamp);
+
+    Ok(sessions)
+}
+
+/// Restore the dotfiles a session restored, as they were at its commit
+///
+/// The session's profile and conflict policy are used, so the home
+/// directory ends up as it did where the session was recorded. The vault
+/// needs the session's commit, e.g. from a pull; fails with
+/// `VersionNotFound` otherwise.
+pub fn replay_session(config: &Config, session: &Session) -> Result<RestoreSummary, DotfilesError> {
+    let commit = session.commit.as_deref().ok_or_else(|| {
+        DotfilesError::SessionNotFound(format!("{} (no vault commit recorded)", session.id))
+    })?;
+    let commit_id = resolve_version(config, commit)?.commit_id;
+
+    let mut config = config.clone();
+    config.on_conflict = session.request.on_conflict;
+    if session.request.profile.is_some() {
+        config.profile = session.request.profile.clone();
+    }
+
+    info!(
+        "Replaying restore session {} of {} dotfiles at {}",
+        session.id,
+        session.restored.len(),
+        &commit_id[..7]
+    );
+
+    restore_files_at(&config, &session.restored, &commit_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::history::commit_changes;
+    use crate::restore::restore_all_dotfiles;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_replay_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash 1").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim 1").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+
+        fs::remove_file(config.home_dir.join(".bashrc")).unwrap();
+        let summary = restore_all_dotfiles(&config).unwrap();
+        let request = SessionRequest {
+            command: "restore --all".to_string(),
+            ..SessionRequest::default()
+        };
+        let session = record_session(&config, request, &summary).unwrap().unwrap();
+        assert_eq!(session.restored, vec![PathBuf::from(".bashrc")]);
+        assert!(session.commit.is_some());
+
+        // The vault moves on, but replays restore what the session restored
+        fs::write(config.home_dir.join(".bashrc"), "bash 2").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+        fs::remove_file(config.home_dir.join(".bashrc")).unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim 2").unwrap();
+
+        let loaded = load_session(&config, &session.id).unwrap();
+        assert_eq!(loaded, session);
+        let replayed = replay_session(&config, &loaded).unwrap();
+        assert_eq!(replayed.restored, vec![PathBuf::from(".bashrc")]);
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "bash 1"
+        );
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".vimrc")).unwrap(),
+            "vim 2"
+        );
+
+        assert_eq!(list_sessions(&config).unwrap(), vec![session]);
+        assert!(matches!(
+            load_session(&config, "missing"),
+            Err(DotfilesError::SessionNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use log::{debug, info};
+use std::path::PathBuf;
+
+use crate::backend::open_backend;
+use crate::backup::{Dotfile, has_drifted};
+use crate::hooks::{HookContext, HookEvent, run_hook};
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::restore::{list_backed_up_dotfiles, restore_entry};
+use crate::{Config, DotfilesError};
+
+/// Outcome of one step of a bootstrap simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationCheck {
+    /// What was checked, e.g. `restore .zshrc`
+    pub name: String,
+
+    /// Why the check failed, or `None` if it passed
+    pub error: Option<String>,
+}
+
+impl SimulationCheck {
+    fn new(name: String, result: Result<(), String>) -> Self {
+        Self {
+            name,
+            error: result.err(),
+        }
+    }
+
+    /// Check if the step succeeded
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Report of a bootstrap simulation
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    /// The throwaway home directory the vault was restored into
+    pub home_dir: PathBuf,
+
+    /// Whether the throwaway home directory was kept for inspection
+    pub kept: bool,
+
+    /// Checks in the order they ran
+    pub checks: Vec<SimulationCheck>,
+}
+
+impl SimulationReport {
+    /// Check if every step succeeded
+    pub fn success(&self) -> bool {
+        self.checks.iter().all(SimulationCheck::passed)
+    }
+}
+
+/// Rebuild a home directory from the vault in a throwaway location
+///
+/// Runs the restore hooks (with `simulated` set and `HOME` pointing at the
+/// throwaway directory), restores every entry that applies to this machine
+/// and verifies each restored file against the vault. The vault itself is
+/// only read. The directory is deleted afterwards unless `keep` is set.
+pub fn simulate_bootstrap(config: &Config, keep: bool) -> Result<SimulationReport, DotfilesError> {
+    if !config.vault_dir.exists() {
+        return Err(DotfilesError::NoDotfilesVaultDir);
+    }
+
+    let sandbox = tempfile::Builder::new()
+        .prefix("dotfilesvault-bootstrap-")
+        .tempdir()?;
+
+    let mut sim = config.clone();
+    sim.home_dir = sandbox.path().to_path_buf();
+    info!("Simulating bootstrap in {:?}", sim.home_dir);
+
+    let mut checks = Vec::new();
+    let entries = bootstrap_entries(&sim)?;
+    let files: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| entry.dotfile(&sim).original_path)
+        .collect();
+
+    run_simulated_hook(&sim, HookEvent::PreRestore, &files, &mut checks);
+
+    let backend = open_backend(&sim)?;
+    for entry in &entries {
+        let result = restore_entry(&sim, backend.as_ref(), entry)
+            .and_then(|_| has_drifted(&sim, backend.as_ref(), entry))
+            .map_err(|err| err.to_string())
+            .and_then(|drifted| match drifted {
+                true => Err("restored file doesn't match the vault".to_string()),
+                false => Ok(()),
+            });
+
+        checks.push(SimulationCheck::new(
+            format!("restore {}", entry.id),
+            result,
+        ));
+    }
+
+    run_simulated_hook(&sim, HookEvent::PostRestore, &files, &mut checks);
+
+    let home_dir = if keep {
+        sandbox.keep()
+    } else {
+        sandbox.path().to_path_buf()
+    };
+
+    Ok(SimulationReport {
+        home_dir,
+        kept: keep,
+        checks,
+    })
+}
+
+/// Get the entries a bootstrap on this machine would restore
+fn bootstrap_entries(config: &Config) -> Result<Vec<DotfileEntry>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+
+    if !manifest.entries.is_empty() {
+        return Ok(manifest
+            .entries
+            .into_values()
+            .filter(DotfileEntry::applies)
+            .collect());
+    }
+
+    // Vaults from before the manifest only have their files to go by
+    Ok(list_backed_up_dotfiles(config)?
+        .into_iter()
+        .map(|relative| {
+            manifest.entry_for(
+                &Dotfile::new(config.home_dir.join(relative), config),
+                config,
+            )
+        })
+        .collect())
+}
+
+/// Run a hook in simulation mode, recording a check if it is installed
+fn run_simulated_hook(
+    config: &Config,
+    hook: HookEvent,
+    files: &[PathBuf],
+    checks: &mut Vec<SimulationCheck>,
+) {
+    let mut context = HookContext::new(config, hook, files.to_vec());
+    context.simulated = true;
+
+    match run_hook(config, &context) {
+        Ok(false) => debug!("No {} hook to simulate", hook.name()),
+        Ok(true) => checks.push(SimulationCheck::new(
+            format!("{} hook", hook.name()),
+            Ok(()),
+        )),
+        Err(err) => checks.push(SimulationCheck::new(
+            format!("{} hook", hook.name()),
+            Err(err.to_string()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_simulate_bootstrap() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(config.home_dir.join(".config/fish")).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".config/fish/.fishrc"), "fish").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+
+        let report = simulate_bootstrap(&config, false).unwrap();
+        assert!(report.success());
+        assert_eq!(report.checks.len(), 2);
+        assert!(!report.home_dir.exists());
+
+        // The real home directory is never touched
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "bash"
+        );
+
+        // A tracked file missing from the vault fails its check
+        fs::remove_file(config.vault_dir.join(".bashrc")).unwrap();
+        let report = simulate_bootstrap(&config, false).unwrap();
+        assert!(!report.success());
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use git2::Repository;
+use log::info;
+use serde::Serialize;
+
+use crate::history::signature;
+use crate::lock::VaultLock;
+use crate::restore::{RestoreSummary, restore_all_at};
+use crate::{Config, DotfilesError};
+
+/// Prefix of the vault tags that mark snapshots
+pub const SNAPSHOT_TAG_PREFIX: &str = "snapshot/";
+
+/// A named state of the whole vault, kept as an annotated git tag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    /// Name given when the snapshot was created
+    pub name: String,
+
+    /// Commit the snapshot points to
+    pub commit_id: String,
+
+    /// When the snapshot was created
+    pub created: DateTime<Local>,
+
+    /// Description of the snapshot
+    pub message: String,
+}
+
+/// Tag a vault's current commit as a named snapshot
+///
+/// The message defaults to the snapshot's name. Names must be valid in git
+/// refs and unique.
+pub fn create_snapshot(
+    config: &Config,
+    name: &str,
+    message: Option<&str>,
+) -> Result<Snapshot, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let _lock = VaultLock::acquire(config)?;
+
+    let tag_name = format!("{}{}", SNAPSHOT_TAG_PREFIX, name);
+    if name.is_empty() || !git2::Reference::is_valid_name(&format!("refs/tags/{}", tag_name)) {
+        return Err(DotfilesError::InvalidSnapshotName(name.to_string()));
+    }
+    if repo
+        .find_reference(&format!("refs/tags/{}", tag_name))
+        .is_ok()
+    {
+        return Err(DotfilesError::SnapshotExists(name.to_string()));
+    }
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| {
+            DotfilesError::VersionNotFound("HEAD (the vault has no commits)".to_string())
+        })?;
+    let message = message.unwrap_or(name);
+    repo.tag(
+        &tag_name,
+        head.as_object(),
+        &signature(config)?,
+        message,
+        false,
+    )?;
+
+    info!("Created snapshot {} at {}", name, head.id());
+
+    find_snapshot(config, name)
+}
+
+/// List the vault's snapshots, oldest first
+pub fn list_snapshots(config: &Config) -> Result<Vec<Snapshot>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let mut snapshots = Vec::new();
+    for tag_name in repo
+        .tag_names(Some(&format!("{}*", SNAPSHOT_TAG_PREFIX)))?
+        .iter()
+        .flatten()
+    {
+        let name = &tag_name[SNAPSHOT_TAG_PREFIX.len()..];
+        snapshots.push(read_snapshot(&repo, name)?);
+    }
+    snapshots.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(snapshots)
+}
+
+/// Look up a snapshot by name
+pub fn find_snapshot(config: &Config, name: &str) -> Result<Snapshot, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    read_snapshot(&repo, name)
+}
+
+/// Restore every dotfile as it was in a snapshot
+///
+/// Works like `restore_all_dotfiles`, reading files from the snapshot's
+/// commit instead of the vault directory. Tracked files that weren't in the
+/// vault yet when the snapshot was taken are left alone.
+pub fn restore_snapshot(config: &Config, name: &str) -> Result<RestoreSummary, DotfilesError> {
+    let snapshot = find_snapshot(config, name)?;
+    info!("Restoring snapshot {} ({})", name, snapshot.commit_id);
+    restore_all_at(config, Some(&snapshot.commit_id))
+}
+
+/// Read a snapshot's tag
+///
+/// Lightweight tags made by hand with `git tag snapshot/NAME` count too;
+/// they take their date and message from the commit.
+fn read_snapshot(repo: &Repository, name: &str) -> Result<Snapshot, DotfilesError> {
+    let reference = repo
+        .find_reference(&format!("refs/tags/{}{}", SNAPSHOT_TAG_PREFIX, name))
+        .map_err(|_| DotfilesError::SnapshotNotFound(name.to_string()))?;
+    let commit = reference.peel_to_commit()?;
+
+    let (seconds, message) = match reference.peel_to_tag() {
+        Ok(tag) => (
+            tag.tagger()
+                .map_or(commit.time().seconds(), |tagger| tagger.when().seconds()),
+            tag.message().unwrap_or("").trim().to_string(),
+        ),
+        Err(_) => (
+            commit.time().seconds(),
+            commit.summary().unwrap_or("").to_string(),
+        ),
+    };
+
+    Ok(Snapshot {
+        name: name.to_string(),
+        commit_id: commit.id().to_string(),
+        created: Local
+            .timestamp_opt(seconds, 0)
+            .single()
+            .unwrap_or_else(Local::now),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{commit_changes, init_git_repo};
+    use crate::restore::restore_all_dotfiles;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        fs::write(config.vault_dir.join(".bashrc"), "old\n").unwrap();
+        let first = commit_changes(&config, "Add bashrc").unwrap();
+        let snapshot = create_snapshot(&config, "pre-reinstall", None).unwrap();
+        assert_eq!(snapshot.commit_id, first);
+        assert_eq!(snapshot.message, "pre-reinstall");
+
+        assert!(matches!(
+            create_snapshot(&config, "pre-reinstall", None),
+            Err(DotfilesError::SnapshotExists(_))
+        ));
+        assert!(matches!(
+            create_snapshot(&config, "bad..name", None),
+            Err(DotfilesError::InvalidSnapshotName(_))
+        ));
+
+        fs::write(config.vault_dir.join(".bashrc"), "new\n").unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "set number\n").unwrap();
+        commit_changes(&config, "Update").unwrap();
+        restore_all_dotfiles(&config).unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "edited\n").unwrap();
+
+        let names: Vec<String> = list_snapshots(&config)
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.name)
+            .collect();
+        assert_eq!(names, vec!["pre-reinstall".to_string()]);
+
+        // Files added after the snapshot are left alone
+        let summary = restore_snapshot(&config, "pre-reinstall").unwrap();
+        assert_eq!(summary.restored, vec![std::path::PathBuf::from(".bashrc")]);
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "old\n"
+        );
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".vimrc")).unwrap(),
+            "edited\n"
+        );
+
+        assert!(matches!(
+            restore_snapshot(&config, "missing"),
+            Err(DotfilesError::SnapshotNotFound(_))
+        ));
+    }
+}
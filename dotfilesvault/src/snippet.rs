@@ -0,0 +1,311 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name of the directory inside the vault holding snippets
+pub const SNIPPETS_DIR: &str = "snippets";
+
+/// Home-relative path of the generated script defining every snippet
+pub const SNIPPETS_SCRIPT: &str = ".dotfilesvault/aliases.sh";
+
+/// Line sourcing the generated script, for shell rc files
+pub const SOURCE_LINE: &str = "[ -f ~/.dotfilesvault/aliases.sh ] && . ~/.dotfilesvault/aliases.sh";
+
+/// Header written at the top of the generated script
+const SCRIPT_HEADER: &str = "# Generated by dotfilesvault from the vault's snippets, edit them with `dotfilesvault snippet`";
+
+/// What a snippet defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnippetKind {
+    /// A shell alias
+    Alias,
+
+    /// A shell function
+    Function,
+}
+
+impl SnippetKind {
+    /// Every kind, in the order they are defined in the generated script
+    pub const ALL: [SnippetKind; 2] = [SnippetKind::Alias, SnippetKind::Function];
+
+    /// Get the kind's name, also its directory in the vault
+    pub fn name(self) -> &'static str {
+        match self {
+            SnippetKind::Alias => "alias",
+            SnippetKind::Function => "function",
+        }
+    }
+}
+
+impl FromStr for SnippetKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "alias" => Ok(SnippetKind::Alias),
+            "function" => Ok(SnippetKind::Function),
+            _ => Err(format!(
+                "invalid snippet kind {:?}, expected alias or function",
+                value
+            )),
+        }
+    }
+}
+
+/// A shell alias or function managed in the vault
+///
+/// Each snippet is a file of its own in the vault, so it has its own history
+/// instead of being buried in the history of a whole rc file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snippet {
+    /// What the snippet defines
+    pub kind: SnippetKind,
+
+    /// Name of the alias or function
+    pub name: String,
+
+    /// What the alias expands to, or the body of the function
+    pub body: String,
+}
+
+impl Snippet {
+    /// Parse a `NAME=BODY` definition, as in `alias gs='git status'`
+    pub fn parse(kind: SnippetKind, definition: &str) -> Result<Self, DotfilesError> {
+        let invalid = || DotfilesError::InvalidSnippet(definition.to_string());
+
+        let (name, body) = definition.split_once('=').ok_or_else(invalid)?;
+        let valid_name = !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+        if !valid_name || body.trim().is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            kind,
+            name: name.to_string(),
+            body: body.trim().to_string(),
+        })
+    }
+
+    /// Get the path of the snippet's file in the vault
+    pub fn path(&self, config: &Config) -> PathBuf {
+        snippet_path(config, self.kind, &self.name)
+    }
+
+    /// Render the snippet as shell code
+    pub fn to_shell(&self) -> String {
+        match self.kind {
+            SnippetKind::Alias => {
+                format!(
+                    "alias {}='{}'\n",
+                    self.name,
+                    self.body.replace('\'', r"'\''")
+                )
+            }
+            SnippetKind::Function => {
+                let mut function = format!("{}() {{\n", self.name);
+                for line in self.body.lines() {
+                    function.push_str("    ");
+                    function.push_str(line);
+                    function.push('\n');
+                }
+                function.push_str("}\n");
+                function
+            }
+        }
+    }
+}
+
+/// Get the path of a snippet's file in the vault
+fn snippet_path(config: &Config, kind: SnippetKind, name: &str) -> PathBuf {
+    config
+        .vault_dir
+        .join(SNIPPETS_DIR)
+        .join(kind.name())
+        .join(format!("{}.sh", name))
+}
+
+/// Get the path of the generated script in the home directory
+pub fn script_path(config: &Config) -> PathBuf {
+    config.home_dir.join(SNIPPETS_SCRIPT)
+}
+
+/// Read the snippets stored in the vault, aliases first, each sorted by name
+pub fn list_snippets(config: &Config) -> Result<Vec<Snippet>, DotfilesError> {
+    let mut snippets = Vec::new();
+
+    for kind in SnippetKind::ALL {
+        let dir = config.vault_dir.join(SNIPPETS_DIR).join(kind.name());
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_suffix(".sh"))
+                    .map(str::to_string)
+            })
+            .collect();
+        names.sort();
+
+        for name in names {
+            let body = fs::read_to_string(snippet_path(config, kind, &name))?;
+            snippets.push(Snippet {
+                kind,
+                name,
+                body: body.trim_end().to_string(),
+            });
+        }
+    }
+
+    Ok(snippets)
+}
+
+/// Store a snippet in the vault and regenerate the script
+///
+/// A snippet of the other kind with the same name is replaced, since the
+/// shell can't have both.
+pub fn add_snippet(config: &Config, snippet: &Snippet) -> Result<(), DotfilesError> {
+    config.init_vault_dir()?;
+
+    for kind in SnippetKind::ALL {
+        let path = snippet_path(config, kind, &snippet.name);
+        if kind != snippet.kind && path.is_file() {
+            debug!("Replacing {} {}", kind.name(), snippet.name);
+            fs::remove_file(path)?;
+        }
+    }
+
+    let path = snippet.path(config);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&path, format!("{}\n", snippet.body).as_bytes())?;
+
+    info!("Added {} {}", snippet.kind.name(), snippet.name);
+
+    write_snippets_script(config)
+}
+
+/// Remove a snippet from the vault and regenerate the script
+pub fn remove_snippet(config: &Config, name: &str) -> Result<Snippet, DotfilesError> {
+    let snippet = list_snippets(config)?
+        .into_iter()
+        .find(|snippet| snippet.name == name)
+        .ok_or_else(|| DotfilesError::SnippetNotFound(name.to_string()))?;
+
+    fs::remove_file(snippet.path(config))?;
+
+    info!("Removed {} {}", snippet.kind.name(), snippet.name);
+
+    write_snippets_script(config)?;
+
+    Ok(snippet)
+}
+
+/// Render the script defining every snippet
+pub fn render_script(snippets: &[Snippet]) -> String {
+    let mut script = format!("{}\n", SCRIPT_HEADER);
+    for snippet in snippets {
+        script.push_str(&snippet.to_shell());
+    }
+
+    script
+}
+
+/// Write the script defining the vault's snippets into the home directory
+///
+/// Nothing is written for a vault without snippets unless an earlier script
+/// has to be emptied.
+pub fn write_snippets_script(config: &Config) -> Result<(), DotfilesError> {
+    let snippets = list_snippets(config)?;
+    let path = script_path(config);
+    if snippets.is_empty() && !path.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&path, render_script(&snippets).as_bytes())?;
+
+    debug!("Wrote {} snippets to {:?}", snippets.len(), path);
+
+    Ok(())
+}
+
+/// Check if a shell rc file in the home directory loads the generated script
+pub fn is_sourced(config: &Config) -> bool {
+    [".bashrc", ".zshrc", ".bash_profile", ".profile"]
+        .iter()
+        .filter_map(|rc| fs::read_to_string(config.home_dir.join(rc)).ok())
+        .any(|content| content.contains(SNIPPETS_SCRIPT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snippets() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+
+        let alias = Snippet::parse(SnippetKind::Alias, "gs=git status").unwrap();
+        let function =
+            Snippet::parse(SnippetKind::Function, "mkcd=mkdir -p \"$1\" && cd \"$1\"").unwrap();
+        assert!(Snippet::parse(SnippetKind::Alias, "git status").is_err());
+        assert!(Snippet::parse(SnippetKind::Alias, "-x=y").is_err());
+
+        add_snippet(&config, &alias).unwrap();
+        add_snippet(&config, &function).unwrap();
+        add_snippet(
+            &config,
+            &Snippet::parse(SnippetKind::Alias, "say=echo 'hi'").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(list_snippets(&config).unwrap().len(), 3);
+
+        let script = fs::read_to_string(script_path(&config)).unwrap();
+        assert!(script.contains("alias gs='git status'\n"));
+        assert!(script.contains(r"alias say='echo '\''hi'\'''"));
+        assert!(script.contains("mkcd() {\n    mkdir -p \"$1\" && cd \"$1\"\n}\n"));
+
+        // Redefining a name as the other kind replaces it
+        add_snippet(
+            &config,
+            &Snippet::parse(SnippetKind::Function, "gs=git status -sb").unwrap(),
+        )
+        .unwrap();
+        let snippets = list_snippets(&config).unwrap();
+        assert_eq!(snippets.len(), 3);
+        assert!(!snippets.contains(&alias));
+
+        remove_snippet(&config, "gs").unwrap();
+        assert!(matches!(
+            remove_snippet(&config, "gs"),
+            Err(DotfilesError::SnippetNotFound(_))
+        ));
+        assert!(
+            !fs::read_to_string(script_path(&config))
+                .unwrap()
+                .contains("gs")
+        );
+        assert!(!is_sourced(&config));
+    }
+}
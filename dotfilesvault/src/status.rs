@@ -0,0 +1,160 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::backend::open_backend;
+use crate::backup::{Dotfile, find_dotfiles, has_drifted};
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::restore::list_backed_up_dotfiles;
+use crate::symlink::{is_file_or_link, is_preserved_link};
+use crate::{Config, DotfilesError};
+
+/// How a dotfile differs between the home directory and the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileStatus {
+    /// Tracked and the home copy differs from the vault
+    Modified,
+
+    /// In the home directory but not yet in the vault
+    New,
+
+    /// In the vault but no longer in the home directory
+    Deleted,
+}
+
+impl FileStatus {
+    /// Get the one-letter code used in porcelain output
+    pub fn code(self) -> char {
+        match self {
+            FileStatus::Modified => 'M',
+            FileStatus::New => '?',
+            FileStatus::Deleted => 'D',
+        }
+    }
+
+    /// Get a human-readable label
+    pub fn label(self) -> &'static str {
+        match self {
+            FileStatus::Modified => "modified",
+            FileStatus::New => "new",
+            FileStatus::Deleted => "deleted",
+        }
+    }
+}
+
+/// A dotfile that differs between the home directory and the vault
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusEntry {
+    /// Path of the file relative to the home directory
+    pub path: PathBuf,
+
+    /// How the file differs
+    pub status: FileStatus,
+}
+
+/// Compare the home directory against the vault
+///
+/// Returns the modified, new and deleted dotfiles sorted by path. Files whose
+/// manifest conditions don't hold on this machine are left out.
+pub fn get_status(config: &Config) -> Result<Vec<StatusEntry>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    let backend = open_backend(config)?;
+    let mut statuses: BTreeMap<PathBuf, FileStatus> = BTreeMap::new();
+
+    let mut check = |entry: &DotfileEntry| -> Result<(), DotfilesError> {
+        if !entry.applies() || statuses.contains_key(&entry.source) {
+            return Ok(());
+        }
+
+        let dotfile = entry.dotfile(config);
+        let status = match (
+            dotfile.original_path.is_file() || is_preserved_link(config, &dotfile.original_path),
+            is_file_or_link(&dotfile.vault_path),
+        ) {
+            (true, false) => Some(FileStatus::New),
+            (false, true) => Some(FileStatus::Deleted),
+            (true, true) if has_drifted(config, backend.as_ref(), entry)? => {
+                Some(FileStatus::Modified)
+            }
+            _ => None,
+        };
+
+        if let Some(status) = status {
+            statuses.insert(entry.source.clone(), status);
+        }
+
+        Ok(())
+    };
+
+    // Dotfiles found in the home directory
+    for dotfile in find_dotfiles(config)? {
+        check(&manifest.entry_for(&dotfile, config))?;
+    }
+
+    // Tracked files, including ones the scan doesn't pick up or that are gone
+    for entry in manifest.entries.values() {
+        check(entry)?;
+    }
+
+    // Vaults from before the manifest only have their files to go by
+    if manifest.entries.is_empty() && config.vault_dir.exists() {
+        for relative in list_backed_up_dotfiles(config)? {
+            let dotfile = Dotfile::new(config.home_dir.join(&relative), config);
+            check(&manifest.entry_for(&dotfile, config))?;
+        }
+    }
+
+    config.hash_cache.save(config)?;
+
+    Ok(statuses
+        .into_iter()
+        .map(|(path, status)| StatusEntry { path, status })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_get_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim").unwrap();
+        fs::write(config.home_dir.join(".zshrc"), "zsh").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        assert!(get_status(&config).unwrap().is_empty());
+
+        fs::write(config.home_dir.join(".bashrc"), "bash changed").unwrap();
+        fs::remove_file(config.home_dir.join(".vimrc")).unwrap();
+        fs::write(config.home_dir.join(".inputrc"), "input").unwrap();
+
+        let status = get_status(&config).unwrap();
+        assert_eq!(
+            status,
+            vec![
+                StatusEntry {
+                    path: PathBuf::from(".bashrc"),
+                    status: FileStatus::Modified,
+                },
+                StatusEntry {
+                    path: PathBuf::from(".inputrc"),
+                    status: FileStatus::New,
+                },
+                StatusEntry {
+                    path: PathBuf::from(".vimrc"),
+                    status: FileStatus::Deleted,
+                },
+            ]
+        );
+    }
+}
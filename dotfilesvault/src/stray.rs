@@ -0,0 +1,225 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backup::Dotfile;
+use crate::history::commit_changes;
+use crate::lock::VaultLock;
+use crate::manifest::Manifest;
+use crate::restore::list_backed_up_dotfiles;
+use crate::template::is_template;
+use crate::utils::remove_empty_parents;
+use crate::{Config, DotfilesError};
+
+/// What to do with files in the vault that no manifest entry tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrayAction {
+    /// Track them, as dotfiles at the same path in the home directory
+    Adopt,
+
+    /// Remove them from the vault
+    Delete,
+
+    /// Keep them in the vault and stop reporting them, e.g. a README
+    Ignore,
+}
+
+impl StrayAction {
+    /// Name used on the command line and in output
+    pub fn name(&self) -> &'static str {
+        match self {
+            StrayAction::Adopt => "adopt",
+            StrayAction::Delete => "delete",
+            StrayAction::Ignore => "ignore",
+        }
+    }
+}
+
+impl FromStr for StrayAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "adopt" => Ok(StrayAction::Adopt),
+            "delete" => Ok(StrayAction::Delete),
+            "ignore" => Ok(StrayAction::Ignore),
+            _ => Err(format!(
+                "invalid action {:?}, expected adopt, delete or ignore",
+                value
+            )),
+        }
+    }
+}
+
+/// Outcome of dealing with stray files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StraySummary {
+    /// What was done with the files
+    pub action: StrayAction,
+
+    /// Vault-relative paths of the files
+    pub files: Vec<PathBuf>,
+
+    /// The commit recording the change, if there was one
+    pub commit: Option<String>,
+}
+
+/// Find the files in the vault that no manifest entry tracks
+///
+/// These were copied into the vault by hand or left behind, and are neither
+/// restored nor backed up. Templates count as tracked, since restores pick
+/// them up, and so do all files of vaults from before the manifest. Files
+/// recorded as ignored aren't reported, nor is the vault's own `.gitignore`.
+pub fn find_stray_files(config: &Config) -> Result<Vec<PathBuf>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    if manifest.entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let tracked: HashSet<&PathBuf> = manifest
+        .entries
+        .values()
+        .map(|entry| &entry.target)
+        .collect();
+    let mut strays: Vec<PathBuf> = list_backed_up_dotfiles(config)?
+        .into_iter()
+        .filter(|path| {
+            !tracked.contains(path)
+                && !is_template(path)
+                && !manifest.ignored.contains(path)
+                && path != Path::new(".gitignore")
+        })
+        .collect();
+    strays.sort();
+
+    debug!("Found {} stray files in the vault", strays.len());
+    Ok(strays)
+}
+
+/// Adopt, delete or ignore stray files and commit the result
+///
+/// `files` picks the vault-relative paths to act on, all stray files if it's
+/// empty; paths that aren't stray fail with `DotfileNotFound`. With
+/// `config.dry_run` set nothing is changed.
+pub fn resolve_stray_files(
+    config: &Config,
+    files: &[PathBuf],
+    action: StrayAction,
+) -> Result<StraySummary, DotfilesError> {
+    let strays = find_stray_files(config)?;
+    let files = if files.is_empty() {
+        strays
+    } else {
+        if let Some(missing) = files.iter().find(|file| !strays.contains(file)) {
+            return Err(DotfilesError::DotfileNotFound(
+                missing.display().to_string(),
+            ));
+        }
+        files.to_vec()
+    };
+
+    let mut summary = StraySummary {
+        action,
+        files,
+        commit: None,
+    };
+    if summary.files.is_empty() || config.dry_run {
+        return Ok(summary);
+    }
+
+    {
+        let _lock = VaultLock::acquire(config)?;
+        let mut manifest = Manifest::load(config)?;
+        for file in &summary.files {
+            match action {
+                StrayAction::Adopt => {
+                    let dotfile = Dotfile {
+                        original_path: config.home_dir.join(file),
+                        vault_path: config.vault_dir.join(file),
+                    };
+                    manifest.track(&dotfile, config);
+                }
+                StrayAction::Delete => {
+                    let path = config.vault_dir.join(file);
+                    fs::remove_file(&path)?;
+                    remove_empty_parents(&config.vault_dir, &path);
+                }
+                StrayAction::Ignore => {
+                    manifest.ignored.insert(file.clone());
+                }
+            }
+            debug!("Did {} for stray file {:?}", action.name(), file);
+        }
+        if action != StrayAction::Delete {
+            manifest.save(config)?;
+        }
+    }
+
+    let message = match action {
+        StrayAction::Adopt => "Adopt stray files",
+        StrayAction::Delete => "Delete stray files",
+        StrayAction::Ignore => "Ignore stray files",
+    };
+    summary.commit = Some(commit_changes(config, message)?);
+    info!(
+        "Did {} for {} stray files",
+        action.name(),
+        summary.files.len()
+    );
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::history::init_git_repo;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_stray_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+
+        fs::write(config.vault_dir.join("README.md"), "my dotfiles").unwrap();
+        fs::write(config.vault_dir.join(".vimrc"), "vim").unwrap();
+        fs::create_dir_all(config.vault_dir.join(".config/old")).unwrap();
+        fs::write(config.vault_dir.join(".config/old/rc"), "old").unwrap();
+        assert_eq!(
+            find_stray_files(&config).unwrap(),
+            vec![
+                PathBuf::from(".config/old/rc"),
+                PathBuf::from(".vimrc"),
+                PathBuf::from("README.md")
+            ]
+        );
+
+        assert!(matches!(
+            resolve_stray_files(&config, &[PathBuf::from(".bashrc")], StrayAction::Delete),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+
+        resolve_stray_files(&config, &[PathBuf::from(".vimrc")], StrayAction::Adopt).unwrap();
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.find_by_source(".vimrc").is_some());
+
+        resolve_stray_files(&config, &[PathBuf::from("README.md")], StrayAction::Ignore).unwrap();
+        let summary = resolve_stray_files(&config, &[], StrayAction::Delete).unwrap();
+        assert_eq!(summary.files, vec![PathBuf::from(".config/old/rc")]);
+        assert!(summary.commit.is_some());
+        assert!(!config.vault_dir.join(".config").exists());
+        assert!(config.vault_dir.join("README.md").exists());
+
+        assert!(find_stray_files(&config).unwrap().is_empty());
+    }
+}
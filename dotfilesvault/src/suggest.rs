@@ -0,0 +1,149 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backup::ignore_matcher;
+use crate::manifest::Manifest;
+use crate::{Config, DotfilesError};
+
+/// How recently a file must have been modified to be suggested
+pub const SUGGESTION_WINDOW_DAYS: i64 = 7;
+
+/// Well-known configuration files, as `(application, home-relative path)`
+pub const APP_CATALOG: &[(&str, &str)] = &[
+    ("alacritty", ".config/alacritty/alacritty.toml"),
+    ("alacritty", ".config/alacritty/alacritty.yml"),
+    ("bash", ".bashrc"),
+    ("bash", ".bash_profile"),
+    ("fish", ".config/fish/config.fish"),
+    ("git", ".gitconfig"),
+    ("git", ".config/git/config"),
+    ("helix", ".config/helix/config.toml"),
+    ("hyprland", ".config/hypr/hyprland.conf"),
+    ("i3", ".config/i3/config"),
+    ("kitty", ".config/kitty/kitty.conf"),
+    ("neovim", ".config/nvim/init.lua"),
+    ("neovim", ".config/nvim/init.vim"),
+    ("ssh", ".ssh/config"),
+    ("starship", ".config/starship.toml"),
+    ("sway", ".config/sway/config"),
+    ("tmux", ".tmux.conf"),
+    ("tmux", ".config/tmux/tmux.conf"),
+    ("vim", ".vimrc"),
+    ("wezterm", ".config/wezterm/wezterm.lua"),
+    ("zellij", ".config/zellij/config.kdl"),
+    ("zsh", ".zshrc"),
+];
+
+/// A recently edited configuration file that isn't tracked yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Application the file belongs to
+    pub app: String,
+
+    /// Path of the file relative to the home directory
+    pub path: PathBuf,
+
+    /// When the file was last modified
+    pub modified: DateTime<Local>,
+}
+
+impl Suggestion {
+    /// Get the command that starts tracking the file
+    pub fn command(&self) -> String {
+        format!("dotfilesvault backup ~/{}", self.path.display())
+    }
+}
+
+/// Find catalog files modified within `window` of `now` that aren't tracked
+///
+/// Files excluded by the ignore patterns are never suggested.
+pub fn suggest_untracked(
+    config: &Config,
+    now: DateTime<Local>,
+    window: Duration,
+) -> Result<Vec<Suggestion>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    let matcher = ignore_matcher(config)?;
+    let mut suggestions = Vec::new();
+
+    for (app, relative) in APP_CATALOG {
+        let path = config.home_dir.join(relative);
+
+        if manifest.find_by_source(relative).is_some()
+            || matcher
+                .matched_path_or_any_parents(&path, false)
+                .is_ignore()
+        {
+            continue;
+        }
+
+        let Some(modified) = modified_at(&path) else {
+            continue;
+        };
+
+        if now - modified <= window {
+            debug!("Suggesting untracked {} config {:?}", app, path);
+            suggestions.push(Suggestion {
+                app: app.to_string(),
+                path: PathBuf::from(relative),
+                modified,
+            });
+        }
+    }
+
+    Ok(suggestions)
+}
+
+/// Get the modification time of a regular file
+fn modified_at(path: &Path) -> Option<DateTime<Local>> {
+    let metadata = fs::metadata(path).ok()?;
+
+    if !metadata.is_file() {
+        return None;
+    }
+
+    metadata.modified().ok().map(DateTime::<Local>::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Dotfile;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_suggest_untracked() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(config.home_dir.join(".config/nvim")).unwrap();
+        fs::write(config.home_dir.join(".config/nvim/init.lua"), "-- nvim").unwrap();
+        fs::write(config.home_dir.join(".zshrc"), "# zsh").unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.track(
+            &Dotfile::new(config.home_dir.join(".zshrc"), &config),
+            &config,
+        );
+        manifest.save(&config).unwrap();
+
+        let window = Duration::days(SUGGESTION_WINDOW_DAYS);
+        let suggestions = suggest_untracked(&config, Local::now(), window).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].app, "neovim");
+        assert_eq!(
+            suggestions[0].command(),
+            "dotfilesvault backup ~/.config/nvim/init.lua"
+        );
+
+        // Files edited before the window are not suggested
+        let later = Local::now() + Duration::days(SUGGESTION_WINDOW_DAYS + 1);
+        assert!(
+            suggest_untracked(&config, later, window)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
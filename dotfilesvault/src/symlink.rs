@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Config;
+
+/// What backups do with dotfiles that are symlinks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Store the link itself in the vault and recreate it on restore
+    #[default]
+    Preserve,
+
+    /// Store the content the link points to, restored as a regular file
+    Follow,
+}
+
+impl SymlinkPolicy {
+    /// Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymlinkPolicy::Preserve => "preserve",
+            SymlinkPolicy::Follow => "follow",
+        }
+    }
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "preserve" => Ok(SymlinkPolicy::Preserve),
+            "follow" => Ok(SymlinkPolicy::Follow),
+            _ => Err(format!(
+                "invalid symlink policy {:?}, expected preserve or follow",
+                value
+            )),
+        }
+    }
+}
+
+/// Check if a home path is a symlink backed up as a link rather than its content
+pub fn is_preserved_link(config: &Config, path: &Path) -> bool {
+    config.symlinks == SymlinkPolicy::Preserve && path.is_symlink()
+}
+
+/// Check if a path is a file, or a symlink stored as one
+///
+/// Unlike `Path::is_file` this counts links to directories and dangling links.
+pub fn is_file_or_link(path: &Path) -> bool {
+    path.is_file() || path.is_symlink()
+}
+
+/// Check if two paths are symlinks with the same target
+pub fn same_link(a: &Path, b: &Path) -> bool {
+    match (fs::read_link(a), fs::read_link(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Copy a symlink, replacing whatever file or link is at the destination
+///
+/// The target is copied as is, so relative links stay relative.
+pub fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    create_symlink(&fs::read_link(from)?, to)
+}
+
+/// Create a symlink, replacing whatever file or link is at its path
+///
+/// A real directory at the path is never replaced.
+pub fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if link.is_symlink() || link.is_file() {
+        fs::remove_file(link)?;
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    {
+        let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+        if resolved.is_dir() {
+            std::os::windows::fs::symlink_dir(target, link)
+        } else {
+            std::os::windows::fs::symlink_file(target, link)
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = target;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "symlinks aren't supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,1194 @@
+use anyhow::Result;
+use git2::{
+    AutotagOption, BranchType, Buf, Cred, CredentialType, FetchOptions, PushOptions,
+    RemoteCallbacks, Repository,
+    build::{CheckoutBuilder, RepoBuilder},
+};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+use crate::backup::{Dotfile, backup_specific_dotfiles, is_trackable};
+use crate::cache::content_hash;
+use crate::capabilities::{check_remote, remote_error};
+use crate::conflicts::ConflictPolicy;
+use crate::history::{commit_changes, has_uncommitted_changes, init_git_repo};
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::plan::Plan;
+use crate::progress::Progress;
+use crate::restore::{
+    RestoreSummary, decode_entry_content, list_backed_up_dotfiles, restore_files_at,
+};
+use crate::utils::{copy_file, hostname, write_file};
+use crate::{Config, DotfilesError, t};
+
+/// Name of the git remote the vault is mirrored to
+pub const REMOTE_NAME: &str = "origin";
+
+/// Name of the state file recording what `sync_vault` left alone, see
+/// `Config::state_path`
+pub const SYNC_STATE_FILE: &str = "dotfilesvault-sync.json";
+
+/// First line of git bundle files, in version 2 of the format
+pub const BUNDLE_HEADER: &str = "# v2 git bundle";
+
+/// Result of pulling from the remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullOutcome {
+    /// The vault already contained everything on the remote
+    UpToDate,
+
+    /// The vault was fast-forwarded to the remote branch
+    FastForward,
+
+    /// Local and remote changes were merged in a merge commit
+    Merged,
+}
+
+/// Result of recovering a vault from its remote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Recovery {
+    /// URL the vault was cloned from
+    pub url: String,
+
+    /// Number of dotfiles the recovered manifest tracks
+    pub tracked: usize,
+
+    /// Home-relative paths of tracked dotfiles missing from the recovered vault
+    pub missing: Vec<String>,
+}
+
+/// Top-level files of a dotfiles repository that configure the repository
+/// itself rather than the home directory
+pub const REPOSITORY_FILES: [&str; 4] = [".gitignore", ".gitattributes", ".gitmodules", ".github"];
+
+/// Result of importing an existing dotfiles repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Import {
+    /// Repository URL or directory the dotfiles were imported from
+    pub source: String,
+
+    /// Whether the source was cloned with its history rather than copied
+    pub cloned: bool,
+
+    /// Home-relative paths of the imported dotfiles, now tracked
+    pub tracked: Vec<PathBuf>,
+
+    /// Vault-relative paths of files not laid out like the home directory,
+    /// besides the repository's own git files
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Add the vault remote, or update its URL if it already exists
+pub fn set_remote(config: &Config, url: &str) -> Result<(), DotfilesError> {
+    let repo = init_git_repo(config)?;
+
+    match repo.find_remote(REMOTE_NAME) {
+        Ok(remote) if remote.url() == Some(url) => {}
+        Ok(_) => {
+            debug!("Setting {} URL to {}", REMOTE_NAME, url);
+            repo.remote_set_url(REMOTE_NAME, url)?;
+        }
+        Err(_) => {
+            debug!("Adding remote {} at {}", REMOTE_NAME, url);
+            repo.remote(REMOTE_NAME, url)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the URL of the vault remote, if one is configured
+pub fn remote_url(config: &Config) -> Result<Option<String>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    Ok(repo
+        .find_remote(REMOTE_NAME)
+        .ok()
+        .and_then(|remote| remote.url().map(str::to_string)))
+}
+
+/// Push the current branch of the vault to the remote
+pub fn push(config: &Config) -> Result<(), DotfilesError> {
+    let (repo, url) = open_with_remote(config)?;
+    let branch = current_branch(&repo)?;
+
+    if repo.head().is_err() {
+        return Err(DotfilesError::SyncFailed(
+            "nothing to push, the vault has no commits yet".to_string(),
+        ));
+    }
+
+    let refspec = format!("refs/heads/{0}:refs/heads/{0}", branch);
+    let mut options = PushOptions::new();
+    options.remote_callbacks(remote_callbacks(
+        config,
+        Progress::objects(config, t!("progress-push")),
+    ));
+
+    let mut remote = repo.find_remote(REMOTE_NAME)?;
+    remote
+        .push(&[refspec.as_str()], Some(&mut options))
+        .map_err(|err| aborted_or(config, &url, err))?;
+
+    info!("Pushed {} to {}", branch, REMOTE_NAME);
+
+    Ok(())
+}
+
+/// Write the vault's branches into a git bundle file
+///
+/// A bundle carries the vault's history where its remote can't be reached,
+/// e.g. on a USB stick: git clones and pulls from the file like from a
+/// remote, as in `git clone vault.bundle ~/dotfilesvault`. Returns the names
+/// of the branches written.
+pub fn export_bundle(config: &Config, path: &Path) -> Result<Vec<String>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let head = repo.head().map_err(|_| {
+        DotfilesError::SyncFailed("nothing to bundle, the vault has no commits yet".to_string())
+    })?;
+
+    let mut header = format!("{}\n", BUNDLE_HEADER);
+    let mut walk = repo.revwalk()?;
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let reference = branch.get();
+        if let (Some(name), Some(oid)) = (reference.name(), reference.target()) {
+            header.push_str(&format!("{} {}\n", oid, name));
+            walk.push(oid)?;
+            branches.push(name.trim_start_matches("refs/heads/").to_string());
+        }
+    }
+    if let Some(oid) = head.target() {
+        header.push_str(&format!("{} HEAD\n", oid));
+    }
+    header.push('\n');
+
+    let mut builder = repo.packbuilder()?;
+    builder.insert_walk(&mut walk)?;
+    let mut pack = Buf::new();
+    builder.write_buf(&mut pack)?;
+
+    let mut content = header.into_bytes();
+    content.extend_from_slice(&pack);
+    write_file(path, &content)?;
+
+    info!(
+        "Wrote {} objects of {} branches to {:?}",
+        builder.object_count(),
+        branches.len(),
+        path
+    );
+
+    Ok(branches)
+}
+
+/// Fetch the remote branch and integrate it into the vault
+///
+/// Fast-forwards when possible and otherwise creates a merge commit. If the
+/// merge would conflict nothing is changed and the conflicting paths are
+/// returned in the error.
+pub fn pull(config: &Config) -> Result<PullOutcome, DotfilesError> {
+    let (repo, url) = open_with_remote(config)?;
+    let branch = current_branch(&repo)?;
+    let _lock = VaultLock::acquire(config)?;
+
+    let mut options = FetchOptions::new();
+    options
+        .remote_callbacks(remote_callbacks(
+            config,
+            Progress::objects(config, t!("progress-pull")),
+        ))
+        .download_tags(AutotagOption::None);
+
+    let mut remote = repo.find_remote(REMOTE_NAME)?;
+    remote
+        .fetch(&[branch.as_str()], Some(&mut options), None)
+        .map_err(|err| aborted_or(config, &url, err))?;
+
+    let remote_ref = format!("refs/remotes/{}/{}", REMOTE_NAME, branch);
+    let fetched = match repo.find_reference(&remote_ref) {
+        Ok(reference) => repo.reference_to_annotated_commit(&reference)?,
+        Err(_) => {
+            debug!("Remote has no branch {}", branch);
+            return Ok(PullOutcome::UpToDate);
+        }
+    };
+
+    let (analysis, _) = repo.merge_analysis(&[&fetched])?;
+    let local_ref = format!("refs/heads/{}", branch);
+
+    if analysis.is_up_to_date() {
+        return Ok(PullOutcome::UpToDate);
+    }
+
+    if analysis.is_unborn() {
+        repo.reference(&local_ref, fetched.id(), false, "pull: initial")?;
+        repo.set_head(&local_ref)?;
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+
+        info!("Checked out {} from {}", branch, REMOTE_NAME);
+        return Ok(PullOutcome::FastForward);
+    }
+
+    if analysis.is_fast_forward() {
+        let mut reference = repo.find_reference(&local_ref)?;
+        reference.set_target(fetched.id(), "pull: fast-forward")?;
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+
+        info!("Fast-forwarded {} to {}", branch, fetched.id());
+        return Ok(PullOutcome::FastForward);
+    }
+
+    let local = repo.head()?.peel_to_commit()?;
+    let theirs = repo.find_commit(fetched.id())?;
+    let mut index = repo.merge_commits(&local, &theirs, None)?;
+
+    if index.has_conflicts() {
+        let paths = index
+            .conflicts()?
+            .filter_map(|conflict| conflict.ok())
+            .filter_map(|conflict| conflict.our.or(conflict.their))
+            .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+            .collect();
+
+        return Err(DotfilesError::MergeConflicts(paths));
+    }
+
+    let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+    let signature = crate::history::signature(config)?;
+    let message = format!("Merge {}/{} into {}", REMOTE_NAME, branch, branch);
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &message,
+        &tree,
+        &[&local, &theirs],
+    )?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+
+    info!("Merged {}/{} into {}", REMOTE_NAME, branch, branch);
+
+    Ok(PullOutcome::Merged)
+}
+
+/// How `sync_vault` settles a file changed both at home and on the remote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncConflictPolicy {
+    /// Back up the home version over the remote one
+    PreferHome,
+
+    /// Restore the remote version over the home one
+    PreferVault,
+
+    /// Ask which side wins for each file, leaving files without an answer alone
+    #[default]
+    Ask,
+}
+
+impl SyncConflictPolicy {
+    /// Name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyncConflictPolicy::PreferHome => "prefer-home",
+            SyncConflictPolicy::PreferVault => "prefer-vault",
+            SyncConflictPolicy::Ask => "ask",
+        }
+    }
+}
+
+impl FromStr for SyncConflictPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "prefer-home" => Ok(SyncConflictPolicy::PreferHome),
+            "prefer-vault" => Ok(SyncConflictPolicy::PreferVault),
+            "ask" => Ok(SyncConflictPolicy::Ask),
+            _ => Err(format!(
+                "invalid sync conflict policy {:?}, expected prefer-home, prefer-vault or ask",
+                value
+            )),
+        }
+    }
+}
+
+/// Outcome of syncing the home directory with the remote
+#[derive(Debug, Serialize)]
+pub struct SyncSummary {
+    /// What pulling did, none in a dry run
+    pub pulled: Option<PullOutcome>,
+
+    /// Backups of the files edited at home
+    pub backup: Plan,
+
+    /// Restores of the files changed on the remote
+    pub restore: RestoreSummary,
+
+    /// Home-relative paths of files changed on both sides and left alone
+    pub conflicts: Vec<PathBuf>,
+
+    /// The commit recording the backups, if there were any
+    pub commit: Option<String>,
+
+    /// Whether the vault was pushed
+    pub pushed: bool,
+}
+
+/// Sync the home directory with the remote in both directions
+///
+/// Vault changes are committed and the remote is pulled. Each tracked file is
+/// then compared, by content hash, with its stored copy from before the pull:
+/// files changed only at home are backed up, files changed only on the
+/// remote are restored, and files changed on both sides are settled by
+/// `policy`. With `Ask`, `ask` picks the side for each file, or leaves both
+/// copies alone by returning `None`; those files keep their base in the sync
+/// state, so the next sync still sees both sides as changed. The backups are
+/// committed and the vault is pushed. Templates, starters, adopted files and
+/// links are left to `backup` and `restore`. A dry run doesn't pull, so it
+/// only sees the remote changes pulled before, and changes nothing.
+pub fn sync_vault<F>(
+    config: &Config,
+    policy: SyncConflictPolicy,
+    mut ask: F,
+) -> Result<SyncSummary, DotfilesError>
+where
+    F: FnMut(&Path) -> Option<SyncConflictPolicy>,
+{
+    if !config.dry_run && has_uncommitted_changes(config)? {
+        commit_changes(config, "Commit vault changes before syncing")?;
+    }
+    let before = head_id(config)?;
+    let pulled = if config.dry_run {
+        None
+    } else {
+        Some(pull(config)?)
+    };
+    let after = head_id(config)?;
+
+    let manifest = Manifest::load(config)?;
+    let mut state = SyncState::load(config);
+    let repo = init_git_repo(config)?;
+    let base_tree = match &before {
+        Some(id) => Some(repo.find_commit(git2::Oid::from_str(id)?)?.tree()?),
+        None => None,
+    };
+
+    let mut backups = Vec::new();
+    let mut restores = Vec::new();
+    let mut conflicts = Vec::new();
+    for entry in manifest.entries.values() {
+        config.cancellation.check()?;
+        if !entry.applies()
+            || entry.is_template()
+            || entry.create_if_missing
+            || entry.links_to_vault(config)
+        {
+            continue;
+        }
+        let dotfile = entry.dotfile(config);
+        if dotfile.original_path.is_symlink() || dotfile.vault_path.is_symlink() {
+            continue;
+        }
+
+        let home = match fs::read(&dotfile.original_path) {
+            Ok(content) if dotfile.original_path.is_file() => Some(content_hash(&content)?),
+            _ => None,
+        };
+        let vault = match fs::read(&dotfile.vault_path) {
+            Ok(content) => Some(content_hash(&decode_entry_content(
+                config, entry, &content,
+            )?)?),
+            Err(_) => None,
+        };
+        if home == vault {
+            state.conflicts.remove(&entry.id);
+            continue;
+        }
+
+        let base = match state.conflicts.get(&entry.id) {
+            Some(base) => git2::Oid::from_str(base).ok(),
+            None => match &base_tree {
+                Some(tree) => stored_hash(config, &repo, tree, entry)?,
+                None => None,
+            },
+        };
+        let side = match (home != base, vault != base) {
+            (false, false) => continue,
+            (true, false) => SyncConflictPolicy::PreferHome,
+            (false, true) => SyncConflictPolicy::PreferVault,
+            (true, true) => match policy {
+                SyncConflictPolicy::Ask => ask(&entry.source).unwrap_or(SyncConflictPolicy::Ask),
+                policy => policy,
+            },
+        };
+        match side {
+            // Deletions are left to `clean` and `untrack`
+            SyncConflictPolicy::PreferHome if home.is_none() => {}
+            SyncConflictPolicy::PreferVault if vault.is_none() => {}
+            SyncConflictPolicy::PreferHome => backups.push(entry.source.clone()),
+            SyncConflictPolicy::PreferVault => restores.push(entry.source.clone()),
+            SyncConflictPolicy::Ask => {
+                debug!("Leaving {:?} alone, it changed on both sides", entry.source);
+                conflicts.push(entry.source.clone());
+                if let Some(base) = base {
+                    state
+                        .conflicts
+                        .entry(entry.id.clone())
+                        .or_insert_with(|| base.to_string());
+                }
+                continue;
+            }
+        }
+        state.conflicts.remove(&entry.id);
+    }
+    drop(base_tree);
+    drop(repo);
+
+    let backup = if backups.is_empty() {
+        Plan::default()
+    } else {
+        let files: Vec<String> = backups
+            .iter()
+            .map(|path| config.home_dir.join(path).display().to_string())
+            .collect();
+        backup_specific_dotfiles(config, &files)?
+    };
+
+    // Both copies were compared already, the side picked wins
+    let restore = match &after {
+        Some(commit_id) if !restores.is_empty() => {
+            let mut config = config.clone();
+            config.on_conflict = ConflictPolicy::Overwrite;
+            restore_files_at(&config, &restores, commit_id)?
+        }
+        _ => RestoreSummary::default(),
+    };
+
+    let mut commit = None;
+    let mut pushed = false;
+    if !config.dry_run {
+        state.save(config)?;
+        if has_uncommitted_changes(config)? {
+            let host = hostname().unwrap_or_else(|| "this machine".to_string());
+            commit = Some(commit_changes(config, &format!("Sync from {}", host))?);
+        }
+        if head_id(config)?.is_some() {
+            push(config)?;
+            pushed = true;
+        }
+    }
+
+    info!(
+        "Synced: {} backed up, {} restored, {} left alone",
+        backups.len(),
+        restore.restored.len(),
+        conflicts.len()
+    );
+
+    Ok(SyncSummary {
+        pulled,
+        backup,
+        restore,
+        conflicts,
+        commit,
+        pushed,
+    })
+}
+
+/// Files `sync_vault` left alone, stored in `SYNC_STATE_FILE`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    /// Hash of the content both sides changed from, by entry ID
+    conflicts: BTreeMap<String, String>,
+}
+
+impl SyncState {
+    fn path(config: &Config) -> PathBuf {
+        config.state_path(SYNC_STATE_FILE)
+    }
+
+    /// Load the state, starting over if it can't be read
+    fn load(config: &Config) -> Self {
+        fs::read(Self::path(config))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, config: &Config) -> Result<(), DotfilesError> {
+        let path = Self::path(config);
+        if self.conflicts.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+
+        let content = serde_json::to_vec_pretty(self).map_err(std::io::Error::from)?;
+        write_file(&path, &content)?;
+
+        Ok(())
+    }
+}
+
+/// Get the commit the vault's HEAD points to, if it has one
+fn head_id(config: &Config) -> Result<Option<String>, DotfilesError> {
+    let repo = init_git_repo(config)?;
+    let head = repo.head().ok().and_then(|head| head.target());
+
+    Ok(head.map(|oid| oid.to_string()))
+}
+
+/// Hash the content an entry had in a commit's tree, decoded as it would be
+/// restored
+fn stored_hash(
+    config: &Config,
+    repo: &Repository,
+    tree: &git2::Tree,
+    entry: &DotfileEntry,
+) -> Result<Option<git2::Oid>, DotfilesError> {
+    let Ok(file) = tree.get_path(&entry.stored_path(config)) else {
+        return Ok(None);
+    };
+    let Ok(blob) = repo.find_blob(file.id()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(content_hash(&decode_entry_content(
+        config,
+        entry,
+        blob.content(),
+    )?)?))
+}
+
+/// Clone a deleted vault again from the remote set in the config file
+///
+/// Only runs when the vault directory is missing or empty, so an existing
+/// vault is never overwritten. The clone is then checked against its
+/// manifest, and tracked dotfiles the vault doesn't hold are reported.
+pub fn recover(config: &Config) -> Result<Recovery, DotfilesError> {
+    if config.vault_dir.exists() && fs::read_dir(&config.vault_dir)?.next().is_some() {
+        return Err(DotfilesError::SyncFailed(format!(
+            "{} already exists, use pull to update it",
+            config.vault_dir.display()
+        )));
+    }
+
+    let url = config.remote_url.clone().ok_or_else(|| {
+        DotfilesError::SyncFailed(
+            "no remote configured, set git.remote in the config file".to_string(),
+        )
+    })?;
+
+    check_remote(&url)?;
+    info!("Cloning vault from {}", url);
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(remote_callbacks(
+        config,
+        Progress::objects(config, t!("progress-clone")),
+    ));
+    let repo = RepoBuilder::new()
+        .fetch_options(options)
+        .clone(&url, &config.vault_dir)
+        .map_err(|err| aborted_or(config, &url, err))?;
+
+    if repo.head().is_err() {
+        warn!("The remote at {} has no commits", url);
+    }
+
+    cloned_vault(config, url)
+}
+
+/// Set up the vault on a new machine by cloning it from a remote
+///
+/// Only runs when the vault directory is missing or empty. The clone must be
+/// a dotfilesvault vault, with a manifest, or have no commits yet; other
+/// repositories are removed again, `import_dotfiles` takes those. The URL
+/// becomes the sync remote, and tracked dotfiles the vault doesn't hold are
+/// reported like for `recover`.
+pub fn clone_vault(config: &Config, url: &str) -> Result<Recovery, DotfilesError> {
+    if config.vault_dir.exists() && fs::read_dir(&config.vault_dir)?.next().is_some() {
+        return Err(DotfilesError::SyncFailed(format!(
+            "{} already exists, use pull to update it",
+            config.vault_dir.display()
+        )));
+    }
+
+    clone_into_vault(config, url)?;
+
+    let repo = Repository::open(&config.vault_dir)?;
+    let compatible = repo.head().is_err() || Manifest::path(config).is_file();
+    let checked = if compatible {
+        Manifest::load(config).and_then(|_| set_remote(config, url))
+    } else {
+        Err(DotfilesError::SyncFailed(format!(
+            "{} has no dotfilesvault manifest, use import to bring in other dotfiles repositories",
+            url
+        )))
+    };
+    if let Err(err) = checked {
+        debug!("Removing the clone from {:?}", config.vault_dir);
+        fs::remove_dir_all(&config.vault_dir)?;
+        return Err(err);
+    }
+
+    cloned_vault(config, url.to_string())
+}
+
+/// Check a freshly cloned vault against its manifest
+fn cloned_vault(config: &Config, url: String) -> Result<Recovery, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+    let mut missing: Vec<String> = manifest
+        .entries
+        .values()
+        .filter(|entry| !config.vault_dir.join(entry.stored_path(config)).exists())
+        .map(|entry| entry.source.display().to_string())
+        .collect();
+    missing.sort();
+
+    Ok(Recovery {
+        url,
+        tracked: manifest.entries.len(),
+        missing,
+    })
+}
+
+/// Import an existing dotfiles repository laid out with home-relative paths
+///
+/// Git repositories, local or remote, are cloned into the vault with their
+/// history, and other directories are copied and committed. Only runs when
+/// the vault directory is missing or empty. Files that belong in the home
+/// directory are tracked; the rest, like a README, an install script or the
+/// repository's own `.gitignore`, stays in the vault untracked. An import
+/// that finds no dotfiles is undone.
+pub fn import_dotfiles(config: &Config, source: &str) -> Result<Import, DotfilesError> {
+    if config.vault_dir.exists() && fs::read_dir(&config.vault_dir)?.next().is_some() {
+        return Err(DotfilesError::ImportFailed(
+            source.to_string(),
+            format!("{} is not empty", config.vault_dir.display()),
+        ));
+    }
+
+    let local = Path::new(source);
+    let cloned = !local.is_dir() || Repository::open(local).is_ok();
+    let imported = if cloned {
+        clone_into_vault(config, source)
+    } else {
+        copy_into_vault(config, local)
+    }
+    .and_then(|()| track_imported(config, source, cloned));
+
+    if imported.is_err() && config.vault_dir.exists() {
+        debug!("Removing the failed import from {:?}", config.vault_dir);
+        fs::remove_dir_all(&config.vault_dir)?;
+    }
+
+    imported
+}
+
+/// Clone a repository into the vault directory
+fn clone_into_vault(config: &Config, url: &str) -> Result<(), DotfilesError> {
+    check_remote(url)?;
+    info!("Cloning dotfiles from {}", url);
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(remote_callbacks(
+        config,
+        Progress::objects(config, t!("progress-clone")),
+    ));
+    RepoBuilder::new()
+        .fetch_options(options)
+        .clone(url, &config.vault_dir)
+        .map_err(|err| aborted_or(config, url, err))?;
+
+    Ok(())
+}
+
+/// Copy the files of a directory into the vault directory
+fn copy_into_vault(config: &Config, dir: &Path) -> Result<(), DotfilesError> {
+    info!("Copying dotfiles from {:?}", dir);
+
+    // The repository comes first so the directory's own .gitignore wins
+    init_git_repo(config)?;
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()) {
+        config.cancellation.check()?;
+        let Ok(relative) = entry.path().strip_prefix(dir) else {
+            continue;
+        };
+
+        let target = config.vault_dir.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_file() {
+            copy_file(entry.path(), &target, config.bwlimit)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Track the imported files laid out like the home directory and commit them
+fn track_imported(config: &Config, source: &str, cloned: bool) -> Result<Import, DotfilesError> {
+    let mut files = list_backed_up_dotfiles(config)?;
+    files.sort();
+
+    let mut manifest = Manifest::load(config)?;
+    let mut tracked = Vec::new();
+    let mut skipped = Vec::new();
+    for relative in files {
+        let home_path = config.home_dir.join(&relative);
+        let repository_file = relative
+            .components()
+            .next()
+            .and_then(|first| first.as_os_str().to_str())
+            .is_some_and(|first| REPOSITORY_FILES.contains(&first));
+
+        if repository_file {
+            continue;
+        }
+        if !is_trackable(config, &home_path) {
+            debug!(
+                "Not tracking {:?}: not laid out like the home directory",
+                relative
+            );
+            skipped.push(relative);
+            continue;
+        }
+
+        let entry = manifest.track(&Dotfile::new(home_path, config), config);
+        tracked.push(entry.source.clone());
+    }
+
+    if tracked.is_empty() {
+        return Err(DotfilesError::ImportFailed(
+            source.to_string(),
+            "no files laid out like the home directory, e.g. .bashrc or .config/".to_string(),
+        ));
+    }
+
+    manifest.save(config)?;
+    commit_changes(config, &format!("Import dotfiles from {}", source))?;
+
+    info!("Imported {} dotfiles from {}", tracked.len(), source);
+
+    Ok(Import {
+        source: source.to_string(),
+        cloned,
+        tracked,
+        skipped,
+    })
+}
+
+/// Open the vault repository, requiring the remote to be configured
+fn open_with_remote(config: &Config) -> Result<(Repository, String), DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let Some(url) = remote_url(config)? else {
+        return Err(DotfilesError::SyncFailed(
+            "no remote configured, pass --url or set git.remote in the config file".to_string(),
+        ));
+    };
+    check_remote(&url)?;
+
+    Ok((repo, url))
+}
+
+/// Get the short name of the branch HEAD points to, even if it has no commits yet
+fn current_branch(repo: &Repository) -> Result<String, DotfilesError> {
+    let head = repo.find_reference("HEAD")?;
+    let target = head
+        .symbolic_target()
+        .ok_or_else(|| DotfilesError::SyncFailed("HEAD is detached".to_string()))?;
+
+    Ok(target.trim_start_matches("refs/heads/").to_string())
+}
+
+/// The cancellation error if a transfer was aborted because of it, or the
+/// git error, see `remote_error`
+fn aborted_or(config: &Config, url: &str, err: git2::Error) -> DotfilesError {
+    config
+        .cancellation
+        .check()
+        .err()
+        .unwrap_or_else(|| remote_error(url, err))
+}
+
+/// Callbacks authenticating through the SSH agent or git credential helpers
+///
+/// Transfers are shown on `progress`, and aborted once the configuration's
+/// cancellation token is cancelled or times out.
+fn remote_callbacks(config: &Config, progress: Progress) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+
+    let received = progress.clone();
+    callbacks.transfer_progress(move |stats| {
+        received.set(
+            stats.received_objects(),
+            stats.total_objects(),
+            stats.received_bytes() as u64,
+        );
+        !config.cancellation.is_cancelled()
+    });
+    callbacks.push_transfer_progress(move |current, total, bytes| {
+        progress.set(current, total, bytes as u64);
+    });
+    callbacks.sideband_progress(|_| !config.cancellation.is_cancelled());
+    callbacks.push_negotiation(|_| match config.cancellation.check() {
+        Ok(()) => Ok(()),
+        Err(err) => Err(git2::Error::from_str(&err.to_string())),
+    });
+
+    callbacks.credentials(|url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            let git_config = git2::Config::open_default()?;
+            return Cred::credential_helper(&git_config, url, username);
+        }
+
+        Cred::default()
+    });
+
+    callbacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn vault(temp_dir: &TempDir, name: &str, remote: &str) -> Config {
+        let config = Config::new(temp_dir.path().join(name), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        set_remote(&config, remote).unwrap();
+
+        config
+    }
+
+    #[test]
+    fn test_push_and_pull() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let url = remote_dir.to_string_lossy().into_owned();
+
+        let first = vault(&temp_dir, "first", &url);
+        let second = vault(&temp_dir, "second", &url);
+        assert_eq!(remote_url(&first).unwrap(), Some(url.clone()));
+
+        fs::write(first.vault_dir.join(".bashrc"), "bash\n").unwrap();
+        commit_changes(&first, "First").unwrap();
+        push(&first).unwrap();
+
+        // An empty vault picks up the remote branch
+        assert_eq!(pull(&second).unwrap(), PullOutcome::FastForward);
+        assert!(second.vault_dir.join(".bashrc").is_file());
+        assert_eq!(pull(&second).unwrap(), PullOutcome::UpToDate);
+
+        // Diverged histories touching different files are merged
+        fs::write(first.vault_dir.join(".vimrc"), "vim\n").unwrap();
+        commit_changes(&first, "Add vimrc").unwrap();
+        push(&first).unwrap();
+
+        fs::write(second.vault_dir.join(".zshrc"), "zsh\n").unwrap();
+        commit_changes(&second, "Add zshrc").unwrap();
+        assert_eq!(pull(&second).unwrap(), PullOutcome::Merged);
+        assert!(second.vault_dir.join(".vimrc").is_file());
+        assert!(second.vault_dir.join(".zshrc").is_file());
+    }
+
+    #[test]
+    fn test_sync_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let url = remote_dir.to_string_lossy().into_owned();
+        let machine = |name: &str| {
+            let mut config = vault(&temp_dir, name, &url);
+            config.home_dir = temp_dir.path().join(format!("{}-home", name));
+            fs::create_dir_all(&config.home_dir).unwrap();
+            config
+        };
+        let first = machine("first");
+        let second = machine("second");
+        let read =
+            |config: &Config, name: &str| fs::read_to_string(config.home_dir.join(name)).unwrap();
+
+        fs::write(first.home_dir.join(".bashrc"), "bash 1").unwrap();
+        fs::write(first.home_dir.join(".vimrc"), "vim 1").unwrap();
+        crate::backup::backup_all_dotfiles(&first).unwrap();
+        commit_changes(&first, "Backup").unwrap();
+        push(&first).unwrap();
+
+        // A new machine gets every file
+        let summary = sync_vault(&second, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert!(summary.pulled.is_some());
+        assert_eq!(summary.restore.restored.len(), 2);
+        assert_eq!(read(&second, ".bashrc"), "bash 1");
+
+        // Edits on one side travel to the other
+        fs::write(second.home_dir.join(".vimrc"), "vim 2").unwrap();
+        let summary = sync_vault(&second, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert_eq!(summary.backup.copies.len(), 1);
+        assert!(summary.commit.is_some() && summary.pushed);
+        fs::write(first.home_dir.join(".bashrc"), "bash 2").unwrap();
+        let summary = sync_vault(&first, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert_eq!(summary.restore.restored, vec![PathBuf::from(".vimrc")]);
+        assert_eq!(read(&first, ".vimrc"), "vim 2");
+        assert_eq!(read(&first, ".bashrc"), "bash 2");
+
+        // Files changed on both sides are left alone until a side is picked
+        fs::write(second.home_dir.join(".bashrc"), "bash 3").unwrap();
+        let summary = sync_vault(&second, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert_eq!(summary.conflicts, vec![PathBuf::from(".bashrc")]);
+        assert_eq!(read(&second, ".bashrc"), "bash 3");
+        let summary = sync_vault(&second, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert_eq!(summary.conflicts, vec![PathBuf::from(".bashrc")]);
+        let summary = sync_vault(&second, SyncConflictPolicy::Ask, |path| {
+            assert_eq!(path, Path::new(".bashrc"));
+            Some(SyncConflictPolicy::PreferVault)
+        })
+        .unwrap();
+        assert!(summary.conflicts.is_empty());
+        assert_eq!(read(&second, ".bashrc"), "bash 2");
+        assert!(!second.vault_dir.join(".git").join(SYNC_STATE_FILE).exists());
+
+        fs::write(second.home_dir.join(".vimrc"), "vim 3").unwrap();
+        fs::write(first.home_dir.join(".vimrc"), "vim 4").unwrap();
+        sync_vault(&first, SyncConflictPolicy::Ask, |_| None).unwrap();
+        sync_vault(&second, SyncConflictPolicy::PreferHome, |_| None).unwrap();
+        sync_vault(&first, SyncConflictPolicy::Ask, |_| None).unwrap();
+        assert_eq!(read(&first, ".vimrc"), "vim 3");
+        assert_eq!(
+            "prefer-vault".parse::<SyncConflictPolicy>(),
+            Ok(SyncConflictPolicy::PreferVault)
+        );
+    }
+
+    #[test]
+    fn test_export_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        let bundle = temp_dir.path().join("vault.bundle");
+        init_git_repo(&config).unwrap();
+        assert!(export_bundle(&config, &bundle).is_err());
+
+        fs::write(config.vault_dir.join(".bashrc"), "bash\n").unwrap();
+        let commit = commit_changes(&config, "First").unwrap();
+        let branches = export_bundle(&config, &bundle).unwrap();
+        assert_eq!(branches.len(), 1);
+
+        let content = fs::read(&bundle).unwrap();
+        let split = content
+            .windows(2)
+            .position(|window| window == b"\n\n")
+            .unwrap();
+        let header = String::from_utf8(content[..split].to_vec()).unwrap();
+        let mut lines = header.lines();
+        assert_eq!(lines.next(), Some(BUNDLE_HEADER));
+        assert_eq!(
+            lines.next(),
+            Some(format!("{} refs/heads/{}", commit, branches[0]).as_str())
+        );
+        assert_eq!(lines.next(), Some(format!("{} HEAD", commit).as_str()));
+
+        // The pack holds the whole history, files included
+        let other = Repository::init_bare(temp_dir.path().join("other.git")).unwrap();
+        let odb = other.odb().unwrap();
+        let mut writer = odb.packwriter().unwrap();
+        std::io::Write::write_all(&mut writer, &content[split + 2..]).unwrap();
+        writer.commit().unwrap();
+        let tree = other
+            .find_commit(git2::Oid::from_str(&commit).unwrap())
+            .unwrap()
+            .tree()
+            .unwrap();
+        assert!(tree.get_path(Path::new(".bashrc")).is_ok());
+    }
+
+    #[test]
+    fn test_recover_clones_deleted_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let url = remote_dir.to_string_lossy().into_owned();
+
+        let mut config = vault(&temp_dir, "vault", &url);
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash\n").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim\n").unwrap();
+        crate::backup::backup_all_dotfiles(&config).unwrap();
+        fs::remove_file(config.vault_dir.join(".vimrc")).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+        push(&config).unwrap();
+
+        // Without a configured remote there is nothing to recover from
+        fs::remove_dir_all(&config.vault_dir).unwrap();
+        assert!(matches!(
+            recover(&config),
+            Err(DotfilesError::SyncFailed(_))
+        ));
+
+        config.remote_url = Some(url.clone());
+        let recovery = recover(&config).unwrap();
+        assert_eq!(recovery.url, url);
+        assert_eq!(recovery.tracked, 2);
+        assert_eq!(recovery.missing, vec![".vimrc"]);
+        assert!(config.vault_dir.join(".bashrc").is_file());
+
+        // An existing vault is left alone
+        assert!(matches!(
+            recover(&config),
+            Err(DotfilesError::SyncFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_clone_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let url = remote_dir.to_string_lossy().into_owned();
+
+        let config = vault(&temp_dir, "vault", &url);
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash\n").unwrap();
+        crate::backup::backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+        push(&config).unwrap();
+
+        let other = Config::new(temp_dir.path().join("other"), config.home_dir.clone());
+        let cloned = clone_vault(&other, &url).unwrap();
+        assert_eq!(cloned.tracked, 1);
+        assert!(cloned.missing.is_empty());
+        assert!(other.vault_dir.join(".bashrc").is_file());
+        assert_eq!(remote_url(&other).unwrap(), Some(url.clone()));
+        assert!(matches!(
+            clone_vault(&other, &url),
+            Err(DotfilesError::SyncFailed(_))
+        ));
+
+        // Repositories that aren't vaults are refused and removed
+        let plain = temp_dir.path().join("plain");
+        let repo = Repository::init(&plain).unwrap();
+        fs::write(plain.join(".bashrc"), "bash\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(".bashrc")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Add", &tree, &[])
+            .unwrap();
+        let third = Config::new(temp_dir.path().join("third"), config.home_dir.clone());
+        assert!(matches!(
+            clone_vault(&third, &plain.to_string_lossy()),
+            Err(DotfilesError::SyncFailed(_))
+        ));
+        assert!(!third.vault_dir.exists());
+    }
+
+    #[test]
+    fn test_import_dotfiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("dotfiles");
+        fs::create_dir_all(source.join(".config/nvim")).unwrap();
+        fs::write(source.join(".bashrc"), "bash\n").unwrap();
+        fs::write(source.join(".config/nvim/init.lua"), "-- init\n").unwrap();
+        fs::write(source.join(".gitignore"), "*.swp\n").unwrap();
+        fs::write(source.join("README.md"), "My dotfiles\n").unwrap();
+
+        // A plain directory is copied
+        let config = Config::new(temp_dir.path().join("copied"), temp_dir.path().join("home"));
+        let imported = import_dotfiles(&config, &source.to_string_lossy()).unwrap();
+        assert!(!imported.cloned);
+        assert_eq!(
+            imported.tracked,
+            vec![
+                PathBuf::from(".bashrc"),
+                PathBuf::from(".config/nvim/init.lua")
+            ]
+        );
+        assert_eq!(imported.skipped, vec![PathBuf::from("README.md")]);
+        assert_eq!(
+            fs::read_to_string(config.vault_dir.join(".gitignore")).unwrap(),
+            "*.swp\n"
+        );
+        assert_eq!(Manifest::load(&config).unwrap().entries.len(), 2);
+        assert!(matches!(
+            import_dotfiles(&config, &source.to_string_lossy()),
+            Err(DotfilesError::ImportFailed(..))
+        ));
+
+        // A repository is cloned with its history
+        let source_config = Config::new(source.clone(), temp_dir.path().join("home"));
+        init_git_repo(&source_config).unwrap();
+        commit_changes(&source_config, "My dotfiles").unwrap();
+        let config = Config::new(temp_dir.path().join("cloned"), temp_dir.path().join("home"));
+        let imported = import_dotfiles(&config, &source.to_string_lossy()).unwrap();
+        assert!(imported.cloned);
+        let repo = Repository::open(&config.vault_dir).unwrap();
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        assert_eq!(revwalk.count(), 2);
+
+        // Nothing laid out like a home directory
+        let empty = temp_dir.path().join("empty");
+        fs::create_dir_all(&empty).unwrap();
+        fs::write(empty.join("README.md"), "Nothing here\n").unwrap();
+        let config = Config::new(temp_dir.path().join("none"), temp_dir.path().join("home"));
+        assert!(matches!(
+            import_dotfiles(&config, &empty.to_string_lossy()),
+            Err(DotfilesError::ImportFailed(..))
+        ));
+        assert!(!config.vault_dir.exists());
+    }
+
+    #[test]
+    fn test_pull_conflict_leaves_vault_untouched() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let url = remote_dir.to_string_lossy().into_owned();
+
+        let first = vault(&temp_dir, "first", &url);
+        let second = vault(&temp_dir, "second", &url);
+
+        fs::write(first.vault_dir.join(".bashrc"), "base\n").unwrap();
+        commit_changes(&first, "Base").unwrap();
+        push(&first).unwrap();
+        pull(&second).unwrap();
+
+        fs::write(first.vault_dir.join(".bashrc"), "first\n").unwrap();
+        commit_changes(&first, "First").unwrap();
+        push(&first).unwrap();
+
+        fs::write(second.vault_dir.join(".bashrc"), "second\n").unwrap();
+        commit_changes(&second, "Second").unwrap();
+
+        match pull(&second) {
+            Err(DotfilesError::MergeConflicts(paths)) => assert_eq!(paths, vec![".bashrc"]),
+            other => panic!("expected conflicts, got {:?}", other),
+        }
+        assert_eq!(
+            fs::read_to_string(second.vault_dir.join(".bashrc")).unwrap(),
+            "second\n"
+        );
+    }
+}
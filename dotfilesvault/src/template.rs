@@ -0,0 +1,139 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::Config;
+
+/// Extension marking vault files that are rendered on restore
+///
+/// `.gitconfig.tmpl` in the vault is restored as `~/.gitconfig`.
+pub const TEMPLATE_EXTENSION: &str = "tmpl";
+
+/// Check if a vault-relative path is a template
+pub fn is_template(relative_path: &Path) -> bool {
+    relative_path
+        .extension()
+        .is_some_and(|extension| extension == TEMPLATE_EXTENSION)
+}
+
+/// Get the vault-relative template path for a file
+pub fn template_path(relative_path: &Path) -> PathBuf {
+    let mut path = relative_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(TEMPLATE_EXTENSION);
+
+    PathBuf::from(path)
+}
+
+/// Get the home-relative path a template is restored to
+pub fn rendered_path(relative_path: &Path) -> PathBuf {
+    if is_template(relative_path) {
+        relative_path.with_extension("")
+    } else {
+        relative_path.to_path_buf()
+    }
+}
+
+/// Variables available to templates
+///
+/// The built-in `hostname`, `os`, `arch`, `username`, `home`, `profile`,
+/// `name` and `email` can be overridden by the `[variables]` table of the
+/// config file.
+pub fn template_variables(config: &Config) -> BTreeMap<String, String> {
+    let mut variables = BTreeMap::new();
+
+    let mut set = |name: &str, value: String| {
+        variables.insert(name.to_string(), value);
+    };
+    set("hostname", crate::utils::hostname().unwrap_or_default());
+    set("os", std::env::consts::OS.to_string());
+    set("arch", std::env::consts::ARCH.to_string());
+    set(
+        "username",
+        std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_default(),
+    );
+    set("home", config.home_dir.to_string_lossy().into_owned());
+    set("profile", config.profile.clone().unwrap_or_default());
+    set("name", config.author_name.clone().unwrap_or_default());
+    set("email", config.author_email.clone().unwrap_or_default());
+
+    variables.extend(config.variables.clone());
+
+    variables
+}
+
+/// Render a template, replacing `{{ name }}` with the variable's value
+///
+/// Unknown variables and unclosed tags are errors, so a typo never ends up
+/// in a restored file.
+pub fn render(template: &str, variables: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+
+        let tag = &rest[start + 2..];
+        let end = tag.find("}}").ok_or_else(|| {
+            let line = template.len() - rest.len() + start;
+            format!(
+                "unclosed {{{{ on line {}",
+                template[..line].matches('\n').count() + 1
+            )
+        })?;
+
+        let name = tag[..end].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| format!("unknown variable {:?}", name))?;
+        output.push_str(value);
+
+        rest = &tag[end + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let variables = BTreeMap::from([
+            ("email".to_string(), "me@example.com".to_string()),
+            ("hostname".to_string(), "laptop".to_string()),
+        ]);
+
+        assert_eq!(
+            render(
+                "[user]\n  email = {{ email }}\n# {{hostname}}\n",
+                &variables
+            )
+            .unwrap(),
+            "[user]\n  email = me@example.com\n# laptop\n"
+        );
+        assert!(render("{{ missing }}", &variables).is_err());
+        assert_eq!(
+            render("ok\n{{ email", &variables).unwrap_err(),
+            "unclosed {{ on line 2"
+        );
+    }
+
+    #[test]
+    fn test_template_paths() {
+        assert!(is_template(Path::new(".gitconfig.tmpl")));
+        assert!(!is_template(Path::new(".gitconfig")));
+        assert_eq!(
+            template_path(Path::new(".config/app.toml")),
+            PathBuf::from(".config/app.toml.tmpl")
+        );
+        assert_eq!(
+            rendered_path(Path::new(".gitconfig.tmpl")),
+            PathBuf::from(".gitconfig")
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use log::{debug, info};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::utils::{shell_quote, write_file};
+use crate::{Config, DotfilesError};
+
+/// Name of the directory inside the vault holding toolchain snapshots
+pub const TOOLCHAINS_DIR: &str = "toolchains";
+
+/// Header written at the top of every snapshot file
+const SNAPSHOT_HEADER: &str =
+    "# Generated by dotfilesvault, see `dotfilesvault toolchains hints` to reinstall";
+
+/// A language toolchain or package source whose state can be captured
+#[derive(Debug, Clone, Copy)]
+pub struct Toolchain {
+    /// Name of the toolchain, also the snapshot file name
+    pub name: &'static str,
+
+    /// Command printing the installed items
+    pub command: &'static [&'static str],
+
+    /// Command reinstalling one item, with `{}` standing for the item
+    pub install: &'static str,
+
+    /// Extract an item from a line of the command output
+    pub parse: fn(&str) -> Option<String>,
+}
+
+/// Toolchains dotfilesvault knows how to capture
+pub const TOOLCHAINS: &[Toolchain] = &[
+    Toolchain {
+        name: "rustup",
+        command: &["rustup", "toolchain", "list"],
+        install: "rustup toolchain install {}",
+        parse: first_word,
+    },
+    Toolchain {
+        name: "pyenv",
+        command: &["pyenv", "versions", "--bare"],
+        install: "pyenv install {}",
+        parse: first_word,
+    },
+    Toolchain {
+        name: "nvm",
+        command: &[
+            "sh",
+            "-c",
+            ". \"${NVM_DIR:-$HOME/.nvm}/nvm.sh\" && nvm ls --no-colors",
+        ],
+        install: "nvm install {}",
+        parse: parse_nvm_line,
+    },
+    Toolchain {
+        name: "homebrew-taps",
+        command: &["brew", "tap"],
+        install: "brew tap {}",
+        parse: first_word,
+    },
+    Toolchain {
+        name: "macports",
+        command: &["port", "-q", "installed", "requested"],
+        install: "sudo port install {}",
+        parse: first_word,
+    },
+];
+
+/// The captured state of a toolchain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainSnapshot {
+    /// Name of the toolchain
+    pub name: String,
+
+    /// Installed items, e.g. toolchain names or versions
+    pub items: Vec<String>,
+}
+
+impl Toolchain {
+    /// Run the toolchain's command and parse the installed items
+    ///
+    /// Returns `None` if the toolchain isn't installed or its command fails.
+    pub fn capture(&self) -> Option<ToolchainSnapshot> {
+        let (program, args) = self.command.split_first()?;
+        let output = Command::new(program).args(args).output().ok()?;
+
+        if !output.status.success() {
+            debug!("Skipping {}: command failed", self.name);
+            return None;
+        }
+
+        let items = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(self.parse)
+            .collect();
+
+        Some(ToolchainSnapshot {
+            name: self.name.to_string(),
+            items,
+        })
+    }
+
+    /// Get the command reinstalling an item
+    ///
+    /// Items are read from the vault, so anything but a plain name or
+    /// version is shell-quoted.
+    pub fn install_command(&self, item: &str) -> String {
+        let plain = !item.is_empty()
+            && item
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-+@/:=".contains(c));
+        if plain {
+            self.install.replace("{}", item)
+        } else {
+            self.install.replace("{}", &shell_quote(item))
+        }
+    }
+}
+
+/// Find a known toolchain by name
+pub fn find_toolchain(name: &str) -> Option<&'static Toolchain> {
+    TOOLCHAINS.iter().find(|toolchain| toolchain.name == name)
+}
+
+/// Get the path of a toolchain's snapshot file in the vault
+pub fn snapshot_path(config: &Config, name: &str) -> PathBuf {
+    config
+        .vault_dir
+        .join(TOOLCHAINS_DIR)
+        .join(format!("{}.txt", name))
+}
+
+/// Capture every installed toolchain into snapshot files in the vault
+pub fn capture_toolchains(config: &Config) -> Result<Vec<ToolchainSnapshot>, DotfilesError> {
+    let mut snapshots = Vec::new();
+    for toolchain in TOOLCHAINS {
+        let Some(snapshot) = toolchain.capture() else {
+            continue;
+        };
+
+        write_snapshot(config, &snapshot)?;
+        info!("Captured {} {} items", snapshot.items.len(), snapshot.name);
+        snapshots.push(snapshot);
+    }
+
+    Ok(snapshots)
+}
+
+/// Write a snapshot file into the vault
+pub fn write_snapshot(config: &Config, snapshot: &ToolchainSnapshot) -> Result<(), DotfilesError> {
+    let mut content = format!("{}\n", SNAPSHOT_HEADER);
+    for item in &snapshot.items {
+        content.push_str(item);
+        content.push('\n');
+    }
+
+    let path = snapshot_path(config, &snapshot.name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(&path, content.as_bytes())?;
+
+    Ok(())
+}
+
+/// Read the snapshots stored in the vault
+pub fn read_snapshots(config: &Config) -> Result<Vec<ToolchainSnapshot>, DotfilesError> {
+    let mut snapshots = Vec::new();
+
+    for toolchain in TOOLCHAINS {
+        let path = snapshot_path(config, toolchain.name);
+        if !path.is_file() {
+            continue;
+        }
+
+        let items = fs::read_to_string(&path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+
+        snapshots.push(ToolchainSnapshot {
+            name: toolchain.name.to_string(),
+            items,
+        });
+    }
+
+    Ok(snapshots)
+}
+
+/// Get the commands reinstalling every item in the stored snapshots
+pub fn reinstall_hints(config: &Config) -> Result<Vec<String>, DotfilesError> {
+    let mut hints = Vec::new();
+
+    for snapshot in read_snapshots(config)? {
+        let Some(toolchain) = find_toolchain(&snapshot.name) else {
+            continue;
+        };
+
+        hints.extend(
+            snapshot
+                .items
+                .iter()
+                .map(|item| toolchain.install_command(item)),
+        );
+    }
+
+    Ok(hints)
+}
+
+/// Take the first word of a line, skipping blank lines and pyenv's `system`
+fn first_word(line: &str) -> Option<String> {
+    match line.split_whitespace().next() {
+        Some("system") | None => None,
+        Some(word) => Some(word.to_string()),
+    }
+}
+
+/// Parse an installed version from `nvm ls`, e.g. `->     v18.17.0 *`
+fn parse_nvm_line(line: &str) -> Option<String> {
+    let word = line.trim_start_matches("->").split_whitespace().next()?;
+
+    // Alias lines such as `default -> 18 (-> v18.17.0)` are not installs
+    (word.starts_with('v') && word[1..].starts_with(|c: char| c.is_ascii_digit()))
+        .then(|| word.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(
+            first_word("stable-x86_64-unknown-linux-gnu (default)"),
+            Some("stable-x86_64-unknown-linux-gnu".to_string())
+        );
+        assert_eq!(first_word("system"), None);
+        assert_eq!(
+            parse_nvm_line("->     v18.17.0 *"),
+            Some("v18.17.0".to_string())
+        );
+        assert_eq!(parse_nvm_line("default -> 18 (-> v18.17.0)"), None);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_hints() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+
+        let snapshot = ToolchainSnapshot {
+            name: "pyenv".to_string(),
+            items: vec!["3.11.4".to_string(), "3.12.0".to_string()],
+        };
+        write_snapshot(&config, &snapshot).unwrap();
+
+        assert_eq!(read_snapshots(&config).unwrap(), vec![snapshot]);
+        assert_eq!(
+            reinstall_hints(&config).unwrap(),
+            vec!["pyenv install 3.11.4", "pyenv install 3.12.0"]
+        );
+
+        let pyenv = find_toolchain("pyenv").unwrap();
+        assert_eq!(
+            pyenv.install_command("3.12; rm -rf ~"),
+            "pyenv install '3.12; rm -rf ~'"
+        );
+        assert_eq!(pyenv.install_command("it's"), "pyenv install 'it'\\''s'");
+    }
+}
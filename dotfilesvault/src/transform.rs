@@ -0,0 +1,188 @@
+use anyhow::Result;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, DotfilesError};
+
+/// Name of the directory inside the vault holding WASM transform plugins
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// Fuel available to a single transform call, bounding its running time
+#[cfg(feature = "wasm")]
+const WASM_FUEL: u64 = 5_000_000_000;
+
+/// Maximum linear memory a transform may use
+#[cfg(feature = "wasm")]
+const WASM_MAX_MEMORY: usize = 256 * 1024 * 1024;
+
+/// Direction in which a transform is applied
+///
+/// Passed to the plugin's `transform` export as `0` for backup and `1` for
+/// restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformDirection {
+    /// Home file content is being written to the vault
+    Backup = 0,
+
+    /// Vault content is being written to the home directory
+    Restore = 1,
+}
+
+/// Get the path of a transform plugin in the vault
+pub fn transform_path(config: &Config, name: &str) -> PathBuf {
+    config
+        .vault_dir
+        .join(PLUGINS_DIR)
+        .join(format!("{}.wasm", name))
+}
+
+/// Apply the named transform plugin to file content
+///
+/// Plugins are WebAssembly modules stored as `<vault>/plugins/<name>.wasm`.
+/// They run without any imports, so they cannot touch the filesystem, the
+/// network or the environment. A plugin must export:
+///
+/// - `memory`: its linear memory
+/// - `alloc(len: i32) -> i32`: reserve `len` bytes for the input
+/// - `transform(direction: i32, ptr: i32, len: i32) -> i64`: transform the
+///   input and return `(out_ptr << 32) | out_len`, or a negative value on error
+pub fn apply_transform(
+    config: &Config,
+    name: &str,
+    direction: TransformDirection,
+    input: &[u8],
+) -> Result<Vec<u8>, DotfilesError> {
+    let path = transform_path(config, name);
+
+    if !path.is_file() {
+        return Err(DotfilesError::TransformNotFound(name.to_string()));
+    }
+
+    debug!("Applying transform {} ({:?})", name, direction);
+
+    run_wasm_transform(&path, direction, input)
+        .map_err(|err| DotfilesError::TransformFailed(name.to_string(), err))
+}
+
+#[cfg(feature = "wasm")]
+fn run_wasm_transform(
+    path: &Path,
+    direction: TransformDirection,
+    input: &[u8],
+) -> Result<Vec<u8>, String> {
+    use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+    let mut wasm_config = wasmtime::Config::new();
+    wasm_config.consume_fuel(true);
+
+    let engine = Engine::new(&wasm_config).map_err(|err| err.to_string())?;
+    let module = Module::from_file(&engine, path).map_err(|err| err.to_string())?;
+
+    let limits: StoreLimits = StoreLimitsBuilder::new()
+        .memory_size(WASM_MAX_MEMORY)
+        .build();
+    let mut store = Store::new(&engine, limits);
+    store.limiter(|limits| limits);
+    store.set_fuel(WASM_FUEL).map_err(|err| err.to_string())?;
+
+    // No imports are provided, which is what keeps the plugin sandboxed
+    let instance = Instance::new(&mut store, &module, &[]).map_err(|err| err.to_string())?;
+
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or("plugin does not export `memory`")?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut store, "alloc")
+        .map_err(|err| err.to_string())?;
+    let transform = instance
+        .get_typed_func::<(i32, i32, i32), i64>(&mut store, "transform")
+        .map_err(|err| err.to_string())?;
+
+    let len = i32::try_from(input.len()).map_err(|_| "input is too large")?;
+    let ptr = alloc.call(&mut store, len).map_err(|err| err.to_string())?;
+    memory
+        .write(&mut store, ptr as u32 as usize, input)
+        .map_err(|err| err.to_string())?;
+
+    let packed = transform
+        .call(&mut store, (direction as i32, ptr, len))
+        .map_err(|err| err.to_string())?;
+
+    if packed < 0 {
+        return Err(format!("plugin returned error code {}", packed));
+    }
+
+    let out_ptr = (packed >> 32) as u32 as usize;
+    let out_len = (packed & 0xffff_ffff) as u32 as usize;
+
+    let mut output = vec![0u8; out_len];
+    memory
+        .read(&store, out_ptr, &mut output)
+        .map_err(|err| err.to_string())?;
+
+    Ok(output)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run_wasm_transform(
+    _path: &Path,
+    _direction: TransformDirection,
+    _input: &[u8],
+) -> Result<Vec<u8>, String> {
+    Err("dotfilesvault was built without WASM support (enable the `wasm` feature)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_apply_missing_transform() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+
+        assert!(matches!(
+            apply_transform(&config, "missing", TransformDirection::Backup, b"data"),
+            Err(DotfilesError::TransformNotFound(_))
+        ));
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_apply_wasm_transform() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        std::fs::create_dir_all(config.vault_dir.join(PLUGINS_DIR)).unwrap();
+
+        // Drops the first byte of the input on backup, keeps it on restore
+        let module = r#"
+            (module
+              (memory (export "memory") 1)
+              (global $next (mut i32) (i32.const 1024))
+              (func (export "alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get $len)))
+                (local.get $ptr))
+              (func (export "transform") (param $dir i32) (param $ptr i32) (param $len i32) (result i64)
+                (if (i32.eqz (local.get $dir))
+                  (then
+                    (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+                    (local.set $len (i32.sub (local.get $len) (i32.const 1)))))
+                (i64.or
+                  (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                  (i64.extend_i32_u (local.get $len)))))
+        "#;
+        std::fs::write(transform_path(&config, "strip"), module).unwrap();
+
+        assert_eq!(
+            apply_transform(&config, "strip", TransformDirection::Backup, b"xdata").unwrap(),
+            b"data"
+        );
+        assert_eq!(
+            apply_transform(&config, "strip", TransformDirection::Restore, b"xdata").unwrap(),
+            b"xdata"
+        );
+    }
+}
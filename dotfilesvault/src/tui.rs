@@ -0,0 +1,363 @@
+use anyhow::Result;
+use log::LevelFilter;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::backup::backup_specific_dotfiles;
+use crate::diff::diff_files;
+use crate::history::get_dotfile_history;
+use crate::plan::PlannedAction;
+use crate::restore::{list_backed_up_dotfiles, restore_specific_dotfile};
+use crate::status::{FileStatus, get_status};
+use crate::utils::format_timestamp;
+use crate::{Config, DotfilesError, t};
+
+/// What the panel next to the file list shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// Versions of the selected file in the vault
+    History,
+
+    /// Differences between the vault and the home copy of the selected file
+    Diff,
+}
+
+/// A tracked dotfile and how it differs from the home directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedFile {
+    /// Path relative to the home directory
+    pub path: PathBuf,
+
+    /// How the home copy differs, or `None` if it's up to date
+    pub status: Option<FileStatus>,
+}
+
+/// State of the terminal UI
+///
+/// Kept apart from drawing so key handling works without a terminal.
+pub struct App<'a> {
+    config: &'a Config,
+
+    /// Tracked dotfiles, sorted by path
+    pub files: Vec<TrackedFile>,
+
+    /// Index of the selected file
+    pub selected: usize,
+
+    /// What the side panel shows
+    pub panel: Panel,
+
+    /// Lines of the side panel for the selected file
+    pub panel_lines: Vec<String>,
+
+    /// Outcome of the last action, shown above the key help
+    pub message: String,
+
+    /// Whether the user asked to quit
+    pub quit: bool,
+}
+
+impl<'a> App<'a> {
+    /// Load the tracked dotfiles of a vault
+    pub fn new(config: &'a Config) -> Result<Self, DotfilesError> {
+        let mut app = Self {
+            config,
+            files: Vec::new(),
+            selected: 0,
+            panel: Panel::History,
+            panel_lines: Vec::new(),
+            message: String::new(),
+            quit: false,
+        };
+        app.refresh()?;
+
+        Ok(app)
+    }
+
+    /// Reload the tracked files, their status and the side panel
+    pub fn refresh(&mut self) -> Result<(), DotfilesError> {
+        let statuses: HashMap<PathBuf, FileStatus> = get_status(self.config)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.status))
+            .collect();
+
+        let mut paths = list_backed_up_dotfiles(self.config)?;
+        paths.sort();
+        self.files = paths
+            .into_iter()
+            .map(|path| TrackedFile {
+                status: statuses.get(&path).copied(),
+                path,
+            })
+            .collect();
+
+        self.selected = self.selected.min(self.files.len().saturating_sub(1));
+        self.load_panel();
+
+        Ok(())
+    }
+
+    /// Get the path of the selected file, as given on the command line
+    fn selected_path(&self) -> Option<String> {
+        self.files
+            .get(self.selected)
+            .map(|file| file.path.display().to_string())
+    }
+
+    /// Fill the side panel for the selected file
+    fn load_panel(&mut self) {
+        let Some(path) = self.selected_path() else {
+            self.panel_lines = Vec::new();
+            return;
+        };
+
+        let lines = match self.panel {
+            Panel::History => get_dotfile_history(self.config, &path, 0, None).map(|versions| {
+                versions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, version)| {
+                        format!(
+                            "v{}  {}  {} - {}",
+                            i + 1,
+                            &version.commit_id[..7],
+                            format_timestamp(&version.timestamp, self.config),
+                            version.message.lines().next().unwrap_or("")
+                        )
+                    })
+                    .collect()
+            }),
+            Panel::Diff => diff_files(self.config, &[path], true).map(|diffs| {
+                diffs
+                    .iter()
+                    .flat_map(|(_, diff)| diff.lines().map(str::to_string))
+                    .collect()
+            }),
+        };
+
+        self.panel_lines = lines.unwrap_or_else(|err| vec![err.to_string()]);
+    }
+
+    /// Back up the selected file and commit it
+    fn backup_selected(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+
+        let files = [path.clone()];
+        let result = backup_specific_dotfiles(self.config, &files).and_then(|plan| {
+            plan.commit(
+                self.config,
+                &format!("Backup specific dotfiles: {:?}", files),
+            )
+        });
+
+        self.message = match result {
+            Ok(plan) if !plan.secrets.is_empty() => t!(
+                "tui-backup-refused",
+                path = path.as_str(),
+                count = plan.secrets.len()
+            ),
+            Ok(_) => t!("tui-backed-up", path = path.as_str()),
+            Err(err) => t!("failed-backup-dotfiles", error = err.to_string()),
+        };
+    }
+
+    /// Restore the selected file to the home directory
+    fn restore_selected(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+
+        self.message = match restore_specific_dotfile(self.config, &path) {
+            Ok(plan) => match plan
+                .copies
+                .iter()
+                .find(|copy| copy.action == PlannedAction::Quarantine)
+            {
+                Some(copy) => t!(
+                    "restore-kept-local-edits-to",
+                    file = path.as_str(),
+                    quarantined = copy.destination.display().to_string()
+                ),
+                None => t!("tui-restored", path = path.as_str()),
+            },
+            Err(err) => t!("failed-restore-dotfile", error = err.to_string()),
+        };
+    }
+
+    /// Reload after an action, showing errors instead of the action's outcome
+    fn refresh_or_report(&mut self) {
+        if let Err(err) = self.refresh() {
+            self.message = err.to_string();
+        }
+    }
+
+    /// React to a key press
+    pub fn handle_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') if self.selected + 1 < self.files.len() => {
+                self.selected += 1;
+                self.load_panel();
+            }
+            KeyCode::Up | KeyCode::Char('k') if self.selected > 0 => {
+                self.selected -= 1;
+                self.load_panel();
+            }
+            KeyCode::Char('h') => {
+                self.panel = Panel::History;
+                self.load_panel();
+            }
+            KeyCode::Char('d') => {
+                self.panel = Panel::Diff;
+                self.load_panel();
+            }
+            KeyCode::Char('b') => {
+                self.backup_selected();
+                self.refresh_or_report();
+            }
+            KeyCode::Char('r') => {
+                self.restore_selected();
+                self.refresh_or_report();
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the file list, the side panel and the key help
+    pub fn draw(&self, frame: &mut Frame) {
+        let [main, message, help] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, panel_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .files
+            .iter()
+            .map(|file| {
+                let status = match file.status {
+                    Some(FileStatus::Modified) => t!("status-modified"),
+                    Some(FileStatus::New) => t!("status-new"),
+                    Some(FileStatus::Deleted) => t!("status-deleted"),
+                    None => t!("tui-status-clean"),
+                };
+                ListItem::new(format!("{}  ({})", file.path.display(), status))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(t!("tui-files-title", count = self.files.len())),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        let title = match self.panel {
+            Panel::History => t!("tui-history-title"),
+            Panel::Diff => t!("tui-diff-title"),
+        };
+        let lines: Vec<Line> = self
+            .panel_lines
+            .iter()
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+            panel_area,
+        );
+
+        frame.render_widget(Paragraph::new(self.message.as_str()), message);
+        frame.render_widget(Paragraph::new(t!("tui-help")), help);
+    }
+}
+
+/// Run the terminal UI until the user quits
+///
+/// Logging is silenced while the UI owns the terminal, so log lines don't
+/// garble the screen.
+pub fn run_tui(config: &Config) -> Result<(), DotfilesError> {
+    let mut app = App::new(config)?;
+
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Off);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    log::set_max_level(level);
+
+    result.map_err(DotfilesError::Io)
+}
+
+/// Draw the UI and handle key presses until the user quits
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            app.handle_key(key.code);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::init_git_repo;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_app_backup_and_navigation() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        init_git_repo(&config).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "alias ll='ls -l'\n").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "set number\n").unwrap();
+        backup_specific_dotfiles(&config, &[".bashrc".to_string(), ".vimrc".to_string()])
+            .and_then(|plan| plan.commit(&config, "Backup"))
+            .unwrap();
+
+        fs::write(config.home_dir.join(".vimrc"), "set nonumber\n").unwrap();
+        let mut app = App::new(&config).unwrap();
+        assert_eq!(app.files[0].path, PathBuf::from(".bashrc"));
+        assert_eq!(app.panel_lines.len(), 1);
+
+        let vimrc = app
+            .files
+            .iter()
+            .position(|file| file.path.as_os_str() == ".vimrc")
+            .unwrap();
+        for _ in 0..vimrc {
+            app.handle_key(KeyCode::Down);
+        }
+        app.handle_key(KeyCode::Char('d'));
+        assert_eq!(app.files[vimrc].status, Some(FileStatus::Modified));
+        assert!(app.panel_lines.iter().any(|line| line == "+set nonumber"));
+
+        app.handle_key(KeyCode::Char('b'));
+        assert_eq!(app.files[vimrc].status, None);
+        assert!(app.panel_lines.is_empty());
+
+        app.handle_key(KeyCode::Char('q'));
+        assert!(app.quit);
+    }
+}
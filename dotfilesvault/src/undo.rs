@@ -0,0 +1,393 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::lock::VaultLock;
+use crate::symlink::{copy_symlink, is_file_or_link};
+use crate::utils::{copy_file, write_file};
+use crate::{Config, DotfilesError};
+
+/// Directory in the vault holding the undo records
+pub const UNDO_DIR: &str = ".undo";
+
+/// Ignore file keeping the undo records out of vault commits
+const UNDO_GITIGNORE: &str = "*\n";
+
+/// Name of the file listing the files of an undo record
+const RECORD_FILE: &str = "record.json";
+
+/// Directory of an undo record holding the replaced home files
+const FILES_DIR: &str = "files";
+
+/// Number of undo records kept, older ones are deleted
+const KEEP_RECORDS: usize = 20;
+
+/// A home file a restore replaced or created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoFile {
+    /// Home-relative path of the file
+    pub path: PathBuf,
+
+    /// Whether the file existed before the restore, and was saved
+    pub existed: bool,
+
+    /// SHA-256 of what the restore left at the path (of the target for
+    /// links), if it got that far
+    #[serde(default)]
+    pub restored: Option<String>,
+}
+
+/// The home files the restores of a command replaced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoRecord {
+    /// ID of the record, from the time of the first restore
+    pub id: String,
+
+    /// When the first file was replaced
+    pub timestamp: DateTime<Local>,
+
+    /// Files replaced or created, in the order they were restored
+    pub files: Vec<UndoFile>,
+}
+
+/// Undo record shared by the restores of a command
+///
+/// Clones share the record, so every restore run with a configuration (or
+/// its clones) adds to the same one. It is created on the first file saved.
+#[derive(Debug, Clone, Default)]
+pub struct UndoLog {
+    record: Arc<Mutex<Option<UndoRecord>>>,
+}
+
+/// Get the directory of the undo records
+fn undo_dir(config: &Config) -> PathBuf {
+    config.vault_dir.join(UNDO_DIR)
+}
+
+/// Save a home file before a restore replaces it
+///
+/// The file is copied into the command's undo record (links as links), or
+/// noted as created if it doesn't exist yet, so `undo_last_restore` can put
+/// things back. Only the first version of a file is kept and files outside
+/// the home directory aren't recorded. Nothing is saved in a dry run.
+pub fn save_for_undo(config: &Config, path: &Path) -> Result<(), DotfilesError> {
+    if config.dry_run {
+        return Ok(());
+    }
+    let Ok(relative) = path.strip_prefix(&config.home_dir) else {
+        debug!(
+            "Not recording {:?} for undo, it is outside the home directory",
+            path
+        );
+        return Ok(());
+    };
+
+    let mut record = config
+        .undo
+        .record
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let record = match &mut *record {
+        Some(record) => record,
+        empty => empty.insert(new_record(config)?),
+    };
+    if record.files.iter().any(|file| file.path == relative) {
+        return Ok(());
+    }
+
+    let existed = is_file_or_link(path);
+    if existed {
+        let copy = undo_dir(config)
+            .join(&record.id)
+            .join(FILES_DIR)
+            .join(relative);
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.is_symlink() {
+            copy_symlink(path, &copy)?;
+        } else {
+            copy_file(path, &copy, None)?;
+        }
+    }
+    record.files.push(UndoFile {
+        path: relative.to_path_buf(),
+        existed,
+        restored: None,
+    });
+    write_record(config, record)?;
+
+    debug!("Saved {:?} for undo in {}", relative, record.id);
+    Ok(())
+}
+
+/// Note what a restore left at a home file saved with `save_for_undo`
+///
+/// `undo_last_restore` compares it with the home file, so edits made since
+/// the restore aren't lost. Files that weren't saved are ignored.
+pub fn record_restored(config: &Config, path: &Path) -> Result<(), DotfilesError> {
+    if config.dry_run {
+        return Ok(());
+    }
+    let Ok(relative) = path.strip_prefix(&config.home_dir) else {
+        return Ok(());
+    };
+
+    let mut record = config
+        .undo
+        .record
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let Some(record) = &mut *record else {
+        return Ok(());
+    };
+    let Some(file) = record.files.iter_mut().find(|file| file.path == relative) else {
+        return Ok(());
+    };
+    file.restored = home_hash(path)?;
+
+    write_record(config, record)
+}
+
+/// Write the list of files of an undo record
+fn write_record(config: &Config, record: &UndoRecord) -> Result<(), DotfilesError> {
+    let content = serde_json::to_vec_pretty(record).map_err(std::io::Error::from)?;
+    write_file(
+        undo_dir(config).join(&record.id).join(RECORD_FILE),
+        &content,
+    )?;
+
+    Ok(())
+}
+
+/// Get the SHA-256 of a home file, or of its target for links
+///
+/// Returns `None` if there's no file.
+fn home_hash(path: &Path) -> Result<Option<String>, DotfilesError> {
+    let content = if path.is_symlink() {
+        fs::read_link(path)?.into_os_string().into_encoded_bytes()
+    } else if path.is_file() {
+        fs::read(path)?
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(format!("{:x}", Sha256::digest(&content))))
+}
+
+/// Start an undo record, deleting the oldest ones past the limit
+fn new_record(config: &Config) -> Result<UndoRecord, DotfilesError> {
+    let dir = undo_dir(config);
+    fs::create_dir_all(&dir)?;
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        write_file(gitignore, UNDO_GITIGNORE.as_bytes())?;
+    }
+
+    let records = list_undo_records(config)?;
+    for record in records
+        .iter()
+        .take((records.len() + 1).saturating_sub(KEEP_RECORDS))
+    {
+        fs::remove_dir_all(dir.join(&record.id))?;
+    }
+
+    let timestamp = Local::now();
+    let base = timestamp.format("%Y%m%d-%H%M%S").to_string();
+    let mut id = base.clone();
+    let mut count = 1;
+    while dir.join(&id).exists() {
+        count += 1;
+        id = format!("{}-{}", base, count);
+    }
+    fs::create_dir_all(dir.join(&id))?;
+
+    Ok(UndoRecord {
+        id,
+        timestamp,
+        files: Vec::new(),
+    })
+}
+
+/// List the undo records, oldest first
+///
+/// Directories without a readable record are skipped.
+pub fn list_undo_records(config: &Config) -> Result<Vec<UndoRecord>, DotfilesError> {
+    let dir = undo_dir(config);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut records: Vec<UndoRecord> = fs::read_dir(&dir)?
+        .filter_map(|entry| {
+            let content = fs::read(entry.ok()?.path().join(RECORD_FILE)).ok()?;
+            serde_json::from_slice(&content).ok()
+        })
+        .collect();
+    records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.id.cmp(&b.id)));
+
+    Ok(records)
+}
+
+/// Revert the most recent restore
+///
+/// Home files the restore replaced are put back and files it created are
+/// deleted, then the record is dropped, so running it again reverts the
+/// restore before. Fails with `NothingToUndo` if no restore was recorded, and
+/// with `ChangedSinceRestore`, before changing anything, if a file no longer
+/// holds what the restore left there, unless `force` is set. In a dry run
+/// nothing is changed.
+pub fn undo_last_restore(config: &Config, force: bool) -> Result<UndoRecord, DotfilesError> {
+    let _lock = VaultLock::acquire(config)?;
+    let record = list_undo_records(config)?
+        .pop()
+        .ok_or(DotfilesError::NothingToUndo)?;
+    if !force {
+        for file in &record.files {
+            let home = config.home_dir.join(&file.path);
+            if file.restored.is_some() && home_hash(&home)? != file.restored {
+                return Err(DotfilesError::ChangedSinceRestore(
+                    home.display().to_string(),
+                ));
+            }
+        }
+    }
+    if config.dry_run {
+        return Ok(record);
+    }
+
+    let dir = undo_dir(config).join(&record.id);
+    for file in &record.files {
+        let home = config.home_dir.join(&file.path);
+        if is_file_or_link(&home) {
+            fs::remove_file(&home)?;
+        }
+        if !file.existed {
+            info!("Removed {:?}, the restore created it", home);
+            continue;
+        }
+
+        let copy = dir.join(FILES_DIR).join(&file.path);
+        if let Some(parent) = home.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if copy.is_symlink() {
+            copy_symlink(&copy, &home)?;
+        } else {
+            copy_file(&copy, &home, None)?;
+        }
+        info!("Put back {:?}", home);
+    }
+    fs::remove_dir_all(&dir)?;
+
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::conflicts::ConflictPolicy;
+    #[cfg(feature = "git")]
+    use crate::history::{commit_changes, has_uncommitted_changes};
+    use crate::restore::{restore_all_dotfiles, restore_specific_dotfile};
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_undo_last_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.on_conflict = ConflictPolicy::Overwrite;
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+        assert!(matches!(
+            undo_last_restore(&config, false),
+            Err(DotfilesError::NothingToUndo)
+        ));
+
+        fs::write(config.home_dir.join(".bashrc"), "local edits").unwrap();
+        fs::remove_file(config.home_dir.join(".vimrc")).unwrap();
+        restore_all_dotfiles(&config).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "bash"
+        );
+        // The records stay out of the vault's commits
+        assert!(config.vault_dir.join(UNDO_DIR).is_dir());
+        assert!(!has_uncommitted_changes(&config).unwrap());
+
+        // The edits come back and the file the restore created goes away
+        let record = undo_last_restore(&config, false).unwrap();
+        assert_eq!(record.files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "local edits"
+        );
+        assert!(!config.home_dir.join(".vimrc").exists());
+        assert!(list_undo_records(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_undo_keeps_changes_since_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.on_conflict = ConflictPolicy::Overwrite;
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+
+        fs::write(config.home_dir.join(".bashrc"), "local edits").unwrap();
+        restore_all_dotfiles(&config).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "edited after").unwrap();
+
+        // Nothing is touched unless forced
+        assert!(matches!(
+            undo_last_restore(&config, false),
+            Err(DotfilesError::ChangedSinceRestore(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "edited after"
+        );
+        assert_eq!(list_undo_records(&config).unwrap().len(), 1);
+
+        undo_last_restore(&config, true).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "local edits"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_undo_without_git() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        config.on_conflict = ConflictPolicy::Overwrite;
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::create_dir_all(&config.vault_dir).unwrap();
+        fs::write(config.vault_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "local edits").unwrap();
+
+        restore_specific_dotfile(&config, ".bashrc").unwrap();
+        assert!(!config.vault_dir.join(".git").exists());
+        assert_eq!(list_undo_records(&config).unwrap().len(), 1);
+
+        undo_last_restore(&config, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.home_dir.join(".bashrc")).unwrap(),
+            "local edits"
+        );
+    }
+}
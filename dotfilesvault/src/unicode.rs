@@ -0,0 +1,293 @@
+use anyhow::Result;
+#[cfg(feature = "git")]
+use git2::Repository;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+#[cfg(feature = "git")]
+use crate::history::commit_changes;
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::restore::list_backed_up_dotfiles;
+use crate::utils::remove_empty_parents;
+use crate::{Config, DotfilesError};
+
+/// Put a path into the composed Unicode form (NFC) the vault stores paths in
+///
+/// macOS hands out file names decomposed (NFD, `e` followed by a combining
+/// accent), Linux keeps them as written, which is almost always composed.
+/// Components that aren't valid UTF-8 are kept as they are.
+pub fn nfc_path(path: &Path) -> PathBuf {
+    if is_nfc_path(path) {
+        return path.to_path_buf();
+    }
+
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => match name.to_str() {
+                Some(name) => name.nfc().collect::<String>().into(),
+                None => name.to_os_string(),
+            },
+            other => other.as_os_str().to_os_string(),
+        })
+        .collect()
+}
+
+/// Check if a path is already in composed form, or isn't valid UTF-8
+pub fn is_nfc_path(path: &Path) -> bool {
+    path.to_str().is_none_or(is_nfc)
+}
+
+/// Outcome of moving a vault's paths to composed form
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NormalizeSummary {
+    /// Vault files renamed, as their new vault-relative paths
+    pub renamed: Vec<PathBuf>,
+
+    /// Vault files stored under both forms, of which the newer copy was kept
+    pub merged: Vec<PathBuf>,
+
+    /// Manifest entries whose paths changed
+    pub entries: usize,
+
+    /// The commit recording the changes, if there were any
+    pub commit: Option<String>,
+}
+
+impl NormalizeSummary {
+    /// Check if the vault was already in composed form
+    pub fn is_empty(&self) -> bool {
+        self.renamed.is_empty() && self.merged.is_empty() && self.entries == 0
+    }
+}
+
+/// Move a vault's file names and manifest paths to composed form
+///
+/// Vaults backed up from macOS and Linux can hold the same dotfile under
+/// both forms. Decomposed files are renamed; when both forms are stored,
+/// the more recently modified copy is kept. Manifest entries are merged the
+/// same way, keeping the settings of the composed one, and the vault
+/// repository is set to precompose the names git reads on macOS. With
+/// `config.dry_run` set nothing is changed.
+#[cfg(feature = "git")]
+pub fn normalize_vault(config: &Config) -> Result<NormalizeSummary, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+    let mut summary = NormalizeSummary::default();
+
+    {
+        let _lock = VaultLock::acquire(config)?;
+
+        let files = list_backed_up_dotfiles(config)?;
+        let stored: HashSet<&PathBuf> = files.iter().collect();
+        for path in files.iter().filter(|path| !is_nfc_path(path)) {
+            let composed = nfc_path(path);
+            if stored.contains(&composed) {
+                summary.merged.push(composed.clone());
+                if !config.dry_run {
+                    merge_file(config, path, &composed)?;
+                }
+            } else {
+                summary.renamed.push(composed.clone());
+                if !config.dry_run {
+                    rename_file(config, path, &composed)?;
+                }
+            }
+        }
+
+        let mut manifest = Manifest::load(config)?;
+        summary.entries = normalize_manifest(&mut manifest);
+        if summary.entries > 0 && !config.dry_run {
+            manifest.save(config)?;
+        }
+
+        if !config.dry_run {
+            repo.config()?.set_bool("core.precomposeunicode", true)?;
+        }
+    }
+
+    if !summary.is_empty() && !config.dry_run {
+        summary.commit = Some(commit_changes(config, "Normalize Unicode paths")?);
+    }
+    info!(
+        "Normalized {} vault files and {} manifest entries",
+        summary.renamed.len() + summary.merged.len(),
+        summary.entries
+    );
+
+    Ok(summary)
+}
+
+/// Put a manifest's paths into composed form, merging entries that collide
+///
+/// Returns the number of entries that changed or were merged away.
+#[cfg(feature = "git")]
+fn normalize_manifest(manifest: &mut Manifest) -> usize {
+    let mut changed = 0;
+    let mut entries: BTreeMap<String, DotfileEntry> = BTreeMap::new();
+
+    // Composed entries go first, so their settings win
+    let (composed, decomposed): (Vec<DotfileEntry>, Vec<DotfileEntry>) = manifest
+        .entries
+        .values()
+        .cloned()
+        .partition(|entry| is_nfc_path(Path::new(&entry.id)));
+    for mut entry in composed.into_iter().chain(decomposed) {
+        let id = entry.id.nfc().collect::<String>();
+        let source = nfc_path(&entry.source);
+        let target = nfc_path(&entry.target);
+        if id != entry.id || source != entry.source || target != entry.target {
+            changed += 1;
+        }
+        entry.id = id;
+        entry.source = source;
+        entry.target = target;
+
+        if entries.contains_key(&entry.id) {
+            debug!("Merged manifest entry {} into its composed form", entry.id);
+        } else {
+            entries.insert(entry.id.clone(), entry);
+        }
+    }
+    manifest.entries = entries;
+
+    let directories = manifest
+        .directories
+        .iter()
+        .map(|dir| nfc_path(dir))
+        .collect();
+    if directories != manifest.directories {
+        changed += 1;
+        manifest.directories = directories;
+    }
+
+    changed
+}
+
+/// Rename a decomposed vault file to its composed form
+///
+/// Goes through a temporary name, since filesystems that ignore the
+/// difference between the forms would take the rename for a no-op.
+#[cfg(feature = "git")]
+fn rename_file(config: &Config, from: &Path, to: &Path) -> Result<(), DotfilesError> {
+    let from_path = config.vault_dir.join(from);
+    let to_path = config.vault_dir.join(to);
+    let temp_path =
+        from_path.with_file_name(format!(".dotfilesvault-normalize-{}", std::process::id()));
+
+    fs::rename(&from_path, &temp_path)?;
+    if let Some(parent) = to_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&temp_path, &to_path)?;
+    remove_empty_parents(&config.vault_dir, &from_path);
+
+    debug!("Renamed {:?} to {:?}", from, to);
+    Ok(())
+}
+
+/// Keep the newer of a file stored under both forms, under the composed one
+#[cfg(feature = "git")]
+fn merge_file(config: &Config, decomposed: &Path, composed: &Path) -> Result<(), DotfilesError> {
+    let decomposed_path = config.vault_dir.join(decomposed);
+    let composed_path = config.vault_dir.join(composed);
+
+    let modified = |path: &Path| fs::symlink_metadata(path).and_then(|meta| meta.modified());
+    if modified(&decomposed_path)? > modified(&composed_path)? {
+        warn!("Keeping {:?}, the newer copy of {:?}", decomposed, composed);
+        fs::rename(&decomposed_path, &composed_path)?;
+    } else {
+        warn!("Keeping {:?}, the newer copy of {:?}", composed, decomposed);
+        fs::remove_file(&decomposed_path)?;
+    }
+    remove_empty_parents(&config.vault_dir, &decomposed_path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use crate::history::init_git_repo;
+    use tempfile::TempDir;
+
+    const COMPOSED: &str = "caf\u{e9}";
+    const DECOMPOSED: &str = "cafe\u{301}";
+
+    #[test]
+    fn test_nfc_path() {
+        let path = Path::new(".config").join(DECOMPOSED).join("rc");
+        assert!(!is_nfc_path(&path));
+        assert_eq!(
+            nfc_path(&path),
+            Path::new(".config").join(COMPOSED).join("rc")
+        );
+        assert!(is_nfc_path(&nfc_path(&path)));
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn test_normalize_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+
+        // The same directory from Linux and macOS, and a file only from macOS
+        let composed_dir = config.vault_dir.join(".config").join(COMPOSED);
+        let decomposed_dir = config.vault_dir.join(".config").join(DECOMPOSED);
+        fs::create_dir_all(&composed_dir).unwrap();
+        fs::create_dir_all(&decomposed_dir).unwrap();
+        fs::write(composed_dir.join("rc"), "old").unwrap();
+        fs::write(decomposed_dir.join("rc"), "new").unwrap();
+        fs::write(decomposed_dir.join("extra"), "extra").unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(composed_dir.join("rc"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let mut manifest = Manifest::default();
+        for dir in [COMPOSED, DECOMPOSED] {
+            let dotfile = crate::backup::Dotfile::new(
+                config.home_dir.join(".config").join(dir).join("rc"),
+                &config,
+            );
+            let mut entry = DotfileEntry::new(&dotfile, &config);
+            entry.source = Path::new(".config").join(dir).join("rc");
+            entry.id = format!(".config/{}/rc", dir);
+            entry.group = Some(dir.to_string());
+            manifest.entries.insert(entry.id.clone(), entry);
+        }
+        manifest.save(&config).unwrap();
+
+        let summary = normalize_vault(&config).unwrap();
+        assert_eq!(
+            summary.renamed,
+            vec![Path::new(".config").join(COMPOSED).join("extra")]
+        );
+        assert_eq!(
+            summary.merged,
+            vec![Path::new(".config").join(COMPOSED).join("rc")]
+        );
+        assert!(summary.commit.is_some());
+
+        assert_eq!(fs::read_to_string(composed_dir.join("rc")).unwrap(), "new");
+        assert!(composed_dir.join("extra").exists());
+        assert!(!decomposed_dir.exists());
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert_eq!(manifest.entries.len(), 1);
+        let entry = &manifest.entries[&format!(".config/{}/rc", COMPOSED)];
+        assert_eq!(entry.group.as_deref(), Some(COMPOSED));
+
+        // Running it again finds nothing to do
+        assert!(normalize_vault(&config).unwrap().is_empty());
+    }
+}
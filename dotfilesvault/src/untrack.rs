@@ -0,0 +1,336 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::backup::{Dotfile, VAULTIGNORE_FILE};
+use crate::compact::purge_history;
+use crate::history::commit_changes;
+use crate::lock::VaultLock;
+use crate::manifest::{DotfileEntry, Manifest};
+use crate::metadata::MetadataManifest;
+use crate::profile::PROFILES_DIR;
+use crate::symlink::is_file_or_link;
+use crate::utils::{normalize_path, remove_empty_parents};
+use crate::{Config, DotfilesError};
+
+/// Outcome of removing dotfiles from the vault
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UntrackSummary {
+    /// Home-relative paths of the dotfiles no longer tracked
+    pub removed: Vec<PathBuf>,
+
+    /// Vault-relative files deleted, including profile variants
+    pub deleted: Vec<PathBuf>,
+
+    /// Home-relative paths added to `.vaultignore`, as they are in tracked directories
+    pub ignored: Vec<PathBuf>,
+
+    /// The commit recording the removal
+    pub commit: Option<String>,
+
+    /// Number of past commits rewritten or dropped to purge the files
+    pub purged: usize,
+}
+
+/// Stop tracking dotfiles and delete them from the vault
+///
+/// `files` are home paths of tracked dotfiles, or of directories tracked
+/// with all their files. The home copies are left alone, except that adopted
+/// files linked into the vault get their content back as regular files. The
+/// stored copies and profile variants are deleted and the removal is
+/// committed, so restores no longer bring them back. Files inside a tracked
+/// directory are added to `.vaultignore` so later backups don't pick them up
+/// again.
+///
+/// The files stay in the vault history unless `purge_history` is set, in
+/// which case the history is rewritten without them, see `purge_history`.
+/// With `config.dry_run` set nothing is changed.
+pub fn untrack_dotfiles(
+    config: &Config,
+    files: &[String],
+    purge: bool,
+) -> Result<UntrackSummary, DotfilesError> {
+    let mut summary = UntrackSummary::default();
+
+    {
+        let _lock = VaultLock::acquire(config)?;
+        let mut manifest = Manifest::load(config)?;
+        let mut metadata = MetadataManifest::load(config)?;
+
+        let mut entries: Vec<DotfileEntry> = Vec::new();
+        for file in files {
+            let path = normalize_path(file, config);
+            let relative = path
+                .strip_prefix(&config.home_dir)
+                .unwrap_or(&path)
+                .to_path_buf();
+
+            if manifest.directories.remove(&relative) {
+                debug!("Stopped tracking directory {:?}", relative);
+                entries.extend(
+                    manifest
+                        .entries
+                        .values()
+                        .filter(|entry| entry.source.starts_with(&relative))
+                        .cloned(),
+                );
+                summary.removed.push(relative);
+                continue;
+            }
+
+            let entry = manifest.entry_for(&Dotfile::new(path.clone(), config), config);
+            let tracked = manifest.entries.contains_key(&entry.id);
+            if !tracked && !config.vault_dir.join(&entry.target).exists() {
+                return Err(DotfilesError::DotfileNotFound(file.clone()));
+            }
+            if manifest
+                .directories
+                .iter()
+                .any(|dir| relative.starts_with(dir))
+            {
+                summary.ignored.push(relative.clone());
+            }
+            summary.removed.push(relative);
+            entries.push(entry);
+        }
+
+        for entry in &entries {
+            // An adopted file's home copy would be left as a dangling link
+            if entry.links_to_vault(config) && !config.dry_run {
+                let dotfile = entry.dotfile(config);
+                fs::remove_file(&dotfile.original_path)?;
+                fs::copy(&dotfile.vault_path, &dotfile.original_path)?;
+                debug!("Replaced the link of {} with a copy", entry.id);
+            }
+            for stored in stored_paths(entry) {
+                let path = config.vault_dir.join(&stored);
+                if path.is_file() || path.is_symlink() {
+                    if !config.dry_run {
+                        fs::remove_file(&path)?;
+                        remove_empty_parents(&config.vault_dir, &path);
+                    }
+                    summary.deleted.push(stored);
+                }
+            }
+            manifest.entries.remove(&entry.id);
+            metadata.files.remove(&entry.id);
+        }
+
+        if config.dry_run {
+            return Ok(summary);
+        }
+
+        if !summary.ignored.is_empty() {
+            ignore_paths(config, &summary.ignored)?;
+        }
+        manifest.save(config)?;
+        metadata.save(config)?;
+    }
+
+    let names: Vec<String> = summary
+        .removed
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    summary.commit = Some(commit_changes(
+        config,
+        &format!("Remove {}", names.join(", ")),
+    )?);
+
+    if purge && !summary.deleted.is_empty() {
+        summary.purged = purge_history(config, &summary.deleted)?;
+    }
+
+    info!(
+        "Removed {} dotfiles, deleting {} vault files",
+        summary.removed.len(),
+        summary.deleted.len()
+    );
+
+    Ok(summary)
+}
+
+/// Find tracked dotfiles that were deleted from the home directory
+///
+/// The vault still holds them, so they would linger forever. Entries whose
+/// conditions don't hold on this machine aren't reported, nor is starter
+/// content, which restores create where it's missing. Returns home-relative
+/// paths, for `untrack_dotfiles`.
+pub fn find_orphaned_dotfiles(config: &Config) -> Result<Vec<PathBuf>, DotfilesError> {
+    let manifest = Manifest::load(config)?;
+
+    let mut orphaned: Vec<PathBuf> = manifest
+        .entries
+        .values()
+        .filter(|entry| entry.applies() && !entry.create_if_missing)
+        .filter(|entry| !is_file_or_link(&entry.dotfile(config).original_path))
+        .filter(|entry| {
+            stored_paths(entry)
+                .iter()
+                .any(|stored| is_file_or_link(&config.vault_dir.join(stored)))
+        })
+        .map(|entry| entry.source.clone())
+        .collect();
+    orphaned.sort();
+
+    debug!("Found {} orphaned dotfiles", orphaned.len());
+    Ok(orphaned)
+}
+
+/// Get the vault-relative paths an entry is stored at, in common and profile variants
+fn stored_paths(entry: &DotfileEntry) -> Vec<PathBuf> {
+    let mut paths = vec![entry.target.clone()];
+    for profile in &entry.profiles {
+        paths.push(Path::new(PROFILES_DIR).join(profile).join(&entry.target));
+    }
+
+    paths
+}
+
+/// Add home-relative paths to the vault's `.vaultignore`
+fn ignore_paths(config: &Config, paths: &[PathBuf]) -> Result<(), DotfilesError> {
+    let path = config.vault_dir.join(VAULTIGNORE_FILE);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        writeln!(file)?;
+    }
+    for relative in paths {
+        let pattern = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(file, "/{}", pattern)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{backup_all_dotfiles, backup_specific_dotfiles};
+    use crate::history::init_git_repo;
+    use crate::restore::restore_all_dotfiles;
+    use crate::snapshot::{create_snapshot, restore_snapshot};
+    use tempfile::TempDir;
+
+    fn setup_test_env() -> (Config, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        init_git_repo(&config).unwrap();
+        fs::create_dir_all(config.home_dir.join(".config/app")).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".oldrc"), "token=1").unwrap();
+        fs::write(config.home_dir.join(".config/app/a"), "a").unwrap();
+        fs::write(config.home_dir.join(".config/app/b"), "b").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        backup_specific_dotfiles(&config, &[".config/app".to_string()]).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+
+        (config, temp_dir)
+    }
+
+    #[test]
+    fn test_untrack_dotfiles() {
+        let (config, _temp_dir) = setup_test_env();
+
+        let summary = untrack_dotfiles(
+            &config,
+            &[".oldrc".to_string(), ".config/app/a".to_string()],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            summary.deleted,
+            vec![PathBuf::from(".oldrc"), PathBuf::from(".config/app/a")]
+        );
+        assert_eq!(summary.ignored, vec![PathBuf::from(".config/app/a")]);
+        assert!(config.home_dir.join(".oldrc").exists());
+        assert!(!config.vault_dir.join(".oldrc").exists());
+
+        // Neither restores nor backups bring them back
+        fs::remove_file(config.home_dir.join(".oldrc")).unwrap();
+        restore_all_dotfiles(&config).unwrap();
+        assert!(!config.home_dir.join(".oldrc").exists());
+        backup_all_dotfiles(&config).unwrap();
+        assert!(!config.vault_dir.join(".config/app/a").exists());
+        assert!(config.vault_dir.join(".config/app/b").exists());
+
+        // The history still has them
+        let repo = git2::Repository::open(&config.vault_dir).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let before = head.parent(0).unwrap().tree().unwrap();
+        assert!(before.get_path(Path::new(".oldrc")).is_ok());
+
+        assert!(matches!(
+            untrack_dotfiles(&config, &[".missingrc".to_string()], false),
+            Err(DotfilesError::DotfileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_orphaned_dotfiles() {
+        let (config, _temp_dir) = setup_test_env();
+        assert!(find_orphaned_dotfiles(&config).unwrap().is_empty());
+
+        fs::remove_file(config.home_dir.join(".oldrc")).unwrap();
+        fs::remove_file(config.home_dir.join(".config/app/a")).unwrap();
+        let orphaned = find_orphaned_dotfiles(&config).unwrap();
+        assert_eq!(
+            orphaned,
+            vec![PathBuf::from(".config/app/a"), PathBuf::from(".oldrc")]
+        );
+
+        let names: Vec<String> = orphaned
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        untrack_dotfiles(&config, &names, false).unwrap();
+        assert!(find_orphaned_dotfiles(&config).unwrap().is_empty());
+        assert!(!config.vault_dir.join(".oldrc").exists());
+    }
+
+    #[test]
+    fn test_untrack_dotfiles_purges_history() {
+        let (config, _temp_dir) = setup_test_env();
+        create_snapshot(&config, "before", None).unwrap();
+        fs::write(config.home_dir.join(".oldrc"), "token=2").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Change oldrc").unwrap();
+
+        let summary = untrack_dotfiles(&config, &[".oldrc".to_string()], true).unwrap();
+        assert!(summary.purged > 0);
+
+        let repo = git2::Repository::open(&config.vault_dir).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        let mut messages = Vec::new();
+        for oid in walk {
+            let commit = repo.find_commit(oid.unwrap()).unwrap();
+            assert!(
+                commit
+                    .tree()
+                    .unwrap()
+                    .get_path(Path::new(".oldrc"))
+                    .is_err()
+            );
+            messages.push(commit.message().unwrap().to_string());
+        }
+        assert!(!messages.contains(&"Change oldrc".to_string()));
+
+        // The snapshot was moved along, and no longer has the file either
+        fs::remove_dir_all(&config.home_dir).unwrap();
+        restore_snapshot(&config, "before").unwrap();
+        assert!(config.home_dir.join(".bashrc").exists());
+        assert!(!config.home_dir.join(".oldrc").exists());
+    }
+}
@@ -1,19 +1,32 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::Config;
 
+/// Size of the buffer used for throttled copies
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Expand a tilde in a path to the home directory
+///
+/// On Windows `~\` works like `~/`.
 pub fn expand_tilde<P: AsRef<Path>>(path: P) -> PathBuf {
     let path_str = path.as_ref().to_string_lossy();
+    let rest = path_str
+        .strip_prefix("~/")
+        .or_else(|| path_str.strip_prefix("~\\").filter(|_| cfg!(windows)));
 
-    if path_str.starts_with("~/") || path_str == "~" {
-        if let Some(home_dir) = dirs::home_dir() {
-            if path_str == "~" {
-                return home_dir;
-            }
-
-            return home_dir.join(path_str.strip_prefix("~/").unwrap());
-        }
+    if (rest.is_some() || path_str == "~")
+        && let Some(home_dir) = dirs::home_dir()
+    {
+        return match rest {
+            Some(rest) => home_dir.join(rest),
+            None => home_dir,
+        };
     }
 
     path.as_ref().to_path_buf()
@@ -45,6 +58,267 @@ pub fn human_readable_size(size: u64) -> String {
     format!("{:.2} {}", size, UNITS[unit_index])
 }
 
+/// Check if a file looks binary, i.e. has a NUL byte in its first 8000
+/// bytes as git checks
+pub fn is_binary_file(path: &Path) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(8000);
+    File::open(path)?.take(8000).read_to_end(&mut head)?;
+
+    Ok(head.contains(&0))
+}
+
+/// Parse a human-readable size such as `512K`, `10M` or `1G` into bytes
+pub fn parse_size(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    number.checked_mul(multiplier)
+}
+
+/// Parse a human-readable duration such as `90`, `300ms`, `30s`, `10m`, `2h` or `7d`
+///
+/// A number without a unit is in seconds.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" => return Some(Duration::from_millis(number)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    number.checked_mul(multiplier).map(Duration::from_secs)
+}
+
+/// Copy a file, optionally limiting throughput to `bwlimit` bytes per second
+///
+/// The content is written to a temporary file next to `to`, synced to disk
+/// and renamed into place. Because the rename never crosses a filesystem
+/// boundary, this works when the vault and home live on different devices,
+/// and readers never observe a half-written file.
+pub fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    to: Q,
+    bwlimit: Option<u64>,
+) -> io::Result<u64> {
+    let to = to.as_ref();
+
+    // Write through symlinks instead of replacing them
+    let to = if to.is_symlink() {
+        fs::canonicalize(to).unwrap_or_else(|_| to.to_path_buf())
+    } else {
+        to.to_path_buf()
+    };
+
+    let temp_path = temp_path_for(&to);
+    let result = copy_contents(from.as_ref(), &temp_path, bwlimit).and_then(|copied| {
+        File::open(&temp_path)?.sync_all()?;
+        fs::rename(&temp_path, &to)?;
+        Ok(copied)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Write content to a file atomically via a temporary file next to it
+pub fn write_file<P: AsRef<Path>>(path: P, content: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp_path = temp_path_for(path);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Quote a value for a POSIX shell
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Get the hostname of this machine
+pub fn hostname() -> Option<String> {
+    hostname::get()
+        .ok()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Move a file, falling back to copy and delete across filesystems
+pub fn move_file<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    match fs::rename(from.as_ref(), to.as_ref()) {
+        Ok(()) => Ok(()),
+        Err(err) if is_cross_device(&err) => {
+            copy_file(from.as_ref(), to.as_ref(), None)?;
+            fs::remove_file(from.as_ref())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Remove the directories a removed file leaves empty, up to but not including `root`
+pub fn remove_empty_parents(root: &Path, path: &Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Check if an IO error was caused by an operation crossing filesystems (EXDEV)
+pub fn is_cross_device(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::CrossesDevices
+}
+
+/// Get a temporary path in the same directory as `path`
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Copy file content and permissions, throttling if a limit is set
+fn copy_contents(from: &Path, to: &Path, bwlimit: Option<u64>) -> io::Result<u64> {
+    let limit = match bwlimit {
+        Some(limit) if limit > 0 => limit,
+        _ => return fs::copy(from, to),
+    };
+
+    let mut reader = File::open(from)?;
+    let mut writer = File::create(to)?;
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE.min(limit as usize).max(1)];
+    let mut copied = 0u64;
+    let start = Instant::now();
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+
+        // Sleep until the average rate drops back under the limit
+        let expected = Duration::from_secs_f64(copied as f64 / limit as f64);
+        let elapsed = start.elapsed();
+        if expected > elapsed {
+            thread::sleep(expected - elapsed);
+        }
+    }
+
+    writer.flush()?;
+
+    // Keep the permission bits, like `fs::copy` does
+    let permissions = fs::metadata(from)?.permissions();
+    fs::set_permissions(to, permissions)?;
+
+    Ok(copied)
+}
+
+/// Default `strftime`-style format for timestamps shown to people
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Time format name selecting ISO 8601 / RFC 3339 output
+pub const ISO_TIME_FORMAT: &str = "iso8601";
+
+/// Check that a time format is `iso8601` or a valid `strftime`-style format
+pub fn validate_time_format(format: &str) -> Result<(), String> {
+    if format == ISO_TIME_FORMAT {
+        return Ok(());
+    }
+
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("invalid time format: {}", format));
+    }
+
+    Ok(())
+}
+
+/// Format a timestamp as RFC 3339, in UTC (with a `Z` suffix) if `utc` is set
+pub fn rfc3339(timestamp: &DateTime<Local>, utc: bool) -> String {
+    if utc {
+        timestamp
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    } else {
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+}
+
+/// Format a timestamp for display using the configured time format and zone
+pub fn format_timestamp(timestamp: &DateTime<Local>, config: &Config) -> String {
+    if config.time_format == ISO_TIME_FORMAT {
+        return rfc3339(timestamp, config.utc);
+    }
+
+    if config.utc {
+        timestamp
+            .with_timezone(&Utc)
+            .format(&config.time_format)
+            .to_string()
+    } else {
+        timestamp.format(&config.time_format).to_string()
+    }
+}
+
+/// Parse a date or timestamp given on the command line, in local time
+///
+/// Accepts RFC 3339 (`2024-05-01T12:00:00+02:00`), `YYYY-MM-DD HH:MM[:SS]`
+/// and `YYYY-MM-DD` (meaning the end of that day).
+pub fn parse_datetime(input: &str) -> Option<DateTime<Local>> {
+    let input = input.trim();
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Some(datetime.with_timezone(&Local));
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(23, 59, 59))
+        })?;
+
+    Local.from_local_datetime(&naive).earliest()
+}
+
 /// Check if a path is inside the home directory
 pub fn is_in_home_dir<P: AsRef<Path>>(path: P, config: &Config) -> bool {
     path.as_ref().starts_with(&config.home_dir)
@@ -96,6 +370,100 @@ mod tests {
         assert_eq!(human_readable_size(1024 * 1024 * 1024), "1.00 GB");
     }
 
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("500"), Some(500));
+        assert_eq!(parse_size("512K"), Some(512 * 1024));
+        assert_eq!(parse_size("10MB"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("fast"), None);
+        assert_eq!(parse_size("10X"), None);
+    }
+
+    #[test]
+    fn test_parse_datetime() {
+        let date = parse_datetime("2024-05-01").unwrap();
+        assert_eq!(
+            date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2024-05-01 23:59:59"
+        );
+
+        let time = parse_datetime("2024-05-01 08:30").unwrap();
+        assert_eq!(time.format("%H:%M").to_string(), "08:30");
+
+        let rfc = parse_datetime("2024-05-01T08:30:00Z").unwrap();
+        assert_eq!(rfc.timestamp(), 1714552200);
+
+        assert!(parse_datetime("yesterday-ish").is_none());
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let mut config = Config::new(PathBuf::from("/vault"), PathBuf::from("/home"));
+        let time = parse_datetime("2024-05-01T08:30:00Z").unwrap();
+
+        config.utc = true;
+        assert_eq!(format_timestamp(&time, &config), "2024-05-01 08:30:00");
+
+        config.time_format = "%d.%m.%Y".to_string();
+        assert_eq!(format_timestamp(&time, &config), "01.05.2024");
+
+        config.time_format = ISO_TIME_FORMAT.to_string();
+        assert_eq!(format_timestamp(&time, &config), "2024-05-01T08:30:00Z");
+
+        assert!(validate_time_format("%Y-%m-%d").is_ok());
+        assert!(validate_time_format("%Q").is_err());
+    }
+
+    #[test]
+    fn test_copy_file_with_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        std::fs::write(&from, vec![7u8; 4096]).unwrap();
+
+        let copied = copy_file(&from, &to, Some(1024 * 1024)).unwrap();
+
+        assert_eq!(copied, 4096);
+        assert_eq!(std::fs::read(&to).unwrap(), vec![7u8; 4096]);
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_write_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+
+        write_file(&path, b"first").unwrap();
+        write_file(&path, b"second").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_move_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        std::fs::write(&from, "content").unwrap();
+
+        move_file(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "content");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_cross_device() {
+        // EXDEV on Linux
+        assert!(is_cross_device(&io::Error::from_raw_os_error(18)));
+        assert!(!is_cross_device(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
     #[test]
     fn test_is_in_home_dir() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, value};
+
+#[cfg(feature = "git")]
+use crate::bare::{VaultLayout, open_bare_repo};
+use crate::config_file::{ConfigFile, is_vault_name};
+#[cfg(feature = "git")]
+use crate::history::init_git_repo;
+use crate::utils::write_file;
+use crate::{Config, DotfilesError};
+
+/// Name selecting the vault set by the config file's top-level settings
+pub const DEFAULT_VAULT: &str = "default";
+
+/// A vault defined in the config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VaultInfo {
+    /// Name of the vault, `default` for the top-level settings
+    pub name: String,
+
+    /// Directory of the vault
+    pub vault_dir: PathBuf,
+
+    /// Whether this is the vault commands use when `--vault` names none
+    pub default: bool,
+
+    /// Whether this is the vault of the running command
+    pub current: bool,
+}
+
+/// List the vaults in a config file
+///
+/// The vault of the top-level settings comes first, as `default`, followed
+/// by the named vaults. `config` is the configuration of the running
+/// command, marked as current.
+pub fn list_vaults(path: &Path, config: &Config) -> Result<Vec<VaultInfo>, DotfilesError> {
+    let file = if path.exists() {
+        ConfigFile::parse(&fs::read_to_string(path)?, path)?
+    } else {
+        ConfigFile::default()
+    };
+
+    let selected = file.vault.clone();
+    let mut names = vec![DEFAULT_VAULT.to_string()];
+    names.extend(file.vaults.keys().cloned());
+
+    let mut vaults = Vec::new();
+    for name in names {
+        let mut vault = Config::default();
+        file.clone().apply_vault(&mut vault, path, Some(&name))?;
+        vaults.push(VaultInfo {
+            current: vault.vault_dir == config.vault_dir,
+            default: selected.as_deref().unwrap_or(DEFAULT_VAULT) == name,
+            vault_dir: vault.vault_dir,
+            name,
+        });
+    }
+
+    Ok(vaults)
+}
+
+/// Read a config file for editing, keeping its comments and layout
+fn read_document(path: &Path) -> Result<DocumentMut, DotfilesError> {
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+
+    content
+        .parse::<DocumentMut>()
+        .map_err(|err| DotfilesError::InvalidConfig(path.display().to_string(), err.to_string()))
+}
+
+/// Write an edited config file back
+fn write_document(path: &Path, document: &DocumentMut) -> Result<(), DotfilesError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file(path, document.to_string().as_bytes())?;
+
+    Ok(())
+}
+
+/// Add a named vault to a config file
+///
+/// `vault_dir` is written as given, so `~` stays portable; without it the
+/// vault lives in `~/dotfilesvault-<name>`. Fails with `VaultExists` if the
+/// name is taken.
+pub fn create_vault(path: &Path, name: &str, vault_dir: Option<&str>) -> Result<(), DotfilesError> {
+    if !is_vault_name(name) || name == DEFAULT_VAULT {
+        return Err(DotfilesError::InvalidConfig(
+            path.display().to_string(),
+            format!(
+                "invalid vault name {:?}, use letters, digits, -, _ and . and not {:?}",
+                name, DEFAULT_VAULT
+            ),
+        ));
+    }
+
+    let mut document = read_document(path)?;
+    let vaults = document
+        .entry("vaults")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| {
+            DotfilesError::InvalidConfig(
+                path.display().to_string(),
+                "vaults is not a table".to_string(),
+            )
+        })?;
+    if vaults.contains_key(name) {
+        return Err(DotfilesError::VaultExists(name.to_string()));
+    }
+
+    let mut table = Table::new();
+    if let Some(vault_dir) = vault_dir {
+        table.insert("vault_dir", value(vault_dir));
+    }
+    vaults.insert(name, Item::Table(table));
+    write_document(path, &document)?;
+
+    info!("Added vault {} to {:?}", name, path);
+
+    Ok(())
+}
+
+/// Make a vault the one commands use when `--vault` names none
+///
+/// `default` switches back to the vault of the top-level settings.
+pub fn switch_vault(path: &Path, name: &str) -> Result<(), DotfilesError> {
+    let mut document = read_document(path)?;
+
+    if name == DEFAULT_VAULT {
+        document.remove("vault");
+    } else {
+        let defined = document
+            .get("vaults")
+            .and_then(Item::as_table_like)
+            .is_some_and(|vaults| vaults.contains_key(name));
+        if !defined {
+            return Err(DotfilesError::VaultNotFound(name.to_string()));
+        }
+
+        // Top-level settings are written before the first table header
+        document.insert("vault", value(name));
+    }
+    write_document(path, &document)?;
+
+    debug!("Switched the default vault to {}", name);
+
+    Ok(())
+}
+
+/// Create a vault's directory and repository if they don't exist yet
+#[cfg(feature = "git")]
+pub fn init_vault(config: &Config) -> Result<(), DotfilesError> {
+    match config.layout {
+        VaultLayout::Copy => {
+            config.init_vault_dir()?;
+            init_git_repo(config)?;
+        }
+        VaultLayout::Bare => {
+            open_bare_repo(config)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_named_vaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let home = temp_dir.path().join("home");
+        fs::write(
+            &path,
+            format!(
+                "# my settings\nhome_dir = {:?}\nbwlimit = \"1M\"\n\n[git]\nauthor_name = \"Me\"\n",
+                home.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        create_vault(&path, "work", Some("vaults/work")).unwrap();
+        create_vault(&path, "play", None).unwrap();
+        assert!(matches!(
+            create_vault(&path, "work", None),
+            Err(DotfilesError::VaultExists(_))
+        ));
+        assert!(create_vault(&path, DEFAULT_VAULT, None).is_err());
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("# my settings\n")
+        );
+
+        // Named vaults keep the top-level settings they don't override
+        let work = Config::load_vault(Some(&path), Some("work")).unwrap();
+        assert_eq!(work.vault_dir, home.join("vaults/work"));
+        assert_eq!(work.vault_name.as_deref(), Some("work"));
+        assert_eq!(work.bwlimit, Some(1024 * 1024));
+        assert_eq!(work.author_name.as_deref(), Some("Me"));
+        let play = Config::load_vault(Some(&path), Some("play")).unwrap();
+        assert_eq!(play.vault_dir, home.join("dotfilesvault-play"));
+        assert!(matches!(
+            Config::load_vault(Some(&path), Some("missing")),
+            Err(DotfilesError::VaultNotFound(_))
+        ));
+        let dir = Config::load_vault(Some(&path), Some("/srv/vault")).unwrap();
+        assert_eq!(dir.vault_name, None);
+
+        switch_vault(&path, "work").unwrap();
+        let config = Config::load_vault(Some(&path), None).unwrap();
+        assert_eq!(config.vault_name.as_deref(), Some("work"));
+        let vaults = list_vaults(&path, &config).unwrap();
+        let names: Vec<&str> = vaults.iter().map(|vault| vault.name.as_str()).collect();
+        assert_eq!(names, vec![DEFAULT_VAULT, "play", "work"]);
+        assert!(vaults[2].default && vaults[2].current);
+        assert!(!vaults[0].default);
+
+        assert!(matches!(
+            switch_vault(&path, "missing"),
+            Err(DotfilesError::VaultNotFound(_))
+        ));
+        switch_vault(&path, DEFAULT_VAULT).unwrap();
+        let config = Config::load_vault(Some(&path), None).unwrap();
+        assert_eq!(config.vault_dir, Config::default().vault_dir);
+        assert_eq!(config.vault_name, None);
+    }
+}
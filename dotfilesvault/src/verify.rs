@@ -0,0 +1,338 @@
+use anyhow::Result;
+use git2::{ObjectType, Oid, Repository, Status, StatusOptions, TreeWalkMode, TreeWalkResult};
+use log::debug;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+
+use crate::history::is_partial_clone;
+use crate::manifest::Manifest;
+use crate::metadata::MetadataManifest;
+use crate::profile::PROFILES_DIR;
+use crate::stray::find_stray_files;
+use crate::{Config, DotfilesError};
+
+/// What is wrong with the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemKind {
+    /// A vault file differs from the last commit
+    Uncommitted,
+
+    /// A git object is missing or its content doesn't match its ID
+    CorruptObject,
+
+    /// The manifest or the metadata file can't be read, or records files the
+    /// vault doesn't have
+    InvalidManifest,
+
+    /// A vault file can't be mapped back to a file in the home directory
+    Unmapped,
+}
+
+impl ProblemKind {
+    /// Name used in output
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProblemKind::Uncommitted => "uncommitted",
+            ProblemKind::CorruptObject => "corrupt-object",
+            ProblemKind::InvalidManifest => "invalid-manifest",
+            ProblemKind::Unmapped => "unmapped",
+        }
+    }
+}
+
+/// A problem found by `verify_vault`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VaultProblem {
+    /// What is wrong
+    pub kind: ProblemKind,
+
+    /// The vault-relative file, manifest entry or git object concerned
+    pub subject: String,
+
+    /// Details, e.g. the git error
+    pub detail: String,
+}
+
+impl VaultProblem {
+    fn new(kind: ProblemKind, subject: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Check the integrity of the vault
+///
+/// Reports vault files that differ from the last commit, git objects
+/// reachable from any reference that are missing or corrupted, manifest and
+/// metadata files that can't be read or record files that aren't in the
+/// vault, and vault files that can't be mapped back into the home
+/// directory. Blobs missing from partial clones are expected and not
+/// reported. Nothing is changed.
+pub fn verify_vault(config: &Config) -> Result<Vec<VaultProblem>, DotfilesError> {
+    let repo =
+        Repository::open(&config.vault_dir).map_err(|_| DotfilesError::NoDotfilesVaultDir)?;
+
+    let mut problems = Vec::new();
+    check_worktree(&repo, &mut problems)?;
+    check_objects(config, &repo, &mut problems)?;
+    check_manifests(config, &mut problems)?;
+
+    debug!("Found {} problems in the vault", problems.len());
+    Ok(problems)
+}
+
+/// Report vault files that were changed, deleted or added since the last commit
+fn check_worktree(
+    repo: &Repository,
+    problems: &mut Vec<VaultProblem>,
+) -> Result<(), DotfilesError> {
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let Some(path) = entry.path() else {
+            continue;
+        };
+        let status = entry.status();
+        let detail = if status.intersects(Status::WT_DELETED | Status::INDEX_DELETED) {
+            "deleted since the last commit"
+        } else if status.contains(Status::WT_NEW) {
+            "not committed"
+        } else {
+            "changed since the last commit"
+        };
+        problems.push(VaultProblem::new(ProblemKind::Uncommitted, path, detail));
+    }
+
+    Ok(())
+}
+
+/// Read every object reachable from the references, reporting the ones that
+/// are missing or corrupted
+fn check_objects(
+    config: &Config,
+    repo: &Repository,
+    problems: &mut Vec<VaultProblem>,
+) -> Result<(), DotfilesError> {
+    let odb = repo.odb()?;
+    let partial = is_partial_clone(repo);
+    let mut seen = HashSet::new();
+
+    let mut walk = repo.revwalk()?;
+    for reference in repo.references()? {
+        let reference = reference?;
+        let Some(oid) = reference.target() else {
+            continue;
+        };
+        match reference.peel_to_commit() {
+            Ok(commit) => walk.push(commit.id())?,
+            Err(err) if reference.name() != Some("refs/stash") => {
+                problems.push(corrupt(oid, "reference target", err));
+            }
+            Err(_) => {}
+        }
+    }
+
+    for oid in walk {
+        config.cancellation.check()?;
+        let commit = match oid.and_then(|oid| repo.find_commit(oid)) {
+            Ok(commit) => commit,
+            Err(err) => {
+                // The history behind a missing commit can't be walked
+                problems.push(corrupt(Oid::zero(), "commit", err));
+                break;
+            }
+        };
+        let tree = match commit.tree() {
+            Ok(tree) => tree,
+            Err(err) => {
+                problems.push(corrupt(commit.tree_id(), "tree", err));
+                continue;
+            }
+        };
+        if !seen.insert(tree.id()) {
+            continue;
+        }
+
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if !seen.insert(entry.id()) {
+                return TreeWalkResult::Skip;
+            }
+            let kind = entry.kind();
+            if kind == Some(ObjectType::Blob) && partial {
+                return TreeWalkResult::Ok;
+            }
+            if matches!(kind, Some(ObjectType::Tree | ObjectType::Blob))
+                && let Err(err) = odb.read(entry.id())
+            {
+                let name = format!("{}{}", dir, entry.name().unwrap_or("?"));
+                problems.push(corrupt(entry.id(), &name, err));
+                return TreeWalkResult::Skip;
+            }
+            TreeWalkResult::Ok
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Describe an object that can't be read
+fn corrupt(oid: Oid, what: &str, err: git2::Error) -> VaultProblem {
+    VaultProblem::new(
+        ProblemKind::CorruptObject,
+        oid.to_string(),
+        format!("{}: {}", what, err.message()),
+    )
+}
+
+/// Check the manifest, the metadata file and how vault files map to the
+/// home directory
+fn check_manifests(config: &Config, problems: &mut Vec<VaultProblem>) -> Result<(), DotfilesError> {
+    let manifest = match Manifest::load(config) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            problems.push(VaultProblem::new(
+                ProblemKind::InvalidManifest,
+                Manifest::path(config).display().to_string(),
+                err.to_string(),
+            ));
+            return Ok(());
+        }
+    };
+
+    for entry in manifest.entries.values() {
+        if escapes(&entry.source) {
+            problems.push(VaultProblem::new(
+                ProblemKind::Unmapped,
+                entry.target.display().to_string(),
+                format!("restores to {:?}, outside the home directory", entry.source),
+            ));
+        }
+
+        let stored = std::iter::once(entry.target.clone()).chain(
+            entry
+                .profiles
+                .iter()
+                .map(|profile| Path::new(PROFILES_DIR).join(profile).join(&entry.target)),
+        );
+        let in_vault = stored
+            .map(|path| config.vault_dir.join(path))
+            .any(|path| path.exists() || path.is_symlink());
+        if !in_vault {
+            problems.push(VaultProblem::new(
+                ProblemKind::InvalidManifest,
+                entry.id.clone(),
+                format!("tracked, but {:?} isn't in the vault", entry.target),
+            ));
+        }
+    }
+
+    match MetadataManifest::load(config) {
+        Ok(metadata) => {
+            for id in metadata.files.keys() {
+                if !manifest.entries.contains_key(id) {
+                    problems.push(VaultProblem::new(
+                        ProblemKind::InvalidManifest,
+                        id.clone(),
+                        "metadata recorded for a dotfile that isn't tracked",
+                    ));
+                }
+            }
+        }
+        Err(err) => problems.push(VaultProblem::new(
+            ProblemKind::InvalidManifest,
+            MetadataManifest::path(config).display().to_string(),
+            err.to_string(),
+        )),
+    }
+
+    for path in find_stray_files(config)? {
+        problems.push(VaultProblem::new(
+            ProblemKind::Unmapped,
+            path.display().to_string(),
+            "no manifest entry tracks it",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check if a home-relative path leads outside the home directory
+fn escapes(path: &Path) -> bool {
+    path.components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::backup_all_dotfiles;
+    use crate::history::commit_changes;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new(temp_dir.path().join("vault"), temp_dir.path().join("home"));
+        fs::create_dir_all(&config.home_dir).unwrap();
+        fs::write(config.home_dir.join(".bashrc"), "bash").unwrap();
+        fs::write(config.home_dir.join(".vimrc"), "vim").unwrap();
+        backup_all_dotfiles(&config).unwrap();
+        commit_changes(&config, "Backup").unwrap();
+        assert_eq!(verify_vault(&config).unwrap(), Vec::new());
+
+        fs::write(config.vault_dir.join(".vimrc"), "edited").unwrap();
+        fs::write(config.vault_dir.join(".stray"), "stray").unwrap();
+        let kinds: Vec<(ProblemKind, String)> = verify_vault(&config)
+            .unwrap()
+            .into_iter()
+            .map(|problem| (problem.kind, problem.subject))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ProblemKind::Uncommitted, ".stray".to_string()),
+                (ProblemKind::Uncommitted, ".vimrc".to_string()),
+                (ProblemKind::Unmapped, ".stray".to_string()),
+            ]
+        );
+        fs::remove_file(config.vault_dir.join(".stray")).unwrap();
+        commit_changes(&config, "Edit").unwrap();
+
+        // Corrupt the loose object of the committed .bashrc
+        let repo = Repository::open(&config.vault_dir).unwrap();
+        let blob = repo
+            .head()
+            .unwrap()
+            .peel_to_tree()
+            .unwrap()
+            .get_path(Path::new(".bashrc"))
+            .unwrap()
+            .id()
+            .to_string();
+        let object = config
+            .vault_dir
+            .join(".git/objects")
+            .join(&blob[..2])
+            .join(&blob[2..]);
+        let mut permissions = fs::metadata(&object).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&object, permissions).unwrap();
+        fs::write(&object, b"garbage").unwrap();
+
+        let problems = verify_vault(&config).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::CorruptObject);
+        assert_eq!(problems[0].subject, blob);
+    }
+}
@@ -4,9 +4,10 @@ use std::path::PathBuf;
 use tempfile::TempDir;
 
 use dotfilesvault::backup::{backup_all_dotfiles, find_dotfiles};
+use dotfilesvault::conflicts::ConflictPolicy;
 use dotfilesvault::history::{commit_changes, get_dotfile_history};
 use dotfilesvault::restore::{list_backed_up_dotfiles, restore_specific_dotfile};
-use dotfilesvault::{Config, is_dotfile};
+use dotfilesvault::{Config, DotfilesError, is_dotfile};
 
 /// Set up a test environment with dotfiles
 fn setup_test_env() -> (Config, TempDir) {
@@ -54,7 +55,7 @@ fn setup_test_env() -> (Config, TempDir) {
 #[test]
 fn test_full_backup_and_restore_flow() {
     // Set up test environment
-    let (config, _temp_dir) = setup_test_env();
+    let (mut config, _temp_dir) = setup_test_env();
 
     // Step 1: Find dotfiles
     let dotfiles = find_dotfiles(&config).unwrap();
@@ -97,8 +98,13 @@ fn test_full_backup_and_restore_flow() {
         .unwrap();
     writeln!(file, "# Modified content").unwrap();
 
-    // Step 6: Restore the dotfile
+    // Step 6: Restore the dotfile, which needs forcing over the modification
     let bashrc_rel_path = ".bashrc";
+    assert!(matches!(
+        restore_specific_dotfile(&config, bashrc_rel_path),
+        Err(DotfilesError::RestoreConflict(_))
+    ));
+    config.on_conflict = ConflictPolicy::Overwrite;
     restore_specific_dotfile(&config, bashrc_rel_path).unwrap();
 
     // Step 7: Verify the content was restored
@@ -106,7 +112,7 @@ fn test_full_backup_and_restore_flow() {
     assert!(content.contains("# Test content for .bashrc"));
 
     // Step 8: Get history of the dotfile
-    let history = get_dotfile_history(&config, bashrc_rel_path).unwrap();
+    let history = get_dotfile_history(&config, bashrc_rel_path, 0, None).unwrap();
     assert!(!history.is_empty());
 }
 
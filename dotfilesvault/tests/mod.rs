@@ -1,2 +1,3 @@
+#[cfg(feature = "git")]
 pub mod integration;
 pub mod unit;